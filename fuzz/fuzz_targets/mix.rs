@@ -0,0 +1,23 @@
+#![no_main]
+
+use audio_recorder_rs::{MixMode, Recorder, TargetFormat};
+use libfuzzer_sys::fuzz_target;
+
+// a callback buffer of any length and channel count, as a malformed device delivers it
+fuzz_target!(|input: (u8, f32, Vec<TargetFormat>, Vec<TargetFormat>)| {
+    let (channels, mic_level, mic, system) = input;
+    let channels = channels as u16;
+
+    let mic = Recorder::channels_to_mono(mic, channels);
+
+    for mode in [MixMode::DualMono, MixMode::StereoOverlay { mic_level }] {
+        let system = mode.system_layout(system.clone(), channels);
+
+        let mut out = Vec::new();
+        for (mic, frame) in mic.iter().zip(system.chunks(mode.system_channels())) {
+            mode.mix_frame(*mic, frame, &mut out);
+        }
+        // frames cut short by the end of the buffer
+        mode.mix_frame(0.0, &[], &mut out);
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use audio_recorder_rs::read_pipe_frame;
+use libfuzzer_sys::fuzz_target;
+
+// arbitrary bytes on the pipe, including truncated frames and corrupt lengths
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Cursor::new(data);
+    while let Ok(Some(_)) = read_pipe_frame(&mut reader) {}
+});
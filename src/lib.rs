@@ -8,10 +8,55 @@
 //!
 //! To use the recorder, create an instance of the `Recorder` struct and call its `start` method to begin recording.
 //! This will start a background thread that will record audio from the default input device. the
-//! start function will return a receiver which acts as a stream to receive the audio data in
-//! `TargetFormat`.
+//! start function will return a receiver which acts as a stream to receive the audio data as
+//! `AudioChunk`s of `TargetFormat` samples.
 //! Call the `stop` method to stop recording.
+//!
+//! ## Stability
+//!
+//! Everything the crate exposes is re-exported here; the internal module layout is not
+//! part of the public API. Enums and structs expected to grow (errors, events, chunk and
+//! summary types, ...) are `#[non_exhaustive]`, so match them with a wildcard arm and
+//! read their fields instead of constructing them, and new variants or fields will not be
+//! breaking changes.
+
+// the capture, mixing and sink paths report failures instead of panicking
+#![cfg_attr(
+    not(test),
+    deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)
+)]
 
 mod recorder;
 
-pub use recorder::Recorder;
+pub use recorder::{
+    AdaptiveChunking, AlignmentReport, Annotation, AnnotationKind, AnnotationLog, AudioChunk,
+    AudioClass, AudioDevice, AudioInjector, AudioProcessor, AudioReceiver, AudioRecorderError,
+    AudioRecvError, AudioSource, AutoGainControl, BackpressurePolicy, BufferOccupancy,
+    CalibrationReport, CallbackStats, CaptureBuilder, CaptureClock, ChannelMode, ChunkFeatures,
+    ChunkLayout, ClockMapping, ClockSource, Config, DegradationStep, DeviceConfig, DeviceRegistry,
+    DeviceType, DualMonoLayout, ExternalClock, HighPassFilter, InputLevel, LatencyMode,
+    LatencyPadding, LevelHistogram, MemorySink, MixMode, MixerSession, MonoLayout, NoiseProfile,
+    Permission, PipeFrame, PipeSink, PitchEstimate, PullReader, PunchFill, RateConversion,
+    Recorder, RecorderConfig, RecorderConfigBuilder, RecorderEvent, RecordingQuota,
+    ResamplerQuality, SessionMode, SessionReceivers, ShutdownReport, SilenceGate, SinkOutcome,
+    SinkPolicy, SoftLimiter, SourceReceivers, StreamSummary, SyncPolicy, TargetFormat,
+    TrackReceiver, TypedChunk, TypedReceiver, TypedSession, VadMode, ValidationIssue, WavRotation,
+    WavSink, WriterStats, read_pipe_frame, stitch_wav_segments,
+};
+
+#[cfg(target_os = "macos")]
+pub use recorder::AggregateDevice;
+#[cfg(feature = "async")]
+pub use recorder::AudioStream;
+#[cfg(feature = "aac")]
+pub use recorder::{AacSettings, AacSink};
+#[cfg(feature = "helper")]
+pub use recorder::{CaptureHelper, HelperSettings, run_capture_helper};
+#[cfg(feature = "decode")]
+pub use recorder::{DecodedAudio, decode_file, decode_to_wav};
+#[cfg(feature = "mp3")]
+pub use recorder::{Mp3Settings, Mp3Sink};
+#[cfg(feature = "opus")]
+pub use recorder::{OpusEncoderSink, OpusSettings};
+#[cfg(feature = "serde")]
+pub use recorder::{read_chunk, write_chunk};
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use super::{
+    Recorder,
+    dsp::{segmenter::Segmenter, vad::VadMode},
+};
+
+/// Settings of the chunks cut at the pauses of the speech, see
+/// `Recorder::set_adaptive_chunking`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveChunking {
+    /// Shortest chunk delivered, except for the last one of the recording.
+    pub min: Duration,
+    /// Length from which a chunk ends at the first pause.
+    pub target: Duration,
+    /// Longest chunk delivered, cut at the last pause after `min` when the speech goes
+    /// on, e.g. the 30 s window of Whisper.
+    pub max: Duration,
+    /// How the pauses are detected.
+    pub vad: VadMode,
+}
+
+impl Default for AdaptiveChunking {
+    fn default() -> Self {
+        AdaptiveChunking {
+            min: Duration::from_secs(5),
+            target: Duration::from_secs(15),
+            max: Duration::from_secs(30),
+            vad: VadMode::default(),
+        }
+    }
+}
+
+impl AdaptiveChunking {
+    /// Creates the segmenter of a delivered stream with the given layout.
+    pub fn segmenter(&self, sample_rate: u32, channels: u16) -> Segmenter {
+        Segmenter::new(
+            sample_rate,
+            channels,
+            self.vad,
+            self.min,
+            self.target,
+            self.max,
+        )
+    }
+}
+
+impl Recorder {
+    /// Delivers chunks cut at the pauses of the speech, between `chunking.min` and
+    /// `chunking.max` and close to `chunking.target` (or `None` to deliver chunks as the
+    /// devices produce them, or paced by `set_chunk_duration`).
+    ///
+    /// Speech recognizers transcribe whole utterances much better than fixed windows
+    /// cutting words in half. Overrides `set_chunk_duration`.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_adaptive_chunking(&mut self, chunking: Option<AdaptiveChunking>) {
+        self.adaptive_chunking = chunking;
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    dictionary::CFDictionary,
+    number::CFNumber,
+    string::{CFString, CFStringRef},
+};
+use core_foundation_sys::dictionary::CFDictionaryRef;
+
+use super::errors::AudioRecorderError;
+
+type AudioObjectID = u32;
+type OSStatus = i32;
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+#[link(name = "CoreAudio", kind = "framework")]
+unsafe extern "C" {
+    fn AudioObjectGetPropertyDataSize(
+        object: AudioObjectID,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+    ) -> OSStatus;
+    fn AudioObjectGetPropertyData(
+        object: AudioObjectID,
+        address: *const AudioObjectPropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> OSStatus;
+    fn AudioHardwareCreateAggregateDevice(
+        description: CFDictionaryRef,
+        device: *mut AudioObjectID,
+    ) -> OSStatus;
+    fn AudioHardwareDestroyAggregateDevice(device: AudioObjectID) -> OSStatus;
+}
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*code)
+}
+
+const SYSTEM_OBJECT: AudioObjectID = 1;
+const PROPERTY_DEVICES: u32 = fourcc(b"dev#");
+const PROPERTY_NAME: u32 = fourcc(b"lnam");
+const PROPERTY_DEVICE_UID: u32 = fourcc(b"uid ");
+const SCOPE_GLOBAL: u32 = fourcc(b"glob");
+const ELEMENT_MAIN: u32 = 0;
+
+/// Name of the aggregate device, as enumerated by cpal.
+const AGGREGATE_NAME: &str = "audio-recorder-rs aggregate";
+
+/// Keeps the UIDs of the aggregate devices of this process unique.
+static AGGREGATE_COUNT: AtomicU32 = AtomicU32::new(0);
+
+fn global(selector: u32) -> AudioObjectPropertyAddress {
+    AudioObjectPropertyAddress {
+        selector,
+        scope: SCOPE_GLOBAL,
+        element: ELEMENT_MAIN,
+    }
+}
+
+/// Reads a `CFString` property of an audio object.
+fn string_property(object: AudioObjectID, selector: u32) -> Option<String> {
+    let address = global(selector);
+    let mut value: CFStringRef = ptr::null();
+    let mut size = size_of::<CFStringRef>() as u32;
+
+    // SAFETY: the property is a `CFStringRef` the caller owns, written into `value`.
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            &mut value as *mut CFStringRef as *mut c_void,
+        )
+    };
+    if status != 0 || value.is_null() {
+        return None;
+    }
+
+    // SAFETY: the string was returned under the create rule.
+    Some(unsafe { CFString::wrap_under_create_rule(value) }.to_string())
+}
+
+/// The CoreAudio devices, with their names and UIDs.
+fn devices() -> Result<Vec<(String, String)>, AudioRecorderError> {
+    let address = global(PROPERTY_DEVICES);
+    let mut size = 0;
+
+    // SAFETY: the device list is an array of `AudioObjectID`s of `size` bytes.
+    let ids = unsafe {
+        let status =
+            AudioObjectGetPropertyDataSize(SYSTEM_OBJECT, &address, 0, ptr::null(), &mut size);
+        if status != 0 {
+            tracing::error!("Failed to get the size of the device list: {}", status);
+            return Err(AudioRecorderError::DeviceError(
+                "Failed to list CoreAudio devices",
+            ));
+        }
+
+        let mut ids = vec![0 as AudioObjectID; size as usize / size_of::<AudioObjectID>()];
+        let status = AudioObjectGetPropertyData(
+            SYSTEM_OBJECT,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            ids.as_mut_ptr() as *mut c_void,
+        );
+        if status != 0 {
+            tracing::error!("Failed to get the device list: {}", status);
+            return Err(AudioRecorderError::DeviceError(
+                "Failed to list CoreAudio devices",
+            ));
+        }
+        ids.truncate(size as usize / size_of::<AudioObjectID>());
+        ids
+    };
+
+    Ok(ids
+        .into_iter()
+        .filter_map(|id| {
+            Some((
+                string_property(id, PROPERTY_NAME)?,
+                string_property(id, PROPERTY_DEVICE_UID)?,
+            ))
+        })
+        .collect())
+}
+
+fn uid_of(devices: &[(String, String)], name: &str) -> Result<String, AudioRecorderError> {
+    match devices.iter().find(|(device, _)| device == name) {
+        Some((_, uid)) => Ok(uid.clone()),
+        None => {
+            tracing::error!("CoreAudio device {:?} not found", name);
+            Err(AudioRecorderError::DeviceError("Device not found"))
+        }
+    }
+}
+
+fn dictionary(pairs: &[(&str, CFType)]) -> CFDictionary<CFType, CFType> {
+    let pairs: Vec<(CFType, CFType)> = pairs
+        .iter()
+        .map(|(key, value)| (CFString::new(key).as_CFType(), value.clone()))
+        .collect();
+    CFDictionary::from_CFType_pairs(&pairs)
+}
+
+/// A private CoreAudio aggregate device combining a microphone and a loopback device
+/// (e.g. BlackHole), so both are captured by a single stream on one hardware clock.
+///
+/// The microphone drives the clock and the loopback device is drift compensated by
+/// CoreAudio, which removes the drift between the two sources that separate streams
+/// suffer. Record it as a single input device, whose first channels are the
+/// microphone's, followed by the loopback device's:
+///
+/// ```no_run
+/// # #[cfg(target_os = "macos")]
+/// # {
+/// use audio_recorder_rs::{AggregateDevice, Recorder, RecorderConfig};
+///
+/// let aggregate = AggregateDevice::create("MacBook Pro Microphone", "BlackHole 2ch")
+///     .expect("Failed to create the aggregate device");
+///
+/// let mut recorder = Recorder::new();
+/// let config = RecorderConfig::builder()
+///     .input_only(true)
+///     .input_device(aggregate.name())
+///     .build();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// # }
+/// ```
+///
+/// The device is private to this process and destroyed when dropped.
+#[derive(Debug)]
+pub struct AggregateDevice {
+    id: AudioObjectID,
+}
+
+impl AggregateDevice {
+    /// Creates the aggregate device of the CoreAudio devices named `input` and
+    /// `loopback`, as listed by `AudioDevice::list_all`.
+    #[tracing::instrument]
+    pub fn create(input: &str, loopback: &str) -> Result<AggregateDevice, AudioRecorderError> {
+        let devices = devices()?;
+        let input_uid = uid_of(&devices, input)?;
+        let loopback_uid = uid_of(&devices, loopback)?;
+
+        let subdevice = |uid: &str, drift: bool| {
+            dictionary(&[
+                ("uid", CFString::new(uid).as_CFType()),
+                ("drift", CFNumber::from(drift as i32).as_CFType()),
+            ])
+            .as_CFType()
+        };
+        let subdevices =
+            CFArray::from_CFTypes(&[subdevice(&input_uid, false), subdevice(&loopback_uid, true)]);
+
+        let uid = format!(
+            "audio-recorder-rs.aggregate.{}.{}",
+            std::process::id(),
+            AGGREGATE_COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let description = dictionary(&[
+            ("name", CFString::new(AGGREGATE_NAME).as_CFType()),
+            ("uid", CFString::new(&uid).as_CFType()),
+            ("subdevices", subdevices.as_CFType()),
+            ("master", CFString::new(&input_uid).as_CFType()),
+            ("clock", CFString::new(&input_uid).as_CFType()),
+            ("private", CFNumber::from(1).as_CFType()),
+        ]);
+
+        let mut id = 0;
+        // SAFETY: the description outlives the call, which writes the new device to `id`.
+        let status = unsafe {
+            AudioHardwareCreateAggregateDevice(description.as_concrete_TypeRef(), &mut id)
+        };
+        if status != 0 {
+            tracing::error!("Failed to create the aggregate device: {}", status);
+            return Err(AudioRecorderError::DeviceError(
+                "Failed to create the aggregate device",
+            ));
+        }
+
+        tracing::info!(
+            "Created aggregate device {} of {:?} and {:?}",
+            id,
+            input,
+            loopback
+        );
+        Ok(AggregateDevice { id })
+    }
+
+    /// Name of the device, to record it with `RecorderConfigBuilder::input_device`.
+    pub fn name(&self) -> &'static str {
+        AGGREGATE_NAME
+    }
+}
+
+impl Drop for AggregateDevice {
+    fn drop(&mut self) {
+        // SAFETY: the device was created by this value and is destroyed once.
+        let status = unsafe { AudioHardwareDestroyAggregateDevice(self.id) };
+        if status != 0 {
+            tracing::error!("Failed to destroy aggregate device {}: {}", self.id, status);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use crossbeam_channel::Receiver;
+
+use super::{
+    Recorder,
+    chunk::AudioChunk,
+    dsp::{classifier::AudioClassifier, features::FeatureExtractor, pitch::PitchTracker},
+    events::{EventBus, RecorderEvent},
+};
+
+/// A fundamental frequency estimate of the delivered stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct PitchEstimate {
+    /// Position of the estimated frame in the recording.
+    pub time: Duration,
+    /// The estimated f0 in Hz, or `None` when the frame has no clear pitch.
+    pub frequency: Option<f32>,
+    /// How periodic the frame is, from 0 (not at all) to 1.
+    pub confidence: f32,
+}
+
+/// Lightweight features of a delivered chunk, for downstream heuristics that would
+/// otherwise need a second pass over the audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct ChunkFeatures {
+    /// Sequence number of the chunk, see `AudioChunk::sequence`.
+    pub sequence: u64,
+    /// Position of the chunk in the recording.
+    pub time: Duration,
+    /// Mean square of the (mono downmixed) samples.
+    pub energy: f32,
+    /// Share of frames crossing zero, from 0 to 1.
+    pub zero_crossing_rate: f32,
+    /// Magnitude-weighted mean frequency of the chunk, in Hz.
+    pub spectral_centroid: f32,
+}
+
+/// A processor observing every chunk delivered on the data channel.
+pub trait Analyzer: Send {
+    fn process(&mut self, chunk: &AudioChunk);
+}
+
+/// Emits a `RecorderEvent::Classification` for every classified window.
+struct ClassificationAnalyzer {
+    classifier: AudioClassifier,
+    events: EventBus,
+    window: u64,
+}
+
+impl Analyzer for ClassificationAnalyzer {
+    fn process(&mut self, chunk: &AudioChunk) {
+        let window_frames = self.classifier.window_frames() as u64;
+        let frames_to_duration =
+            |frames: u64| Duration::from_secs_f64(frames as f64 / chunk.sample_rate.max(1) as f64);
+
+        for class in self.classifier.process(&chunk.samples) {
+            self.events.emit(RecorderEvent::Classification {
+                start: frames_to_duration(self.window * window_frames),
+                duration: frames_to_duration(window_frames),
+                class,
+            });
+            self.window += 1;
+        }
+    }
+}
+
+/// Publishes a `PitchEstimate` for every analysis frame.
+struct PitchAnalyzer {
+    tracker: PitchTracker,
+    subscribers: EventBus<PitchEstimate>,
+    frame: u64,
+}
+
+impl Analyzer for PitchAnalyzer {
+    fn process(&mut self, chunk: &AudioChunk) {
+        let hop_frames = self.tracker.hop_frames() as u64;
+
+        for (frequency, confidence) in self.tracker.process(&chunk.samples) {
+            self.subscribers.emit(PitchEstimate {
+                time: Duration::from_secs_f64(self.frame as f64 / chunk.sample_rate.max(1) as f64),
+                frequency,
+                confidence,
+            });
+            self.frame += hop_frames;
+        }
+    }
+}
+
+/// Publishes the `ChunkFeatures` of every chunk.
+struct FeatureAnalyzer {
+    extractor: FeatureExtractor,
+    subscribers: EventBus<ChunkFeatures>,
+}
+
+impl Analyzer for FeatureAnalyzer {
+    fn process(&mut self, chunk: &AudioChunk) {
+        let (energy, zero_crossing_rate, spectral_centroid) =
+            self.extractor.process(&chunk.samples);
+
+        self.subscribers.emit(ChunkFeatures {
+            sequence: chunk.sequence,
+            time: chunk.position(),
+            energy,
+            zero_crossing_rate,
+            spectral_centroid,
+        });
+    }
+}
+
+impl Recorder {
+    /// Subscribes to the features (energy, zero-crossing rate, spectral centroid) of
+    /// every delivered chunk, published on a side channel.
+    ///
+    /// Features are only computed while someone is subscribed, and dropping the receiver
+    /// unsubscribes. Takes effect on the next call to `start`.
+    pub fn subscribe_features(&self) -> Receiver<ChunkFeatures> {
+        self.features.subscribe()
+    }
+
+    /// Subscribes to the pitch (f0) estimates of the delivered stream, published about
+    /// every 20 ms on a side channel, e.g. for tuners or voice training.
+    ///
+    /// Pitch tracking only runs while someone is subscribed, and dropping the receiver
+    /// unsubscribes. Takes effect on the next call to `start`.
+    pub fn subscribe_pitch(&self) -> Receiver<PitchEstimate> {
+        self.pitch.subscribe()
+    }
+
+    /// Labels every window of the delivered stream as speech, music or noise, reported
+    /// as `RecorderEvent::Classification` events.
+    ///
+    /// Enables smarter downstream routing, e.g. only transcribing speech regions.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_classification(&mut self, classification: bool) {
+        self.classification = classification;
+    }
+
+    /// Creates the analyzers enabled for a delivered stream with the given layout.
+    pub fn analyzers(&self, sample_rate: u32, channels: u16) -> Vec<Box<dyn Analyzer>> {
+        let mut analyzers: Vec<Box<dyn Analyzer>> = Vec::new();
+
+        if self.classification {
+            analyzers.push(Box::new(ClassificationAnalyzer {
+                classifier: AudioClassifier::new(sample_rate, channels),
+                events: self.events.clone(),
+                window: 0,
+            }));
+        }
+
+        if self.pitch.has_subscribers() {
+            analyzers.push(Box::new(PitchAnalyzer {
+                tracker: PitchTracker::new(sample_rate, channels),
+                subscribers: self.pitch.clone(),
+                frame: 0,
+            }));
+        }
+
+        if self.features.has_subscribers() {
+            analyzers.push(Box::new(FeatureAnalyzer {
+                extractor: FeatureExtractor::new(sample_rate, channels),
+                subscribers: self.features.clone(),
+            }));
+        }
+
+        analyzers
+    }
+}
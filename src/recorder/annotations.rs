@@ -0,0 +1,224 @@
+use std::{
+    fmt::Write as _,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use super::{Recorder, chunk::AudioChunk, delivery::StreamSummary, errors::AudioRecorderError};
+
+/// What an `Annotation` says about its range of the recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnnotationKind {
+    /// The speaker talking, e.g. a diarization label.
+    Speaker,
+    /// The language spoken, e.g. a guess of a language identifier.
+    Language,
+    /// What was said, e.g. the text of an external speech recognizer.
+    Transcript,
+    /// Any other metadata, written with this name in the manifest.
+    Custom(String),
+}
+
+impl AnnotationKind {
+    /// Name of the kind in the manifest.
+    pub fn name(&self) -> &str {
+        match self {
+            AnnotationKind::Speaker => "speaker",
+            AnnotationKind::Language => "language",
+            AnnotationKind::Transcript => "transcript",
+            AnnotationKind::Custom(name) => name,
+        }
+    }
+}
+
+/// Metadata attached to a range of frames of the delivered stream, see
+/// `Recorder::annotations`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Annotation {
+    /// The frames annotated, as counted by `AudioChunk::frame`.
+    pub frames: Range<u64>,
+    pub kind: AnnotationKind,
+    pub value: String,
+}
+
+impl Annotation {
+    pub fn new(frames: Range<u64>, kind: AnnotationKind, value: impl Into<String>) -> Self {
+        Annotation {
+            frames,
+            kind,
+            value: value.into(),
+        }
+    }
+
+    /// Annotates the frames of `chunk`.
+    pub fn of_chunk(chunk: &AudioChunk, kind: AnnotationKind, value: impl Into<String>) -> Self {
+        Annotation::new(chunk.frame..chunk.end_frame(), kind, value)
+    }
+
+    /// Position of the annotated frames in a stream at `sample_rate`.
+    pub fn times(&self, sample_rate: u32) -> Range<Duration> {
+        let time = |frame: u64| match sample_rate {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(frame as f64 / rate as f64),
+        };
+        time(self.frames.start)..time(self.frames.end)
+    }
+}
+
+/// Escapes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// The annotations of a recording, shared between the recorder and its consumers.
+///
+/// Clones annotate the same recording, so an ASR or diarization thread can annotate
+/// the chunks it received while the recording goes on, and after it ended.
+#[derive(Debug, Clone, Default)]
+pub struct AnnotationLog(Arc<Mutex<Vec<Annotation>>>);
+
+impl AnnotationLog {
+    pub fn annotate(&self, annotation: Annotation) {
+        tracing::debug!("Annotating {:?}", annotation);
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(annotation);
+    }
+
+    /// Annotates the frames of `chunk`, see `Annotation::of_chunk`.
+    pub fn annotate_chunk(&self, chunk: &AudioChunk, kind: AnnotationKind, value: &str) {
+        self.annotate(Annotation::of_chunk(chunk, kind, value));
+    }
+
+    /// Every annotation, ordered by their first frame.
+    pub fn snapshot(&self) -> Vec<Annotation> {
+        let mut annotations = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        annotations.sort_by_key(|annotation| (annotation.frames.start, annotation.frames.end));
+        annotations
+    }
+
+    /// The annotations overlapping `frames`, ordered by their first frame.
+    pub fn overlapping(&self, frames: Range<u64>) -> Vec<Annotation> {
+        self.snapshot()
+            .into_iter()
+            .filter(|annotation| {
+                annotation.frames.start < frames.end && frames.start < annotation.frames.end
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Writes the manifest of the recording to `path`: the stream described by
+    /// `summary`, the `files` it was written to and every annotation, as JSON.
+    ///
+    /// ```json
+    /// {
+    ///   "sample_rate": 48000,
+    ///   "channels": 2,
+    ///   "frames": 96000,
+    ///   "files": ["meeting.wav"],
+    ///   "annotations": [
+    ///     {"start_frame": 0, "end_frame": 48000, "kind": "speaker", "value": "alice"}
+    ///   ]
+    /// }
+    /// ```
+    pub fn write_manifest(
+        &self,
+        path: impl AsRef<Path>,
+        summary: Option<&StreamSummary>,
+        files: &[PathBuf],
+    ) -> Result<(), AudioRecorderError> {
+        let path = path.as_ref();
+        let annotations = self.snapshot();
+        tracing::debug!(
+            "Writing the manifest {:?} with {} annotations",
+            path,
+            annotations.len()
+        );
+
+        let mut json = String::from("{\n");
+        if let Some(summary) = summary {
+            let _ = writeln!(json, "  \"sample_rate\": {},", summary.sample_rate);
+            let _ = writeln!(json, "  \"channels\": {},", summary.channels);
+            let _ = writeln!(json, "  \"frames\": {},", summary.frames);
+        }
+
+        let files: Vec<String> = files
+            .iter()
+            .map(|file| json_string(&file.to_string_lossy()))
+            .collect();
+        let _ = writeln!(json, "  \"files\": [{}],", files.join(", "));
+
+        json.push_str("  \"annotations\": [");
+        for (index, annotation) in annotations.iter().enumerate() {
+            let separator = match index {
+                0 => "\n",
+                _ => ",\n",
+            };
+            let _ = write!(
+                json,
+                "{separator}    {{\"start_frame\": {}, \"end_frame\": {}, \"kind\": {}, \"value\": {}}}",
+                annotation.frames.start,
+                annotation.frames.end,
+                json_string(annotation.kind.name()),
+                json_string(&annotation.value)
+            );
+        }
+        match annotations.is_empty() {
+            true => json.push_str("]\n}\n"),
+            false => json.push_str("\n  ]\n}\n"),
+        }
+
+        std::fs::write(path, json).map_err(AudioRecorderError::IoError)
+    }
+}
+
+impl Recorder {
+    /// The annotations of the current (or last) recording, a handle consumers attach
+    /// metadata to chunk ranges with (speaker, language, external ASR text), so the
+    /// recorder keeps the single timeline of the recording.
+    ///
+    /// The annotations are written to the manifest of the `WavSink` of the recording,
+    /// or with `AnnotationLog::write_manifest`. They are cleared when a recording starts.
+    pub fn annotations(&self) -> AnnotationLog {
+        self.annotations.clone()
+    }
+}
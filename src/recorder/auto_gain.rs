@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use super::{Recorder, dsp::agc::AutoGain};
+
+/// Settings of the automatic gain control of the microphone, see
+/// `Recorder::set_auto_gain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoGainControl {
+    /// RMS level, in dBFS, the microphone is brought to.
+    pub target_db: f32,
+    /// Largest gain applied, in dB, so a quiet room is not boosted to noise.
+    pub max_gain_db: f32,
+    /// How quickly the gain drops when the level rises.
+    pub attack: Duration,
+    /// How quickly the gain rises when the level drops.
+    pub release: Duration,
+}
+
+impl Default for AutoGainControl {
+    fn default() -> Self {
+        AutoGainControl {
+            target_db: -20.0,
+            max_gain_db: 30.0,
+            attack: Duration::from_millis(50),
+            release: Duration::from_millis(1500),
+        }
+    }
+}
+
+impl AutoGainControl {
+    /// Creates the processor of a microphone stream with the given layout.
+    pub fn processor(&self, sample_rate: u32, channels: u16) -> AutoGain {
+        AutoGain::new(
+            sample_rate,
+            channels,
+            self.target_db,
+            self.max_gain_db,
+            self.attack,
+            self.release,
+        )
+    }
+}
+
+impl Recorder {
+    /// Adjusts the gain of the microphone continuously to bring it to the target level
+    /// of `agc` (or `None` to keep the gain set with `set_gain`).
+    ///
+    /// Raw microphone levels vary wildly across hardware, and speech recognition is
+    /// more accurate at a consistent level. The automatic gain is applied after the
+    /// manual gain and the noise profile.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_auto_gain(&mut self, agc: Option<AutoGainControl>) {
+        self.auto_gain = agc;
+    }
+}
@@ -0,0 +1,383 @@
+use std::sync::{
+    Arc, Weak,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use crossbeam_channel::{Receiver, SendError, SendTimeoutError, Sender, TrySendError};
+
+use super::{
+    Recorder,
+    constants::{BACKPRESSURE_POLL_INTERVAL, END_OF_STREAM_TIMEOUT},
+    delivery::StreamMessage,
+};
+
+/// What happens to a chunk delivered while the bounded data channel is full, see
+/// `Recorder::set_backpressure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum BackpressurePolicy {
+    /// The default: the delivery waits for the consumer, which stalls the capture
+    /// threads, so the devices overrun instead.
+    #[default]
+    Block,
+    /// The oldest chunk on the channel is dropped to make room, keeping the consumer
+    /// close to live audio.
+    DropOldest,
+    /// The chunk is dropped, keeping the audio the consumer has not received yet.
+    DropNewest,
+}
+
+/// The policy of a bounded data channel, applied by the `ChunkSender`.
+pub struct Backpressure {
+    policy: BackpressurePolicy,
+    /// Receiving side of the channel, to drop the oldest chunk.
+    ///
+    /// This keeps the channel connected, so a consumer that went away is detected with
+    /// `consumer` instead.
+    receiver: Receiver<StreamMessage>,
+    /// Dead once the consumer dropped every `AudioReceiver` of the channel.
+    consumer: Weak<()>,
+    /// The chunks dropped from this stream.
+    dropped: u64,
+    /// The chunks dropped from every stream of the recording.
+    counter: Arc<AtomicU64>,
+    /// Whether the stream is ending, see `close`.
+    closing: bool,
+    /// Whether the consumer stopped reading while the stream was ending.
+    stalled: bool,
+    /// Cleared when the recording stops, ending the wait of `Block`.
+    recording_signal: Arc<AtomicBool>,
+}
+
+impl Backpressure {
+    pub fn new(
+        policy: BackpressurePolicy,
+        receiver: Receiver<StreamMessage>,
+        consumer: Weak<()>,
+        counter: Arc<AtomicU64>,
+        recording_signal: Arc<AtomicBool>,
+    ) -> Self {
+        Backpressure {
+            policy,
+            receiver,
+            consumer,
+            dropped: 0,
+            counter,
+            closing: false,
+            stalled: false,
+            recording_signal,
+        }
+    }
+
+    /// Marks the stream as ending, so `Block` stops waiting on the consumer forever:
+    /// the remaining sends wait at most `END_OF_STREAM_TIMEOUT`, then evict the oldest
+    /// chunks like `DropOldest`, and the end marker still arrives last.
+    pub fn close(&mut self) {
+        self.closing = true;
+    }
+
+    /// Whether the consumer dropped the channel, which then counts as disconnected.
+    fn consumer_gone(&self) -> bool {
+        self.consumer.strong_count() == 0
+    }
+
+    /// Number of chunks dropped from this stream.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn count_drop(&mut self) {
+        self.dropped += 1;
+        let total = self.counter.fetch_add(1, Ordering::Relaxed) + 1;
+        match total {
+            1 => tracing::warn!("Data channel full, dropping chunks ({:?})", self.policy),
+            _ => tracing::debug!("Data channel full, dropped {} chunks", total),
+        }
+    }
+
+    /// Sends `message` on `tx`, the bounded channel of the policy.
+    ///
+    /// The end marker is never dropped: when the channel is full, it replaces the oldest
+    /// chunk whatever the policy, except `Block` which waits for the consumer until the
+    /// recording stops, then up to `END_OF_STREAM_TIMEOUT`. Once the consumer dropped the
+    /// channel, every send fails as if it were disconnected.
+    pub fn send(
+        &mut self,
+        tx: &Sender<StreamMessage>,
+        mut message: StreamMessage,
+    ) -> Result<(), SendError<StreamMessage>> {
+        if self.consumer_gone() {
+            return Err(SendError(message));
+        }
+
+        if self.policy == BackpressurePolicy::Block && !self.stalled {
+            while !self.closing {
+                message = match tx.send_timeout(message, BACKPRESSURE_POLL_INTERVAL) {
+                    Ok(()) => return Ok(()),
+                    Err(SendTimeoutError::Disconnected(message)) => {
+                        return Err(SendError(message));
+                    }
+                    Err(SendTimeoutError::Timeout(message)) => message,
+                };
+
+                if self.consumer_gone() {
+                    tracing::debug!("Consumer dropped the data channel while it was full");
+                    return Err(SendError(message));
+                }
+                if !self.recording_signal.load(Ordering::Relaxed) {
+                    self.close();
+                }
+            }
+
+            message = match tx.send_timeout(message, END_OF_STREAM_TIMEOUT) {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Disconnected(message)) => return Err(SendError(message)),
+                Err(SendTimeoutError::Timeout(message)) if self.consumer_gone() => {
+                    return Err(SendError(message));
+                }
+                Err(SendTimeoutError::Timeout(message)) => {
+                    tracing::warn!("Consumer stopped reading, evicting chunks to end the stream");
+                    self.stalled = true;
+                    message
+                }
+            };
+        }
+
+        loop {
+            let rejected = match tx.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(message)) => return Err(SendError(message)),
+                Err(TrySendError::Full(rejected)) => rejected,
+            };
+
+            match (&rejected, self.policy) {
+                (StreamMessage::Data(_), BackpressurePolicy::DropNewest) => {
+                    self.count_drop();
+                    return Ok(());
+                }
+                _ => {
+                    if self.receiver.try_recv().is_ok() {
+                        self.count_drop();
+                    }
+                }
+            }
+            message = rejected;
+        }
+    }
+}
+
+impl Recorder {
+    /// Bounds the data channel to `capacity` chunks (or `None` for the default
+    /// unbounded channel), applying `policy` to the chunks delivered while it is full, so
+    /// a stalled consumer cannot make the buffered audio grow without limit.
+    ///
+    /// Dropped chunks leave gaps in the `sequence` of the chunks received, and are
+    /// counted by `get_dropped_chunks` and `StreamSummary::dropped_chunks`. With
+    /// `BackpressurePolicy::Block`, the capture waits for the consumer, and since some
+    /// streams are delivered from the capture callbacks, a slow consumer then makes the
+    /// devices overrun. When the recording ends, `Block` waits at most a second for a
+    /// consumer that stopped reading, then drops the oldest chunks so that `stop`
+    /// returns and the end marker still gets through. A consumer that drops its
+    /// `AudioReceiver` ends the delivery of the stream, whatever the policy.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_backpressure(&mut self, capacity: Option<usize>, policy: BackpressurePolicy) {
+        self.channel_capacity = capacity.map(|capacity| capacity.max(1));
+        self.backpressure = policy;
+    }
+
+    /// Returns the number of chunks dropped by the backpressure policy in the current
+    /// (or last) recording, see `set_backpressure`.
+    pub fn get_dropped_chunks(&self) -> u64 {
+        self.dropped_chunks.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::recorder::{chunk::AudioChunk, delivery::StreamSummary};
+
+    fn data(sequence: u64) -> StreamMessage {
+        StreamMessage::Data(AudioChunk {
+            samples: vec![0.0; 4],
+            channels: 1,
+            sample_rate: 48000,
+            timestamp: Instant::now(),
+            sequence,
+            frame: sequence * 4,
+            speech: None,
+            clock_time: None,
+            time: Duration::ZERO,
+        })
+    }
+
+    fn end() -> StreamMessage {
+        StreamMessage::End(StreamSummary {
+            frames: 0,
+            chunks: 0,
+            sample_rate: 48000,
+            channels: 1,
+            fingerprint: None,
+            levels: Vec::new(),
+            dropped_chunks: 0,
+        })
+    }
+
+    /// A full channel of `capacity` chunks, numbered from 0, and the consumer keeping it
+    /// alive.
+    fn full_channel(
+        policy: BackpressurePolicy,
+        capacity: u64,
+    ) -> (
+        Sender<StreamMessage>,
+        Receiver<StreamMessage>,
+        Arc<()>,
+        Backpressure,
+    ) {
+        let (tx, rx) = crossbeam_channel::bounded(capacity as usize);
+        let consumer = Arc::new(());
+        let mut backpressure = Backpressure::new(
+            policy,
+            rx.clone(),
+            Arc::downgrade(&consumer),
+            Arc::default(),
+            Arc::new(AtomicBool::new(true)),
+        );
+        for sequence in 0..capacity {
+            backpressure
+                .send(&tx, data(sequence))
+                .expect("Failed to send");
+        }
+        (tx, rx, consumer, backpressure)
+    }
+
+    /// Sequences of the chunks on the channel, `None` for the end marker.
+    fn drain(rx: &Receiver<StreamMessage>) -> Vec<Option<u64>> {
+        rx.try_iter()
+            .map(|message| match message {
+                StreamMessage::Data(chunk) => Some(chunk.sequence),
+                StreamMessage::End(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest_evicts_the_oldest_chunk() {
+        let (tx, rx, _consumer, mut backpressure) = full_channel(BackpressurePolicy::DropOldest, 2);
+
+        backpressure.send(&tx, data(2)).expect("Failed to send");
+
+        assert_eq!(drain(&rx), vec![Some(1), Some(2)]);
+        assert_eq!(backpressure.dropped(), 1);
+    }
+
+    #[test]
+    fn drop_newest_discards_the_delivered_chunk() {
+        let (tx, rx, _consumer, mut backpressure) = full_channel(BackpressurePolicy::DropNewest, 2);
+
+        backpressure.send(&tx, data(2)).expect("Failed to send");
+
+        assert_eq!(drain(&rx), vec![Some(0), Some(1)]);
+        assert_eq!(backpressure.dropped(), 1);
+    }
+
+    #[test]
+    fn drop_policies_never_drop_the_end_marker() {
+        for policy in [
+            BackpressurePolicy::DropOldest,
+            BackpressurePolicy::DropNewest,
+        ] {
+            let (tx, rx, _consumer, mut backpressure) = full_channel(policy, 2);
+
+            backpressure.send(&tx, end()).expect("Failed to send");
+
+            assert_eq!(drain(&rx), vec![Some(1), None], "{policy:?}");
+            assert_eq!(backpressure.dropped(), 1, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn block_waits_for_the_consumer() {
+        let (tx, rx, _consumer, mut backpressure) = full_channel(BackpressurePolicy::Block, 2);
+
+        let consumer = rx.clone();
+        let reader = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            consumer.recv().is_ok()
+        });
+
+        backpressure.send(&tx, data(2)).expect("Failed to send");
+        assert!(reader.join().unwrap_or(false));
+
+        assert_eq!(drain(&rx), vec![Some(1), Some(2)]);
+        assert_eq!(backpressure.dropped(), 0);
+    }
+
+    #[test]
+    fn block_evicts_for_the_end_marker_once_closing() {
+        let (tx, rx, _consumer, mut backpressure) = full_channel(BackpressurePolicy::Block, 2);
+        backpressure.close();
+
+        let started = Instant::now();
+        backpressure.send(&tx, data(2)).expect("Failed to send");
+        backpressure.send(&tx, end()).expect("Failed to send");
+
+        // only the first send waits, the consumer is then known to have stopped
+        assert!(started.elapsed() < END_OF_STREAM_TIMEOUT * 2);
+        assert_eq!(drain(&rx), vec![Some(2), None]);
+        assert_eq!(backpressure.dropped(), 2);
+    }
+
+    #[test]
+    fn block_ends_the_wait_when_the_recording_stops() {
+        let (tx, rx, _consumer, mut backpressure) = full_channel(BackpressurePolicy::Block, 2);
+        let recording_signal = backpressure.recording_signal.clone();
+
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            recording_signal.store(false, Ordering::Relaxed);
+        });
+
+        let started = Instant::now();
+        backpressure.send(&tx, data(2)).expect("Failed to send");
+        backpressure.send(&tx, end()).expect("Failed to send");
+        stopper.join().expect("Failed to join the stopper");
+
+        assert!(started.elapsed() < END_OF_STREAM_TIMEOUT * 2);
+        assert_eq!(drain(&rx), vec![Some(2), None]);
+    }
+
+    #[test]
+    fn dropped_consumer_disconnects_the_channel() {
+        for policy in [
+            BackpressurePolicy::Block,
+            BackpressurePolicy::DropOldest,
+            BackpressurePolicy::DropNewest,
+        ] {
+            let (tx, _rx, consumer, mut backpressure) = full_channel(policy, 2);
+
+            // the consumer goes away while the capture is delivering
+            let dropper = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                drop(consumer);
+            });
+
+            let started = Instant::now();
+            while backpressure.send(&tx, data(2)).is_ok() {
+                assert!(
+                    started.elapsed() < END_OF_STREAM_TIMEOUT,
+                    "{policy:?} kept delivering to a dropped consumer"
+                );
+            }
+            dropper.join().expect("Failed to join the dropper");
+
+            assert!(backpressure.send(&tx, end()).is_err(), "{policy:?}");
+        }
+    }
+}
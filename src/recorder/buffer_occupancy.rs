@@ -0,0 +1,107 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use super::{
+    Recorder,
+    constants::{AudioSource, BACKPRESSURE_HIGH, BACKPRESSURE_LOW},
+};
+
+/// Fill level of the ring buffer between the capture callback of a source and the
+/// thread mixing it, see `Recorder::get_buffer_occupancy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct BufferOccupancy {
+    /// Samples waiting in the buffer after the last callback.
+    pub occupied: usize,
+    /// Samples the buffer holds, past which the source drops audio.
+    pub capacity: usize,
+    /// Highest `occupied` of the current (or last) recording.
+    pub peak: usize,
+}
+
+impl BufferOccupancy {
+    /// `occupied` as a fraction of `capacity`, from 0 to 1.
+    pub fn fill(&self) -> f32 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+
+        self.occupied as f32 / self.capacity as f32
+    }
+}
+
+/// Occupancy of the ring buffer of a source, shared with its capture callback.
+#[derive(Debug, Clone, Default)]
+pub struct OccupancyCounters {
+    counters: Arc<[AtomicUsize; 3]>,
+    /// Whether the fill is above `BACKPRESSURE_HIGH` and not yet back below
+    /// `BACKPRESSURE_LOW`.
+    pressured: Arc<AtomicBool>,
+}
+
+const OCCUPIED: usize = 0;
+const CAPACITY: usize = 1;
+const PEAK: usize = 2;
+
+impl OccupancyCounters {
+    /// Records the occupancy after a callback, returning whether the buffer just
+    /// became pressured (`Some(true)`) or relieved (`Some(false)`).
+    pub fn record(&self, occupied: usize, capacity: usize) -> Option<bool> {
+        self.counters[OCCUPIED].store(occupied, Ordering::Relaxed);
+        self.counters[CAPACITY].store(capacity, Ordering::Relaxed);
+        self.counters[PEAK].fetch_max(occupied, Ordering::Relaxed);
+
+        let fill = occupied as f32 / capacity.max(1) as f32;
+        let pressured = match self.pressured.load(Ordering::Relaxed) {
+            false => fill >= BACKPRESSURE_HIGH,
+            true => fill > BACKPRESSURE_LOW,
+        };
+
+        match self.pressured.swap(pressured, Ordering::Relaxed) == pressured {
+            true => None,
+            false => Some(pressured),
+        }
+    }
+
+    /// Whether the buffer filled past `BACKPRESSURE_HIGH` and did not drain since.
+    pub fn is_pressured(&self) -> bool {
+        self.pressured.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.counters
+            .iter()
+            .for_each(|counter| counter.store(0, Ordering::Relaxed));
+        self.pressured.store(false, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> BufferOccupancy {
+        BufferOccupancy {
+            occupied: self.counters[OCCUPIED].load(Ordering::Relaxed),
+            capacity: self.counters[CAPACITY].load(Ordering::Relaxed),
+            peak: self.counters[PEAK].load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Recorder {
+    /// Returns the fill level of the ring buffer `source` is captured into, when
+    /// recording both devices.
+    ///
+    /// A buffer filling up means its consumer falls behind and audio is about to be
+    /// dropped, so adaptive consumers (e.g. an encoder that can lower its bitrate) can
+    /// react first. The buffer crossing 75% and falling back below 25% is also reported
+    /// with `RecorderEvent::Backpressure` and `RecorderEvent::BackpressureRelieved`.
+    ///
+    /// Sources read straight from their callback (the microphone when both devices run at
+    /// the same rate) report an empty buffer. The backlog of a data channel itself is
+    /// `AudioReceiver::len`.
+    pub fn get_buffer_occupancy(&self, source: AudioSource) -> BufferOccupancy {
+        match source {
+            AudioSource::Input => self.input_buffer.snapshot(),
+            AudioSource::Output => self.output_buffer.snapshot(),
+        }
+    }
+}
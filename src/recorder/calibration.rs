@@ -0,0 +1,294 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use cpal::traits::DeviceTrait;
+
+use super::{
+    Recorder,
+    config::RecorderConfig,
+    constants::{
+        ALIGNMENT_CAPTURE_MS, ALIGNMENT_MAX_MS, ALIGNMENT_MIN_CORRELATION, ALIGNMENT_PROBE_MS,
+        CALIBRATION_MAX_GAIN_DB, CALIBRATION_TARGET_PEAK_DB, CALIBRATION_WINDOW_MS, TargetFormat,
+    },
+    devices::{AudioDevice, DeviceType},
+    dsp::level::{peak, rms, to_dbfs},
+    errors::AudioRecorderError,
+    get_default_device::get_default_output_device,
+    receiver::{AudioReceiver, AudioRecvError},
+};
+
+/// Result of an input calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct CalibrationReport {
+    /// Level of the quietest windows of the capture (10th percentile), in dBFS.
+    pub noise_floor_db: f32,
+    /// Peak level of the capture, in dBFS.
+    pub peak_db: f32,
+    /// Gain bringing the peak to the calibration target, in dB.
+    pub recommended_gain_db: f32,
+}
+
+/// Result of an alignment calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct AlignmentReport {
+    /// How long after the system audio the microphone picked up the probe, in
+    /// milliseconds, to pass to `Recorder::set_alignment_offset_ms`.
+    pub offset_ms: i32,
+    /// Normalized correlation of the two captures at that offset, from 0 to 1.
+    pub correlation: f32,
+}
+
+/// A capture reduced to its RMS level every millisecond, starting at `start`.
+struct Envelope {
+    start: Instant,
+    levels: Vec<f32>,
+}
+
+/// Receives one stream of the calibration until `deadline`, reducing it to its envelope.
+fn capture_envelope(receiver: &AudioReceiver, deadline: Instant) -> Option<Envelope> {
+    let mut start = None;
+    let mut levels = Vec::new();
+    let mut pending: Vec<TargetFormat> = Vec::new();
+
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        let chunk = match receiver.recv_timeout(timeout) {
+            Ok(chunk) => chunk,
+            Err(AudioRecvError::Ended) => break,
+            Err(_) => continue,
+        };
+
+        let window = (chunk.sample_rate / 1000).max(1) as usize * chunk.channels.max(1) as usize;
+        start.get_or_insert(chunk.timestamp);
+        pending.extend(chunk.samples);
+
+        let whole = pending.len() - pending.len() % window;
+        levels.extend(pending[..whole].chunks(window).map(rms));
+        pending.drain(..whole);
+    }
+
+    Some(Envelope {
+        start: start?,
+        levels,
+    })
+}
+
+/// Subtracts the mean of the levels, so the correlation follows their changes.
+fn centered(levels: &[f32]) -> Vec<f32> {
+    let mean = levels.iter().sum::<f32>() / levels.len().max(1) as f32;
+    levels.iter().map(|level| level - mean).collect()
+}
+
+/// Finds how many milliseconds after the system audio envelope the microphone envelope
+/// best matches it, along with the normalized correlation at that lag.
+fn correlate(mic: &Envelope, system: &Envelope) -> (i32, f32) {
+    // position of the system capture on the timeline of the microphone capture
+    let system_start = match system.start.checked_duration_since(mic.start) {
+        Some(later) => later.as_millis() as i64,
+        None => -(mic.start.duration_since(system.start).as_millis() as i64),
+    };
+
+    let mic_levels = centered(&mic.levels);
+    let system_levels = centered(&system.levels);
+    let energy = |levels: &[f32]| levels.iter().map(|l| l * l).sum::<f32>();
+    let norm = (energy(&mic_levels) * energy(&system_levels)).sqrt();
+    if norm <= f32::EPSILON {
+        return (0, 0.0);
+    }
+
+    let mut best = (0, f32::MIN);
+    for lag in -ALIGNMENT_MAX_MS..=ALIGNMENT_MAX_MS {
+        let offset = system_start + lag as i64;
+        let sum: f32 = system_levels
+            .iter()
+            .enumerate()
+            .filter_map(|(j, level)| {
+                let i = usize::try_from(j as i64 + offset).ok()?;
+                Some(level * mic_levels.get(i)?)
+            })
+            .sum();
+
+        if sum > best.1 {
+            best = (lag, sum);
+        }
+    }
+
+    (best.0, best.1 / norm)
+}
+
+/// Computes the calibration report of interleaved `samples`.
+fn analyze(samples: &[TargetFormat], sample_rate: u32, channels: u16) -> CalibrationReport {
+    let window_frames = (sample_rate * CALIBRATION_WINDOW_MS / 1000).max(1) as usize;
+    let window = window_frames * channels.max(1) as usize;
+
+    let mut levels: Vec<f32> = samples.chunks(window).map(rms).collect();
+    levels.sort_by(f32::total_cmp);
+
+    let noise_floor = levels.get(levels.len() / 10).copied().unwrap_or(0.0);
+    let peak_db = to_dbfs(peak(samples));
+
+    CalibrationReport {
+        noise_floor_db: to_dbfs(noise_floor),
+        peak_db,
+        recommended_gain_db: (CALIBRATION_TARGET_PEAK_DB - peak_db)
+            .clamp(-CALIBRATION_MAX_GAIN_DB, CALIBRATION_MAX_GAIN_DB),
+    }
+}
+
+impl Recorder {
+    /// Guided "set up your mic" routine.
+    ///
+    /// Records the input device for `duration` without gain (nor automatic gain), then
+    /// reports its noise floor, peak and the gain that brings the peak to -3 dBFS. The
+    /// user should speak at their normal level during the capture.
+    ///
+    /// When `apply_gain` is `true` the recommended gain becomes the input gain of the
+    /// following recordings, otherwise the previous input gain is restored.
+    #[tracing::instrument]
+    pub fn calibrate_input(
+        &mut self,
+        duration: Duration,
+        apply_gain: bool,
+    ) -> Result<CalibrationReport, AudioRecorderError> {
+        tracing::info!("Calibrating input");
+
+        if self.get_is_recording() {
+            tracing::warn!("Recording is already in progress");
+            return Err(AudioRecorderError::RecordingInProgress);
+        }
+
+        let previous_gain_db = self.get_input_gain_db();
+        self.set_input_gain_db(0.0);
+        // the level is measured as the device delivers it
+        let previous_agc = self.auto_gain.take();
+
+        let receiver = match self.start(RecorderConfig::builder().input_only(true).build()) {
+            Ok(r) => r,
+            Err(e) => {
+                self.set_input_gain_db(previous_gain_db);
+                self.auto_gain = previous_agc;
+                return Err(e);
+            }
+        };
+
+        let config = match self.get_config() {
+            Ok(c) => c,
+            Err(e) => {
+                self.stop();
+                self.set_input_gain_db(previous_gain_db);
+                self.auto_gain = previous_agc;
+                return Err(e);
+            }
+        };
+
+        let mut samples: Vec<TargetFormat> = Vec::new();
+        let instant = Instant::now();
+
+        while instant.elapsed() < duration {
+            match receiver.recv_timeout(duration.saturating_sub(instant.elapsed())) {
+                Ok(chunk) => samples.extend(chunk.samples),
+                Err(AudioRecvError::Ended) => break,
+                Err(_) => {}
+            }
+        }
+
+        self.stop();
+        self.auto_gain = previous_agc;
+
+        let report = analyze(&samples, config.sample_rate, config.channels);
+        tracing::info!("Calibration finished: {:?}", report);
+
+        if apply_gain {
+            self.set_input_gain_db(report.recommended_gain_db);
+        } else {
+            self.set_input_gain_db(previous_gain_db);
+        }
+
+        Ok(report)
+    }
+
+    /// Measures the offset between the microphone and the system audio of this setup.
+    ///
+    /// Both default devices are recorded while a short probe tone plays on the default
+    /// output device, and the offset at which the microphone best picks up the probe
+    /// captured as system audio is found by cross-correlating their levels. The probe
+    /// must reach the microphone through speakers, so this fails with headphones.
+    ///
+    /// When `apply` is `true` the measured offset becomes the alignment offset of the
+    /// following recordings, see `set_alignment_offset_ms`.
+    #[tracing::instrument]
+    pub fn calibrate_alignment(
+        &mut self,
+        apply: bool,
+    ) -> Result<AlignmentReport, AudioRecorderError> {
+        tracing::info!("Calibrating alignment");
+
+        let output = get_default_output_device()?.name().map_err(|e| {
+            tracing::error!("Failed to get the output device name: {}", e);
+            AudioRecorderError::DeviceError("Failed to get the output device name")
+        })?;
+        let output = AudioDevice::find(&output, &[DeviceType::Output])?;
+
+        let receivers = self.start_separate()?;
+        let deadline = Instant::now() + Duration::from_millis(ALIGNMENT_CAPTURE_MS);
+
+        let system = receivers.system.clone();
+        let system = thread::Builder::new()
+            .name("arec-alignment".to_string())
+            .spawn(move || capture_envelope(&system, deadline));
+        let system = match system {
+            Ok(system) => system,
+            Err(e) => {
+                tracing::error!("Failed to spawn alignment thread: {}", e);
+                self.stop();
+                return Err(AudioRecorderError::IoError(e));
+            }
+        };
+
+        // a moment of silence ahead of the probe, so its onset stands out
+        let probe = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(ALIGNMENT_CAPTURE_MS / 4));
+            output.play_test_tone(Duration::from_millis(ALIGNMENT_PROBE_MS))
+        });
+
+        let mic = capture_envelope(&receivers.mic, deadline);
+        self.stop();
+
+        let probe = probe
+            .join()
+            .unwrap_or_else(|_| Err(AudioRecorderError::ThreadPanicked("Probe")));
+        let system = system.join().ok().flatten();
+        probe?;
+
+        let (mic, system) = match (mic, system) {
+            (Some(mic), Some(system)) => (mic, system),
+            _ => {
+                tracing::error!("A stream delivered nothing during the calibration");
+                return Err(AudioRecorderError::NotEnoughAudio(
+                    "a stream delivered nothing during the calibration",
+                ));
+            }
+        };
+
+        let (offset_ms, correlation) = correlate(&mic, &system);
+        let report = AlignmentReport {
+            offset_ms,
+            correlation,
+        };
+        tracing::info!("Alignment calibration finished: {:?}", report);
+
+        if correlation < ALIGNMENT_MIN_CORRELATION {
+            tracing::error!("The microphone did not pick up the probe");
+            return Err(AudioRecorderError::ProbeNotDetected { correlation });
+        }
+
+        if apply {
+            self.set_alignment_offset_ms(offset_ms);
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use super::{Recorder, constants::AudioSource};
+
+/// Statistics of the capture callbacks of a stream, see `Recorder::get_callback_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct CallbackStats {
+    /// Number of callbacks of the current (or last) recording.
+    pub count: u64,
+    /// Average time spent in a callback.
+    pub average: Duration,
+    /// Longest time spent in a callback.
+    pub max: Duration,
+    /// Number of callbacks that took longer than the audio they carried, i.e. too long
+    /// for real time.
+    pub over_budget: u64,
+}
+
+/// Counters of the capture callbacks of a stream, shared with the callbacks.
+#[derive(Debug, Clone, Default)]
+pub struct CallbackCounters(Arc<[AtomicU64; 4]>);
+
+const COUNT: usize = 0;
+const TOTAL_NANOS: usize = 1;
+const MAX_NANOS: usize = 2;
+const OVER_BUDGET: usize = 3;
+
+impl CallbackCounters {
+    /// Records a callback that started at `started` and carried `budget` of audio,
+    /// returning how long it took when that exceeds the budget.
+    pub fn record(&self, started: Instant, budget: Duration) -> Option<Duration> {
+        let elapsed = started.elapsed();
+        let nanos = elapsed.as_nanos() as u64;
+
+        self.0[COUNT].fetch_add(1, Ordering::Relaxed);
+        self.0[TOTAL_NANOS].fetch_add(nanos, Ordering::Relaxed);
+        self.0[MAX_NANOS].fetch_max(nanos, Ordering::Relaxed);
+
+        if budget.is_zero() || elapsed <= budget {
+            return None;
+        }
+
+        self.0[OVER_BUDGET].fetch_add(1, Ordering::Relaxed);
+        Some(elapsed)
+    }
+
+    pub fn reset(&self) {
+        self.0
+            .iter()
+            .for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+
+    pub fn snapshot(&self) -> CallbackStats {
+        let count = self.0[COUNT].load(Ordering::Relaxed);
+        let total = self.0[TOTAL_NANOS].load(Ordering::Relaxed);
+
+        CallbackStats {
+            count,
+            average: Duration::from_nanos(total.checked_div(count).unwrap_or(0)),
+            max: Duration::from_nanos(self.0[MAX_NANOS].load(Ordering::Relaxed)),
+            over_budget: self.0[OVER_BUDGET].load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Recorder {
+    /// Returns the count and duration of the capture callbacks of `source` in the
+    /// current (or last) recording.
+    ///
+    /// A callback exceeding its budget (the duration of the audio it carries) is also
+    /// reported with `RecorderEvent::SlowCallback`, e.g. when analyzers or sinks fed from
+    /// the callback are too slow for real time.
+    pub fn get_callback_stats(&self, source: AudioSource) -> CallbackStats {
+        match source {
+            AudioSource::Input => self.input_callbacks.snapshot(),
+            AudioSource::Output => self.output_callbacks.snapshot(),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use super::{Recorder, clock_source::ClockMapping};
+
+/// A handle on the clock of the recordings of a `Recorder`, mapping the frames of the
+/// delivered stream to monotonic time and back.
+///
+/// It is `Send + Sync` and cheap to clone, so it can be handed to a screen or video
+/// capture, whose frames are then placed on the timeline of the audio: `media_time`
+/// of the `Instant` a video frame was captured is its presentation time, on the same
+/// clock as `AudioChunk::position`.
+///
+/// The clock follows the drift of the audio device, as of the last delivered chunk.
+/// It reads `None` until the first chunk of a recording was delivered.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Instant;
+///
+/// use audio_recorder_rs::{Recorder, RecorderConfig};
+///
+/// let mut recorder = Recorder::new();
+/// let clock = recorder.capture_clock();
+/// let receiver = recorder
+///     .start(RecorderConfig::default())
+///     .expect("Failed to start recording");
+///
+/// std::thread::spawn(move || {
+///     // in the video capture thread
+///     let captured = Instant::now();
+///     if let Some(pts) = clock.media_time(captured) {
+///         println!("Video frame at {:?}", pts);
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureClock {
+    mapping: Arc<Mutex<Option<ClockMapping>>>,
+}
+
+impl CaptureClock {
+    /// The latest reading of the clocks, see `Recorder::get_clock_mapping`.
+    pub fn mapping(&self) -> Option<ClockMapping> {
+        *self.mapping.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sample rate of the delivered stream.
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.mapping().map(|mapping| mapping.sample_rate)
+    }
+
+    /// Monotonic time of the first frame of the recording.
+    pub fn origin(&self) -> Option<Instant> {
+        self.mapping().map(|mapping| mapping.origin)
+    }
+
+    /// Monotonic time at which `frame`, as counted by `AudioChunk::frame`, was captured.
+    pub fn instant_of(&self, frame: u64) -> Option<Instant> {
+        self.mapping()
+            .map(|mapping| mapping.to_monotonic(mapping.frame_time(frame)))
+    }
+
+    /// The frame captured at `instant`, zero before the recording started.
+    pub fn frame_of(&self, instant: Instant) -> Option<u64> {
+        self.mapping()
+            .map(|mapping| mapping.frame_at(mapping.from_monotonic(instant)))
+    }
+
+    /// Position of `instant` on the timeline of the audio, the stream time of the frame
+    /// captured then, e.g. the presentation time of a video frame.
+    pub fn media_time(&self, instant: Instant) -> Option<Duration> {
+        self.mapping()
+            .map(|mapping| mapping.from_monotonic(instant))
+    }
+}
+
+impl Recorder {
+    /// Returns a handle on the clock of the recordings, to share their timeline with
+    /// other media, see `CaptureClock`.
+    pub fn capture_clock(&self) -> CaptureClock {
+        CaptureClock {
+            mapping: self.clock_mapping.clone(),
+        }
+    }
+}
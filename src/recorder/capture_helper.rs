@@ -0,0 +1,307 @@
+use std::{
+    env,
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::TryRecvError;
+
+use super::{
+    Recorder,
+    chunk_codec::{read_chunk, write_chunk},
+    config::RecorderConfig,
+    delivery::{StreamMessage, StreamSummary},
+    errors::AudioRecorderError,
+    receiver::{AudioReceiver, AudioRecvError},
+};
+
+/// Environment variable telling a process started by `CaptureHelper` to run the helper.
+const HELPER_ENV: &str = "AUDIO_RECORDER_HELPER";
+const HELPER_INPUT_ONLY_ENV: &str = "AUDIO_RECORDER_HELPER_INPUT_ONLY";
+const HELPER_SAMPLE_RATE_ENV: &str = "AUDIO_RECORDER_HELPER_SAMPLE_RATE";
+const HELPER_STANDARD_ENV: &str = "AUDIO_RECORDER_HELPER_STANDARD_OUTPUT";
+/// How often the helper checks whether the parent asked it to stop.
+const HELPER_POLL: Duration = Duration::from_millis(50);
+
+/// Settings of a `CaptureHelper`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HelperSettings {
+    /// Executable started as the helper, the current executable when `None`. It must
+    /// call `run_capture_helper` first thing in `main`.
+    pub program: Option<PathBuf>,
+    /// Records only the input device, see `RecorderConfigBuilder::input_only`.
+    pub input_only: bool,
+    /// Sample rate of the delivered stream, see `Recorder::set_sample_rate`.
+    pub sample_rate: Option<u32>,
+    /// Delivers 48 kHz stereo, see `Recorder::set_standard_output`.
+    pub standard_output: bool,
+}
+
+/// A recording captured in a child process, streaming its chunks back over the pipe of
+/// its standard output.
+///
+/// The devices are opened in the helper, so a crash of a driver or backend takes down
+/// the helper instead of the host application (e.g. an Electron or Tauri app): the
+/// stream then simply ends.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{CaptureHelper, HelperSettings, run_capture_helper};
+///
+/// fn main() {
+///     // the helper process started below runs this same executable
+///     if let Some(result) = run_capture_helper() {
+///         std::process::exit(result.is_err() as i32);
+///     }
+///
+///     let (mut helper, receiver) =
+///         CaptureHelper::spawn(HelperSettings::default()).expect("Failed to spawn helper");
+///     while let Ok(chunk) = receiver.recv() {
+///         // ...
+///     }
+///     helper.stop();
+/// }
+/// ```
+pub struct CaptureHelper {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl CaptureHelper {
+    /// Starts the helper process, returning the stream of the chunks it records.
+    #[tracing::instrument]
+    pub fn spawn(
+        settings: HelperSettings,
+    ) -> Result<(CaptureHelper, AudioReceiver), AudioRecorderError> {
+        let program = match settings.program {
+            Some(program) => program,
+            None => env::current_exe().map_err(|e| {
+                tracing::error!("Failed to get the current executable: {}", e);
+                AudioRecorderError::IoError(e)
+            })?,
+        };
+
+        let mut command = Command::new(&program);
+        command
+            .env(HELPER_ENV, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if settings.input_only {
+            command.env(HELPER_INPUT_ONLY_ENV, "1");
+        }
+        if let Some(sample_rate) = settings.sample_rate {
+            command.env(HELPER_SAMPLE_RATE_ENV, sample_rate.to_string());
+        }
+        if settings.standard_output {
+            command.env(HELPER_STANDARD_ENV, "1");
+        }
+
+        let mut child = command.spawn().map_err(|e| {
+            tracing::error!("Failed to spawn capture helper {:?}: {}", program, e);
+            AudioRecorderError::IoError(e)
+        })?;
+        tracing::info!("Spawned capture helper {}", child.id());
+
+        let stdin = child.stdin.take();
+        let stdout = match child.stdout.take() {
+            Some(stdout) => stdout,
+            None => {
+                let _ = child.kill();
+                return Err(AudioRecorderError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "Capture helper has no output pipe",
+                )));
+            }
+        };
+
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let receiver = AudioReceiver::new(rx);
+        #[cfg(feature = "async")]
+        let waker = receiver.waker();
+
+        let reader = thread::Builder::new()
+            .name("arec-helper-reader".to_string())
+            .spawn(move || {
+                let mut stdout = BufReader::new(stdout);
+                let mut summary: Option<StreamSummary> = None;
+
+                loop {
+                    let chunk = match read_chunk(&mut stdout) {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(e) => {
+                            tracing::error!("Capture helper stream broke: {}", e);
+                            break;
+                        }
+                    };
+
+                    let summary = summary.get_or_insert_with(|| StreamSummary {
+                        frames: 0,
+                        chunks: 0,
+                        sample_rate: chunk.sample_rate,
+                        channels: chunk.channels,
+                        fingerprint: None,
+                        levels: Vec::new(),
+                        dropped_chunks: 0,
+                    });
+                    summary.chunks += 1;
+                    summary.frames = chunk.end_frame();
+
+                    // without a receiver the pipe is still drained, so the helper never
+                    // blocks on a full pipe
+                    let _ = tx.send(StreamMessage::Data(chunk));
+                    #[cfg(feature = "async")]
+                    waker.wake();
+                }
+
+                let summary = summary.unwrap_or(StreamSummary {
+                    frames: 0,
+                    chunks: 0,
+                    sample_rate: 0,
+                    channels: 0,
+                    fingerprint: None,
+                    levels: Vec::new(),
+                    dropped_chunks: 0,
+                });
+                tracing::debug!("Capture helper stream ended: {:?}", summary);
+                let _ = tx.send(StreamMessage::End(summary));
+                #[cfg(feature = "async")]
+                waker.wake();
+            });
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => {
+                tracing::error!("Failed to spawn helper reader thread: {}", e);
+                let _ = child.kill();
+                return Err(AudioRecorderError::IoError(e));
+            }
+        };
+
+        let helper = CaptureHelper {
+            child,
+            stdin,
+            reader: Some(reader),
+        };
+
+        Ok((helper, receiver))
+    }
+
+    /// Whether the helper process is still running.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Asks the helper to stop, waiting until it delivered the audio it still buffered
+    /// and exited.
+    #[tracing::instrument(skip(self))]
+    pub fn stop(&mut self) {
+        tracing::info!("Stopping capture helper {}", self.child.id());
+
+        // the helper stops once its standard input closes
+        drop(self.stdin.take());
+
+        if let Some(reader) = self.reader.take() {
+            if reader.join().is_err() {
+                tracing::error!("Helper reader thread panicked");
+            }
+        }
+
+        match self.child.wait() {
+            Ok(status) if status.success() => tracing::debug!("Capture helper exited"),
+            Ok(status) => tracing::warn!("Capture helper exited with {}", status),
+            Err(e) => tracing::error!("Failed to wait for the capture helper: {}", e),
+        }
+    }
+}
+
+impl Drop for CaptureHelper {
+    fn drop(&mut self) {
+        if self.reader.is_none() {
+            return;
+        }
+
+        tracing::warn!("Capture helper dropped while running, killing it");
+        if let Err(e) = self.child.kill() {
+            tracing::error!("Failed to kill the capture helper: {}", e);
+        }
+        let _ = self.child.wait();
+    }
+}
+
+/// Runs the capture helper when this process was started by `CaptureHelper::spawn`,
+/// returning `None` otherwise.
+///
+/// Call it first thing in `main` and exit with the result: the helper records with the
+/// settings of the parent, writes every chunk to standard output with `write_chunk`,
+/// and stops, flushing the buffered audio, once its standard input closes.
+pub fn run_capture_helper() -> Option<Result<(), AudioRecorderError>> {
+    env::var_os(HELPER_ENV)?;
+    Some(capture(
+        env::var_os(HELPER_INPUT_ONLY_ENV).is_some(),
+        env::var(HELPER_SAMPLE_RATE_ENV)
+            .ok()
+            .and_then(|rate| rate.parse().ok()),
+        env::var_os(HELPER_STANDARD_ENV).is_some(),
+    ))
+}
+
+fn capture(
+    input_only: bool,
+    sample_rate: Option<u32>,
+    standard_output: bool,
+) -> Result<(), AudioRecorderError> {
+    tracing::info!("Running as capture helper");
+
+    let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+    thread::Builder::new()
+        .name("arec-helper-stdin".to_string())
+        .spawn(move || {
+            // nothing is expected on stdin, it only closes when the parent stops
+            let _ = io::copy(&mut io::stdin().lock(), &mut io::sink());
+            drop(stop_tx);
+        })
+        .map_err(AudioRecorderError::IoError)?;
+
+    let mut config = RecorderConfig::builder().input_only(input_only);
+    if let Some(sample_rate) = sample_rate {
+        config = config.sample_rate(sample_rate);
+    }
+
+    let mut recorder = Recorder::new();
+    recorder.set_standard_output(standard_output);
+    let receiver = recorder.start(config.build())?;
+
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let mut stopping = false;
+
+    loop {
+        // nothing is ever sent, the channel disconnects once stdin closes
+        if !stopping && stop_rx.try_recv() == Err(TryRecvError::Disconnected) {
+            tracing::info!("Parent closed the pipe, stopping the helper");
+            recorder.stop_and_flush();
+            stopping = true;
+        }
+
+        let chunk = match receiver.recv_timeout(HELPER_POLL) {
+            Ok(chunk) => chunk,
+            Err(AudioRecvError::Ended) => break,
+            Err(_) => continue,
+        };
+
+        let written = write_chunk(&mut stdout, &chunk)
+            .and_then(|_| stdout.flush().map_err(AudioRecorderError::IoError));
+        if let Err(e) = written {
+            tracing::error!("Failed to send chunk to the parent: {}", e);
+            recorder.stop();
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
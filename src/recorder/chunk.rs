@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use super::constants::TargetFormat;
+
+/// A chunk of recorded audio, as delivered on the data channel.
+///
+/// Besides the interleaved samples, every chunk carries its layout and position in the
+/// stream: `sequence` and `frame` increase without gaps, so a consumer that lost a chunk
+/// can tell (`frame` differs from the previous chunk's `end_frame`), and `timestamp`
+/// gives a monotonic clock reading to align the audio with other media (`time` gives
+/// one on the clock of choice).
+/// With the `serde` feature, chunks can be encoded to ship them to another process, see
+/// `AudioChunk::to_bytes` and `write_chunk`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct AudioChunk {
+    /// Interleaved samples.
+    pub samples: Vec<TargetFormat>,
+    /// Number of interleaved channels.
+    pub channels: u16,
+    /// Sample rate of the samples.
+    pub sample_rate: u32,
+    /// Monotonic time at which the chunk was delivered.
+    #[cfg_attr(feature = "serde", serde(skip, default = "Instant::now"))]
+    pub timestamp: Instant,
+    /// Index of the chunk in the stream, starting at 0.
+    pub sequence: u64,
+    /// Index of the first frame of the chunk in the stream, starting at 0.
+    pub frame: u64,
+    /// Whether the chunk contains speech, if voice activity detection is enabled with
+    /// `Recorder::set_voice_detection`.
+    pub speech: Option<bool>,
+    /// Time of the first frame of the chunk on the external clock set with
+    /// `Recorder::set_external_clock`.
+    pub clock_time: Option<Duration>,
+    /// Time of the first frame of the chunk on the clock selected with
+    /// `Recorder::set_clock_source`.
+    pub time: Duration,
+}
+
+impl AudioChunk {
+    /// Number of frames in the chunk.
+    pub fn frames(&self) -> u64 {
+        (self.samples.len() / self.channels.max(1) as usize) as u64
+    }
+
+    /// Index of the frame following the chunk, i.e. the `frame` of the next chunk.
+    pub fn end_frame(&self) -> u64 {
+        self.frame + self.frames()
+    }
+
+    /// Position of the first frame of the chunk in the stream.
+    pub fn position(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(self.frame as f64 / self.sample_rate as f64)
+    }
+
+    /// Duration of the audio in the chunk.
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+}
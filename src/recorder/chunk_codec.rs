@@ -0,0 +1,100 @@
+use std::io::{self, Read, Write};
+
+use bincode::Options;
+
+use super::{chunk::AudioChunk, errors::AudioRecorderError};
+
+/// Largest encoded chunk accepted, so a corrupt length cannot allocate unbounded memory.
+const CHUNK_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Varint encoded integers, little endian samples.
+fn options() -> impl Options {
+    bincode::options().with_limit(CHUNK_MAX_BYTES)
+}
+
+fn codec_error(context: &'static str) -> impl FnOnce(bincode::Error) -> AudioRecorderError {
+    move |e| {
+        tracing::error!("{}: {}", context, e);
+        AudioRecorderError::Codec { context, source: e }
+    }
+}
+
+impl AudioChunk {
+    /// Encodes the chunk with bincode: the samples, layout, position, voice activity and
+    /// clock times.
+    ///
+    /// The `timestamp` is a reading of this process's monotonic clock and is not
+    /// encoded; decoded chunks are stamped with the time they were decoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AudioRecorderError> {
+        options()
+            .serialize(self)
+            .map_err(codec_error("Failed to encode chunk"))
+    }
+
+    /// Decodes a chunk encoded with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<AudioChunk, AudioRecorderError> {
+        options()
+            .deserialize(bytes)
+            .map_err(codec_error("Failed to decode chunk"))
+    }
+}
+
+/// Writes `chunk` to a socket or pipe as a length-prefixed frame: the encoded length
+/// (`u32`, little endian), then the chunk encoded with `AudioChunk::to_bytes`.
+pub fn write_chunk(writer: &mut impl Write, chunk: &AudioChunk) -> Result<(), AudioRecorderError> {
+    let bytes = chunk.to_bytes()?;
+
+    writer
+        .write_all(&(bytes.len() as u32).to_le_bytes())
+        .and_then(|_| writer.write_all(&bytes))
+        .map_err(|e| {
+            tracing::error!("Failed to write chunk: {}", e);
+            AudioRecorderError::IoError(e)
+        })
+}
+
+/// Reads the next chunk written by `write_chunk`, returning `None` when the stream
+/// ends between two chunks.
+pub fn read_chunk(reader: &mut impl Read) -> Result<Option<AudioChunk>, AudioRecorderError> {
+    let mut length = [0u8; 4];
+    let mut read = 0;
+    while read < length.len() {
+        match reader.read(&mut length[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(AudioRecorderError::IoError(
+                    io::ErrorKind::UnexpectedEof.into(),
+                ));
+            }
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                tracing::error!("Failed to read chunk length: {}", e);
+                return Err(AudioRecorderError::IoError(e));
+            }
+        }
+    }
+
+    let length = u32::from_le_bytes(length) as u64;
+    if length > CHUNK_MAX_BYTES {
+        tracing::error!("Chunk of {} bytes exceeds the limit", length);
+        return Err(AudioRecorderError::Codec {
+            context: "Chunk exceeds the size limit",
+            source: Box::new(bincode::ErrorKind::SizeLimit),
+        });
+    }
+
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes).map_err(|e| {
+        tracing::error!("Failed to read chunk: {}", e);
+        AudioRecorderError::IoError(e)
+    })?;
+    if (bytes.len() as u64) < length {
+        tracing::error!("Chunk ended after {} of {} bytes", bytes.len(), length);
+        return Err(AudioRecorderError::IoError(
+            io::ErrorKind::UnexpectedEof.into(),
+        ));
+    }
+
+    AudioChunk::from_bytes(&bytes).map(Some)
+}
@@ -0,0 +1,152 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::{
+    Recorder,
+    constants::{
+        CLOCK_ADJUST_INTERVAL_FRAMES, CLOCK_DRIFT_SMOOTHING, CLOCK_DRIFT_TOLERANCE_MS, TargetFormat,
+    },
+};
+
+/// The clock of another medium the recording is synchronized to, e.g. the capture clock
+/// of a screen recording, see `Recorder::set_external_clock`.
+#[derive(Clone)]
+pub struct ExternalClock {
+    now: Arc<dyn Fn() -> Duration + Send + Sync>,
+    rate_adjust: bool,
+}
+
+impl fmt::Debug for ExternalClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalClock")
+            .field("rate_adjust", &self.rate_adjust)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExternalClock {
+    /// Creates a clock read with `now`, which returns the current time of the external
+    /// timeline and must never go backwards.
+    pub fn new(now: impl Fn() -> Duration + Send + Sync + 'static) -> Self {
+        ExternalClock {
+            now: Arc::new(now),
+            rate_adjust: false,
+        }
+    }
+
+    /// Also adjusts the rate of the audio to the clock, so a long recording neither
+    /// runs ahead of nor lags behind it, when the audio device clock drifts.
+    pub fn rate_adjust(mut self, rate_adjust: bool) -> Self {
+        self.rate_adjust = rate_adjust;
+        self
+    }
+}
+
+/// Follows the drift of a delivered stream against an external clock.
+///
+/// The clock time of the first delivered frame is estimated when the first block
+/// arrives, and the difference between the elapsed clock time and the delivered audio
+/// is smoothed to ignore the jitter of the device callbacks. With rate adjustment, a
+/// frame is repeated or dropped at most every `CLOCK_ADJUST_INTERVAL_FRAMES` frames
+/// while the drift exceeds the tolerance, which is inaudible.
+pub struct ClockSync {
+    clock: ExternalClock,
+    sample_rate: u32,
+    channels: usize,
+    /// Clock time of the first frame.
+    origin: Option<Duration>,
+    frames: u64,
+    /// Smoothed clock time minus audio time, in seconds.
+    drift: f64,
+    since_adjust: u64,
+}
+
+impl ClockSync {
+    pub fn new(clock: ExternalClock, sample_rate: u32, channels: u16) -> Self {
+        ClockSync {
+            clock,
+            sample_rate: sample_rate.max(1),
+            channels: channels.max(1) as usize,
+            origin: None,
+            frames: 0,
+            drift: 0.0,
+            since_adjust: 0,
+        }
+    }
+
+    /// Clock time of the given frame of the stream, once the first block arrived.
+    pub fn time_of(&self, frame: u64) -> Option<Duration> {
+        self.origin
+            .map(|origin| origin + Duration::from_secs_f64(frame as f64 / self.sample_rate as f64))
+    }
+
+    /// Measures the drift at the end of a block of interleaved samples, returning it
+    /// with frames repeated or dropped when rate adjustment is enabled.
+    pub fn process(&mut self, data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        let now = (self.clock.now)();
+        let frames = (data.len() / self.channels) as u64;
+        let rate = self.sample_rate as f64;
+
+        let origin = *self.origin.get_or_insert_with(|| {
+            now.saturating_sub(Duration::from_secs_f64(frames as f64 / rate))
+        });
+
+        // positive when the audio lags behind the clock
+        let elapsed = now.saturating_sub(origin).as_secs_f64();
+        let drift = elapsed - (self.frames + frames) as f64 / rate;
+        self.drift += CLOCK_DRIFT_SMOOTHING * (drift - self.drift);
+
+        let tolerance = CLOCK_DRIFT_TOLERANCE_MS as f64 / 1000.0;
+        if !self.clock.rate_adjust || self.drift.abs() <= tolerance {
+            self.since_adjust += frames;
+            self.frames += frames;
+            return data;
+        }
+
+        let mut correction = ((self.drift.abs() - tolerance) * rate).ceil() as u64;
+        let lagging = self.drift > 0.0;
+        let mut output = Vec::with_capacity(data.len() + self.channels);
+        let mut applied: i64 = 0;
+
+        for frame in data.chunks(self.channels) {
+            self.since_adjust += 1;
+
+            if correction > 0 && self.since_adjust >= CLOCK_ADJUST_INTERVAL_FRAMES {
+                self.since_adjust = 0;
+                correction -= 1;
+
+                if lagging {
+                    output.extend_from_slice(frame);
+                    applied += 1;
+                } else {
+                    applied -= 1;
+                    continue;
+                }
+            }
+
+            output.extend_from_slice(frame);
+        }
+
+        if applied != 0 {
+            tracing::trace!("Adjusted {} frames to the external clock", applied);
+        }
+
+        self.drift -= applied as f64 / rate;
+        self.frames += (output.len() / self.channels) as u64;
+        output
+    }
+}
+
+impl Recorder {
+    /// Timestamps the delivered chunks with an external clock (or `None` to disable it),
+    /// see `AudioChunk::clock_time`, e.g. to keep the audio of a long screen recording in
+    /// sync with its video. With `ExternalClock::rate_adjust`, the audio is also
+    /// stretched or squeezed by single frames to follow the clock.
+    ///
+    /// The silence gate skips audio, so the clock time of the chunks following a
+    /// skipped stretch is early by its duration.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_external_clock(&mut self, clock: Option<ExternalClock>) {
+        self.external_clock = clock;
+    }
+}
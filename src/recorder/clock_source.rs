@@ -0,0 +1,184 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::Recorder;
+
+/// The clock the delivered chunks are timestamped with, see `AudioChunk::time` and
+/// `Recorder::set_clock_source`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ClockSource {
+    /// The clock of the stream, which counts the delivered frames from the first one:
+    /// it runs at the rate of the audio device and never jitters, but drifts from the
+    /// CPU clocks over long recordings.
+    Stream,
+    /// The monotonic clock of the process, from the first frame of the recording (see
+    /// `ClockMapping::origin`), e.g. to sync with media timestamped with `Instant`.
+    #[default]
+    Monotonic,
+    /// The wall clock, from the Unix epoch, e.g. to sync with recordings of other
+    /// machines. It jumps when the system time is changed.
+    Wall,
+}
+
+/// A simultaneous reading of the clocks of a recording, to convert times between them,
+/// see `Recorder::get_clock_mapping`.
+///
+/// The reading is taken at every delivered chunk, so the stream clock drifting from the
+/// others is accounted for as of the last chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClockMapping {
+    /// Time of the stream clock, the end of the last delivered chunk.
+    pub stream: Duration,
+    /// Reading of the monotonic clock.
+    pub monotonic: Instant,
+    /// Reading of the wall clock.
+    pub wall: SystemTime,
+    /// Monotonic time of the first frame, from which `ClockSource::Monotonic` counts.
+    pub origin: Instant,
+    /// Sample rate of the stream, at which the stream clock counts frames.
+    pub sample_rate: u32,
+}
+
+impl ClockMapping {
+    /// The stream time of a frame, as counted by `AudioChunk::frame`.
+    pub fn frame_time(&self, frame: u64) -> Duration {
+        match self.sample_rate {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(frame as f64 / rate as f64),
+        }
+    }
+
+    /// The frame at a stream time.
+    pub fn frame_at(&self, stream: Duration) -> u64 {
+        (stream.as_secs_f64() * self.sample_rate as f64).round() as u64
+    }
+
+    /// The monotonic time of a stream time.
+    pub fn to_monotonic(&self, stream: Duration) -> Instant {
+        match stream.checked_sub(self.stream) {
+            Some(ahead) => self.monotonic + ahead,
+            None => self
+                .monotonic
+                .checked_sub(self.stream - stream)
+                .unwrap_or(self.origin),
+        }
+    }
+
+    /// The wall time of a stream time.
+    pub fn to_wall(&self, stream: Duration) -> SystemTime {
+        match stream.checked_sub(self.stream) {
+            Some(ahead) => self.wall + ahead,
+            None => self
+                .wall
+                .checked_sub(self.stream - stream)
+                .unwrap_or(UNIX_EPOCH),
+        }
+    }
+
+    /// The stream time of a monotonic time, zero before the stream started.
+    pub fn from_monotonic(&self, instant: Instant) -> Duration {
+        match instant.checked_duration_since(self.monotonic) {
+            Some(ahead) => self.stream + ahead,
+            None => self
+                .stream
+                .saturating_sub(self.monotonic.duration_since(instant)),
+        }
+    }
+
+    /// The stream time of a wall time, zero before the stream started.
+    pub fn from_wall(&self, wall: SystemTime) -> Duration {
+        match wall.duration_since(self.wall) {
+            Ok(ahead) => self.stream + ahead,
+            Err(e) => self.stream.saturating_sub(e.duration()),
+        }
+    }
+
+    /// The time on `source` of a stream time, as found in `AudioChunk::time`.
+    pub fn to_source(&self, source: ClockSource, stream: Duration) -> Duration {
+        match source {
+            ClockSource::Stream => stream,
+            ClockSource::Monotonic => self.to_monotonic(stream).duration_since(self.origin),
+            ClockSource::Wall => self
+                .to_wall(stream)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Timestamps the chunks of a data channel on the selected clock, publishing the
+/// mapping between the clocks.
+///
+/// The CPU clocks are read when a chunk is delivered, which is when its last frame was
+/// captured, give or take a callback.
+pub struct ChunkTiming {
+    source: ClockSource,
+    sample_rate: u32,
+    origin: Option<Instant>,
+    mapping: Arc<Mutex<Option<ClockMapping>>>,
+}
+
+impl ChunkTiming {
+    /// Returns the time of the first frame of a chunk, given its position and duration
+    /// in the stream.
+    pub fn stamp(&mut self, position: Duration, duration: Duration) -> Duration {
+        let (monotonic, wall) = (Instant::now(), SystemTime::now());
+        let origin = *self.origin.get_or_insert_with(|| {
+            let origin = monotonic.checked_sub(duration).unwrap_or(monotonic);
+            tracing::debug!("Stream clock started, timestamping on {:?}", self.source);
+            origin
+        });
+
+        let mapping = ClockMapping {
+            stream: position + duration,
+            monotonic,
+            wall,
+            origin,
+            sample_rate: self.sample_rate,
+        };
+        *self.mapping.lock().unwrap_or_else(PoisonError::into_inner) = Some(mapping);
+
+        mapping.to_source(self.source, position)
+    }
+}
+
+impl Recorder {
+    /// Selects the clock of `AudioChunk::time`: the stream clock, the monotonic clock
+    /// (the default) or the wall clock. Downstream sync targets need different clocks,
+    /// and `get_clock_mapping` converts between them.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_clock_source(&mut self, source: ClockSource) {
+        self.clock_source = source;
+    }
+
+    /// Returns the latest reading of the clocks of the running (or last) recording, once
+    /// a chunk was delivered.
+    pub fn get_clock_mapping(&self) -> Option<ClockMapping> {
+        *self
+            .clock_mapping
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Creates the timing of a data channel delivering a stream at `sample_rate`,
+    /// clearing the mapping of the last recording.
+    pub fn chunk_timing(&self, sample_rate: u32) -> ChunkTiming {
+        *self
+            .clock_mapping
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+
+        ChunkTiming {
+            source: self.clock_source,
+            sample_rate,
+            origin: None,
+            mapping: self.clock_mapping.clone(),
+        }
+    }
+}
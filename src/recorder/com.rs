@@ -0,0 +1,54 @@
+/// Initializes COM on the current thread for as long as it is alive.
+///
+/// WASAPI needs COM initialized on every thread touching devices. Worker threads join
+/// the multithreaded apartment and leave it when the guard drops. A thread that already
+/// initialized COM differently (e.g. the single-threaded apartment of a GUI framework)
+/// is left untouched, as cpal works in either apartment as long as it is initialized.
+///
+/// A no-op on other platforms.
+pub struct ComGuard {
+    #[cfg(target_os = "windows")]
+    initialized: bool,
+}
+
+impl ComGuard {
+    #[cfg(target_os = "windows")]
+    pub fn init() -> Self {
+        use windows::Win32::{
+            Foundation::RPC_E_CHANGED_MODE,
+            System::Com::{COINIT_MULTITHREADED, CoInitializeEx},
+        };
+
+        // SAFETY: balanced by `CoUninitialize` on drop when it succeeds.
+        let result = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+
+        let initialized = match result {
+            r if r.is_ok() => true,
+            RPC_E_CHANGED_MODE => {
+                tracing::debug!("COM already initialized in another apartment");
+                false
+            }
+            r => {
+                tracing::warn!("Failed to initialize COM: {:?}", r);
+                false
+            }
+        };
+
+        ComGuard { initialized }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn init() -> Self {
+        ComGuard {}
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.initialized {
+            // SAFETY: the thread initialized COM in `init`.
+            unsafe { windows::Win32::System::Com::CoUninitialize() };
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::time::Duration;
+
+use super::{
+    Recorder,
+    adaptive_chunking::AdaptiveChunking,
+    constants::{
+        CLOCK_DELAY, LOW_LATENCY_BUFFER_FRAMES, LOW_LATENCY_MIX_MS, LOW_LATENCY_POLL_MS,
+        RESAMPLER_FFT_CHUNK_SIZE, ROBUST_BUFFER_FRAMES,
+    },
+    latency::LatencyPadding,
+    mix::{ChannelMode, MixMode},
+};
+
+/// The trade-off between CPU, latency and quality of the resamplers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ResamplerQuality {
+    /// Linear interpolation in short chunks, the cheapest and for low-latency
+    /// scenarios, at the cost of some aliasing.
+    Fast,
+    /// The default: an FFT resampler, sharp and cheap but with the latency of its
+    /// chunks.
+    #[default]
+    Balanced,
+    /// A long windowed sinc filter with cubic interpolation in long chunks, the most
+    /// accurate and the most costly.
+    High,
+}
+
+impl ResamplerQuality {
+    /// Number of frames processed by the resampler at once, unless set with
+    /// `RecorderConfigBuilder::resampler_chunk_size`.
+    pub fn chunk_size(&self) -> usize {
+        match self {
+            ResamplerQuality::Fast => RESAMPLER_FFT_CHUNK_SIZE / 4,
+            ResamplerQuality::Balanced => RESAMPLER_FFT_CHUNK_SIZE,
+            ResamplerQuality::High => RESAMPLER_FFT_CHUNK_SIZE * 4,
+        }
+    }
+}
+
+/// A preset trading the latency of the recording against its robustness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum LatencyMode {
+    /// The default: device buffers, one second mixes and relaxed polling.
+    #[default]
+    Balanced,
+    /// Small fixed device buffers, 10 ms mixes, short ring buffers and tight polling,
+    /// targeting less than 20 ms from capture to delivery, e.g. for live monitoring.
+    Low,
+    /// Large device and ring buffers, stream rebuilds on failure, silence backfilled
+    /// over device dropouts and drift correction against the system clock, for
+    /// unattended long recordings where dropouts matter more than latency.
+    Robust,
+}
+
+/// The configuration of a recording, consumed by `Recorder::start`.
+///
+/// Built with `RecorderConfig::builder()`. Settings left unset keep the value configured
+/// on the `Recorder` (e.g. with `set_sample_rate`) or their default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecorderConfig {
+    input_only: bool,
+    sample_rate: Option<u32>,
+    channel_mode: Option<ChannelMode>,
+    mix_mode: Option<MixMode>,
+    output_channels: Option<u16>,
+    input_device: Option<String>,
+    output_device: Option<String>,
+    host: Option<cpal::HostId>,
+    buffer_size: Option<u32>,
+    mix_chunk: Option<Duration>,
+    chunk_duration: Option<Duration>,
+    adaptive_chunking: Option<AdaptiveChunking>,
+    poll_interval: Duration,
+    resampler_quality: ResamplerQuality,
+    resampler_chunk_size: Option<usize>,
+    latency: Option<LatencyPadding>,
+    latency_mode: LatencyMode,
+    #[cfg(feature = "noise-suppression")]
+    noise_suppression: bool,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            input_only: false,
+            sample_rate: None,
+            channel_mode: None,
+            mix_mode: None,
+            output_channels: None,
+            input_device: None,
+            output_device: None,
+            host: None,
+            buffer_size: None,
+            mix_chunk: None,
+            chunk_duration: None,
+            adaptive_chunking: None,
+            poll_interval: Duration::from_millis(CLOCK_DELAY as _),
+            resampler_quality: ResamplerQuality::default(),
+            resampler_chunk_size: None,
+            latency: None,
+            latency_mode: LatencyMode::default(),
+            #[cfg(feature = "noise-suppression")]
+            noise_suppression: false,
+        }
+    }
+}
+
+impl RecorderConfig {
+    pub fn builder() -> RecorderConfigBuilder {
+        RecorderConfigBuilder::default()
+    }
+
+    /// A preset for speech recognition: the microphone alone, in 16 kHz mono, delivered
+    /// in chunks cut at the pauses of the speech with the default `AdaptiveChunking`.
+    /// Settings given to the returned builder override it.
+    pub fn transcription() -> RecorderConfigBuilder {
+        RecorderConfig::builder()
+            .input_only(true)
+            .sample_rate(16_000)
+            .output_channels(1)
+            .adaptive_chunking(AdaptiveChunking::default())
+    }
+
+    /// Whether only the input device is recorded.
+    pub fn input_only(&self) -> bool {
+        self.input_only
+    }
+
+    /// Sample rate requested for the delivered stream, see `Recorder::set_sample_rate`.
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    /// Name of the input device to record, `None` for the default one.
+    pub fn input_device(&self) -> Option<&str> {
+        self.input_device.as_deref()
+    }
+
+    /// Name of the system audio device to record, `None` for the default one.
+    pub fn output_device(&self) -> Option<&str> {
+        self.output_device.as_deref()
+    }
+
+    /// Host the default input device is taken from, `None` for the default host.
+    pub fn host(&self) -> Option<cpal::HostId> {
+        self.host
+    }
+
+    /// Buffer size requested from the devices in frames, `None` for their default.
+    pub fn buffer_size(&self) -> Option<u32> {
+        self.buffer_size
+    }
+
+    /// Audio accumulated by the resampling mixer before mixing, `None` for one second.
+    pub fn mix_chunk(&self) -> Option<Duration> {
+        self.mix_chunk
+    }
+
+    /// Audio held by every delivered chunk, see `Recorder::set_chunk_duration`.
+    pub fn chunk_duration(&self) -> Option<Duration> {
+        self.chunk_duration
+    }
+
+    /// How the delivered chunks are cut at the pauses of the speech, see
+    /// `Recorder::set_adaptive_chunking`.
+    pub fn adaptive_chunking(&self) -> Option<AdaptiveChunking> {
+        self.adaptive_chunking
+    }
+
+    /// Longest the recording threads wait before checking for work again; they are
+    /// woken as soon as new audio arrives or the recording is stopped.
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    pub fn resampler_quality(&self) -> ResamplerQuality {
+        self.resampler_quality
+    }
+
+    /// Number of frames the resamplers process at once.
+    pub fn resampler_chunk_size(&self) -> usize {
+        self.resampler_chunk_size
+            .unwrap_or_else(|| self.resampler_quality.chunk_size())
+    }
+
+    /// Whether the noise of the input stream is suppressed with RNNoise.
+    #[cfg(feature = "noise-suppression")]
+    pub fn noise_suppression(&self) -> bool {
+        self.noise_suppression
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    /// Converts a device config to the stream config requested from the device.
+    pub fn stream_config(&self, config: &cpal::SupportedStreamConfig) -> cpal::StreamConfig {
+        let mut stream_config: cpal::StreamConfig = config.clone().into();
+
+        if let Some(frames) = self.buffer_size {
+            stream_config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
+
+        stream_config
+    }
+}
+
+/// Builds a `RecorderConfig`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use audio_recorder_rs::{Recorder, RecorderConfig, ResamplerQuality};
+///
+/// let config = RecorderConfig::builder()
+///     .sample_rate(16_000)
+///     .buffer_size(256)
+///     .poll_interval(Duration::from_millis(20))
+///     .resampler_quality(ResamplerQuality::Fast)
+///     .build();
+///
+/// let mut recorder = Recorder::new();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecorderConfigBuilder {
+    config: RecorderConfig,
+}
+
+impl RecorderConfigBuilder {
+    /// Records only the input device, without the system audio.
+    pub fn input_only(mut self, input_only: bool) -> Self {
+        self.config.input_only = input_only;
+        self
+    }
+
+    /// Sample rate both sources are resampled to, see `Recorder::set_sample_rate`.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.config.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// Channel layout of the mixed stream, see `Recorder::set_channel_mode`.
+    pub fn channel_mode(mut self, channel_mode: ChannelMode) -> Self {
+        self.config.channel_mode = Some(channel_mode);
+        self
+    }
+
+    /// How the microphone and system audio are combined, see `Recorder::set_mix_mode`.
+    pub fn mix_mode(mut self, mix_mode: MixMode) -> Self {
+        self.config.mix_mode = Some(mix_mode);
+        self
+    }
+
+    /// Channel count the delivered stream is remixed to, see
+    /// `Recorder::set_output_channels`.
+    pub fn output_channels(mut self, channels: u16) -> Self {
+        self.config.output_channels = Some(channels);
+        self
+    }
+
+    /// Records the input device with this name, as listed by `AudioDevice::list_all`.
+    pub fn input_device(mut self, name: impl Into<String>) -> Self {
+        self.config.input_device = Some(name.into());
+        self
+    }
+
+    /// Records the system audio from the device with this name, either an output device
+    /// captured in loopback mode or an input device such as a monitor source.
+    pub fn output_device(mut self, name: impl Into<String>) -> Self {
+        self.config.output_device = Some(name.into());
+        self
+    }
+
+    /// Records the default input device of this host instead of the default host, e.g.
+    /// `HostId::Jack` with the `jack` feature.
+    ///
+    /// Hosts such as JACK have no loopback capture, so the system audio still comes
+    /// from the default host unless `output_device` names another device (e.g. a JACK
+    /// device listed by `AudioDevice::list_all`). The default input device is not
+    /// followed when it changes.
+    pub fn host(mut self, host: cpal::HostId) -> Self {
+        self.config.host = Some(host);
+        self
+    }
+
+    /// Buffer size requested from the devices, in frames. Smaller buffers lower the
+    /// capture latency but risk underruns.
+    pub fn buffer_size(mut self, frames: u32) -> Self {
+        self.config.buffer_size = Some(frames);
+        self
+    }
+
+    /// Audio accumulated by the resampling mixer before mixing (one second by default).
+    pub fn mix_chunk(mut self, mix_chunk: Duration) -> Self {
+        self.config.mix_chunk = Some(mix_chunk);
+        self
+    }
+
+    /// Delivers chunks holding exactly `chunk_duration` of audio, e.g. the 10 ms or
+    /// 20 ms frames of streaming speech recognition and VoIP encoders, see
+    /// `Recorder::set_chunk_duration`.
+    pub fn chunk_duration(mut self, chunk_duration: Duration) -> Self {
+        self.config.chunk_duration = Some(chunk_duration);
+        self
+    }
+
+    /// Delivers chunks cut at the pauses of the speech, see
+    /// `Recorder::set_adaptive_chunking`.
+    pub fn adaptive_chunking(mut self, chunking: AdaptiveChunking) -> Self {
+        self.config.adaptive_chunking = Some(chunking);
+        self
+    }
+
+    /// Longest the recording threads wait before checking for work again; they are
+    /// woken as soon as new audio arrives or the recording is stopped.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.config.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn resampler_quality(mut self, resampler_quality: ResamplerQuality) -> Self {
+        self.config.resampler_quality = resampler_quality;
+        self
+    }
+
+    /// Number of frames the resamplers process at once, overriding the default of the
+    /// `ResamplerQuality`: shorter chunks lower the latency, longer ones the CPU usage.
+    pub fn resampler_chunk_size(mut self, frames: usize) -> Self {
+        self.config.resampler_chunk_size = Some(frames.max(1));
+        self
+    }
+
+    /// Applies the preset of `latency_mode` to the buffer size, mix chunk, poll
+    /// interval and resampler quality; settings given after this override it. The
+    /// buffering, retries and corrections of the mode always apply.
+    pub fn latency_mode(mut self, latency_mode: LatencyMode) -> Self {
+        self.config.latency_mode = latency_mode;
+
+        if latency_mode == LatencyMode::Low {
+            self.config.buffer_size = Some(LOW_LATENCY_BUFFER_FRAMES);
+            self.config.mix_chunk = Some(Duration::from_millis(LOW_LATENCY_MIX_MS));
+            self.config.poll_interval = Duration::from_millis(LOW_LATENCY_POLL_MS);
+            self.config.resampler_quality = ResamplerQuality::Fast;
+        }
+
+        if latency_mode == LatencyMode::Robust {
+            self.config.buffer_size = Some(ROBUST_BUFFER_FRAMES);
+        }
+
+        self
+    }
+
+    /// Suppresses the noise of the microphone (keyboards, traffic, fans) with RNNoise
+    /// before delivery, for voice call recordings.
+    ///
+    /// Adds 10 ms of latency, more when the microphone does not run at 48 kHz and is
+    /// resampled for RNNoise.
+    #[cfg(feature = "noise-suppression")]
+    pub fn noise_suppression(mut self, noise_suppression: bool) -> Self {
+        self.config.noise_suppression = noise_suppression;
+        self
+    }
+
+    /// Startup latency padding, see `Recorder::set_latency_padding`.
+    pub fn latency(mut self, latency: LatencyPadding) -> Self {
+        self.config.latency = Some(latency);
+        self
+    }
+
+    pub fn build(self) -> RecorderConfig {
+        self.config
+    }
+}
+
+impl Recorder {
+    /// Applies the settings of `config` to the recorder, keeping it for the recording.
+    pub fn apply_config(&mut self, config: RecorderConfig) {
+        tracing::debug!("Applying config {:?}", config);
+
+        if let Some(sample_rate) = config.sample_rate {
+            self.sample_rate = Some(sample_rate);
+        }
+        if let Some(channel_mode) = config.channel_mode {
+            self.channel_mode = channel_mode;
+        }
+        if let Some(mix_mode) = config.mix_mode {
+            self.mix_mode = mix_mode;
+        }
+        if let Some(latency) = config.latency {
+            self.latency_padding = latency;
+        }
+        if let Some(channels) = config.output_channels {
+            self.output_channels = Some(channels);
+        }
+        if let Some(chunk_duration) = config.chunk_duration {
+            self.chunk_duration = Some(chunk_duration);
+        }
+        if let Some(chunking) = config.adaptive_chunking {
+            self.adaptive_chunking = Some(chunking);
+        }
+
+        self.config = config;
+    }
+
+    /// Converts a device config to the stream config requested from the device.
+    pub fn stream_config(&self, config: &cpal::SupportedStreamConfig) -> cpal::StreamConfig {
+        self.config.stream_config(config)
+    }
+}
@@ -1,20 +1,157 @@
-use std::iter::Sum;
+use std::{iter::Sum, time::Duration};
 
 use dasp_sample::ToSample;
 
 pub type TargetFormat = f32;
 pub const CLOCK_DELAY: u32 = 400;
 
-pub const RESAMPLER_SLEEP_DELAY: u32 = 10;
+/// Drift from an external clock that is left alone, absorbing the callback jitter.
+pub const CLOCK_DRIFT_TOLERANCE_MS: u32 = 10;
+/// Smoothing factor of the drift measured at every block.
+pub const CLOCK_DRIFT_SMOOTHING: f64 = 0.05;
+/// Fewest frames between two frames repeated or dropped to follow a clock (0.1%).
+pub const CLOCK_ADJUST_INTERVAL_FRAMES: u64 = 1000;
+
+pub const LOW_LATENCY_BUFFER_FRAMES: u32 = 128;
+pub const LOW_LATENCY_MIX_MS: u64 = 10;
+pub const LOW_LATENCY_POLL_MS: u64 = 5;
+
+pub const ROBUST_BUFFER_FRAMES: u32 = 4096;
+/// How many times larger the ring buffers of a robust recording are.
+pub const ROBUST_RING_FACTOR: usize = 4;
+pub const ROBUST_STREAM_RETRIES: u32 = 5;
+pub const ROBUST_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest device dropout backfilled with silence.
+pub const ROBUST_MAX_BACKFILL: Duration = Duration::from_secs(5);
 pub const RESAMPLER_CHUNK_SIZE: usize = 44100;
+pub const RESAMPLER_FFT_CHUNK_SIZE: usize = 1024;
+/// Length of the sinc filter of `ResamplerQuality::High`, in taps.
+pub const RESAMPLER_SINC_LEN: usize = 256;
+/// Number of sinc filters `ResamplerQuality::High` interpolates between.
+pub const RESAMPLER_SINC_OVERSAMPLING: usize = 256;
+
+pub const STANDARD_SAMPLE_RATE: u32 = 48000;
+pub const STANDARD_CHANNELS: u16 = 2;
+
+pub const FINGERPRINT_FRAME_SIZE: usize = 2048;
+pub const FINGERPRINT_HOP_SIZE: usize = 512;
+/// One more band than bits, as each bit compares two adjacent bands.
+pub const FINGERPRINT_BANDS: usize = 33;
+pub const FINGERPRINT_MIN_HZ: f32 = 300.0;
+pub const FINGERPRINT_MAX_HZ: f32 = 2000.0;
+
+pub const CLASSIFIER_WINDOW_MS: u32 = 1000;
+pub const CLASSIFIER_FRAME_MS: u32 = 20;
+pub const CLASSIFIER_SILENCE_DB: f32 = -50.0;
+pub const CLASSIFIER_SPEECH_LOW_ENERGY_RATIO: f32 = 0.3;
+pub const CLASSIFIER_NOISE_ZCR: f32 = 0.25;
+
+pub const GATE_FRAME_MS: u32 = 20;
+
+pub const VAD_FRAME_MS: u32 = 20;
+/// Frames quieter than this are never speech.
+pub const VAD_MIN_LEVEL_DB: f32 = -55.0;
+/// How far above the noise floor a frame must be to be speech.
+pub const VAD_MARGIN_DB: f32 = 9.0;
+/// How fast the noise floor follows a louder background, per frame.
+pub const VAD_FLOOR_RISE_DB: f32 = 0.05;
+/// How long speech is held after the last active frame, bridging short pauses.
+pub const VAD_HANGOVER_MS: u32 = 300;
+
+pub const PITCH_FRAME_SIZE: usize = 2048;
+pub const PITCH_HOP_SIZE: usize = 1024;
+pub const PITCH_MIN_HZ: f32 = 60.0;
+pub const PITCH_MAX_HZ: f32 = 1000.0;
+pub const PITCH_THRESHOLD: f32 = 0.15;
+
+pub const LATENCY_PADDING_MAX_MS: u64 = 150;
+pub const LATENCY_PADDING_FALLBACK_MS: u64 = 150;
+/// Largest offset between the microphone and the system audio that is corrected.
+pub const ALIGNMENT_MAX_MS: i32 = 1000;
+/// Duration of the probe tone played by the alignment calibration.
+pub const ALIGNMENT_PROBE_MS: u64 = 300;
+/// Audio captured around the probe tone by the alignment calibration.
+pub const ALIGNMENT_CAPTURE_MS: u64 = 1500;
+/// Lowest normalized correlation at which the probe counts as detected.
+pub const ALIGNMENT_MIN_CORRELATION: f32 = 0.3;
+
+/// Duration over which a gain change is ramped, avoiding clicks.
+pub const GAIN_RAMP_MS: u32 = 20;
+
+/// Fill of a ring buffer from which it is reported as pressured, and below which it
+/// is reported as relieved again.
+pub const BACKPRESSURE_HIGH: f32 = 0.75;
+pub const BACKPRESSURE_LOW: f32 = 0.25;
+
+/// Sample rate RNNoise works at.
+#[cfg(feature = "noise-suppression")]
+pub const RNNOISE_SAMPLE_RATE: u32 = 48000;
+
+/// How long the delivery must fall behind before the next degradation step.
+pub const DEGRADE_AFTER_MS: u64 = 3000;
+/// Chunks queued on a data channel from which its delivery counts as behind.
+pub const DEGRADE_MIN_BACKLOG: usize = 32;
+/// Chunk duration unpaced streams are paced to by `DegradationStep::IncreaseChunkSize`.
+pub const DEGRADE_CHUNK_MS: u32 = 100;
+
+/// Step size of the echo canceller adaptation, between 0 and 1.
+pub const AEC_STEP: f32 = 0.5;
+/// Ratio of the microphone peak to the reference peak above which both parties are
+/// talking and the echo canceller stops adapting.
+pub const AEC_DOUBLE_TALK: f32 = 0.5;
+
+/// Corner frequency, in Hz, of the DC blocker of the high-pass filter.
+pub const DC_BLOCKER_HZ: f32 = 5.0;
+
+/// Window of the short-term loudness matched across the sources of a mix.
+pub const LOUDNESS_WINDOW_MS: u32 = 3000;
+/// Level, in dBFS, below which a block does not count towards the loudness of a source.
+pub const LOUDNESS_GATE_DBFS: f32 = -50.0;
+/// Largest correction, in dB, applied to a source to match the loudness of the others.
+pub const LOUDNESS_MAX_GAIN_DB: f32 = 12.0;
+/// Largest change of the loudness correction of a source, in dB per second.
+pub const LOUDNESS_SLEW_DB: f32 = 3.0;
+
+/// Lowest level, in dBFS, of the level histograms; quieter callbacks count in the first bin.
+pub const LEVEL_HISTOGRAM_FLOOR_DB: f32 = -90.0;
+/// Width, in dB, of a bin of the level histograms.
+pub const LEVEL_HISTOGRAM_BIN_DB: f32 = 3.0;
+/// Number of bins of the level histograms, up to 0 dBFS.
+pub const LEVEL_HISTOGRAM_BINS: usize = 30;
+
+/// Level, in dBFS, below which the automatic gain control holds its gain.
+pub const AGC_FLOOR_DBFS: f32 = -55.0;
+
+pub const CALIBRATION_WINDOW_MS: u32 = 50;
+pub const CALIBRATION_TARGET_PEAK_DB: f32 = -3.0;
+pub const CALIBRATION_MAX_GAIN_DB: f32 = 30.0;
+
+pub const NOISE_PROFILE_FRAME_SIZE: usize = 1024;
+pub const NOISE_PROFILE_HOP_SIZE: usize = 512;
+/// Over-subtraction of the noise, removing the bins hovering just above it.
+pub const NOISE_SUBTRACTION_FACTOR: f32 = 1.5;
+/// Fraction of the magnitude a bin keeps at least, limiting musical noise.
+pub const NOISE_SUBTRACTION_FLOOR: f32 = 0.05;
+
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the end of a recording waits for a consumer that stopped reading under
+/// `BackpressurePolicy::Block`, before evicting its oldest chunks.
+pub const END_OF_STREAM_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often a delivery blocked under `BackpressurePolicy::Block` checks whether the
+/// recording stopped or the consumer went away.
+pub const BACKPRESSURE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub const TEST_TONE_HZ: f32 = 440.0;
+pub const TEST_TONE_LEVEL_DB: f32 = -12.0;
+pub const TEST_TONE_FADE_MS: u32 = 20;
 
-pub enum ResampleTargetStream {
-    /// Resample the input stream to achieve the output rate
+/// A stream captured by the recorder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioSource {
+    /// The input device (microphone).
     Input,
-    /// Resample the output stream to achieve
+    /// The output device (system audio).
     Output,
-    /// No resampling
-    None,
 }
 
 pub trait CustomSample:
@@ -24,6 +161,7 @@ pub trait CustomSample:
     + Sum
     + cpal::SizedSample
     + ToSample<TargetFormat>
+    + cpal::FromSample<TargetFormat>
 {
 }
 
@@ -0,0 +1,228 @@
+use ringbuf::{
+    HeapCons, HeapProd, HeapRb,
+    traits::{Consumer, Observer, Producer, Split},
+};
+
+use super::{
+    Recorder,
+    config::ResamplerQuality,
+    constants::{STANDARD_CHANNELS, STANDARD_SAMPLE_RATE, TargetFormat},
+    errors::AudioRecorderError,
+    mix::remix,
+    resampler_driver::ResamplerDriver,
+};
+
+/// Converts the delivered stream to a fixed sample rate, optionally upmixing or
+/// downmixing it to another channel count.
+pub struct StreamConverter {
+    channels: u16,
+    output_channels: u16,
+    target_rate: u32,
+    driver: Option<ResamplerDriver>,
+    input_producer: HeapProd<TargetFormat>,
+    input_consumer: HeapCons<TargetFormat>,
+    output_producer: HeapProd<TargetFormat>,
+    output_consumer: HeapCons<TargetFormat>,
+}
+
+impl StreamConverter {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        target_rate: u32,
+        output_channels: u16,
+    ) -> Result<Self, AudioRecorderError> {
+        let quality = ResamplerQuality::default();
+        Self::with_quality(
+            sample_rate,
+            channels,
+            target_rate,
+            output_channels,
+            quality,
+            quality.chunk_size(),
+        )
+    }
+
+    /// Creates a converter resampling with `quality`, `chunk_size` frames at once.
+    pub fn with_quality(
+        sample_rate: u32,
+        channels: u16,
+        target_rate: u32,
+        output_channels: u16,
+        quality: ResamplerQuality,
+        chunk_size: usize,
+    ) -> Result<Self, AudioRecorderError> {
+        let output_channels = output_channels.max(1);
+
+        let driver = match sample_rate == target_rate {
+            true => None,
+            false => Some(ResamplerDriver::with_quality(
+                sample_rate as usize,
+                target_rate as usize,
+                output_channels as usize,
+                quality,
+                chunk_size,
+            )?),
+        };
+
+        // two seconds of audio on each side of the resampler
+        let (input_producer, input_consumer) =
+            HeapRb::<TargetFormat>::new(sample_rate as usize * 2 * output_channels as usize)
+                .split();
+        let (output_producer, output_consumer) =
+            HeapRb::<TargetFormat>::new(target_rate as usize * 2 * output_channels as usize)
+                .split();
+
+        Ok(StreamConverter {
+            channels,
+            output_channels,
+            target_rate,
+            driver,
+            input_producer,
+            input_consumer,
+            output_producer,
+            output_consumer,
+        })
+    }
+
+    /// Sample rate of the converted stream.
+    pub fn output_rate(&self) -> u32 {
+        self.target_rate
+    }
+
+    /// Number of interleaved channels of the converted stream.
+    pub fn output_channels(&self) -> u16 {
+        self.output_channels
+    }
+
+    /// Converts a chunk, returning the converted samples available so far.
+    pub fn process(&mut self, data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        let data = remix(data, self.channels, self.output_channels);
+
+        let driver = match &mut self.driver {
+            Some(driver) => driver,
+            None => return data,
+        };
+
+        if self.input_producer.push_slice(&data) < data.len() {
+            tracing::warn!("stream converter fell behind: dropping samples");
+        }
+        driver.drain(&mut self.input_consumer, &mut self.output_producer);
+
+        let mut converted = vec![0.0; self.output_consumer.occupied_len()];
+        self.output_consumer.pop_slice(&mut converted);
+        converted
+    }
+
+    /// Replaces the resampler, if any, with one of `quality` processing `chunk_size`
+    /// frames at once.
+    pub fn set_quality(
+        &mut self,
+        quality: ResamplerQuality,
+        chunk_size: usize,
+    ) -> Result<(), AudioRecorderError> {
+        match &mut self.driver {
+            Some(driver) => driver.set_quality(quality, chunk_size),
+            None => Ok(()),
+        }
+    }
+
+    /// Converts the samples still buffered in the resampler at the end of the stream.
+    pub fn flush(&mut self) -> Vec<TargetFormat> {
+        let driver = match &mut self.driver {
+            Some(driver) => driver,
+            None => return Vec::new(),
+        };
+
+        driver.flush(&mut self.input_consumer, &mut self.output_producer);
+
+        let mut converted = vec![0.0; self.output_consumer.occupied_len()];
+        self.output_consumer.pop_slice(&mut converted);
+        converted
+    }
+}
+
+impl Recorder {
+    /// Standardizes the delivered stream to 48 kHz stereo `f32`.
+    ///
+    /// Whatever the devices, the stream is up/downmixed to stereo and resampled when
+    /// needed, giving downstream encoders a single stable contract instead of a rate that
+    /// depends on the user's hardware.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_standard_output(&mut self, standard_output: bool) {
+        self.standard_output = standard_output;
+    }
+
+    /// Remixes the delivered stream to `channels` channels (1, 2 or more), whatever the
+    /// channels of the devices and the `ChannelMode`, or keeps the mixed layout with
+    /// `None`. This also overrides the stereo layout of `set_standard_output`.
+    ///
+    /// Mono is spread to every channel and stereo keeps its left and right channels;
+    /// other layouts keep their first channels, with the channels they lack left silent.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_output_channels(&mut self, channels: Option<u16>) {
+        self.output_channels = channels;
+    }
+
+    /// Overrides the reported config with the standard layout and the requested channel
+    /// count when enabled.
+    pub fn apply_standard_output(&mut self) {
+        if self.standard_output {
+            self.target_sample_rate = Some(STANDARD_SAMPLE_RATE);
+            self.channels = Some(STANDARD_CHANNELS);
+            self.sample_size = Some(size_of::<TargetFormat>() as u32);
+        }
+
+        if let Some(channels) = self.output_channels {
+            self.channels = Some(channels);
+        }
+    }
+
+    /// Creates the converter of the delivered stream, if the standard output is enabled
+    /// or the stream is not at the target sample rate or channel count.
+    pub fn stream_converter(
+        &self,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Option<StreamConverter>, AudioRecorderError> {
+        if self.output_channels == Some(0) {
+            tracing::error!("Cannot deliver a stream without channels");
+            return Err(AudioRecorderError::InvalidArgument(
+                "Output channels must be at least 1",
+            ));
+        }
+
+        let target_rate = match self.standard_output {
+            true => STANDARD_SAMPLE_RATE,
+            false => self.sample_rate.unwrap_or(sample_rate),
+        };
+        let target_channels = match (self.output_channels, self.standard_output) {
+            (Some(target), _) => target,
+            (None, true) => STANDARD_CHANNELS,
+            (None, false) => channels,
+        };
+
+        if !self.standard_output && (target_rate, target_channels) == (sample_rate, channels) {
+            return Ok(None);
+        }
+
+        tracing::debug!(
+            "Converting {} Hz {}ch to {} Hz {}ch",
+            sample_rate,
+            channels,
+            target_rate,
+            target_channels
+        );
+        StreamConverter::with_quality(
+            sample_rate,
+            channels,
+            target_rate,
+            target_channels,
+            self.config.resampler_quality(),
+            self.config.resampler_chunk_size(),
+        )
+        .map(Some)
+    }
+}
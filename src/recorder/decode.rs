@@ -0,0 +1,200 @@
+use std::{fs::File, io::ErrorKind, path::Path, time::Duration};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_NULL, DecoderOptions},
+    errors::Error,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use super::{constants::TargetFormat, errors::AudioRecorderError, mixer_session::AudioInjector};
+
+/// The audio of a decoded file, see `decode_file`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct DecodedAudio {
+    /// Interleaved samples.
+    pub samples: Vec<TargetFormat>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl DecodedAudio {
+    /// Number of frames decoded.
+    pub fn frames(&self) -> u64 {
+        (self.samples.len() / self.channels.max(1) as usize) as u64
+    }
+
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+    }
+}
+
+fn decode_error(path: &Path) -> impl Fn(Error) -> AudioRecorderError + '_ {
+    move |e| {
+        tracing::error!("Failed to decode {:?}: {}", path, e);
+        AudioRecorderError::Decode {
+            path: path.to_path_buf(),
+            source: e,
+        }
+    }
+}
+
+/// Decodes the audio file at `path` (WAV, FLAC, MP3 or AAC in an MP4/M4A or ADTS
+/// container, and the other formats symphonia reads by default) to `TargetFormat`
+/// samples, e.g. to process a recording that was encoded to a compressed format again.
+///
+/// The format is probed from the content, with the extension as a hint. Only the first
+/// audio track is decoded, and packets that fail to decode are skipped.
+#[tracing::instrument(skip_all)]
+pub fn decode_file(path: impl AsRef<Path>) -> Result<DecodedAudio, AudioRecorderError> {
+    let path = path.as_ref();
+    tracing::info!("Decoding {:?}", path);
+
+    let file = File::open(path).map_err(AudioRecorderError::IoError)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(decode_error(path))?;
+    let mut format = probed.format;
+
+    let track = match format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+    {
+        Some(track) => track,
+        None => {
+            tracing::error!("No audio track in {:?}", path);
+            return Err(AudioRecorderError::Decode {
+                path: path.to_path_buf(),
+                source: Error::Unsupported("no audio track"),
+            });
+        }
+    };
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or_default();
+    let mut channels = track
+        .codec_params
+        .channels
+        .map_or(0, |channels| channels.count() as u16);
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(decode_error(path))?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => {
+                tracing::warn!("The tracks of {:?} changed, stopping there", path);
+                break;
+            }
+            Err(e) => return Err(decode_error(path)(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(e)) => {
+                tracing::warn!("Skipping a corrupt packet of {:?}: {}", path, e);
+                continue;
+            }
+            Err(e) => return Err(decode_error(path)(e)),
+        };
+
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        channels = spec.channels.count() as u16;
+
+        let mut buffer = SampleBuffer::<TargetFormat>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+
+    if sample_rate == 0 {
+        tracing::error!("Unknown sample rate of {:?}", path);
+        return Err(AudioRecorderError::Decode {
+            path: path.to_path_buf(),
+            source: Error::DecodeError("unknown sample rate"),
+        });
+    }
+
+    let audio = DecodedAudio {
+        samples,
+        sample_rate,
+        channels: channels.max(1),
+    };
+    tracing::debug!(
+        "Decoded {} frames at {} Hz {}ch from {:?}",
+        audio.frames(),
+        audio.sample_rate,
+        audio.channels,
+        path
+    );
+    Ok(audio)
+}
+
+/// Decodes the audio file at `input`, see `decode_file`, to a 32-bit float WAV file at
+/// `output`, e.g. to stitch compressed segments with `stitch_wav_segments`.
+///
+/// # Returns
+///
+/// The number of frames written to `output`.
+pub fn decode_to_wav(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> Result<u64, AudioRecorderError> {
+    let audio = decode_file(input)?;
+
+    let spec = WavSpec {
+        channels: audio.channels,
+        sample_rate: audio.sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = WavWriter::create(output, spec).map_err(AudioRecorderError::WavError)?;
+    for sample in &audio.samples {
+        writer
+            .write_sample(*sample)
+            .map_err(AudioRecorderError::WavError)?;
+    }
+    writer.finalize().map_err(AudioRecorderError::WavError)?;
+
+    Ok(audio.frames())
+}
+
+impl AudioInjector {
+    /// Creates a source playing the audio file at `path`, decoded with `decode_file`,
+    /// from the start of the session it is injected into.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<AudioInjector, AudioRecorderError> {
+        let audio = decode_file(path)?;
+
+        let injector = AudioInjector::new(audio.sample_rate, audio.channels);
+        injector.push(audio.samples);
+        Ok(injector)
+    }
+}
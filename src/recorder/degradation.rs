@@ -0,0 +1,99 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use super::{
+    Recorder,
+    buffer_occupancy::OccupancyCounters,
+    constants::{DEGRADE_AFTER_MS, DEGRADE_MIN_BACKLOG},
+};
+
+/// A step the recorder takes to lighten its load when it cannot keep up, see
+/// `Recorder::set_degradation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DegradationStep {
+    /// Stops the analyzers of the delivered stream: the features, classification and
+    /// pitch subscriptions end.
+    DropAnalyzers,
+    /// Switches the resamplers to `ResamplerQuality::Fast`.
+    LowerResamplerQuality,
+    /// Doubles the size of the delivered chunks, or paces them to 100 ms when they are
+    /// delivered as produced, so fewer chunks go through the channel and sinks.
+    IncreaseChunkSize,
+}
+
+/// Decides when the delivery falls behind for long enough to take the next step.
+///
+/// The pipeline falls behind while a capture ring buffer is pressured, or while the
+/// data channel holds a backlog of `DEGRADE_MIN_BACKLOG` chunks. A step is taken once
+/// that lasted `DEGRADE_AFTER_MS` without the backlog shrinking.
+pub struct Degrader {
+    steps: VecDeque<DegradationStep>,
+    buffers: [OccupancyCounters; 2],
+    /// When the pipeline started falling behind, with the backlog at that time.
+    behind_since: Option<(Instant, usize)>,
+}
+
+impl Degrader {
+    /// Returns the step to take given the current backlog of the data channel, if any.
+    pub fn check(&mut self, backlog: usize) -> Option<DegradationStep> {
+        if self.steps.is_empty() {
+            return None;
+        }
+
+        let pressured = self.buffers.iter().any(OccupancyCounters::is_pressured);
+        if !pressured && backlog < DEGRADE_MIN_BACKLOG {
+            self.behind_since = None;
+            return None;
+        }
+
+        let (since, start_backlog) = *self
+            .behind_since
+            .get_or_insert_with(|| (Instant::now(), backlog));
+        if since.elapsed() < Duration::from_millis(DEGRADE_AFTER_MS) {
+            return None;
+        }
+
+        self.behind_since = None;
+        if !pressured && backlog < start_backlog {
+            tracing::debug!("Delivery catching up, not degrading");
+            return None;
+        }
+
+        self.steps.pop_front()
+    }
+}
+
+impl Recorder {
+    /// Degrades the recording in the order of `steps` when it cannot keep up (or never,
+    /// with no steps), instead of dropping audio as the buffers overflow.
+    ///
+    /// While a capture ring buffer stays pressured (see `get_buffer_occupancy`) or the
+    /// data channel keeps a growing backlog for a few seconds, the next step is taken
+    /// and reported with `RecorderEvent::Degraded`. Steps are not undone during the
+    /// recording.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_degradation(&mut self, steps: Vec<DegradationStep>) {
+        self.degradation = steps;
+    }
+
+    /// Creates the degrader of a data channel, if steps are configured.
+    pub fn degrader(&self) -> Option<Degrader> {
+        // every recording starts at the configured quality
+        self.resampler_degraded.store(false, Ordering::SeqCst);
+
+        if self.degradation.is_empty() {
+            return None;
+        }
+
+        Some(Degrader {
+            steps: self.degradation.iter().copied().collect(),
+            buffers: [self.input_buffer.clone(), self.output_buffer.clone()],
+            behind_since: None,
+        })
+    }
+}
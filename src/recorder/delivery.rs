@@ -0,0 +1,608 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{SendError, Sender};
+#[cfg(feature = "async")]
+use futures::task::AtomicWaker;
+
+use super::{
+    Recorder,
+    analysis::Analyzer,
+    backpressure::{Backpressure, BackpressurePolicy},
+    chunk::AudioChunk,
+    clock::{ClockSync, ExternalClock},
+    clock_source::ChunkTiming,
+    config::{LatencyMode, ResamplerQuality},
+    constants::{AudioSource, DEGRADE_CHUNK_MS, TargetFormat},
+    convert::StreamConverter,
+    degradation::{DegradationStep, Degrader},
+    dsp::{
+        fingerprint::Fingerprinter,
+        gate::Gate,
+        limiter::Limiter,
+        segmenter::Segmenter,
+        vad::{VadMode, VoiceDetector},
+    },
+    errors::AudioRecorderError,
+    events::{EventBus, RecorderEvent},
+    level_histogram::{LevelCounters, LevelHistogram},
+    processor::ProcessorChain,
+    quota::QuotaTracker,
+    receiver::AudioReceiver,
+    watchdog::Heartbeat,
+};
+
+/// Summary of a finished recording, delivered as the last item of the data channel.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct StreamSummary {
+    /// Total number of frames delivered.
+    pub frames: u64,
+    /// Total number of chunks delivered.
+    pub chunks: u64,
+    /// Sample rate of the delivered stream.
+    pub sample_rate: u32,
+    /// Number of interleaved channels of the delivered stream.
+    pub channels: u16,
+    /// Acoustic fingerprint of the recording, one sub-fingerprint per analysis frame, if
+    /// enabled with `Recorder::set_fingerprint`.
+    pub fingerprint: Option<Vec<u32>>,
+    /// Level histogram of every source that captured audio, see
+    /// `Recorder::get_level_histogram`.
+    pub levels: Vec<LevelHistogram>,
+    /// Number of chunks dropped by the backpressure policy, counted in `chunks`, see
+    /// `Recorder::set_backpressure`.
+    pub dropped_chunks: u64,
+}
+
+impl StreamSummary {
+    /// Total duration of the delivered audio.
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64)
+    }
+}
+
+/// An item on the data channel.
+#[derive(Debug)]
+pub enum StreamMessage {
+    /// A chunk of audio data.
+    Data(AudioChunk),
+    /// The recording has ended, no data follows.
+    End(StreamSummary),
+}
+
+/// The sending side of the data channel, owned by the capture thread delivering the
+/// final stream.
+///
+/// Counts the delivered frames and sends the `StreamMessage::End` marker when dropped,
+/// which happens once the capture thread has released its streams, so the marker is
+/// always the last item on the channel.
+///
+/// When pacing is enabled, data is regrouped into chunks of exactly `chunk_samples`
+/// samples, and the remainder is flushed as a shorter final chunk before the marker.
+pub struct ChunkSender {
+    tx: Sender<StreamMessage>,
+    summary: StreamSummary,
+    converter: Option<StreamConverter>,
+    chunk_samples: usize,
+    pending: Vec<TargetFormat>,
+    /// Cuts the chunks at the pauses of the speech instead of pacing them, if enabled.
+    segmenter: Option<Segmenter>,
+    sequence: u64,
+    fingerprinter: Option<Fingerprinter>,
+    voice_detector: Option<VoiceDetector>,
+    gate: Option<Gate>,
+    limiter: Option<Limiter>,
+    clock: Option<ClockSync>,
+    timing: Option<ChunkTiming>,
+    processors: Option<ProcessorChain>,
+    /// Policy of the bounded channel, `None` when unbounded.
+    backpressure: Option<Backpressure>,
+    analyzers: Vec<Box<dyn Analyzer>>,
+    pub heartbeat: Option<Heartbeat>,
+    pub quota: Option<QuotaTracker>,
+    degrader: Option<Degrader>,
+    /// Raised to lower the quality of the capture resamplers, see `DegradationStep`.
+    resampler_degraded: Option<Arc<AtomicBool>>,
+    events: Option<EventBus>,
+    /// Level histograms of the sources, summarized when the stream ends.
+    levels: Vec<(AudioSource, LevelCounters)>,
+    /// Raised by `Recorder::stop_and_flush`, see `Drop`.
+    flush_signal: Option<Arc<AtomicBool>>,
+    #[cfg(feature = "async")]
+    waker: Arc<AtomicWaker>,
+}
+
+/// Creates the data channel for a stream with the given layout.
+pub fn channel(sample_rate: u32, channels: u16) -> (ChunkSender, AudioReceiver) {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    sender_of(tx, AudioReceiver::new(rx), sample_rate, channels)
+}
+
+/// Creates the data channel for a stream with the given layout, holding at most
+/// `capacity` chunks, see `Recorder::set_backpressure`.
+pub fn bounded_channel(
+    sample_rate: u32,
+    channels: u16,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    dropped: Arc<AtomicU64>,
+    recording_signal: Arc<AtomicBool>,
+) -> (ChunkSender, AudioReceiver) {
+    let (tx, rx) = crossbeam_channel::bounded(capacity.max(1));
+    let receiver = AudioReceiver::new(rx.clone());
+    let backpressure =
+        Backpressure::new(policy, rx, receiver.consumer(), dropped, recording_signal);
+
+    let (mut sender, receiver) = sender_of(tx, receiver, sample_rate, channels);
+    sender.backpressure = Some(backpressure);
+    (sender, receiver)
+}
+
+fn sender_of(
+    tx: Sender<StreamMessage>,
+    receiver: AudioReceiver,
+    sample_rate: u32,
+    channels: u16,
+) -> (ChunkSender, AudioReceiver) {
+    let sender = ChunkSender {
+        tx,
+        summary: StreamSummary {
+            frames: 0,
+            chunks: 0,
+            sample_rate,
+            channels,
+            fingerprint: None,
+            levels: Vec::new(),
+            dropped_chunks: 0,
+        },
+        converter: None,
+        chunk_samples: 0,
+        pending: Vec::new(),
+        segmenter: None,
+        sequence: 0,
+        fingerprinter: None,
+        voice_detector: None,
+        gate: None,
+        limiter: None,
+        clock: None,
+        timing: None,
+        processors: None,
+        backpressure: None,
+        analyzers: Vec::new(),
+        heartbeat: None,
+        quota: None,
+        degrader: None,
+        resampler_degraded: None,
+        events: None,
+        levels: Vec::new(),
+        flush_signal: None,
+        #[cfg(feature = "async")]
+        waker: receiver.waker(),
+    };
+
+    (sender, receiver)
+}
+
+impl Recorder {
+    /// Continues the chunk sequence numbers and frame positions of an earlier recording,
+    /// given the `StreamSummary` it ended with (or `None` to start from zero).
+    ///
+    /// With `WavSink::resume`, a "continue recording" after an intentional stop then
+    /// produces one coherent recording. The stream must keep the sample rate and channels
+    /// of the earlier recording, otherwise `start` fails.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_resume_from(&mut self, previous: Option<StreamSummary>) {
+        self.resume_from = previous;
+    }
+
+    /// Delivers chunks holding exactly `chunk_duration` of audio (or `None` to deliver
+    /// chunks as the devices produce them).
+    ///
+    /// Device callbacks arrive at an irregular cadence and size; with pacing, consumers
+    /// that assume regular arrival, such as streaming encoders, receive one fixed-size
+    /// chunk per `chunk_duration` of recorded audio.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_chunk_duration(&mut self, chunk_duration: Option<Duration>) {
+        self.chunk_duration = chunk_duration;
+    }
+
+    /// Computes an acoustic fingerprint of the recording, delivered in its `StreamSummary`.
+    ///
+    /// The fingerprint is computed incrementally as chunks are delivered, for
+    /// deduplication and content identification pipelines.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_fingerprint(&mut self, fingerprint: bool) {
+        self.fingerprint = fingerprint;
+    }
+
+    /// Annotates every delivered chunk with whether it contains speech (or `None` to
+    /// disable it), see `AudioChunk::speech`, e.g. so transcription apps skip silence
+    /// without push-to-talk.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_voice_detection(&mut self, mode: Option<VadMode>) {
+        self.voice_detection = mode;
+    }
+
+    /// Creates the data channel for a stream captured with the given layout, converting it
+    /// to the standard layout or target sample rate and pacing it when enabled.
+    pub fn data_channel(
+        &self,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<(ChunkSender, AudioReceiver), AudioRecorderError> {
+        let converter = self.stream_converter(sample_rate, channels)?;
+
+        let (sample_rate, channels) = match &converter {
+            None => (sample_rate, channels),
+            Some(converter) => (converter.output_rate(), converter.output_channels()),
+        };
+
+        let (mut sender, receiver) = match self.channel_capacity {
+            Some(capacity) => bounded_channel(
+                sample_rate,
+                channels,
+                capacity,
+                self.backpressure,
+                self.dropped_chunks.clone(),
+                self.recording_signal.clone(),
+            ),
+            None => channel(sample_rate, channels),
+        };
+        sender.converter = converter;
+
+        if self.fingerprint {
+            sender.fingerprinter = Some(Fingerprinter::new(sample_rate, channels));
+        }
+        // robust recordings follow the system clock unless given another one
+        let clock = match (&self.external_clock, self.config.latency_mode()) {
+            (Some(clock), _) => Some(clock.clone()),
+            (None, LatencyMode::Robust) => {
+                let start = Instant::now();
+                Some(ExternalClock::new(move || start.elapsed()).rate_adjust(true))
+            }
+            (None, _) => None,
+        };
+        sender.clock = clock.map(|clock| ClockSync::new(clock, sample_rate, channels));
+        sender.timing = Some(self.chunk_timing(sample_rate));
+        sender.gate = self
+            .silence_gate
+            .map(|gate| gate.gate(sample_rate, channels));
+        sender.limiter = self
+            .limiter
+            .map(|limiter| limiter.limiter(sample_rate, channels));
+        sender.voice_detector = self
+            .voice_detection
+            .map(|mode| VoiceDetector::new(sample_rate, channels, mode));
+        sender.analyzers = self.analyzers(sample_rate, channels);
+        sender.processors = Some(self.processors.clone());
+
+        if let Some(duration) = self.chunk_duration {
+            let frames = ((duration.as_secs_f64() * sample_rate as f64) as usize).max(1);
+            tracing::debug!("Pacing chunks to {} frames", frames);
+            sender.chunk_samples = frames * channels as usize;
+        }
+        sender.segmenter = self
+            .adaptive_chunking
+            .map(|chunking| chunking.segmenter(sample_rate, channels));
+
+        if let Some(previous) = &self.resume_from {
+            if (previous.sample_rate, previous.channels) != (sample_rate, channels) {
+                tracing::error!(
+                    "Cannot resume a {} Hz {}ch stream as {} Hz {}ch",
+                    previous.sample_rate,
+                    previous.channels,
+                    sample_rate,
+                    channels
+                );
+                return Err(AudioRecorderError::ResumeMismatch {
+                    sample_rate,
+                    channels,
+                });
+            }
+
+            tracing::debug!("Resuming after {} chunks", previous.chunks);
+            sender.sequence = previous.chunks;
+            sender.summary.chunks = previous.chunks;
+            sender.summary.frames = previous.frames;
+        }
+
+        self.watch(&mut sender)?;
+        self.apply_quota(&mut sender, sample_rate, channels);
+        sender.degrader = self.degrader();
+        sender.resampler_degraded = Some(self.resampler_degraded.clone());
+
+        self.events.emit(RecorderEvent::Started {
+            sample_rate,
+            channels,
+        });
+        sender.events = Some(self.events.clone());
+        sender.levels = self.level_histograms();
+        sender.flush_signal = Some(self.flush_signal.clone());
+
+        Ok((sender, receiver))
+    }
+}
+
+impl ChunkSender {
+    /// Adapts the sender to a stream captured with a new layout after a device change,
+    /// converting it to the layout already delivered.
+    pub fn reconfigure(
+        &mut self,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<(), AudioRecorderError> {
+        let (target_rate, target_channels) = (self.summary.sample_rate, self.summary.channels);
+
+        self.converter = match (sample_rate, channels) == (target_rate, target_channels) {
+            true => None,
+            false => Some(StreamConverter::new(
+                sample_rate,
+                channels,
+                target_rate,
+                target_channels,
+            )?),
+        };
+
+        Ok(())
+    }
+
+    /// Sample rate and channels of the delivered stream.
+    pub fn layout(&self) -> (u32, u16) {
+        (self.summary.sample_rate, self.summary.channels)
+    }
+
+    /// Delivers the audio still inside the converter, which is removed.
+    pub fn flush_converter(&mut self) {
+        if let Some(mut converter) = self.converter.take() {
+            let tail = self.limit(converter.flush());
+            let tail = self.gate(tail);
+            if self.pace(tail).is_err() {
+                tracing::debug!("Receiver dropped before the converter tail");
+            }
+        }
+    }
+
+    pub fn send(&mut self, data: Vec<TargetFormat>) -> Result<(), SendError<StreamMessage>> {
+        let step = self
+            .degrader
+            .as_mut()
+            .and_then(|degrader| degrader.check(self.tx.len()));
+        if let Some(step) = step {
+            self.degrade(step);
+        }
+
+        let data = match &mut self.converter {
+            Some(converter) => converter.process(data),
+            None => data,
+        };
+
+        let data = match &mut self.clock {
+            Some(clock) => clock.process(data),
+            None => data,
+        };
+
+        let data = self.limit(data);
+        let data = self.gate(data);
+        self.pace(data)
+    }
+
+    /// Takes a degradation step, as the delivery cannot keep up.
+    fn degrade(&mut self, step: DegradationStep) {
+        tracing::warn!("Delivery falling behind, degrading: {:?}", step);
+
+        match step {
+            DegradationStep::DropAnalyzers => self.analyzers.clear(),
+            DegradationStep::LowerResamplerQuality => {
+                let quality = ResamplerQuality::Fast;
+                if let Some(converter) = &mut self.converter {
+                    if let Err(e) = converter.set_quality(quality, quality.chunk_size()) {
+                        tracing::error!("Failed to lower the converter quality: {}", e);
+                    }
+                }
+                if let Some(degraded) = &self.resampler_degraded {
+                    degraded.store(true, Ordering::SeqCst);
+                }
+            }
+            DegradationStep::IncreaseChunkSize => {
+                let (sample_rate, channels) = self.layout();
+                self.chunk_samples = match self.chunk_samples {
+                    0 => {
+                        (sample_rate * DEGRADE_CHUNK_MS / 1000).max(1) as usize * channels as usize
+                    }
+                    samples => samples * 2,
+                };
+                tracing::debug!("Pacing chunks to {} samples", self.chunk_samples);
+            }
+        }
+
+        if let Some(events) = &self.events {
+            events.emit(RecorderEvent::Degraded { step });
+        }
+    }
+
+    /// Keeps the converted or mixed audio below the limiter threshold, if enabled.
+    fn limit(&mut self, mut data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        if let Some(limiter) = &mut self.limiter {
+            limiter.process(&mut data);
+        }
+        data
+    }
+
+    /// Keeps the audio passing the silence gate, if enabled.
+    fn gate(&mut self, data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        match &mut self.gate {
+            Some(gate) => gate.process(data),
+            None => data,
+        }
+    }
+
+    /// Delivers `data`, regrouped into chunks of `chunk_samples` when paced, or cut at
+    /// the pauses of the speech by the segmenter.
+    fn pace(&mut self, data: Vec<TargetFormat>) -> Result<(), SendError<StreamMessage>> {
+        if let Some(segmenter) = &mut self.segmenter {
+            let segments = segmenter.process(&data);
+            return segments
+                .into_iter()
+                .try_for_each(|segment| self.deliver(segment));
+        }
+
+        if self.chunk_samples == 0 {
+            return self.deliver(data);
+        }
+
+        self.pending.extend_from_slice(&data);
+        while self.pending.len() >= self.chunk_samples {
+            let rest = self.pending.split_off(self.chunk_samples);
+            let chunk = std::mem::replace(&mut self.pending, rest);
+            self.deliver(chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends `message` on the data channel, applying the backpressure policy if bounded.
+    fn push(&mut self, message: StreamMessage) -> Result<(), SendError<StreamMessage>> {
+        match &mut self.backpressure {
+            Some(backpressure) => backpressure.send(&self.tx, message),
+            None => self.tx.send(message),
+        }
+    }
+
+    fn deliver(&mut self, mut data: Vec<TargetFormat>) -> Result<(), SendError<StreamMessage>> {
+        if let Some(quota) = &mut self.quota {
+            quota.admit(&mut data);
+        }
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let mut chunk = AudioChunk {
+            samples: data,
+            channels: self.summary.channels,
+            sample_rate: self.summary.sample_rate,
+            timestamp: Instant::now(),
+            sequence: self.sequence,
+            frame: self.summary.frames,
+            speech: None,
+            clock_time: self
+                .clock
+                .as_ref()
+                .and_then(|clock| clock.time_of(self.summary.frames)),
+            time: Duration::ZERO,
+        };
+
+        if let Some(processors) = &self.processors {
+            processors.process(&mut chunk);
+            if chunk.samples.is_empty() {
+                return Ok(());
+            }
+        }
+
+        chunk.time = match &mut self.timing {
+            Some(timing) => timing.stamp(chunk.position(), chunk.duration()),
+            None => chunk.position(),
+        };
+
+        if let Some(fingerprinter) = &mut self.fingerprinter {
+            fingerprinter.process(&chunk.samples);
+        }
+
+        chunk.speech = self
+            .voice_detector
+            .as_mut()
+            .map(|detector| detector.process(&chunk.samples));
+
+        self.sequence += 1;
+        self.summary.chunks = self.sequence;
+        self.summary.frames = chunk.end_frame();
+
+        for analyzer in &mut self.analyzers {
+            analyzer.process(&chunk);
+        }
+
+        self.push(StreamMessage::Data(chunk))?;
+
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.beat();
+        }
+
+        #[cfg(feature = "async")]
+        self.waker.wake();
+
+        Ok(())
+    }
+}
+
+impl Drop for ChunkSender {
+    fn drop(&mut self) {
+        // a consumer that stopped reading must not keep the capture thread from ending
+        if let Some(backpressure) = &mut self.backpressure {
+            backpressure.close();
+        }
+
+        // the tail still inside the converter is delivered before the last paced chunk
+        let flush = self
+            .flush_signal
+            .as_ref()
+            .is_some_and(|signal| signal.load(Ordering::SeqCst));
+        if flush {
+            self.flush_converter();
+        }
+
+        if let Some(tail) = self.gate.as_mut().map(Gate::flush) {
+            if self.pace(tail).is_err() {
+                tracing::debug!("Receiver dropped before the gated tail");
+            }
+        }
+
+        if let Some(segment) = self.segmenter.as_mut().map(Segmenter::flush) {
+            if self.deliver(segment).is_err() {
+                tracing::debug!("Receiver dropped before the last segment");
+            }
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        if self.deliver(pending).is_err() {
+            tracing::debug!("Receiver dropped before the last paced chunk");
+        }
+
+        self.summary.fingerprint = self.fingerprinter.take().map(Fingerprinter::finish);
+        self.summary.levels = self
+            .levels
+            .iter()
+            .map(|(source, levels)| levels.snapshot(*source))
+            .filter(|histogram| histogram.total_frames() > 0)
+            .collect();
+
+        self.summary.dropped_chunks = self.backpressure.as_ref().map_or(0, Backpressure::dropped);
+
+        if let Some(events) = &self.events {
+            events.emit(RecorderEvent::Stopped {
+                duration: self.summary.duration(),
+            });
+        }
+
+        tracing::debug!("Sending end of stream: {:?}", self.summary);
+        if self.push(StreamMessage::End(self.summary.clone())).is_err() {
+            tracing::debug!("Receiver dropped before end of stream");
+        }
+
+        #[cfg(feature = "async")]
+        self.waker.wake();
+    }
+}
@@ -0,0 +1,128 @@
+use std::{sync::atomic::Ordering, thread};
+
+use cpal::traits::DeviceTrait;
+
+use super::{
+    Recorder,
+    constants::AudioSource,
+    errors::AudioRecorderError,
+    events::RecorderEvent,
+    get_default_device::{get_default_input_device, get_default_output_device},
+    negotiate_config::{resolve_input_config, resolve_output_config},
+};
+
+/// Watches the default device of a source, reporting when it changes.
+pub struct DeviceMonitor {
+    source: AudioSource,
+    current: Option<String>,
+    prefer_f32: bool,
+}
+
+/// Opens the current default device of `source`.
+fn default_device(source: AudioSource) -> Result<cpal::Device, AudioRecorderError> {
+    match source {
+        AudioSource::Input => get_default_input_device(),
+        AudioSource::Output => get_default_output_device(),
+    }
+}
+
+/// Name of the current default device of `source`, if there is one.
+fn default_device_name(source: AudioSource) -> Option<String> {
+    default_device(source).ok()?.name().ok()
+}
+
+impl DeviceMonitor {
+    fn new(source: AudioSource, prefer_f32: bool) -> Self {
+        DeviceMonitor {
+            source,
+            current: default_device_name(source),
+            prefer_f32,
+        }
+    }
+
+    pub fn source(&self) -> AudioSource {
+        self.source
+    }
+
+    /// Returns the name of the new default device if it changed since the last poll.
+    ///
+    /// A default device that went away without a replacement is not reported, the
+    /// change is reported once another device becomes the default.
+    pub fn poll(&mut self) -> Option<String> {
+        let name = default_device_name(self.source)?;
+        if self.current.as_ref() == Some(&name) {
+            return None;
+        }
+
+        tracing::info!("Default {:?} device changed to {:?}", self.source, name);
+        self.current = Some(name.clone());
+        Some(name)
+    }
+
+    /// Opens the new default device along with the stream config to capture it with.
+    pub fn open(&self) -> Result<(cpal::Device, cpal::SupportedStreamConfig), AudioRecorderError> {
+        let device = default_device(self.source)?;
+        let config = match self.source {
+            AudioSource::Input => resolve_input_config(&device, self.prefer_f32)?,
+            AudioSource::Output => resolve_output_config(&device, self.prefer_f32)?,
+        };
+
+        Ok((device, config))
+    }
+}
+
+impl Recorder {
+    /// Follows the default devices while recording.
+    ///
+    /// When the default device of a source changes (a USB microphone is unplugged, a
+    /// Bluetooth headset connects, ...) a `RecorderEvent::DeviceChanged` is emitted
+    /// instead of the stream silently going quiet. Single stream recordings (input only
+    /// and `start_separate`) transparently rebuild their stream on the new device,
+    /// converting it to the layout already delivered; mixed recordings only report the
+    /// change, so they can be restarted.
+    ///
+    /// Devices picked by name are never switched.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_device_monitor(&mut self, device_monitor: bool) {
+        self.device_monitor = device_monitor;
+    }
+
+    /// Creates the monitor of the default device of `source` if enabled.
+    pub fn device_monitor(&self, source: AudioSource) -> Option<DeviceMonitor> {
+        match self.device_monitor {
+            true => Some(DeviceMonitor::new(source, self.prefer_f32)),
+            false => None,
+        }
+    }
+
+    /// Reports the default device changes of a mixed recording, if enabled.
+    pub fn watch_devices(&self) -> Result<(), AudioRecorderError> {
+        let mut monitors = match self.device_monitor {
+            true => vec![
+                DeviceMonitor::new(AudioSource::Input, self.prefer_f32),
+                DeviceMonitor::new(AudioSource::Output, self.prefer_f32),
+            ],
+            false => return Ok(()),
+        };
+
+        let events = self.events.clone();
+        let recording_signal = self.recording_signal.clone();
+        let poll_interval = self.config.poll_interval();
+
+        self.workers().spawn("arec-device-monitor", move || {
+            while recording_signal.load(Ordering::SeqCst) {
+                thread::sleep(poll_interval);
+
+                for monitor in &mut monitors {
+                    if let Some(device) = monitor.poll() {
+                        events.emit(RecorderEvent::DeviceChanged {
+                            source: monitor.source(),
+                            device,
+                        });
+                    }
+                }
+            }
+        })
+    }
+}
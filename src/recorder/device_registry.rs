@@ -0,0 +1,131 @@
+use std::{
+    sync::{
+        Arc, Mutex, OnceLock, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::Receiver;
+
+use super::{devices::AudioDevice, events::EventBus};
+
+/// The registry shared by `DeviceRegistry::global` and `AudioDevice::list_cached`.
+static GLOBAL: OnceLock<DeviceRegistry> = OnceLock::new();
+
+/// A cache of the enumerated devices, refreshed on demand or in the background.
+///
+/// Enumerating the devices takes hundreds of milliseconds on some hosts (ASIO, JACK,
+/// ScreenCaptureKit, ...), so a settings panel listing them repeatedly should read this
+/// cache instead of calling `AudioDevice::list_all`. Subscribers are sent the new list
+/// whenever a refresh finds it changed.
+///
+/// Clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRegistry {
+    devices: Arc<Mutex<Option<Vec<AudioDevice>>>>,
+    changes: EventBus<Vec<AudioDevice>>,
+    watching: Arc<AtomicBool>,
+}
+
+impl DeviceRegistry {
+    /// Creates an empty registry, enumerating the devices on first use.
+    pub fn new() -> Self {
+        DeviceRegistry::default()
+    }
+
+    /// The registry shared across the process.
+    pub fn global() -> &'static DeviceRegistry {
+        GLOBAL.get_or_init(DeviceRegistry::new)
+    }
+
+    /// The cached devices, enumerated now if the cache was never filled.
+    pub fn devices(&self) -> Vec<AudioDevice> {
+        let mut devices = self.devices.lock().unwrap_or_else(PoisonError::into_inner);
+
+        devices.get_or_insert_with(AudioDevice::list_all).clone()
+    }
+
+    /// Enumerates the devices again, notifying the subscribers if they changed.
+    #[tracing::instrument(skip(self))]
+    pub fn refresh(&self) -> Vec<AudioDevice> {
+        // enumerated without the lock, so readers get the previous list meanwhile
+        let listed = AudioDevice::list_all();
+
+        let previous = self
+            .devices
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(listed.clone());
+
+        if previous.as_ref() != Some(&listed) {
+            tracing::debug!("Device list changed, {} devices", listed.len());
+            self.changes.emit(listed.clone());
+        }
+
+        listed
+    }
+
+    /// Fills the cache on a background thread, so the first `devices` call is instant.
+    pub fn warm(&self) {
+        let registry = self.clone();
+
+        let spawned = thread::Builder::new()
+            .name("arec-device-registry".to_string())
+            .spawn(move || {
+                registry.refresh();
+            });
+        if let Err(e) = spawned {
+            tracing::error!("Failed to spawn device registry thread: {}", e);
+        }
+    }
+
+    /// Refreshes the cache every `interval` on a background thread until `unwatch`.
+    ///
+    /// Calling it while already watching does nothing.
+    pub fn watch(&self, interval: Duration) {
+        if self.watching.swap(true, Ordering::SeqCst) {
+            tracing::debug!("Device registry already watching");
+            return;
+        }
+
+        let registry = self.clone();
+        let spawned = thread::Builder::new()
+            .name("arec-device-registry".to_string())
+            .spawn(move || {
+                while registry.watching.load(Ordering::SeqCst) {
+                    registry.refresh();
+                    thread::sleep(interval);
+                }
+                tracing::debug!("Device registry stopped watching");
+            });
+        if let Err(e) = spawned {
+            tracing::error!("Failed to spawn device registry thread: {}", e);
+            self.watching.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Stops the background refresh started by `watch`.
+    pub fn unwatch(&self) {
+        self.watching.store(false, Ordering::SeqCst);
+    }
+
+    /// Subscribes to the device list, sent whenever a refresh finds it changed.
+    ///
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<Vec<AudioDevice>> {
+        self.changes.subscribe()
+    }
+}
+
+impl AudioDevice {
+    /// Lists the devices from the cache of `DeviceRegistry::global`, enumerating them
+    /// only on the first call.
+    ///
+    /// Call `DeviceRegistry::global().refresh()` (or `watch`) to pick up devices
+    /// plugged in since.
+    pub fn list_cached() -> Vec<AudioDevice> {
+        DeviceRegistry::global().devices()
+    }
+}
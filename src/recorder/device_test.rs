@@ -0,0 +1,223 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use cpal::{
+    FromSample, SizedSample,
+    traits::{DeviceTrait, StreamTrait},
+};
+
+use super::{
+    com::ComGuard,
+    constants::{CustomSample, TEST_TONE_FADE_MS, TEST_TONE_HZ, TEST_TONE_LEVEL_DB, TargetFormat},
+    devices::{AudioDevice, DeviceType},
+    dsp::level::{db_to_linear, peak, rms, to_dbfs},
+    errors::AudioRecorderError,
+};
+
+/// Level measured by `AudioDevice::check_input_level`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct InputLevel {
+    /// Peak level of the capture, in dBFS.
+    pub peak_db: f32,
+    /// RMS level of the capture, in dBFS.
+    pub rms_db: f32,
+}
+
+/// Builds an output stream playing the test tone on every channel.
+fn build_tone_stream<S>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    duration: Duration,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    S: SizedSample + FromSample<f32>,
+{
+    let sample_rate = config.sample_rate.0 as f32;
+    let channels = config.channels.max(1) as usize;
+    let amplitude = db_to_linear(TEST_TONE_LEVEL_DB);
+    let total_frames = (duration.as_secs_f32() * sample_rate) as u64;
+    let fade_frames = (TEST_TONE_FADE_MS as f32 / 1000.0 * sample_rate).max(1.0);
+    let mut frame: u64 = 0;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [S], _: &_| {
+            for out in data.chunks_mut(channels) {
+                // short fades keep the tone free of clicks
+                let remaining = total_frames.saturating_sub(frame) as f32;
+                let envelope = (frame as f32 / fade_frames)
+                    .min(remaining / fade_frames)
+                    .min(1.0);
+                let phase = frame as f32 / sample_rate * TEST_TONE_HZ;
+                let value = amplitude * envelope * (2.0 * std::f32::consts::PI * phase).sin();
+
+                out.fill(S::from_sample(value));
+                frame += 1;
+            }
+        },
+        |e| tracing::error!("An error occurred on the test tone stream: {}", e),
+        None,
+    )
+}
+
+/// Builds an input stream appending the captured samples to `samples`.
+fn build_level_stream<S: CustomSample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: Arc<Mutex<Vec<TargetFormat>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    device.build_input_stream(
+        config,
+        move |data: &[S], _: &_| match samples.lock() {
+            Ok(mut samples) => samples.extend(data.iter().map(|s| s.to_sample::<TargetFormat>())),
+            Err(e) => tracing::error!("Failed to lock level samples: {}", e),
+        },
+        |e| tracing::error!("An error occurred on the level check stream: {}", e),
+        None,
+    )
+}
+
+impl AudioDevice {
+    /// Plays a 440 Hz tone at -12 dBFS on this output device for `duration`, blocking
+    /// until it has been played, e.g. behind a "test speaker" button.
+    pub fn play_test_tone(&self, duration: Duration) -> Result<(), AudioRecorderError> {
+        tracing::info!("Playing test tone on {:?}", self.name);
+
+        if self.device_type != DeviceType::Output {
+            tracing::error!("Device {:?} is not an output device", self.name);
+            return Err(AudioRecorderError::DeviceError(
+                "Device is not an output device",
+            ));
+        }
+
+        let _com = ComGuard::init();
+        let device = self.open()?;
+        let supported = device.default_output_config().map_err(|e| {
+            tracing::error!("Failed to get default output config: {}", e);
+            AudioRecorderError::StreamConfig {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+        let config = supported.config();
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::I8 => build_tone_stream::<i8>(&device, &config, duration),
+            cpal::SampleFormat::I16 => build_tone_stream::<i16>(&device, &config, duration),
+            cpal::SampleFormat::I32 => build_tone_stream::<i32>(&device, &config, duration),
+            cpal::SampleFormat::U8 => build_tone_stream::<u8>(&device, &config, duration),
+            cpal::SampleFormat::U16 => build_tone_stream::<u16>(&device, &config, duration),
+            cpal::SampleFormat::U32 => build_tone_stream::<u32>(&device, &config, duration),
+            cpal::SampleFormat::F32 => build_tone_stream::<f32>(&device, &config, duration),
+            cpal::SampleFormat::F64 => build_tone_stream::<f64>(&device, &config, duration),
+            other => {
+                tracing::error!("Unsupported sample format: {:?}", other);
+                return Err(AudioRecorderError::UnsupportedSampleFormat {
+                    device: self.name.clone(),
+                    format: other,
+                });
+            }
+        }
+        .map_err(|e| {
+            tracing::error!("Failed to build test tone stream: {}", e);
+            AudioRecorderError::BuildStream {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+
+        stream.play().map_err(|e| {
+            tracing::error!("Failed to play test tone stream: {}", e);
+            AudioRecorderError::PlayStream {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+
+        thread::sleep(duration);
+        drop(stream);
+
+        tracing::info!("Test tone played");
+        Ok(())
+    }
+
+    /// Captures this input device for `duration` and measures its level, blocking until
+    /// done, e.g. behind a "test microphone" button.
+    pub fn check_input_level(&self, duration: Duration) -> Result<InputLevel, AudioRecorderError> {
+        tracing::info!("Checking the input level of {:?}", self.name);
+
+        if self.device_type != DeviceType::Input {
+            tracing::error!("Device {:?} is not an input device", self.name);
+            return Err(AudioRecorderError::NotAnInputDevice {
+                device: self.name.clone(),
+            });
+        }
+
+        let _com = ComGuard::init();
+        let device = self.open()?;
+        let supported = device.default_input_config().map_err(|e| {
+            tracing::error!("Failed to get default input config: {}", e);
+            AudioRecorderError::StreamConfig {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+        let config = supported.config();
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::I8 => build_level_stream::<i8>(&device, &config, samples.clone()),
+            cpal::SampleFormat::I16 => build_level_stream::<i16>(&device, &config, samples.clone()),
+            cpal::SampleFormat::I32 => build_level_stream::<i32>(&device, &config, samples.clone()),
+            cpal::SampleFormat::U8 => build_level_stream::<u8>(&device, &config, samples.clone()),
+            cpal::SampleFormat::U16 => build_level_stream::<u16>(&device, &config, samples.clone()),
+            cpal::SampleFormat::U32 => build_level_stream::<u32>(&device, &config, samples.clone()),
+            cpal::SampleFormat::F32 => build_level_stream::<f32>(&device, &config, samples.clone()),
+            cpal::SampleFormat::F64 => build_level_stream::<f64>(&device, &config, samples.clone()),
+            other => {
+                tracing::error!("Unsupported sample format: {:?}", other);
+                return Err(AudioRecorderError::UnsupportedSampleFormat {
+                    device: self.name.clone(),
+                    format: other,
+                });
+            }
+        }
+        .map_err(|e| {
+            tracing::error!("Failed to build level check stream: {}", e);
+            AudioRecorderError::BuildStream {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+
+        stream.play().map_err(|e| {
+            tracing::error!("Failed to play level check stream: {}", e);
+            AudioRecorderError::PlayStream {
+                device: self.name.clone(),
+                source: e,
+            }
+        })?;
+
+        thread::sleep(duration);
+        drop(stream);
+
+        let samples = samples.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if samples.is_empty() {
+            tracing::error!("Device {:?} delivered no audio", self.name);
+            return Err(AudioRecorderError::DeviceError("Device delivered no audio"));
+        }
+
+        let level = InputLevel {
+            peak_db: to_dbfs(peak(&samples)),
+            rms_db: to_dbfs(rms(&samples)),
+        };
+        tracing::info!("Input level: {:?}", level);
+
+        Ok(level)
+    }
+}
@@ -0,0 +1,203 @@
+use cpal::{
+    HostId, SampleFormat,
+    traits::{DeviceTrait, HostTrait},
+};
+
+use super::errors::AudioRecorderError;
+
+/// Whether a device captures (input) or renders (output) audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceType {
+    Input,
+    Output,
+}
+
+/// The default stream config of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeviceConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+}
+
+/// A device found while enumerating the available hosts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AudioDevice {
+    /// The device name as reported by the host.
+    pub name: String,
+    /// Whether the device is an input or an output device.
+    pub device_type: DeviceType,
+    /// Every host exposing this device, the default host first.
+    pub hosts: Vec<HostId>,
+    /// Sample formats the device supports, as reported by the first host.
+    pub supported_formats: Vec<SampleFormat>,
+    /// The default stream config, as reported by the first host.
+    pub default_config: Option<DeviceConfig>,
+}
+
+/// Adds a device to `devices`, merging it with an already listed device of the same name
+/// and type exposed by another host.
+fn merge_device(
+    devices: &mut Vec<AudioDevice>,
+    host_id: HostId,
+    device_type: DeviceType,
+    device: &cpal::Device,
+) {
+    let name = match device.name() {
+        Ok(name) => name,
+        Err(e) => {
+            tracing::warn!("Failed to get device name: {}", e);
+            return;
+        }
+    };
+
+    if let Some(listed) = devices
+        .iter_mut()
+        .find(|d| d.name == name && d.device_type == device_type)
+    {
+        if !listed.hosts.contains(&host_id) {
+            listed.hosts.push(host_id);
+        }
+        return;
+    }
+
+    let (supported_formats, default_config) = match device_type {
+        DeviceType::Input => (
+            device
+                .supported_input_configs()
+                .map(|configs| configs.map(|c| c.sample_format()).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            device.default_input_config().ok(),
+        ),
+        DeviceType::Output => (
+            device
+                .supported_output_configs()
+                .map(|configs| configs.map(|c| c.sample_format()).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            device.default_output_config().ok(),
+        ),
+    };
+
+    let mut formats: Vec<SampleFormat> = Vec::new();
+    for format in supported_formats {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+
+    devices.push(AudioDevice {
+        name,
+        device_type,
+        hosts: vec![host_id],
+        supported_formats: formats,
+        default_config: default_config.map(|c| DeviceConfig {
+            sample_rate: c.sample_rate().0,
+            channels: c.channels(),
+            sample_format: c.sample_format(),
+        }),
+    });
+}
+
+impl AudioDevice {
+    /// Lists the input and output devices of every available host, with their supported
+    /// sample formats and default configs.
+    ///
+    /// Interesting loopback devices often live on a non-default host (e.g. ASIO next to
+    /// WASAPI, or ScreenCaptureKit next to CoreAudio), so all hosts are queried. Devices
+    /// exposed by several hosts are listed once, tagged with every host exposing them.
+    pub fn list_all() -> Vec<AudioDevice> {
+        let default_host_id = cpal::default_host().id();
+
+        let mut host_ids = cpal::available_hosts();
+        host_ids.sort_by_key(|id| *id != default_host_id);
+
+        let mut devices = Vec::new();
+
+        for host_id in host_ids {
+            tracing::debug!("Enumerating devices of host {:?}", host_id);
+            let host = match cpal::host_from_id(host_id) {
+                Ok(h) => h,
+                Err(e) => {
+                    tracing::warn!("Failed to open host {:?}: {}", host_id, e);
+                    continue;
+                }
+            };
+
+            match host.input_devices() {
+                Ok(input_devices) => {
+                    for device in input_devices {
+                        merge_device(&mut devices, host_id, DeviceType::Input, &device);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to list input devices of {:?}: {}", host_id, e),
+            }
+
+            match host.output_devices() {
+                Ok(output_devices) => {
+                    for device in output_devices {
+                        merge_device(&mut devices, host_id, DeviceType::Output, &device);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to list output devices of {:?}: {}", host_id, e),
+            }
+        }
+
+        devices
+    }
+
+    /// Finds the listed device named `name`, trying each of `device_types` in order.
+    pub fn find(
+        name: &str,
+        device_types: &[DeviceType],
+    ) -> Result<AudioDevice, AudioRecorderError> {
+        let devices = AudioDevice::list_all();
+
+        for device_type in device_types {
+            if let Some(device) = devices
+                .iter()
+                .find(|d| d.name == name && d.device_type == *device_type)
+            {
+                return Ok(device.clone());
+            }
+        }
+
+        tracing::error!("Device {:?} not found", name);
+        Err(AudioRecorderError::DeviceError("Device not found"))
+    }
+
+    /// Opens the device on the first of its hosts still exposing it.
+    pub fn open(&self) -> Result<cpal::Device, AudioRecorderError> {
+        for host_id in &self.hosts {
+            let host = match cpal::host_from_id(*host_id) {
+                Ok(h) => h,
+                Err(e) => {
+                    tracing::warn!("Failed to open host {:?}: {}", host_id, e);
+                    continue;
+                }
+            };
+
+            let devices = match self.device_type {
+                DeviceType::Input => host.input_devices(),
+                DeviceType::Output => host.output_devices(),
+            };
+
+            let mut devices = match devices {
+                Ok(d) => d,
+                Err(e) => {
+                    tracing::warn!("Failed to list devices of {:?}: {}", host_id, e);
+                    continue;
+                }
+            };
+
+            if let Some(device) = devices.find(|d| d.name().is_ok_and(|name| name == self.name)) {
+                tracing::debug!("Opened device {:?} on host {:?}", self.name, host_id);
+                return Ok(device);
+            }
+        }
+
+        tracing::error!("Device {:?} not found", self.name);
+        Err(AudioRecorderError::DeviceError("Device not found"))
+    }
+}
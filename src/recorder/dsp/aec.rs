@@ -0,0 +1,264 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex, num_complex::Complex};
+
+use crate::recorder::constants::{AEC_DOUBLE_TALK, AEC_STEP, TargetFormat};
+
+use super::level::peak;
+
+/// The adaptive filter modelling the echo path into one microphone channel.
+struct ChannelFilter {
+    /// One spectrum per partition of the echo tail.
+    weights: Vec<Vec<Complex<f32>>>,
+}
+
+/// Cancels the echo of the system audio picked up by the microphone.
+///
+/// A partitioned block frequency domain adaptive filter (PBFDAF) learns the path from
+/// the system audio, downmixed to mono as the reference, to each microphone channel:
+/// the tail is split into partitions of one block, every block the echo is estimated
+/// from the spectra of the last reference blocks and subtracted from the microphone,
+/// and the filter is updated with the normalized error.
+///
+/// Adaptation pauses while the reference is silent or the microphone is louder than the
+/// reference could make it (double talk, with a Geigel detector), and a diverged filter
+/// is reset. The output lags the input by one block, for both sources, so they stay
+/// aligned.
+pub struct EchoCanceller {
+    block: usize,
+    mic_channels: usize,
+    system_channels: usize,
+    forward: Arc<dyn RealToComplex<TargetFormat>>,
+    inverse: Arc<dyn ComplexToReal<TargetFormat>>,
+    filters: Vec<ChannelFilter>,
+    /// Spectra of the last reference blocks, the newest first.
+    history: VecDeque<Vec<Complex<f32>>>,
+    /// Peaks of the last reference blocks, for the double talk detector.
+    peaks: VecDeque<f32>,
+    /// Smoothed power of the reference per bin, summed over the partitions.
+    power: Vec<f32>,
+    /// The previous reference block, the first half of the overlap-save frame.
+    previous: Vec<TargetFormat>,
+    /// Frames not yet part of a processed block.
+    pending_mic: Vec<TargetFormat>,
+    pending_system: Vec<TargetFormat>,
+    /// Processed frames, primed with one block of silence.
+    output_mic: VecDeque<TargetFormat>,
+    output_system: VecDeque<TargetFormat>,
+    input: Vec<TargetFormat>,
+    spectrum: Vec<Complex<f32>>,
+    output: Vec<TargetFormat>,
+}
+
+impl EchoCanceller {
+    pub fn new(
+        sample_rate: u32,
+        mic_channels: usize,
+        system_channels: usize,
+        tail: Duration,
+    ) -> Self {
+        // about 5 ms blocks
+        let block = match sample_rate {
+            0..32000 => 128,
+            _ => 256,
+        };
+        let tail_frames = (tail.as_secs_f64() * sample_rate as f64) as usize;
+        let partitions = tail_frames.div_ceil(block).max(1);
+        tracing::debug!(
+            "Cancelling {:?} of echo with {} partitions of {} frames",
+            tail,
+            partitions,
+            block
+        );
+
+        let mut planner = RealFftPlanner::<TargetFormat>::new();
+        let forward = planner.plan_fft_forward(block * 2);
+        let inverse = planner.plan_fft_inverse(block * 2);
+        let bins = block + 1;
+
+        let (mic_channels, system_channels) = (mic_channels.max(1), system_channels.max(1));
+        EchoCanceller {
+            block,
+            mic_channels,
+            system_channels,
+            input: forward.make_input_vec(),
+            spectrum: forward.make_output_vec(),
+            output: inverse.make_output_vec(),
+            forward,
+            inverse,
+            filters: (0..mic_channels)
+                .map(|_| ChannelFilter {
+                    weights: vec![vec![Complex::default(); bins]; partitions],
+                })
+                .collect(),
+            history: (0..partitions)
+                .map(|_| vec![Complex::default(); bins])
+                .collect(),
+            peaks: vec![0.0; partitions].into(),
+            power: vec![0.0; bins],
+            previous: vec![0.0; block],
+            pending_mic: Vec::new(),
+            pending_system: Vec::new(),
+            output_mic: vec![0.0; block * mic_channels].into(),
+            output_system: vec![0.0; block * system_channels].into(),
+        }
+    }
+
+    /// Cancels the echo from interleaved microphone frames, given the system audio
+    /// frames captured at the same time, and returns as many frames of both.
+    pub fn process(
+        &mut self,
+        mic: Vec<TargetFormat>,
+        system: Vec<TargetFormat>,
+    ) -> (Vec<TargetFormat>, Vec<TargetFormat>) {
+        let frames = (mic.len() / self.mic_channels).min(system.len() / self.system_channels);
+        self.pending_mic
+            .extend_from_slice(&mic[..frames * self.mic_channels]);
+        self.pending_system
+            .extend_from_slice(&system[..frames * self.system_channels]);
+
+        let (mic_block, system_block) = (
+            self.block * self.mic_channels,
+            self.block * self.system_channels,
+        );
+        while self.pending_mic.len() >= mic_block && self.pending_system.len() >= system_block {
+            let mic: Vec<TargetFormat> = self.pending_mic.drain(..mic_block).collect();
+            let system: Vec<TargetFormat> = self.pending_system.drain(..system_block).collect();
+
+            let cancelled = self.cancel(&mic, &system);
+            self.output_mic.extend(cancelled);
+            self.output_system.extend(system);
+        }
+
+        (
+            self.output_mic
+                .drain(..frames * self.mic_channels)
+                .collect(),
+            self.output_system
+                .drain(..frames * self.system_channels)
+                .collect(),
+        )
+    }
+
+    /// Runs one block through the filters, returning the cancelled microphone block.
+    fn cancel(&mut self, mic: &[TargetFormat], system: &[TargetFormat]) -> Vec<TargetFormat> {
+        let reference: Vec<TargetFormat> = system
+            .chunks(self.system_channels)
+            .map(|frame| frame.iter().sum::<f32>() / self.system_channels as f32)
+            .collect();
+
+        // overlap-save: the previous block followed by the new one
+        self.input[..self.block].copy_from_slice(&self.previous);
+        self.input[self.block..].copy_from_slice(&reference);
+        self.previous.copy_from_slice(&reference);
+        if let Err(e) = self.forward.process(&mut self.input, &mut self.spectrum) {
+            tracing::error!("Failed to transform the echo reference: {}", e);
+            return mic.to_vec();
+        }
+
+        if let Some(mut oldest) = self.history.pop_back() {
+            oldest.copy_from_slice(&self.spectrum);
+            self.history.push_front(oldest);
+        }
+        self.peaks.pop_back();
+        self.peaks.push_front(peak(&reference));
+
+        for (k, power) in self.power.iter_mut().enumerate() {
+            let total: f32 = self.history.iter().map(|x| x[k].norm_sqr()).sum();
+            *power = 0.9 * *power + 0.1 * total;
+        }
+
+        let scale = 1.0 / (self.block * 2) as f32;
+        // the regularization, the power of white noise at -60 dBFS
+        let delta = (self.block * 2) as f32 * 1e-6;
+        let reference_peak = self.peaks.iter().fold(0.0, |acc: f32, p| acc.max(*p));
+
+        let mut cancelled = vec![0.0; mic.len()];
+        for channel in 0..self.mic_channels {
+            let near: Vec<TargetFormat> = mic
+                .iter()
+                .skip(channel)
+                .step_by(self.mic_channels)
+                .copied()
+                .collect();
+
+            let mut estimate = vec![Complex::default(); self.power.len()];
+            for (weights, x) in self.filters[channel].weights.iter().zip(&self.history) {
+                for ((estimate, w), x) in estimate.iter_mut().zip(weights).zip(x) {
+                    *estimate += w * x;
+                }
+            }
+            // the imaginary parts of the DC and Nyquist bins must be zero
+            if let Some(first) = estimate.first_mut() {
+                first.im = 0.0;
+            }
+            if let Some(last) = estimate.last_mut() {
+                last.im = 0.0;
+            }
+
+            if let Err(e) = self.inverse.process(&mut estimate, &mut self.output) {
+                tracing::error!("Failed to estimate the echo: {}", e);
+                continue;
+            }
+
+            let error: Vec<TargetFormat> = near
+                .iter()
+                .zip(&self.output[self.block..])
+                .map(|(d, y)| d - y * scale)
+                .collect();
+
+            let near_energy: f32 = near.iter().map(|s| s * s).sum();
+            let error_energy: f32 = error.iter().map(|s| s * s).sum();
+            if error_energy > near_energy * 4.0 && near_energy > 0.0 {
+                tracing::debug!("Echo canceller diverged, resetting channel {}", channel);
+                for weights in &mut self.filters[channel].weights {
+                    weights.fill(Complex::default());
+                }
+                for (out, sample) in cancelled
+                    .iter_mut()
+                    .skip(channel)
+                    .step_by(self.mic_channels)
+                    .zip(&near)
+                {
+                    *out = *sample;
+                }
+                continue;
+            }
+
+            for (out, sample) in cancelled
+                .iter_mut()
+                .skip(channel)
+                .step_by(self.mic_channels)
+                .zip(&error)
+            {
+                *out = *sample;
+            }
+
+            let double_talk = peak(&near) > AEC_DOUBLE_TALK * reference_peak;
+            if reference_peak <= 0.0 || double_talk {
+                continue;
+            }
+
+            // the gradient of the error, with the overlap-save window
+            self.input[..self.block].fill(0.0);
+            self.input[self.block..].copy_from_slice(&error);
+            if let Err(e) = self.forward.process(&mut self.input, &mut self.spectrum) {
+                tracing::error!("Failed to transform the echo error: {}", e);
+                continue;
+            }
+
+            for (weights, x) in self.filters[channel].weights.iter_mut().zip(&self.history) {
+                for (((w, x), e), power) in weights
+                    .iter_mut()
+                    .zip(x)
+                    .zip(&self.spectrum)
+                    .zip(&self.power)
+                {
+                    *w += x.conj() * e * (AEC_STEP / (power + delta));
+                }
+            }
+        }
+
+        cancelled
+    }
+}
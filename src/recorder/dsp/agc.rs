@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use crate::recorder::constants::{AGC_FLOOR_DBFS, TargetFormat};
+
+use super::level::db_to_linear;
+
+/// Brings a stream to a target RMS level.
+///
+/// The level is followed by an envelope rising over `attack` and falling over
+/// `release`, and the gain is the one bringing that envelope to the target, at most
+/// `max_gain_db`. Below `AGC_FLOOR_DBFS` the gain is held, so pauses are not boosted to
+/// the level of speech.
+pub struct AutoGain {
+    channels: usize,
+    target: f32,
+    max_gain: f32,
+    floor: f32,
+    attack: f32,
+    release: f32,
+    /// Smoothed mean square of the frames.
+    envelope: f32,
+    gain: f32,
+}
+
+/// Per-frame smoothing factor of a one-pole filter with the time constant `duration`.
+fn coefficient(duration: Duration, sample_rate: u32) -> f32 {
+    let frames = duration.as_secs_f32() * sample_rate as f32;
+    match frames > 1.0 {
+        true => (-1.0 / frames).exp(),
+        false => 0.0,
+    }
+}
+
+impl AutoGain {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        target_db: f32,
+        max_gain_db: f32,
+        attack: Duration,
+        release: Duration,
+    ) -> Self {
+        AutoGain {
+            channels: channels.max(1) as usize,
+            target: db_to_linear(target_db),
+            max_gain: db_to_linear(max_gain_db.max(0.0)),
+            floor: db_to_linear(AGC_FLOOR_DBFS),
+            attack: coefficient(attack, sample_rate),
+            release: coefficient(release, sample_rate),
+            envelope: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Applies the gain to a block of interleaved samples in place.
+    pub fn process(&mut self, data: &mut [TargetFormat]) {
+        for frame in data.chunks_mut(self.channels) {
+            let square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+
+            let smoothing = match square > self.envelope {
+                true => self.attack,
+                false => self.release,
+            };
+            self.envelope = square + (self.envelope - square) * smoothing;
+
+            let level = self.envelope.sqrt();
+            if level > self.floor {
+                self.gain = (self.target / level).min(self.max_gain);
+            }
+
+            for sample in frame {
+                *sample *= self.gain;
+            }
+        }
+    }
+}
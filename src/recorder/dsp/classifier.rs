@@ -0,0 +1,115 @@
+use crate::recorder::constants::{
+    CLASSIFIER_FRAME_MS, CLASSIFIER_NOISE_ZCR, CLASSIFIER_SILENCE_DB,
+    CLASSIFIER_SPEECH_LOW_ENERGY_RATIO, CLASSIFIER_WINDOW_MS, TargetFormat,
+};
+
+use super::level::to_dbfs;
+
+/// The kind of content of a classified window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AudioClass {
+    Speech,
+    Music,
+    Noise,
+}
+
+/// Labels fixed windows of a stream as speech, music or noise.
+///
+/// A deliberately lightweight classifier built on two features of short frames:
+/// speech alternates syllables and pauses, so a large share of its frames is much
+/// quieter than the window average, while music keeps a steady level; steady content
+/// crossing zero very often is broadband noise (fans, hiss) rather than music. Quiet
+/// windows are noise too.
+pub struct AudioClassifier {
+    channels: usize,
+    frame_size: usize,
+    frames_per_window: usize,
+    frame_energy: f32,
+    frame_crossings: usize,
+    frame_len: usize,
+    last_sample: TargetFormat,
+    window: Vec<(f32, f32)>,
+}
+
+impl AudioClassifier {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let frame_size = ((sample_rate * CLASSIFIER_FRAME_MS / 1000) as usize).max(1);
+
+        AudioClassifier {
+            channels: channels.max(1) as usize,
+            frame_size,
+            frames_per_window: (CLASSIFIER_WINDOW_MS / CLASSIFIER_FRAME_MS) as usize,
+            frame_energy: 0.0,
+            frame_crossings: 0,
+            frame_len: 0,
+            last_sample: 0.0,
+            window: Vec::new(),
+        }
+    }
+
+    /// Number of stream frames in a classified window.
+    pub fn window_frames(&self) -> usize {
+        self.frame_size * self.frames_per_window
+    }
+
+    /// Feeds a block of interleaved samples and returns the labels of the windows it
+    /// completed, in order.
+    pub fn process(&mut self, data: &[TargetFormat]) -> Vec<AudioClass> {
+        let mut labels = Vec::new();
+
+        for frame in data.chunks(self.channels) {
+            let sample = frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat;
+
+            self.frame_energy += sample * sample;
+            if (sample >= 0.0) != (self.last_sample >= 0.0) {
+                self.frame_crossings += 1;
+            }
+            self.last_sample = sample;
+            self.frame_len += 1;
+
+            if self.frame_len < self.frame_size {
+                continue;
+            }
+
+            let rms = (self.frame_energy / self.frame_len as f32).sqrt();
+            let zcr = self.frame_crossings as f32 / self.frame_len as f32;
+            self.window.push((rms, zcr));
+            self.frame_energy = 0.0;
+            self.frame_crossings = 0;
+            self.frame_len = 0;
+
+            if self.window.len() == self.frames_per_window {
+                labels.push(self.classify());
+                self.window.clear();
+            }
+        }
+
+        labels
+    }
+
+    fn classify(&self) -> AudioClass {
+        let frames = self.window.len() as f32;
+        let mean_rms = self.window.iter().map(|(rms, _)| rms).sum::<f32>() / frames;
+        let mean_zcr = self.window.iter().map(|(_, zcr)| zcr).sum::<f32>() / frames;
+
+        if to_dbfs(mean_rms) < CLASSIFIER_SILENCE_DB {
+            return AudioClass::Noise;
+        }
+
+        let low_energy_ratio = self
+            .window
+            .iter()
+            .filter(|(rms, _)| *rms < 0.5 * mean_rms)
+            .count() as f32
+            / frames;
+
+        if low_energy_ratio >= CLASSIFIER_SPEECH_LOW_ENERGY_RATIO {
+            AudioClass::Speech
+        } else if mean_zcr >= CLASSIFIER_NOISE_ZCR {
+            AudioClass::Noise
+        } else {
+            AudioClass::Music
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use realfft::RealFftPlanner;
+
+use crate::recorder::constants::TargetFormat;
+
+/// Computes lightweight features of whole chunks of a stream.
+///
+/// Every chunk is downmixed to mono and measured as is, without any framing, so the
+/// features are cheap enough to compute for every delivered chunk.
+pub struct FeatureExtractor {
+    channels: usize,
+    sample_rate: u32,
+    planner: RealFftPlanner<TargetFormat>,
+    last_sample: TargetFormat,
+}
+
+impl FeatureExtractor {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        FeatureExtractor {
+            channels: channels.max(1) as usize,
+            sample_rate,
+            planner: RealFftPlanner::new(),
+            last_sample: 0.0,
+        }
+    }
+
+    /// Returns the energy (mean square), zero-crossing rate (crossings per frame) and
+    /// spectral centroid (in Hz) of a block of interleaved samples.
+    pub fn process(&mut self, data: &[TargetFormat]) -> (f32, f32, f32) {
+        let mono: Vec<TargetFormat> = data
+            .chunks(self.channels)
+            .map(|frame| frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat)
+            .collect();
+
+        if mono.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let energy = mono.iter().map(|s| s * s).sum::<TargetFormat>() / mono.len() as f32;
+
+        // crossings are counted across chunk boundaries too
+        let mut crossings = 0;
+        let mut last = self.last_sample;
+        for sample in mono.iter().copied() {
+            if (sample >= 0.0) != (last >= 0.0) {
+                crossings += 1;
+            }
+            last = sample;
+        }
+        self.last_sample = last;
+        let zero_crossing_rate = crossings as f32 / mono.len() as f32;
+
+        (energy, zero_crossing_rate, self.spectral_centroid(&mono))
+    }
+
+    /// Magnitude-weighted mean frequency of the spectrum of `mono`, in Hz.
+    fn spectral_centroid(&mut self, mono: &[TargetFormat]) -> f32 {
+        let fft = self.planner.plan_fft_forward(mono.len());
+        let mut input = fft.make_input_vec();
+        let mut spectrum = fft.make_output_vec();
+
+        for (i, (input, sample)) in input.iter_mut().zip(mono).enumerate() {
+            let phase = i as f32 / mono.len() as f32;
+            *input = sample * (0.5 - 0.5 * (2.0 * std::f32::consts::PI * phase).cos());
+        }
+
+        if let Err(e) = fft.process(&mut input, &mut spectrum) {
+            tracing::error!("Failed to compute chunk spectrum: {}", e);
+            return 0.0;
+        }
+
+        let bin_hz = self.sample_rate as f32 / mono.len() as f32;
+        let (weighted, total) =
+            spectrum
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(weighted, total), (bin, value)| {
+                    let magnitude = value.norm();
+                    (
+                        weighted + bin as f32 * bin_hz * magnitude,
+                        total + magnitude,
+                    )
+                });
+
+        match total > 0.0 {
+            true => weighted / total,
+            false => 0.0,
+        }
+    }
+}
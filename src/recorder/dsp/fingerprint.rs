@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use realfft::{RealFftPlanner, RealToComplex, num_complex::Complex};
+
+use crate::recorder::constants::{
+    FINGERPRINT_BANDS, FINGERPRINT_FRAME_SIZE, FINGERPRINT_HOP_SIZE, FINGERPRINT_MAX_HZ,
+    FINGERPRINT_MIN_HZ, TargetFormat,
+};
+
+/// Computes an acoustic fingerprint of a stream, incrementally.
+///
+/// Follows the Haitsma-Kalker scheme used by most fingerprinting systems: the mono
+/// signal is split into overlapping frames, the energy of logarithmically spaced bands
+/// between `FINGERPRINT_MIN_HZ` and `FINGERPRINT_MAX_HZ` is measured, and every frame
+/// yields a 32-bit sub-fingerprint whose bits are the signs of the energy differences
+/// across adjacent bands and frames. Two recordings of the same content produce
+/// fingerprints with a small Hamming distance, regardless of level.
+pub struct Fingerprinter {
+    channels: usize,
+    fft: Arc<dyn RealToComplex<TargetFormat>>,
+    window: Vec<TargetFormat>,
+    band_bins: Vec<(usize, usize)>,
+    mono: Vec<TargetFormat>,
+    input: Vec<TargetFormat>,
+    spectrum: Vec<Complex<TargetFormat>>,
+    previous: Option<Vec<TargetFormat>>,
+    fingerprint: Vec<u32>,
+}
+
+impl Fingerprinter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let fft = RealFftPlanner::<TargetFormat>::new().plan_fft_forward(FINGERPRINT_FRAME_SIZE);
+
+        let window = (0..FINGERPRINT_FRAME_SIZE)
+            .map(|i| {
+                let phase = i as f32 / FINGERPRINT_FRAME_SIZE as f32;
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * phase).cos()
+            })
+            .collect();
+
+        let max_bin = FINGERPRINT_FRAME_SIZE / 2;
+        let to_bin = |hz: f32| {
+            ((hz * FINGERPRINT_FRAME_SIZE as f32 / sample_rate.max(1) as f32).round() as usize)
+                .clamp(1, max_bin)
+        };
+        let edge = |band: usize| {
+            let ratio = FINGERPRINT_MAX_HZ / FINGERPRINT_MIN_HZ;
+            FINGERPRINT_MIN_HZ * ratio.powf(band as f32 / FINGERPRINT_BANDS as f32)
+        };
+        let band_bins = (0..FINGERPRINT_BANDS)
+            .map(|band| {
+                let low = to_bin(edge(band));
+                (low, to_bin(edge(band + 1)).max(low + 1).min(max_bin + 1))
+            })
+            .collect();
+
+        Fingerprinter {
+            channels: channels.max(1) as usize,
+            input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            fft,
+            window,
+            band_bins,
+            mono: Vec::new(),
+            previous: None,
+            fingerprint: Vec::new(),
+        }
+    }
+
+    /// Feeds a block of interleaved samples.
+    pub fn process(&mut self, data: &[TargetFormat]) {
+        self.mono.extend(
+            data.chunks(self.channels)
+                .map(|frame| frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat),
+        );
+
+        while self.mono.len() >= FINGERPRINT_FRAME_SIZE {
+            self.process_frame();
+            self.mono.drain(..FINGERPRINT_HOP_SIZE);
+        }
+    }
+
+    /// Returns the sub-fingerprints of every complete frame fed so far.
+    pub fn finish(self) -> Vec<u32> {
+        self.fingerprint
+    }
+
+    fn process_frame(&mut self) {
+        for ((input, sample), window) in self.input.iter_mut().zip(&self.mono).zip(&self.window) {
+            *input = sample * window;
+        }
+
+        if let Err(e) = self.fft.process(&mut self.input, &mut self.spectrum) {
+            tracing::error!("Failed to compute fingerprint frame: {}", e);
+            return;
+        }
+
+        let energies: Vec<TargetFormat> = self
+            .band_bins
+            .iter()
+            .map(|(low, high)| {
+                self.spectrum[*low..*high]
+                    .iter()
+                    .map(|c| c.norm_sqr())
+                    .sum()
+            })
+            .collect();
+        let differences: Vec<TargetFormat> = energies.windows(2).map(|e| e[0] - e[1]).collect();
+
+        if let Some(previous) = &self.previous {
+            let bits = differences.iter().zip(previous).enumerate().fold(
+                0u32,
+                |bits, (bit, (current, previous))| match current - previous > 0.0 {
+                    true => bits | (1 << bit),
+                    false => bits,
+                },
+            );
+            self.fingerprint.push(bits);
+        }
+
+        self.previous = Some(differences);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::recorder::constants::{GATE_FRAME_MS, TargetFormat};
+
+use super::level::{db_to_linear, rms};
+
+/// Passes a stream only while it is louder than a threshold.
+///
+/// The stream is measured in 20 ms frames. The gate opens on the first frame whose RMS
+/// level reaches the threshold, releasing the pre-roll (the audio just before it) so
+/// the onset of a sound is kept, and closes once `hold` passed without such a frame.
+pub struct Gate {
+    frame_samples: usize,
+    threshold: f32,
+    hold_frames: u32,
+    /// Frames left before the gate closes, 0 when closed.
+    open_frames: u32,
+    pre_roll_samples: usize,
+    pre_roll: VecDeque<TargetFormat>,
+    /// Samples not yet part of a measured frame.
+    pending: Vec<TargetFormat>,
+}
+
+impl Gate {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        threshold_db: f32,
+        hold: Duration,
+        pre_roll: Duration,
+    ) -> Self {
+        let frame_frames = ((sample_rate * GATE_FRAME_MS / 1000) as usize).max(1);
+        let frame_samples = frame_frames * channels.max(1) as usize;
+        let frames_in = |duration: Duration| {
+            (duration.as_secs_f64() * sample_rate as f64 / frame_frames as f64).ceil() as usize
+        };
+
+        let pre_roll_samples = frames_in(pre_roll) * frame_samples;
+
+        Gate {
+            frame_samples,
+            threshold: db_to_linear(threshold_db),
+            hold_frames: frames_in(hold).max(1) as u32,
+            open_frames: 0,
+            pre_roll_samples,
+            pre_roll: VecDeque::with_capacity(pre_roll_samples),
+            pending: Vec::with_capacity(frame_samples),
+        }
+    }
+
+    /// Whether the gate is passing the stream.
+    pub fn is_open(&self) -> bool {
+        self.open_frames > 0
+    }
+
+    /// Feeds a block of interleaved samples and returns the samples that pass the gate.
+    pub fn process(&mut self, data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        self.pending.extend_from_slice(&data);
+
+        let mut output = Vec::new();
+        let complete = self.pending.len() - self.pending.len() % self.frame_samples;
+
+        for frame in self.pending[..complete].chunks(self.frame_samples) {
+            if rms(frame) >= self.threshold {
+                if self.open_frames == 0 {
+                    output.extend(self.pre_roll.drain(..));
+                }
+                self.open_frames = self.hold_frames;
+                output.extend_from_slice(frame);
+            } else if self.open_frames > 0 {
+                self.open_frames -= 1;
+                output.extend_from_slice(frame);
+            } else if self.pre_roll_samples > 0 {
+                self.pre_roll.extend(frame);
+                let excess = self.pre_roll.len().saturating_sub(self.pre_roll_samples);
+                self.pre_roll.drain(..excess);
+            }
+        }
+
+        self.pending.drain(..complete);
+        output
+    }
+
+    /// Returns the samples of the last, partial frame if the gate is open.
+    pub fn flush(&mut self) -> Vec<TargetFormat> {
+        let pending = std::mem::take(&mut self.pending);
+        if self.is_open() { pending } else { Vec::new() }
+    }
+}
@@ -0,0 +1,97 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+use crate::recorder::constants::{DC_BLOCKER_HZ, TargetFormat};
+
+/// State of one channel: the DC blocker and the biquad (transposed direct form II).
+#[derive(Clone, Copy, Default)]
+struct ChannelState {
+    last_input: f32,
+    last_output: f32,
+    s1: f32,
+    s2: f32,
+}
+
+/// Coefficients of a 2nd-order Butterworth high-pass, normalized by `a0`.
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn high_pass(sample_rate: u32, cutoff: f32) -> Self {
+        let omega = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+
+        Biquad {
+            b0: (1.0 + cos) / 2.0 / a0,
+            b1: -(1.0 + cos) / a0,
+            b2: (1.0 + cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        }
+    }
+}
+
+/// Removes the DC offset and the low frequency rumble of a stream.
+///
+/// A one-pole DC blocker with its corner at `DC_BLOCKER_HZ` always runs, followed by a
+/// 2nd-order Butterworth high-pass at the cutoff, if any; both are cheap enough for
+/// the capture callback.
+pub struct HighPass {
+    channels: usize,
+    /// Pole of the DC blocker.
+    pole: f32,
+    biquad: Option<Biquad>,
+    states: Vec<ChannelState>,
+}
+
+impl HighPass {
+    pub fn new(sample_rate: u32, channels: u16, cutoff_hz: Option<f32>) -> Self {
+        let sample_rate = sample_rate.max(1);
+        // a cutoff at or past Nyquist would make the filter unstable
+        let nyquist = sample_rate as f32 / 2.0;
+        let biquad = cutoff_hz
+            .filter(|cutoff| *cutoff > 0.0 && *cutoff < nyquist)
+            .map(|cutoff| Biquad::high_pass(sample_rate, cutoff));
+        if biquad.is_none() && cutoff_hz.is_some() {
+            tracing::warn!(
+                "High-pass cutoff {:?} Hz out of range, only blocking DC",
+                cutoff_hz
+            );
+        }
+
+        let channels = channels.max(1) as usize;
+        HighPass {
+            channels,
+            pole: (-2.0 * PI * DC_BLOCKER_HZ / sample_rate as f32).exp(),
+            biquad,
+            states: vec![ChannelState::default(); channels],
+        }
+    }
+
+    /// Filters a block of interleaved samples in place.
+    pub fn process(&mut self, data: &mut [TargetFormat]) {
+        for frame in data.chunks_mut(self.channels) {
+            for (sample, state) in frame.iter_mut().zip(&mut self.states) {
+                let blocked = *sample - state.last_input + self.pole * state.last_output;
+                state.last_input = *sample;
+                state.last_output = blocked;
+
+                *sample = match &self.biquad {
+                    Some(biquad) => {
+                        let output = biquad.b0 * blocked + state.s1;
+                        state.s1 = biquad.b1 * blocked - biquad.a1 * output + state.s2;
+                        state.s2 = biquad.b2 * blocked - biquad.a2 * output;
+                        output
+                    }
+                    None => blocked,
+                };
+            }
+        }
+    }
+}
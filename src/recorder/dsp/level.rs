@@ -0,0 +1,33 @@
+use crate::recorder::constants::TargetFormat;
+
+/// Lowest level reported, in dBFS, used for digital silence.
+pub const MIN_DBFS: f32 = -120.0;
+
+/// Root mean square of `samples`.
+pub fn rms(samples: &[TargetFormat]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let sum: f32 = samples.iter().map(|s| s * s).sum();
+    (sum / samples.len() as f32).sqrt()
+}
+
+/// Largest absolute sample value of `samples`.
+pub fn peak(samples: &[TargetFormat]) -> f32 {
+    samples.iter().fold(0.0, |acc: f32, s| acc.max(s.abs()))
+}
+
+/// Converts a linear amplitude to dBFS, clamped to `MIN_DBFS`.
+pub fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return MIN_DBFS;
+    }
+
+    (20.0 * amplitude.log10()).max(MIN_DBFS)
+}
+
+/// Converts a gain in dB to a linear factor.
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
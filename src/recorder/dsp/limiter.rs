@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use crate::recorder::constants::TargetFormat;
+
+use super::level::db_to_linear;
+
+/// Keeps a stream below a ceiling by lowering its gain, without clipping it.
+///
+/// The gain drops immediately on the frame that would exceed the threshold, so no
+/// sample ever does, and recovers exponentially over `release` once the stream is
+/// quieter again. All channels of a frame share the gain, keeping the stereo image.
+pub struct Limiter {
+    channels: usize,
+    threshold: f32,
+    /// Per-frame factor moving the gain back towards unity.
+    release: f32,
+    gain: f32,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: u32, channels: u16, threshold_db: f32, release: Duration) -> Self {
+        let release_frames = release.as_secs_f32() * sample_rate as f32;
+        let release = match release_frames > 1.0 {
+            true => (-1.0 / release_frames).exp(),
+            false => 0.0,
+        };
+
+        Limiter {
+            channels: channels.max(1) as usize,
+            // the ceiling of a float stream
+            threshold: db_to_linear(threshold_db).min(1.0),
+            release,
+            gain: 1.0,
+        }
+    }
+
+    /// Limits a block of interleaved samples in place.
+    pub fn process(&mut self, data: &mut [TargetFormat]) {
+        for frame in data.chunks_mut(self.channels) {
+            let peak = frame.iter().fold(0.0, |acc: f32, s| acc.max(s.abs()));
+
+            // recover first, so a loud frame right after a quiet one is still caught
+            self.gain = 1.0 - (1.0 - self.gain) * self.release;
+            if peak * self.gain > self.threshold {
+                self.gain = self.threshold / peak;
+            }
+
+            if self.gain < 1.0 {
+                for sample in frame {
+                    *sample *= self.gain;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 1000;
+
+    fn limiter(channels: u16) -> Limiter {
+        Limiter::new(RATE, channels, -6.0, Duration::from_millis(50))
+    }
+
+    #[test]
+    fn never_exceeds_the_ceiling() {
+        let mut limiter = limiter(1);
+        let ceiling = db_to_linear(-6.0);
+
+        // a swept tone up to 12 dB over full scale
+        let mut data: Vec<TargetFormat> = (0..RATE * 2)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                2.0 * t * (2.0 * std::f32::consts::PI * 50.0 * t * (1.0 + t)).sin()
+            })
+            .collect();
+        limiter.process(&mut data);
+
+        let peak = data.iter().fold(0.0, |acc: f32, s| acc.max(s.abs()));
+        assert!(peak <= ceiling * (1.0 + 1e-6), "peak {peak} over {ceiling}");
+    }
+
+    #[test]
+    fn leaves_quiet_audio_untouched() {
+        let mut limiter = limiter(1);
+        let input = vec![0.1, -0.2, 0.3, -0.4];
+
+        let mut data = input.clone();
+        limiter.process(&mut data);
+
+        assert_eq!(data, input);
+    }
+
+    #[test]
+    fn attacks_on_the_first_loud_frame() {
+        let mut limiter = limiter(1);
+        let ceiling = db_to_linear(-6.0);
+
+        let mut data = vec![0.1; 10];
+        data.extend([2.0; 10]);
+        limiter.process(&mut data);
+
+        assert!(data[..10].iter().all(|s| *s == 0.1));
+        assert!((data[10] - ceiling).abs() < 1e-6);
+        assert!(data[10..].iter().all(|s| *s <= ceiling * (1.0 + 1e-6)));
+    }
+
+    #[test]
+    fn releases_gradually_after_the_step() {
+        let mut limiter = limiter(1);
+
+        let mut loud = vec![2.0; 100];
+        limiter.process(&mut loud);
+
+        // 0.4 s of quiet audio, 8 release time constants
+        let mut quiet = vec![0.4; 400];
+        limiter.process(&mut quiet);
+
+        assert!(quiet[0] < 0.4 * 0.5);
+        assert!(quiet.windows(2).all(|w| w[1] >= w[0]));
+        // 63% of the way back to unity after one time constant
+        assert!((quiet[49] / 0.4 - (1.0 - 0.75 * (-1.0f32).exp())).abs() < 0.05);
+        assert!((quiet[399] - 0.4).abs() < 1e-3);
+    }
+
+    #[test]
+    fn channels_share_the_gain() {
+        let mut limiter = limiter(2);
+
+        let mut data = vec![2.0, 0.5];
+        limiter.process(&mut data);
+
+        assert!((data[0] / data[1] - 4.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::recorder::constants::{
+    LOUDNESS_GATE_DBFS, LOUDNESS_MAX_GAIN_DB, LOUDNESS_SLEW_DB, LOUDNESS_WINDOW_MS, TargetFormat,
+};
+
+use super::level::{db_to_linear, rms, to_dbfs};
+
+/// Follows the short-term loudness of a stream, the mean square over the last
+/// `LOUDNESS_WINDOW_MS`.
+///
+/// Blocks quieter than `LOUDNESS_GATE_DBFS` are ignored, so the pauses of a speaker do
+/// not make them sound quieter than they are.
+struct LoudnessMeter {
+    /// Smoothing factor of the mean square per frame.
+    coefficient: f32,
+    mean_square: Option<f32>,
+}
+
+impl LoudnessMeter {
+    fn new(sample_rate: u32) -> Self {
+        let frames = sample_rate as f32 * LOUDNESS_WINDOW_MS as f32 / 1000.0;
+        LoudnessMeter {
+            coefficient: (-1.0 / frames.max(1.0)).exp(),
+            mean_square: None,
+        }
+    }
+
+    fn process(&mut self, block: &[TargetFormat], frames: usize) {
+        let level = rms(block);
+        if to_dbfs(level) < LOUDNESS_GATE_DBFS {
+            return;
+        }
+
+        let decay = self.coefficient.powi(frames as i32);
+        let square = level * level;
+        self.mean_square = Some(match self.mean_square {
+            Some(mean) => decay * mean + (1.0 - decay) * square,
+            None => square,
+        });
+    }
+
+    /// The loudness in dBFS, once a block passed the gate.
+    fn loudness_db(&self) -> Option<f32> {
+        self.mean_square.map(|mean| to_dbfs(mean.sqrt()))
+    }
+}
+
+/// A source of the matcher.
+struct MatchedSource {
+    meter: LoudnessMeter,
+    /// Manual gain of the source, kept as its offset from the common level.
+    offset_db: f32,
+    /// Gain currently applied on top of the manual one.
+    gain_db: f32,
+}
+
+/// Matches the short-term loudness of the sources of a mix, so no single source
+/// dominates it.
+///
+/// Every block, the loudness of each matched source is measured (with its manual gain
+/// taken out) and the common level is the mean of them. Each source is then brought to
+/// the common level plus its manual gain, with at most `LOUDNESS_MAX_GAIN_DB` of
+/// correction, moving by at most `LOUDNESS_SLEW_DB` per second and ramped over the
+/// block. Sources with a fixed gain are neither measured nor corrected.
+pub struct LoudnessMatcher {
+    sample_rate: u32,
+    channels: usize,
+    sources: Vec<Option<MatchedSource>>,
+}
+
+impl LoudnessMatcher {
+    /// Creates the matcher of sources with the given manual gains, or `None` for the
+    /// sources kept at their manual gain.
+    pub fn new(sample_rate: u32, channels: u16, offsets_db: &[Option<f32>]) -> Self {
+        LoudnessMatcher {
+            sample_rate: sample_rate.max(1),
+            channels: channels.max(1) as usize,
+            sources: offsets_db
+                .iter()
+                .map(|offset| {
+                    offset.map(|offset_db| MatchedSource {
+                        meter: LoudnessMeter::new(sample_rate),
+                        offset_db,
+                        gain_db: 0.0,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Measures one block of every source, all of the same length, and corrects them in
+    /// place.
+    pub fn process(&mut self, blocks: &mut [Vec<TargetFormat>]) {
+        let frames = blocks
+            .first()
+            .map_or(0, |block| block.len() / self.channels);
+        if frames == 0 {
+            return;
+        }
+
+        for (source, block) in self.sources.iter_mut().zip(blocks.iter()) {
+            if let Some(source) = source {
+                source.meter.process(block, frames);
+            }
+        }
+
+        let levels: Vec<f32> = self
+            .sources
+            .iter()
+            .flatten()
+            .filter_map(|source| {
+                source
+                    .meter
+                    .loudness_db()
+                    .map(|loudness| loudness - source.offset_db)
+            })
+            .collect();
+        if levels.is_empty() {
+            return;
+        }
+        let common = levels.iter().sum::<f32>() / levels.len() as f32;
+
+        let max_step = LOUDNESS_SLEW_DB * frames as f32 / self.sample_rate as f32;
+        for (source, block) in self.sources.iter_mut().zip(blocks.iter_mut()) {
+            let source = match source {
+                Some(source) => source,
+                None => continue,
+            };
+            let loudness = match source.meter.loudness_db() {
+                Some(loudness) => loudness - source.offset_db,
+                None => continue,
+            };
+
+            let target = (common - loudness).clamp(-LOUDNESS_MAX_GAIN_DB, LOUDNESS_MAX_GAIN_DB);
+            let previous = source.gain_db;
+            source.gain_db += (target - previous).clamp(-max_step, max_step);
+
+            let (from, to) = (db_to_linear(previous), db_to_linear(source.gain_db));
+            let step = (to - from) / frames as f32;
+            for (i, frame) in block.chunks_mut(self.channels).enumerate() {
+                let gain = from + step * (i + 1) as f32;
+                for sample in frame {
+                    *sample *= gain;
+                }
+            }
+        }
+    }
+
+    /// Gains currently applied on top of the manual ones, in dB, `None` for the sources
+    /// with a fixed gain.
+    pub fn gains_db(&self) -> Vec<Option<f32>> {
+        self.sources
+            .iter()
+            .map(|source| source.as_ref().map(|source| source.gain_db))
+            .collect()
+    }
+}
@@ -0,0 +1,48 @@
+/// Module for the acoustic echo cancellation between two streams.
+pub mod aec;
+
+/// Module for the automatic gain control of a stream.
+pub mod agc;
+
+/// Module for the speech/music/noise classification of a stream.
+pub mod classifier;
+
+/// Module for the per-chunk features of a stream.
+pub mod features;
+
+/// Module for the acoustic fingerprint of a stream.
+pub mod fingerprint;
+
+/// Module for the silence gate of a stream.
+pub mod gate;
+
+/// Module for the high-pass filter and DC blocker of a stream.
+pub mod high_pass;
+
+/// Module for level measurements.
+pub mod level;
+
+/// Module for the loudness matching of the sources of a mix.
+pub mod loudness;
+
+/// Module for the peak limiter of a stream.
+pub mod limiter;
+
+/// Module for the pitch (f0) tracking of a stream.
+pub mod pitch;
+
+/// Module for the ambient noise floor estimation.
+pub mod noise_floor;
+
+/// Module for the RNNoise noise suppression of a stream.
+#[cfg(feature = "noise-suppression")]
+pub mod rnnoise;
+
+/// Module for the segmentation of a stream at its pauses.
+pub mod segmenter;
+
+/// Module for the spectral subtraction of a stationary noise.
+pub mod spectral_subtraction;
+
+/// Module for the voice activity detection of a stream.
+pub mod vad;
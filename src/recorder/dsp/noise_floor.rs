@@ -0,0 +1,37 @@
+use crate::recorder::constants::TargetFormat;
+
+use super::level::{MIN_DBFS, rms, to_dbfs};
+
+/// How fast the estimate may rise towards louder levels, in dB per second.
+const NOISE_FLOOR_RISE_DB_PER_SEC: f32 = 1.0;
+
+/// Tracks the ambient noise floor of a stream using minimum statistics.
+///
+/// The estimate follows quieter blocks immediately and only creeps up slowly, so speech
+/// or music doesn't pull it up while a genuinely louder room still does.
+#[derive(Debug, Clone, Default)]
+pub struct NoiseFloorEstimator {
+    floor_db: Option<f32>,
+}
+
+impl NoiseFloorEstimator {
+    pub fn new() -> Self {
+        NoiseFloorEstimator { floor_db: None }
+    }
+
+    /// Updates the estimate with a block of `frames` frames at `sample_rate` and returns it.
+    pub fn update(&mut self, block: &[TargetFormat], frames: usize, sample_rate: u32) -> f32 {
+        let level_db = to_dbfs(rms(block));
+
+        let floor_db = match self.floor_db {
+            Some(floor_db) if level_db > floor_db => {
+                let elapsed = frames as f32 / sample_rate.max(1) as f32;
+                (floor_db + NOISE_FLOOR_RISE_DB_PER_SEC * elapsed).min(level_db)
+            }
+            _ => level_db.max(MIN_DBFS),
+        };
+
+        self.floor_db = Some(floor_db);
+        floor_db
+    }
+}
@@ -0,0 +1,115 @@
+use crate::recorder::constants::{
+    PITCH_FRAME_SIZE, PITCH_HOP_SIZE, PITCH_MAX_HZ, PITCH_MIN_HZ, PITCH_THRESHOLD, TargetFormat,
+};
+
+/// Estimates the fundamental frequency (f0) of a stream, frame by frame.
+///
+/// Implements the YIN algorithm: the cumulative mean normalized difference function of
+/// each frame is searched for its first dip under `PITCH_THRESHOLD` within the
+/// `PITCH_MIN_HZ`..`PITCH_MAX_HZ` range, refined with parabolic interpolation. Frames
+/// without a clear periodicity (silence, noise, unvoiced speech) yield no frequency.
+pub struct PitchTracker {
+    channels: usize,
+    sample_rate: u32,
+    min_lag: usize,
+    max_lag: usize,
+    mono: Vec<TargetFormat>,
+    difference: Vec<f32>,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let window = PITCH_FRAME_SIZE / 2;
+        let max_lag = ((sample_rate as f32 / PITCH_MIN_HZ) as usize).min(window - 1);
+        let min_lag = ((sample_rate as f32 / PITCH_MAX_HZ) as usize).clamp(2, max_lag);
+
+        PitchTracker {
+            channels: channels.max(1) as usize,
+            sample_rate,
+            min_lag,
+            max_lag,
+            mono: Vec::new(),
+            difference: vec![0.0; max_lag + 1],
+        }
+    }
+
+    /// Number of stream frames between two estimates.
+    pub fn hop_frames(&self) -> usize {
+        PITCH_HOP_SIZE
+    }
+
+    /// Feeds a block of interleaved samples and returns the estimates of the frames it
+    /// completed, in order, as `(frequency in Hz, confidence in 0..=1)`.
+    pub fn process(&mut self, data: &[TargetFormat]) -> Vec<(Option<f32>, f32)> {
+        self.mono.extend(
+            data.chunks(self.channels)
+                .map(|frame| frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat),
+        );
+
+        let mut estimates = Vec::new();
+        while self.mono.len() >= PITCH_FRAME_SIZE {
+            estimates.push(self.estimate());
+            self.mono.drain(..PITCH_HOP_SIZE);
+        }
+
+        estimates
+    }
+
+    fn estimate(&mut self) -> (Option<f32>, f32) {
+        let window = PITCH_FRAME_SIZE / 2;
+        let frame = &self.mono[..PITCH_FRAME_SIZE];
+
+        // cumulative mean normalized difference function
+        self.difference[0] = 1.0;
+        let mut running_sum = 0.0;
+        for lag in 1..=self.max_lag {
+            let d: f32 = (0..window)
+                .map(|i| {
+                    let delta = frame[i] - frame[i + lag];
+                    delta * delta
+                })
+                .sum();
+
+            running_sum += d;
+            self.difference[lag] = match running_sum > 0.0 {
+                true => d * lag as f32 / running_sum,
+                false => 1.0,
+            };
+        }
+
+        let mut lag = self.min_lag;
+        while lag <= self.max_lag {
+            if self.difference[lag] < PITCH_THRESHOLD {
+                // walk down to the bottom of the dip
+                while lag < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+                    lag += 1;
+                }
+                break;
+            }
+            lag += 1;
+        }
+
+        if lag > self.max_lag {
+            return (None, 0.0);
+        }
+
+        let refined = match lag > 1 && lag < self.max_lag {
+            true => {
+                let (a, b, c) = (
+                    self.difference[lag - 1],
+                    self.difference[lag],
+                    self.difference[lag + 1],
+                );
+                let denominator = a - 2.0 * b + c;
+                match denominator.abs() > f32::EPSILON {
+                    true => lag as f32 + 0.5 * (a - c) / denominator,
+                    false => lag as f32,
+                }
+            }
+            false => lag as f32,
+        };
+
+        let confidence = (1.0 - self.difference[lag]).clamp(0.0, 1.0);
+        (Some(self.sample_rate as f32 / refined), confidence)
+    }
+}
@@ -0,0 +1,112 @@
+use nnnoiseless::DenoiseState;
+
+use crate::recorder::{
+    constants::{RNNOISE_SAMPLE_RATE, TargetFormat},
+    convert::StreamConverter,
+    errors::AudioRecorderError,
+};
+
+/// Scale of the samples RNNoise works on, those of 16-bit audio.
+const SCALE: f32 = i16::MAX as f32;
+
+/// Suppresses the noise of a stream with RNNoise, keeping the voice.
+///
+/// RNNoise processes 10 ms frames of 48 kHz mono audio, so each channel has its own
+/// state, and streams at other rates are resampled to 48 kHz and back.
+pub struct NoiseSuppressor {
+    channels: usize,
+    states: Vec<Box<DenoiseState<'static>>>,
+    upsampler: Option<StreamConverter>,
+    downsampler: Option<StreamConverter>,
+    /// Samples at 48 kHz not yet part of a frame.
+    pending: Vec<TargetFormat>,
+    input: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl NoiseSuppressor {
+    pub fn new(sample_rate: u32, channels: u16) -> Result<Self, AudioRecorderError> {
+        let (upsampler, downsampler) = match sample_rate == RNNOISE_SAMPLE_RATE {
+            true => (None, None),
+            false => {
+                tracing::debug!(
+                    "Resampling the suppressed stream from {} Hz to {} Hz",
+                    sample_rate,
+                    RNNOISE_SAMPLE_RATE
+                );
+                (
+                    Some(StreamConverter::new(
+                        sample_rate,
+                        channels,
+                        RNNOISE_SAMPLE_RATE,
+                        channels,
+                    )?),
+                    Some(StreamConverter::new(
+                        RNNOISE_SAMPLE_RATE,
+                        channels,
+                        sample_rate,
+                        channels,
+                    )?),
+                )
+            }
+        };
+
+        let channels = channels.max(1) as usize;
+        Ok(NoiseSuppressor {
+            channels,
+            states: (0..channels).map(|_| DenoiseState::new()).collect(),
+            upsampler,
+            downsampler,
+            pending: Vec::new(),
+            input: vec![0.0; DenoiseState::FRAME_SIZE],
+            output: vec![0.0; DenoiseState::FRAME_SIZE],
+        })
+    }
+
+    /// Feeds a block of interleaved samples and returns the suppressed samples available
+    /// so far.
+    pub fn process(&mut self, data: Vec<TargetFormat>) -> Vec<TargetFormat> {
+        let data = match &mut self.upsampler {
+            Some(upsampler) => upsampler.process(data),
+            None => data,
+        };
+        self.pending.extend(data);
+
+        let frame_samples = DenoiseState::FRAME_SIZE * self.channels;
+        let complete = self.pending.len() - self.pending.len() % frame_samples;
+        let mut suppressed = vec![0.0; complete];
+
+        for (frame, out) in self.pending[..complete]
+            .chunks(frame_samples)
+            .zip(suppressed.chunks_mut(frame_samples))
+        {
+            for (channel, state) in self.states.iter_mut().enumerate() {
+                for (sample, input) in frame
+                    .iter()
+                    .skip(channel)
+                    .step_by(self.channels)
+                    .zip(&mut self.input)
+                {
+                    *input = sample * SCALE;
+                }
+
+                state.process_frame(&mut self.output, &self.input);
+
+                for (sample, output) in out
+                    .iter_mut()
+                    .skip(channel)
+                    .step_by(self.channels)
+                    .zip(&self.output)
+                {
+                    *sample = output / SCALE;
+                }
+            }
+        }
+        self.pending.drain(..complete);
+
+        match &mut self.downsampler {
+            Some(downsampler) => downsampler.process(suppressed),
+            None => suppressed,
+        }
+    }
+}
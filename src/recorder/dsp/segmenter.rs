@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use crate::recorder::constants::{TargetFormat, VAD_FRAME_MS};
+
+use super::vad::{VadMode, VoiceDetector};
+
+/// Cuts a stream into segments between a minimum and a maximum length, at its pauses.
+///
+/// Once a segment reaches the target length, it ends with the next frame without
+/// speech. When the speech goes on until the maximum length, the segment ends at the
+/// last pause after the minimum length, or at the maximum length when there was none.
+pub struct Segmenter {
+    detector: VoiceDetector,
+    frame_samples: usize,
+    min_samples: usize,
+    target_samples: usize,
+    max_samples: usize,
+    pending: Vec<TargetFormat>,
+    /// Samples of `pending` already fed to the detector.
+    scanned: usize,
+    /// End of the last frame without speech after the minimum length.
+    last_pause: Option<usize>,
+}
+
+impl Segmenter {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        mode: VadMode,
+        min: Duration,
+        target: Duration,
+        max: Duration,
+    ) -> Self {
+        let channels = channels.max(1) as usize;
+        let samples =
+            |duration: Duration| ((duration.as_secs_f64() * sample_rate as f64) as usize).max(1);
+
+        let min_samples = samples(min) * channels;
+        let max_samples = (samples(max) * channels).max(min_samples);
+        let target_samples = (samples(target) * channels).clamp(min_samples, max_samples);
+
+        Segmenter {
+            detector: VoiceDetector::new(sample_rate, channels as u16, mode),
+            frame_samples: ((sample_rate * VAD_FRAME_MS / 1000) as usize).max(1) * channels,
+            min_samples,
+            target_samples,
+            max_samples,
+            pending: Vec::with_capacity(max_samples),
+            scanned: 0,
+            last_pause: None,
+        }
+    }
+
+    /// Feeds a block of interleaved samples and returns the segments it completed.
+    pub fn process(&mut self, data: &[TargetFormat]) -> Vec<Vec<TargetFormat>> {
+        self.pending.extend_from_slice(data);
+        let mut segments = Vec::new();
+
+        while self.pending.len() - self.scanned >= self.frame_samples {
+            let end = self.scanned + self.frame_samples;
+            let speech = self.detector.process(&self.pending[self.scanned..end]);
+            self.scanned = end;
+
+            if !speech && end >= self.min_samples {
+                self.last_pause = Some(end);
+            }
+
+            let cut = match (speech, end >= self.target_samples, end >= self.max_samples) {
+                (false, true, _) => Some(end),
+                (_, _, true) => Some(self.last_pause.unwrap_or(self.max_samples)),
+                _ => None,
+            };
+            if let Some(cut) = cut {
+                segments.push(self.cut(cut));
+            }
+        }
+
+        segments
+    }
+
+    /// Ends the segment in progress, returning its audio.
+    pub fn flush(&mut self) -> Vec<TargetFormat> {
+        self.scanned = 0;
+        self.last_pause = None;
+        std::mem::take(&mut self.pending)
+    }
+
+    fn cut(&mut self, at: usize) -> Vec<TargetFormat> {
+        let rest = self.pending.split_off(at);
+        self.scanned -= at;
+        self.last_pause = None;
+        std::mem::replace(&mut self.pending, rest)
+    }
+}
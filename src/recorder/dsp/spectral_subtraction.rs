@@ -0,0 +1,184 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+
+use crate::recorder::constants::{
+    NOISE_PROFILE_FRAME_SIZE, NOISE_PROFILE_HOP_SIZE, NOISE_SUBTRACTION_FACTOR,
+    NOISE_SUBTRACTION_FLOOR, TargetFormat,
+};
+
+/// Periodic Hann window of `len` samples, which sums to one at 50% overlap.
+fn hann(len: usize) -> Vec<TargetFormat> {
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos())
+        .collect()
+}
+
+/// Mean magnitude spectrum of the frames of a mono signal.
+///
+/// Returns `NOISE_PROFILE_FRAME_SIZE / 2 + 1` bins, all zero when `mono` is shorter
+/// than a frame.
+pub fn mean_spectrum(mono: &[TargetFormat]) -> Vec<f32> {
+    let fft = RealFftPlanner::<TargetFormat>::new().plan_fft_forward(NOISE_PROFILE_FRAME_SIZE);
+    let window = hann(NOISE_PROFILE_FRAME_SIZE);
+    let mut input = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+    let mut mean = vec![0.0; spectrum.len()];
+    let mut frames = 0;
+
+    for start in
+        (0..mono.len().saturating_sub(NOISE_PROFILE_FRAME_SIZE - 1)).step_by(NOISE_PROFILE_HOP_SIZE)
+    {
+        let frame = &mono[start..start + NOISE_PROFILE_FRAME_SIZE];
+        for ((input, sample), w) in input.iter_mut().zip(frame).zip(&window) {
+            *input = sample * w;
+        }
+
+        if let Err(e) = fft.process(&mut input, &mut spectrum) {
+            tracing::error!("Failed to compute noise spectrum: {}", e);
+            continue;
+        }
+
+        for (mean, value) in mean.iter_mut().zip(&spectrum) {
+            *mean += value.norm();
+        }
+        frames += 1;
+    }
+
+    if frames > 0 {
+        mean.iter_mut().for_each(|m| *m /= frames as f32);
+    }
+
+    mean
+}
+
+/// Short-time Fourier transform state of one channel.
+struct ChannelState {
+    /// The last `NOISE_PROFILE_FRAME_SIZE` input samples.
+    frame: Vec<TargetFormat>,
+    /// Input samples not yet part of an analyzed frame.
+    pending: Vec<TargetFormat>,
+    /// Overlap-add accumulator of the processed frames.
+    overlap: Vec<TargetFormat>,
+    /// Processed samples ready to be delivered.
+    output: VecDeque<TargetFormat>,
+}
+
+/// Removes a stationary noise (fans, hum, hiss) from a stream by spectral subtraction.
+///
+/// Every channel is analyzed in Hann windowed frames with 50% overlap; the noise
+/// magnitude is subtracted from each bin (keeping the phase) down to a fraction of the
+/// original magnitude, which limits the "musical noise" artifacts, and the frames are
+/// overlap-added back. The output lags the input by one hop.
+pub struct SpectralSubtractor {
+    noise: Vec<f32>,
+    window: Vec<TargetFormat>,
+    forward: Arc<dyn RealToComplex<TargetFormat>>,
+    inverse: Arc<dyn ComplexToReal<TargetFormat>>,
+    channels: Vec<ChannelState>,
+}
+
+impl SpectralSubtractor {
+    /// Creates a subtractor for a stream with `channels` channels, given the noise
+    /// magnitude of every bin of a `NOISE_PROFILE_FRAME_SIZE` frame at its sample rate.
+    pub fn new(noise: Vec<f32>, channels: u16) -> Self {
+        let mut planner = RealFftPlanner::<TargetFormat>::new();
+
+        let channels = (0..channels.max(1))
+            .map(|_| ChannelState {
+                frame: vec![0.0; NOISE_PROFILE_FRAME_SIZE],
+                pending: Vec::with_capacity(NOISE_PROFILE_HOP_SIZE),
+                overlap: vec![0.0; NOISE_PROFILE_FRAME_SIZE],
+                // primed with a hop of silence, so every call returns as much as it got
+                output: VecDeque::from(vec![0.0; NOISE_PROFILE_HOP_SIZE]),
+            })
+            .collect();
+
+        SpectralSubtractor {
+            noise,
+            window: hann(NOISE_PROFILE_FRAME_SIZE),
+            forward: planner.plan_fft_forward(NOISE_PROFILE_FRAME_SIZE),
+            inverse: planner.plan_fft_inverse(NOISE_PROFILE_FRAME_SIZE),
+            channels,
+        }
+    }
+
+    /// Denoises a block of interleaved samples, returning as many samples.
+    pub fn process(&mut self, data: &[TargetFormat]) -> Vec<TargetFormat> {
+        let channel_count = self.channels.len();
+        let frames = data.len() / channel_count;
+
+        for channel in 0..channel_count {
+            for sample in data.iter().skip(channel).step_by(channel_count) {
+                self.channels[channel].pending.push(*sample);
+
+                if self.channels[channel].pending.len() == NOISE_PROFILE_HOP_SIZE {
+                    self.process_hop(channel);
+                }
+            }
+        }
+
+        let mut output = Vec::with_capacity(frames * channel_count);
+        for _ in 0..frames {
+            for state in &mut self.channels {
+                output.push(state.output.pop_front().unwrap_or(0.0));
+            }
+        }
+
+        output
+    }
+
+    /// Analyzes the frame completed by the pending hop of `channel`.
+    fn process_hop(&mut self, channel: usize) {
+        let state = &mut self.channels[channel];
+
+        state.frame.drain(..NOISE_PROFILE_HOP_SIZE);
+        state.frame.append(&mut state.pending);
+
+        let mut input = self.forward.make_input_vec();
+        let mut spectrum = self.forward.make_output_vec();
+        for ((input, sample), w) in input.iter_mut().zip(&state.frame).zip(&self.window) {
+            *input = sample * w;
+        }
+
+        if let Err(e) = self.forward.process(&mut input, &mut spectrum) {
+            tracing::error!("Failed to compute spectrum: {}", e);
+            return;
+        }
+
+        for (value, noise) in spectrum.iter_mut().zip(&self.noise) {
+            let magnitude = value.norm();
+            if magnitude <= 0.0 {
+                continue;
+            }
+
+            let cleaned = (magnitude - NOISE_SUBTRACTION_FACTOR * noise)
+                .max(NOISE_SUBTRACTION_FLOOR * magnitude);
+            *value *= cleaned / magnitude;
+        }
+
+        // the DC and Nyquist bins of a real signal have no imaginary part
+        if let Some(first) = spectrum.first_mut() {
+            first.im = 0.0;
+        }
+        if let Some(last) = spectrum.last_mut() {
+            last.im = 0.0;
+        }
+
+        let mut frame = self.inverse.make_output_vec();
+        if let Err(e) = self.inverse.process(&mut spectrum, &mut frame) {
+            tracing::error!("Failed to compute inverse spectrum: {}", e);
+            return;
+        }
+
+        let scale = 1.0 / NOISE_PROFILE_FRAME_SIZE as f32;
+        for (overlap, sample) in state.overlap.iter_mut().zip(&frame) {
+            *overlap += sample * scale;
+        }
+
+        state
+            .output
+            .extend(state.overlap.drain(..NOISE_PROFILE_HOP_SIZE));
+        state.overlap.resize(NOISE_PROFILE_FRAME_SIZE, 0.0);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::recorder::constants::{
+    TargetFormat, VAD_FLOOR_RISE_DB, VAD_FRAME_MS, VAD_HANGOVER_MS, VAD_MARGIN_DB, VAD_MIN_LEVEL_DB,
+};
+
+use super::level::{rms, to_dbfs};
+
+/// How voice activity is detected, see `Recorder::set_voice_detection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum VadMode {
+    /// Frames well above the tracked noise floor are speech. Cheap, but any loud
+    /// sound counts as speech.
+    #[default]
+    Energy,
+    /// The WebRTC voice activity detector, which tells speech from other sounds.
+    /// Streams at rates it does not support (8, 16, 32 or 48 kHz) fall back to
+    /// `Energy`.
+    #[cfg(feature = "webrtc-vad")]
+    WebRtc,
+}
+
+/// The WebRTC detector of a stream.
+#[cfg(feature = "webrtc-vad")]
+struct WebRtcVad(webrtc_vad::Vad);
+
+// SAFETY: the detector state is plain memory owned by this value, it has no affinity to
+// the thread that created it.
+#[cfg(feature = "webrtc-vad")]
+unsafe impl Send for WebRtcVad {}
+
+#[cfg(feature = "webrtc-vad")]
+impl WebRtcVad {
+    fn new(sample_rate: u32) -> Option<Self> {
+        let rate = match sample_rate {
+            8000 => webrtc_vad::SampleRate::Rate8kHz,
+            16000 => webrtc_vad::SampleRate::Rate16kHz,
+            32000 => webrtc_vad::SampleRate::Rate32kHz,
+            48000 => webrtc_vad::SampleRate::Rate48kHz,
+            _ => {
+                tracing::warn!(
+                    "WebRTC VAD does not support {} Hz, using the energy detector",
+                    sample_rate
+                );
+                return None;
+            }
+        };
+
+        Some(WebRtcVad(webrtc_vad::Vad::new_with_rate_and_mode(
+            rate,
+            webrtc_vad::VadMode::Quality,
+        )))
+    }
+
+    fn is_speech(&mut self, frame: &[TargetFormat]) -> Option<bool> {
+        let samples: Vec<i16> = frame
+            .iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+
+        self.0.is_voice_segment(&samples).ok()
+    }
+}
+
+/// Tells speech from silence in 20 ms frames of a stream.
+///
+/// Every frame is downmixed to mono and compared to a noise floor that drops to the
+/// quietest frames and slowly rises with a louder background. Speech is held for a
+/// short while after the last active frame, so the pauses between words do not split
+/// an utterance.
+pub struct VoiceDetector {
+    channels: usize,
+    frame_size: usize,
+    frame: Vec<TargetFormat>,
+    noise_floor_db: Option<f32>,
+    hangover_frames: u32,
+    hangover: u32,
+    speech: bool,
+    #[cfg(feature = "webrtc-vad")]
+    webrtc: Option<WebRtcVad>,
+}
+
+impl VoiceDetector {
+    pub fn new(sample_rate: u32, channels: u16, mode: VadMode) -> Self {
+        let frame_size = ((sample_rate * VAD_FRAME_MS / 1000) as usize).max(1);
+        #[cfg(not(feature = "webrtc-vad"))]
+        let _ = mode;
+
+        VoiceDetector {
+            channels: channels.max(1) as usize,
+            frame_size,
+            frame: Vec::with_capacity(frame_size),
+            noise_floor_db: None,
+            hangover_frames: VAD_HANGOVER_MS / VAD_FRAME_MS,
+            hangover: 0,
+            speech: false,
+            #[cfg(feature = "webrtc-vad")]
+            webrtc: match mode {
+                VadMode::WebRtc => WebRtcVad::new(sample_rate),
+                VadMode::Energy => None,
+            },
+        }
+    }
+
+    /// Feeds a block of interleaved samples and returns whether it contains speech,
+    /// i.e. whether any frame it completed was speech, or the state of the last frame
+    /// when it completed none.
+    pub fn process(&mut self, data: &[TargetFormat]) -> bool {
+        let mut completed = false;
+        let mut speech = false;
+
+        for frame in data.chunks(self.channels) {
+            self.frame
+                .push(frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat);
+
+            if self.frame.len() < self.frame_size {
+                continue;
+            }
+
+            let active = self.is_active();
+            self.frame.clear();
+
+            if active {
+                self.hangover = self.hangover_frames;
+            } else {
+                self.hangover = self.hangover.saturating_sub(1);
+            }
+            self.speech = active || self.hangover > 0;
+
+            completed = true;
+            speech |= self.speech;
+        }
+
+        if completed { speech } else { self.speech }
+    }
+
+    /// Whether the current frame holds voice activity.
+    fn is_active(&mut self) -> bool {
+        let level = to_dbfs(rms(&self.frame));
+
+        let floor = match self.noise_floor_db {
+            Some(floor) if level < floor => level,
+            Some(floor) => floor + VAD_FLOOR_RISE_DB,
+            None => level,
+        };
+        self.noise_floor_db = Some(floor);
+
+        #[cfg(feature = "webrtc-vad")]
+        if let Some(speech) = self
+            .webrtc
+            .as_mut()
+            .and_then(|webrtc| webrtc.is_speech(&self.frame))
+        {
+            return speech;
+        }
+
+        level > VAD_MIN_LEVEL_DB && level > floor + VAD_MARGIN_DB
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use super::{Recorder, dsp::aec::EchoCanceller, mix::MixLayout};
+
+impl Recorder {
+    /// Cancels the echo of the speakers picked up by the microphone when recording both
+    /// devices, using the captured system audio as the reference (or `None` to keep the
+    /// echo). `tail` is the longest echo cancelled, e.g. 200 ms for a laptop in a
+    /// small room; longer tails cost more CPU.
+    ///
+    /// The filter needs a few seconds of system audio to converge, and pauses while both
+    /// parties talk, keeping the voice of the user. Adds about 5 ms of latency.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_echo_cancellation(&mut self, tail: Option<Duration>) {
+        self.echo_tail = tail;
+    }
+
+    /// Creates the echo canceller of a mix with this layout, if enabled.
+    pub fn echo_canceller(&self, sample_rate: u32, layout: &MixLayout) -> Option<EchoCanceller> {
+        self.echo_tail.map(|tail| {
+            EchoCanceller::new(
+                sample_rate,
+                layout.mic_channels(),
+                layout.system_channels(),
+                tail,
+            )
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+
+use cpal::Sample;
+use fdk_aac::enc::{BitRate, ChannelMode, Encoder, EncoderParams, Transport};
+
+use crate::recorder::{
+    Recorder,
+    errors::{AudioRecorderError, EncoderFailure},
+    receiver::AudioReceiver,
+};
+
+/// Frames per AAC-LC access unit.
+const AAC_FRAME_SIZE: usize = 1024;
+
+/// Largest access unit per channel, as specified for AAC-LC.
+const AAC_MAX_FRAME_BYTES_PER_CHANNEL: usize = 768;
+
+/// Settings of an `AacSink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AacSettings {
+    /// Constant bitrate, in bits per second.
+    pub bitrate: u32,
+}
+
+impl Default for AacSettings {
+    fn default() -> Self {
+        AacSettings { bitrate: 128_000 }
+    }
+}
+
+/// A sink that encodes the recording to an AAC-LC file in an ADTS stream (`.aac`).
+///
+/// AAC is supported by virtually every player; the sink encodes one or two channels.
+pub struct AacSink {
+    handle: JoinHandle<Result<(), AudioRecorderError>>,
+}
+
+/// Maps an encoder error.
+fn encoder_error(e: impl std::fmt::Debug) -> AudioRecorderError {
+    tracing::error!("AAC encoder error: {:?}", e);
+    AudioRecorderError::Encoder {
+        codec: "AAC",
+        source: Box::new(EncoderFailure(format!("{e:?}"))),
+    }
+}
+
+impl AacSink {
+    /// Spawns a thread that encodes every chunk from `receiver` to an AAC file at `path`.
+    ///
+    /// The recorder must have been started, as its config describes the stream.
+    pub fn attach(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        settings: AacSettings,
+    ) -> Result<Self, AudioRecorderError> {
+        let config = recorder
+            .get_config()
+            .inspect_err(|e| tracing::error!("Failed to get recorder config: {}", e))?;
+
+        let channel_mode = match config.channels {
+            1 => ChannelMode::Mono,
+            2 => ChannelMode::Stereo,
+            channels => {
+                tracing::error!("AAC sink does not support {} channels", channels);
+                return Err(AudioRecorderError::UnsupportedStream {
+                    codec: "AAC",
+                    what: format!("{channels} channel streams"),
+                });
+            }
+        };
+
+        let encoder = Encoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(settings.bitrate),
+            sample_rate: config.sample_rate,
+            transport: Transport::Adts,
+            channels: channel_mode,
+        })
+        .map_err(encoder_error)?;
+
+        let path = path.into();
+        let file = File::create(&path).map_err(|e| {
+            tracing::error!("Failed to create {:?}: {}", path, e);
+            AudioRecorderError::IoError(e)
+        })?;
+
+        let channels = config.channels as usize;
+        let handle = thread::Builder::new()
+            .name("arec-aac-sink".to_string())
+            .spawn(move || {
+                let mut writer = BufWriter::new(file);
+                let frame_samples = AAC_FRAME_SIZE * channels;
+                let mut pending: Vec<i16> = Vec::new();
+                let mut output = vec![0u8; AAC_MAX_FRAME_BYTES_PER_CHANNEL * channels];
+
+                // encodes the pending samples, returning how many were consumed
+                let mut encode = |pending: &mut Vec<i16>| -> Result<usize, AudioRecorderError> {
+                    let info = encoder
+                        .encode(&pending[..], &mut output)
+                        .map_err(encoder_error)?;
+                    pending.drain(..info.input_consumed);
+
+                    writer.write_all(&output[..info.output_size]).map_err(|e| {
+                        tracing::error!("Failed to write AAC data: {}", e);
+                        AudioRecorderError::IoError(e)
+                    })?;
+                    Ok(info.input_consumed)
+                };
+
+                while let Ok(chunk) = receiver.recv() {
+                    pending.extend(chunk.samples.iter().map(|s| s.to_sample::<i16>()));
+
+                    while pending.len() >= frame_samples {
+                        if encode(&mut pending)? == 0 {
+                            break;
+                        }
+                    }
+                }
+
+                // the last partial frame is padded with silence
+                if !pending.is_empty() {
+                    pending.resize(frame_samples, 0);
+                    encode(&mut pending)?;
+                }
+
+                writer.flush().map_err(AudioRecorderError::IoError)
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn AAC sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(AacSink { handle })
+    }
+
+    /// Waits for the recording to end and for the file to be written.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    pub fn finish(self) -> Result<(), AudioRecorderError> {
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::error!("AAC sink thread panicked");
+                Err(AudioRecorderError::ThreadPanicked("AAC sink"))
+            }
+        }
+    }
+}
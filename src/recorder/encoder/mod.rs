@@ -0,0 +1,18 @@
+/// Module for encoding a recording to AAC.
+#[cfg(feature = "aac")]
+mod aac_sink;
+
+/// Module for encoding a recording to MP3.
+#[cfg(feature = "mp3")]
+mod mp3_sink;
+
+/// Module for encoding a recording to Opus.
+#[cfg(feature = "opus")]
+mod opus_sink;
+
+#[cfg(feature = "aac")]
+pub use aac_sink::{AacSettings, AacSink};
+#[cfg(feature = "mp3")]
+pub use mp3_sink::{Mp3Settings, Mp3Sink};
+#[cfg(feature = "opus")]
+pub use opus_sink::{OpusEncoderSink, OpusSettings};
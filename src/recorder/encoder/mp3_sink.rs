@@ -0,0 +1,177 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+
+use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, InterleavedPcm, Quality};
+
+use crate::recorder::{
+    Recorder,
+    errors::{AudioRecorderError, EncoderFailure},
+    receiver::AudioReceiver,
+};
+
+/// Settings of an `Mp3Sink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mp3Settings {
+    /// Constant bitrate, in kbps: one of the standard MP3 bitrates from 8 to 320.
+    pub bitrate_kbps: u32,
+}
+
+impl Default for Mp3Settings {
+    fn default() -> Self {
+        Mp3Settings { bitrate_kbps: 128 }
+    }
+}
+
+/// A sink that encodes the recording to an MP3 file, playable virtually everywhere.
+///
+/// MP3 supports one or two channels at the usual sample rates up to 48 kHz.
+pub struct Mp3Sink {
+    handle: JoinHandle<Result<(), AudioRecorderError>>,
+}
+
+/// Maps an encoder error.
+fn encoder_error(e: impl std::fmt::Display) -> AudioRecorderError {
+    tracing::error!("MP3 encoder error: {}", e);
+    AudioRecorderError::Encoder {
+        codec: "MP3",
+        source: Box::new(EncoderFailure(e.to_string())),
+    }
+}
+
+/// The LAME bitrate for `kbps`, if it is a standard MP3 bitrate.
+fn lame_bitrate(kbps: u32) -> Option<Bitrate> {
+    let bitrate = match kbps {
+        8 => Bitrate::Kbps8,
+        16 => Bitrate::Kbps16,
+        24 => Bitrate::Kbps24,
+        32 => Bitrate::Kbps32,
+        40 => Bitrate::Kbps40,
+        48 => Bitrate::Kbps48,
+        64 => Bitrate::Kbps64,
+        80 => Bitrate::Kbps80,
+        96 => Bitrate::Kbps96,
+        112 => Bitrate::Kbps112,
+        128 => Bitrate::Kbps128,
+        160 => Bitrate::Kbps160,
+        192 => Bitrate::Kbps192,
+        224 => Bitrate::Kbps224,
+        256 => Bitrate::Kbps256,
+        320 => Bitrate::Kbps320,
+        _ => return None,
+    };
+
+    Some(bitrate)
+}
+
+impl Mp3Sink {
+    /// Spawns a thread that encodes every chunk from `receiver` to an MP3 file at `path`.
+    ///
+    /// The recorder must have been started, as its config describes the stream.
+    pub fn attach(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        settings: Mp3Settings,
+    ) -> Result<Self, AudioRecorderError> {
+        let config = recorder
+            .get_config()
+            .inspect_err(|e| tracing::error!("Failed to get recorder config: {}", e))?;
+
+        if !matches!(config.channels, 1 | 2) {
+            tracing::error!("MP3 does not support {} channels", config.channels);
+            return Err(AudioRecorderError::UnsupportedStream {
+                codec: "MP3",
+                what: format!("{} channel streams", config.channels),
+            });
+        }
+        let bitrate = lame_bitrate(settings.bitrate_kbps).ok_or_else(|| {
+            tracing::error!("Unsupported MP3 bitrate {} kbps", settings.bitrate_kbps);
+            AudioRecorderError::UnsupportedStream {
+                codec: "MP3",
+                what: format!("{} kbps", settings.bitrate_kbps),
+            }
+        })?;
+
+        let mut builder =
+            Builder::new().ok_or_else(|| encoder_error("Failed to allocate the LAME encoder"))?;
+        builder
+            .set_num_channels(config.channels as u8)
+            .map_err(encoder_error)?;
+        builder
+            .set_sample_rate(config.sample_rate)
+            .map_err(encoder_error)?;
+        builder.set_brate(bitrate).map_err(encoder_error)?;
+        builder.set_quality(Quality::Good).map_err(encoder_error)?;
+        let mut encoder = builder.build().map_err(encoder_error)?;
+
+        let path = path.into();
+        let file = File::create(&path).map_err(|e| {
+            tracing::error!("Failed to create {:?}: {}", path, e);
+            AudioRecorderError::IoError(e)
+        })?;
+
+        let handle = thread::Builder::new()
+            .name("arec-mp3-sink".to_string())
+            .spawn(move || {
+                let mut writer = BufWriter::new(file);
+                let mut buffer: Vec<u8> = Vec::new();
+
+                let write = |writer: &mut BufWriter<File>, data: &[u8]| {
+                    writer.write_all(data).map_err(|e| {
+                        tracing::error!("Failed to write MP3 data: {}", e);
+                        AudioRecorderError::IoError(e)
+                    })
+                };
+
+                while let Ok(chunk) = receiver.recv() {
+                    buffer.clear();
+                    buffer.reserve(mp3lame_encoder::max_required_buffer_size(
+                        chunk.samples.len(),
+                    ));
+
+                    let size = encoder
+                        .encode(InterleavedPcm(&chunk.samples), buffer.spare_capacity_mut())
+                        .map_err(encoder_error)?;
+                    // SAFETY: the encoder initialized `size` bytes of the spare capacity.
+                    unsafe { buffer.set_len(size) };
+
+                    write(&mut writer, &buffer)?;
+                }
+
+                buffer.clear();
+                buffer.reserve(mp3lame_encoder::max_required_buffer_size(0));
+                let size = encoder
+                    .flush::<FlushNoGap>(buffer.spare_capacity_mut())
+                    .map_err(encoder_error)?;
+                // SAFETY: the encoder initialized `size` bytes of the spare capacity.
+                unsafe { buffer.set_len(size) };
+
+                write(&mut writer, &buffer)?;
+                writer.flush().map_err(AudioRecorderError::IoError)
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn MP3 sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(Mp3Sink { handle })
+    }
+
+    /// Waits for the recording to end and for the file to be written.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    pub fn finish(self) -> Result<(), AudioRecorderError> {
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::error!("MP3 sink thread panicked");
+                Err(AudioRecorderError::ThreadPanicked("MP3 sink"))
+            }
+        }
+    }
+}
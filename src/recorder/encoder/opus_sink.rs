@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+use crate::recorder::{
+    Recorder, constants::TargetFormat, errors::AudioRecorderError, receiver::AudioReceiver,
+};
+
+/// Sample rates supported by the Opus encoder.
+const OPUS_SAMPLE_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+
+/// Frame durations supported by the Opus encoder, in microseconds.
+const OPUS_FRAME_DURATIONS_US: [u128; 6] = [2500, 5000, 10000, 20000, 40000, 60000];
+
+/// Largest packet the encoder may produce, as recommended by libopus.
+const OPUS_MAX_PACKET_SIZE: usize = 4000;
+
+/// Samples (at 48 kHz) to skip when decoding, covering the encoder lookahead.
+const OPUS_PRE_SKIP: u16 = 312;
+
+/// Serial number of the single logical stream of an Ogg/Opus file.
+const OGG_SERIAL: u32 = 1;
+
+/// Settings of an `OpusEncoderSink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpusSettings {
+    /// Target bitrate, in bits per second.
+    pub bitrate: u32,
+    /// Duration of every packet: 2.5, 5, 10, 20, 40 or 60 ms.
+    pub frame_duration: Duration,
+}
+
+impl Default for OpusSettings {
+    fn default() -> Self {
+        OpusSettings {
+            bitrate: 64_000,
+            frame_duration: Duration::from_millis(20),
+        }
+    }
+}
+
+/// Where the encoded packets go.
+enum OpusOutput {
+    Packets(Sender<Vec<u8>>),
+    Ogg(PacketWriter<'static, BufWriter<File>>),
+}
+
+/// A sink that encodes the recording to Opus, producing raw packets or an Ogg/Opus file.
+///
+/// Opus only supports 8, 12, 16, 24 or 48 kHz streams with one or two channels, so the
+/// recorder should deliver such a stream, e.g. with `Recorder::set_standard_output` or
+/// `Recorder::set_target_sample_rate`. The last packet is padded with silence.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{OpusEncoderSink, OpusSettings, Recorder, RecorderConfig};
+///
+/// let mut recorder = Recorder::new();
+/// recorder.set_standard_output(true);
+/// let config = RecorderConfig::builder().input_only(true).build();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// let sink = OpusEncoderSink::ogg(receiver, &recorder, "recording.opus", OpusSettings::default())
+///     .expect("Failed to attach Opus sink");
+///
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// recorder.stop();
+///
+/// sink.finish().expect("Failed to encode recording");
+/// ```
+pub struct OpusEncoderSink {
+    handle: JoinHandle<Result<(), AudioRecorderError>>,
+}
+
+/// Maps an encoder error.
+fn encoder_error(e: opus::Error) -> AudioRecorderError {
+    tracing::error!("Opus encoder error: {}", e);
+    AudioRecorderError::Encoder {
+        codec: "Opus",
+        source: Box::new(e),
+    }
+}
+
+/// Maps an Ogg write error.
+fn ogg_error(e: std::io::Error) -> AudioRecorderError {
+    tracing::error!("Failed to write Ogg page: {}", e);
+    AudioRecorderError::IoError(e)
+}
+
+/// The `OpusHead` identification header of an Ogg/Opus stream.
+fn opus_head(sample_rate: u32, channels: u16) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&sample_rate.to_le_bytes());
+    // output gain and channel mapping family
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+/// The `OpusTags` comment header of an Ogg/Opus stream.
+fn opus_tags() -> Vec<u8> {
+    let vendor = concat!("audio-recorder-rs ", env!("CARGO_PKG_VERSION"));
+
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+impl OpusEncoderSink {
+    /// Spawns a thread that encodes every chunk from `receiver` and sends every Opus
+    /// packet on the returned channel, e.g. to stream it over the network.
+    pub fn packets(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        settings: OpusSettings,
+    ) -> Result<(Self, Receiver<Vec<u8>>), AudioRecorderError> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let sink = OpusEncoderSink::spawn(receiver, recorder, settings, OpusOutput::Packets(tx))?;
+
+        Ok((sink, rx))
+    }
+
+    /// Spawns a thread that encodes every chunk from `receiver` to an Ogg/Opus file at
+    /// `path`.
+    pub fn ogg(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        settings: OpusSettings,
+    ) -> Result<Self, AudioRecorderError> {
+        let path = path.into();
+        let file = File::create(&path).map_err(|e| {
+            tracing::error!("Failed to create {:?}: {}", path, e);
+            AudioRecorderError::IoError(e)
+        })?;
+
+        let writer = PacketWriter::new(BufWriter::new(file));
+        OpusEncoderSink::spawn(receiver, recorder, settings, OpusOutput::Ogg(writer))
+    }
+
+    fn spawn(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        settings: OpusSettings,
+        mut output: OpusOutput,
+    ) -> Result<Self, AudioRecorderError> {
+        let config = recorder
+            .get_config()
+            .inspect_err(|e| tracing::error!("Failed to get recorder config: {}", e))?;
+
+        if !OPUS_SAMPLE_RATES.contains(&config.sample_rate) {
+            tracing::error!("Opus does not support {} Hz", config.sample_rate);
+            return Err(AudioRecorderError::UnsupportedStream {
+                codec: "Opus",
+                what: format!("{} Hz streams", config.sample_rate),
+            });
+        }
+        let opus_channels = match config.channels {
+            1 => opus::Channels::Mono,
+            2 => opus::Channels::Stereo,
+            channels => {
+                tracing::error!("Opus does not support {} channels", channels);
+                return Err(AudioRecorderError::UnsupportedStream {
+                    codec: "Opus",
+                    what: format!("{channels} channel streams"),
+                });
+            }
+        };
+        if !OPUS_FRAME_DURATIONS_US.contains(&settings.frame_duration.as_micros()) {
+            tracing::error!(
+                "Unsupported Opus frame duration {:?}",
+                settings.frame_duration
+            );
+            return Err(AudioRecorderError::UnsupportedStream {
+                codec: "Opus",
+                what: format!("{:?} frames", settings.frame_duration),
+            });
+        }
+
+        let mut encoder =
+            opus::Encoder::new(config.sample_rate, opus_channels, opus::Application::Audio)
+                .map_err(encoder_error)?;
+        encoder
+            .set_bitrate(opus::Bitrate::Bits(settings.bitrate as i32))
+            .map_err(encoder_error)?;
+
+        let frame_frames =
+            (settings.frame_duration.as_micros() * config.sample_rate as u128 / 1_000_000) as usize;
+        let frame_samples = frame_frames * config.channels as usize;
+        // granule positions always count 48 kHz samples
+        let granule_per_frame = (frame_frames * 48_000 / config.sample_rate as usize) as u64;
+
+        if let OpusOutput::Ogg(writer) = &mut output {
+            writer
+                .write_packet(
+                    opus_head(config.sample_rate, config.channels),
+                    OGG_SERIAL,
+                    PacketWriteEndInfo::EndPage,
+                    0,
+                )
+                .map_err(ogg_error)?;
+            writer
+                .write_packet(opus_tags(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+                .map_err(ogg_error)?;
+        }
+
+        let handle = thread::Builder::new()
+            .name("arec-opus-sink".to_string())
+            .spawn(move || {
+                let mut pending: Vec<TargetFormat> = Vec::new();
+                let mut buffer = vec![0u8; OPUS_MAX_PACKET_SIZE];
+                // packets are written one behind, so the last one can end the Ogg stream
+                let mut last_packet: Option<Vec<u8>> = None;
+                let mut granule = OPUS_PRE_SKIP as u64;
+
+                let mut emit = |packet: Vec<u8>, end: bool| -> Result<(), AudioRecorderError> {
+                    match &mut output {
+                        OpusOutput::Packets(tx) => {
+                            if tx.send(packet).is_err() {
+                                tracing::debug!("Opus packet receiver dropped");
+                            }
+                            Ok(())
+                        }
+                        OpusOutput::Ogg(writer) => {
+                            granule += granule_per_frame;
+                            let info = match end {
+                                true => PacketWriteEndInfo::EndStream,
+                                false => PacketWriteEndInfo::NormalPacket,
+                            };
+                            writer
+                                .write_packet(packet, OGG_SERIAL, info, granule)
+                                .map_err(ogg_error)
+                        }
+                    }
+                };
+
+                let mut encode = |frame: &[TargetFormat]| -> Result<Vec<u8>, AudioRecorderError> {
+                    let len = encoder
+                        .encode_float(frame, &mut buffer)
+                        .map_err(encoder_error)?;
+                    Ok(buffer[..len].to_vec())
+                };
+
+                while let Ok(chunk) = receiver.recv() {
+                    pending.extend_from_slice(&chunk.samples);
+
+                    while pending.len() >= frame_samples {
+                        let packet = encode(&pending[..frame_samples])?;
+                        pending.drain(..frame_samples);
+
+                        if let Some(previous) = last_packet.replace(packet) {
+                            emit(previous, false)?;
+                        }
+                    }
+                }
+
+                if !pending.is_empty() {
+                    pending.resize(frame_samples, TargetFormat::default());
+                    let packet = encode(&pending)?;
+                    if let Some(previous) = last_packet.replace(packet) {
+                        emit(previous, false)?;
+                    }
+                }
+
+                match last_packet {
+                    Some(packet) => emit(packet, true),
+                    None => Ok(()),
+                }
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn Opus sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(OpusEncoderSink { handle })
+    }
+
+    /// Waits for the recording to end and for every packet to be encoded.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    pub fn finish(self) -> Result<(), AudioRecorderError> {
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::error!("Opus sink thread panicked");
+                Err(AudioRecorderError::ThreadPanicked("Opus sink"))
+            }
+        }
+    }
+}
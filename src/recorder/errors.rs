@@ -1,24 +1,175 @@
-use std::fmt::Display;
+use std::path::PathBuf;
 
-#[derive(Debug)]
+use cpal::traits::DeviceTrait;
+
+use super::permissions::Permission;
+
+/// The errors of the recorder, its sinks and its tools.
+///
+/// Errors caused by cpal, rubato, hound, an encoder, a decoder or the file system keep
+/// them as their `source`, and the errors of a device carry its name. `code` identifies the kind of
+/// failure where the enum cannot be matched.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum AudioRecorderError {
-    SignalError(String),
+    #[error("Device Error: {0}")]
     DeviceError(&'static str),
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("WAV Error: {0}")]
+    WavError(#[from] hound::Error),
+    #[error("Recording is already in progress")]
     RecordingInProgress,
+    /// The user denied a permission the recording needs.
+    #[error("Permission denied: {0:?}")]
+    PermissionDenied(Permission),
+    /// The app bundle lacks this `Info.plist` usage description (macOS).
+    #[error("Missing Info.plist usage description: {0}")]
+    MissingUsageDescription(&'static str),
+    /// The app is not signed with this entitlement (macOS).
+    #[error("Missing entitlement: {0}")]
+    MissingEntitlement(&'static str),
+    /// The layout of the delivered stream is not known yet, as no recording started.
+    #[error("Recorder not configured: {0}")]
+    NotConfigured(&'static str),
+    /// The device has no usable default stream config.
+    #[error("Failed to get the stream config of {device}")]
+    StreamConfig {
+        device: String,
+        #[source]
+        source: cpal::DefaultStreamConfigError,
+    },
+    /// The device captures in a sample format the recorder does not handle.
+    #[error("Unsupported sample format of {device}: {format:?}")]
+    UnsupportedSampleFormat {
+        device: String,
+        format: cpal::SampleFormat,
+    },
+    /// The device given to record from is not an input device.
+    #[error("{device} is not an input device")]
+    NotAnInputDevice { device: String },
+    #[error("Failed to build the stream of {device}")]
+    BuildStream {
+        device: String,
+        #[source]
+        source: cpal::BuildStreamError,
+    },
+    #[error("Failed to play the stream of {device}")]
+    PlayStream {
+        device: String,
+        #[source]
+        source: cpal::PlayStreamError,
+    },
+    #[error("Failed to create the resampler")]
+    Resampler(#[from] rubato::ResamplerConstructionError),
+    /// The encoder of a sink failed.
+    #[error("{codec} encoder error")]
+    Encoder {
+        codec: &'static str,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The codec cannot encode the stream or the requested settings, e.g. 6 channels
+    /// to MP3.
+    #[error("{codec} does not support {what}")]
+    UnsupportedStream { codec: &'static str, what: String },
+    /// The file could not be decoded.
+    #[cfg(feature = "decode")]
+    #[error("Failed to decode {}", path.display())]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: symphonia::core::errors::Error,
+    },
+    /// A chunk could not be serialized or deserialized.
+    #[cfg(feature = "serde")]
+    #[error("{context}")]
+    Codec {
+        context: &'static str,
+        #[source]
+        source: bincode::Error,
+    },
+    /// A pipe frame is not one the sink writes.
+    #[error("Malformed pipe frame: {0}")]
+    MalformedFrame(&'static str),
+    /// A file to append to or to stitch has another format than the recording.
+    #[error("{} has another format", .0.display())]
+    FormatMismatch(PathBuf),
+    /// A resumed stream has another layout than the one it continues.
+    #[error("Cannot resume the stream as {sample_rate} Hz {channels}ch")]
+    ResumeMismatch { sample_rate: u32, channels: u16 },
+    /// A thread of the recorder or a sink panicked or exited early.
+    #[error("{0} thread panicked")]
+    ThreadPanicked(&'static str),
+    /// Too little audio was captured for the measurement.
+    #[error("Not enough audio captured: {0}")]
+    NotEnoughAudio(&'static str),
+    /// The microphone did not pick up the alignment probe.
+    #[error("Calibration probe not detected by the microphone (correlation {correlation})")]
+    ProbeNotDetected { correlation: f32 },
+    /// The recording cannot be handed off.
+    #[error("Cannot hand off the recording: {0}")]
+    HandOff(&'static str),
+    /// An argument or setting the call cannot work with.
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(&'static str),
+}
+
+/// An encoder error that only describes itself, kept as the `source` of `Encoder`.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct EncoderFailure(pub String);
+
+/// Name of `device` in the errors about it.
+pub fn device_name(device: &cpal::Device) -> String {
+    device.name().unwrap_or_else(|_| String::from("Unknown"))
 }
 
-impl Display for AudioRecorderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AudioRecorderError {
+    /// A stable identifier of the kind of failure, e.g. to report it to another
+    /// process or language.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AudioRecorderError::DeviceError(_) => "device",
+            AudioRecorderError::IoError(_) => "io",
+            AudioRecorderError::WavError(_) => "wav",
+            AudioRecorderError::RecordingInProgress => "recording_in_progress",
+            AudioRecorderError::PermissionDenied(_) => "permission_denied",
+            AudioRecorderError::MissingUsageDescription(_) => "missing_usage_description",
+            AudioRecorderError::MissingEntitlement(_) => "missing_entitlement",
+            AudioRecorderError::NotConfigured(_) => "not_configured",
+            AudioRecorderError::StreamConfig { .. } => "stream_config",
+            AudioRecorderError::UnsupportedSampleFormat { .. } => "unsupported_sample_format",
+            AudioRecorderError::NotAnInputDevice { .. } => "not_an_input_device",
+            AudioRecorderError::BuildStream { .. } => "build_stream",
+            AudioRecorderError::PlayStream { .. } => "play_stream",
+            AudioRecorderError::Resampler(_) => "resampler",
+            AudioRecorderError::Encoder { .. } => "encoder",
+            AudioRecorderError::UnsupportedStream { .. } => "unsupported_stream",
+            #[cfg(feature = "decode")]
+            AudioRecorderError::Decode { .. } => "decode",
+            #[cfg(feature = "serde")]
+            AudioRecorderError::Codec { .. } => "codec",
+            AudioRecorderError::MalformedFrame(_) => "malformed_frame",
+            AudioRecorderError::FormatMismatch(_) => "format_mismatch",
+            AudioRecorderError::ResumeMismatch { .. } => "resume_mismatch",
+            AudioRecorderError::ThreadPanicked(_) => "thread_panicked",
+            AudioRecorderError::NotEnoughAudio(_) => "not_enough_audio",
+            AudioRecorderError::ProbeNotDetected { .. } => "probe_not_detected",
+            AudioRecorderError::HandOff(_) => "hand_off",
+            AudioRecorderError::InvalidArgument(_) => "invalid_argument",
+        }
+    }
+
+    /// Name of the device that failed, when the error is about one.
+    pub fn device(&self) -> Option<&str> {
         match self {
-            AudioRecorderError::DeviceError(msg) => {
-                write!(f, "Device Error: {msg}")
-            }
-            AudioRecorderError::SignalError(msg) => {
-                write!(f, "Signal Error: {msg}")
-            }
-            AudioRecorderError::RecordingInProgress => {
-                write!(f, "Recording is already in progress")
-            }
+            AudioRecorderError::StreamConfig { device, .. }
+            | AudioRecorderError::UnsupportedSampleFormat { device, .. }
+            | AudioRecorderError::NotAnInputDevice { device }
+            | AudioRecorderError::BuildStream { device, .. }
+            | AudioRecorderError::PlayStream { device, .. } => Some(device),
+            _ => None,
         }
     }
 }
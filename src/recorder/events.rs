@@ -0,0 +1,161 @@
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use super::{
+    Recorder, constants::AudioSource, degradation::DegradationStep, dsp::classifier::AudioClass,
+    sink::SinkOutcome,
+};
+
+/// An event reported while recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RecorderEvent {
+    /// A worker thread failed, ending the recording.
+    StreamError { thread: String, message: String },
+    /// A window of the delivered stream, starting at `start` into the recording, was
+    /// classified as `class`.
+    Classification {
+        start: Duration,
+        duration: Duration,
+        class: AudioClass,
+    },
+    /// No chunk was delivered for `elapsed` while recording, see `Recorder::set_watchdog`.
+    Stalled { elapsed: Duration },
+    /// The default device of `source` changed to `device`, see
+    /// `Recorder::set_device_monitor`.
+    DeviceChanged { source: AudioSource, device: String },
+    /// Writing to the destination of `sink` failed, see `SinkPolicy`.
+    SinkError {
+        sink: String,
+        message: String,
+        outcome: SinkOutcome,
+    },
+    /// A retried write of `sink` succeeded.
+    SinkRecovered { sink: String },
+    /// A recording started, delivering a stream with this layout.
+    Started { sample_rate: u32, channels: u16 },
+    /// The stream of `source`, captured at `from` Hz, is resampled to `to` Hz, emitted
+    /// before `Started`. Sources delivered at their own rate are not reported.
+    Resampling {
+        source: AudioSource,
+        from: u32,
+        to: u32,
+    },
+    /// `delivered` of the `limit` of the recording quota is used, see
+    /// `Recorder::set_quota`.
+    QuotaWarning {
+        delivered: Duration,
+        limit: Duration,
+    },
+    /// The recording delivered the `limit` of its quota and is stopped.
+    QuotaReached { limit: Duration },
+    /// A recording ended after delivering `duration` of audio, right before the end
+    /// marker of its data channel.
+    Stopped { duration: Duration },
+    /// The device of `source` reported an error, e.g. because it was unplugged.
+    DeviceError {
+        source: AudioSource,
+        message: String,
+    },
+    /// The device of `source` skipped `gap` of audio, typically because the system
+    /// was too busy to service it in time.
+    XRun { source: AudioSource, gap: Duration },
+    /// `dropped` samples of `source` were lost because the recording fell behind.
+    BufferOverrun { source: AudioSource, dropped: usize },
+    /// The ring buffer of `source` is filling up (`occupied` of `capacity` samples),
+    /// its consumer falls behind, see `Recorder::get_buffer_occupancy`.
+    Backpressure {
+        source: AudioSource,
+        occupied: usize,
+        capacity: usize,
+    },
+    /// The ring buffer of `source` drained again after `Backpressure`.
+    BackpressureRelieved { source: AudioSource },
+    /// The recording could not keep up and took `step` to lighten its load, see
+    /// `Recorder::set_degradation`.
+    Degraded { step: DegradationStep },
+    /// A capture callback of `source` took `elapsed`, longer than the `budget` of audio
+    /// it carried, see `Recorder::get_callback_stats`.
+    SlowCallback {
+        source: AudioSource,
+        elapsed: Duration,
+        budget: Duration,
+    },
+}
+
+/// Fans recorder events (or any other side channel item) out to every subscriber.
+#[derive(Debug, Clone)]
+pub struct EventBus<T = RecorderEvent> {
+    subscribers: Arc<Mutex<Vec<Sender<T>>>>,
+}
+
+impl<T> Default for EventBus<T> {
+    fn default() -> Self {
+        EventBus {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<T: Clone + std::fmt::Debug> EventBus<T> {
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+
+        rx
+    }
+
+    /// Whether anyone is subscribed.
+    pub fn has_subscribers(&self) -> bool {
+        !self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    /// Sends `event` to every subscriber, forgetting the ones that went away.
+    pub fn emit(&self, event: T) {
+        tracing::trace!("Emitting event {:?}", event);
+
+        // a subscriber list is never left half updated, a poisoned lock is still usable
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+impl Recorder {
+    /// Returns the error callback of the streams of `source`, reporting every error as
+    /// a `RecorderEvent::DeviceError`.
+    pub fn stream_errors(
+        &self,
+        source: AudioSource,
+    ) -> impl FnMut(cpal::StreamError) + Send + Clone + use<> {
+        let events = self.events.clone();
+
+        move |e| {
+            tracing::error!("An error occurred on the {:?} stream: {}", source, e);
+            events.emit(RecorderEvent::DeviceError {
+                source,
+                message: e.to_string(),
+            });
+        }
+    }
+
+    /// Subscribes to the events reported by the recorder, across recordings.
+    ///
+    /// Events are buffered until received, and dropping the receiver unsubscribes.
+    pub fn subscribe_events(&self) -> Receiver<RecorderEvent> {
+        self.events.subscribe()
+    }
+}
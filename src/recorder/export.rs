@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use hound::{SampleFormat, WavReader, WavWriter};
+
+use super::errors::AudioRecorderError;
+
+/// Concatenates WAV segments, in order, into a single WAV file at `output`.
+///
+/// Every sample of every segment is copied as is, so the result is gapless and sample
+/// accurate. All segments must share the same format (sample rate, channels, sample
+/// format and bit depth).
+///
+/// # Returns
+///
+/// The number of frames written to `output`.
+pub fn stitch_wav_segments<P: AsRef<Path>>(
+    segments: &[P],
+    output: impl AsRef<Path>,
+) -> Result<u64, AudioRecorderError> {
+    let first = match segments.first() {
+        Some(s) => s,
+        None => {
+            return Err(AudioRecorderError::InvalidArgument("No segments to stitch"));
+        }
+    };
+
+    let spec = WavReader::open(first)
+        .map_err(AudioRecorderError::WavError)?
+        .spec();
+    let mut writer = WavWriter::create(output, spec).map_err(AudioRecorderError::WavError)?;
+    let mut samples: u64 = 0;
+
+    for segment in segments {
+        let segment = segment.as_ref();
+        tracing::debug!("Stitching segment {:?}", segment);
+
+        let mut reader = WavReader::open(segment).map_err(AudioRecorderError::WavError)?;
+        if reader.spec() != spec {
+            tracing::error!("Segment {:?} has a different format", segment);
+            return Err(AudioRecorderError::FormatMismatch(segment.to_path_buf()));
+        }
+
+        match spec.sample_format {
+            SampleFormat::Float => {
+                for sample in reader.samples::<f32>() {
+                    writer
+                        .write_sample(sample.map_err(AudioRecorderError::WavError)?)
+                        .map_err(AudioRecorderError::WavError)?;
+                    samples += 1;
+                }
+            }
+            SampleFormat::Int => {
+                for sample in reader.samples::<i32>() {
+                    writer
+                        .write_sample(sample.map_err(AudioRecorderError::WavError)?)
+                        .map_err(AudioRecorderError::WavError)?;
+                    samples += 1;
+                }
+            }
+        }
+    }
+
+    writer.finalize().map_err(AudioRecorderError::WavError)?;
+
+    Ok(samples / spec.channels.max(1) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use hound::WavSpec;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("arec-stitch-{}-{}.wav", std::process::id(), name))
+    }
+
+    fn spec(channels: u16) -> WavSpec {
+        WavSpec {
+            channels,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    fn write(name: &str, spec: WavSpec, samples: &[i16]) -> PathBuf {
+        let path = temp_path(name);
+        let mut writer = WavWriter::create(&path, spec).expect("Failed to create segment");
+        for sample in samples {
+            writer
+                .write_sample(*sample)
+                .expect("Failed to write segment");
+        }
+        writer.finalize().expect("Failed to finalize segment");
+        path
+    }
+
+    fn cleanup(paths: &[&PathBuf]) {
+        for path in paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn concatenates_segments_in_order() {
+        let first = write("order-0", spec(2), &[1, -1, 2, -2]);
+        let second = write("order-1", spec(2), &[3, -3]);
+        let output = temp_path("order-out");
+
+        let frames = stitch_wav_segments(&[&first, &second], &output).expect("Failed to stitch");
+
+        let mut reader = WavReader::open(&output).expect("Failed to open output");
+        let samples = reader
+            .samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Failed to read output");
+        assert_eq!(frames, 3);
+        assert_eq!(reader.spec(), spec(2));
+        drop(reader);
+        assert_eq!(samples, vec![1, -1, 2, -2, 3, -3]);
+
+        cleanup(&[&first, &second, &output]);
+    }
+
+    #[test]
+    fn rejects_segments_of_another_format() {
+        let first = write("format-0", spec(2), &[1, -1]);
+        let second = write("format-1", spec(1), &[2]);
+        let output = temp_path("format-out");
+
+        let result = stitch_wav_segments(&[&first, &second], &output);
+        assert!(matches!(result, Err(AudioRecorderError::FormatMismatch(_))));
+
+        cleanup(&[&first, &second, &output]);
+    }
+
+    #[test]
+    fn rejects_an_empty_list() {
+        let output = temp_path("empty-out");
+
+        let segments: [PathBuf; 0] = [];
+
+        let result = stitch_wav_segments(&segments, &output);
+        assert!(matches!(
+            result,
+            Err(AudioRecorderError::InvalidArgument(_))
+        ));
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,42 @@
+use super::{Recorder, constants::AudioSource, dsp::level::db_to_linear};
+
+impl Recorder {
+    /// Sets the gain, in dB, applied to `source`, e.g. behind the microphone and system
+    /// audio volume sliders of an app.
+    ///
+    /// Takes effect immediately, including on a running recording, where the change is
+    /// ramped over 20 ms in the capture callbacks to avoid clicks.
+    pub fn set_gain(&mut self, source: AudioSource, db: f32) {
+        tracing::debug!("Setting {:?} gain to {} dB", source, db);
+        match source {
+            AudioSource::Input => {
+                self.input_gain_db = db;
+                self.input_gain.set(db_to_linear(db));
+            }
+            AudioSource::Output => {
+                self.output_gain_db = db;
+                self.output_gain.set(db_to_linear(db));
+            }
+        }
+    }
+
+    /// Returns the gain, in dB, applied to `source`.
+    pub fn get_gain(&self, source: AudioSource) -> f32 {
+        match source {
+            AudioSource::Input => self.input_gain_db,
+            AudioSource::Output => self.output_gain_db,
+        }
+    }
+
+    /// Sets the gain, in dB, applied to the input (microphone) stream.
+    ///
+    /// Takes effect immediately, including on a running recording.
+    pub fn set_input_gain_db(&mut self, db: f32) {
+        self.set_gain(AudioSource::Input, db);
+    }
+
+    /// Returns the gain, in dB, applied to the input (microphone) stream.
+    pub fn get_input_gain_db(&self) -> f32 {
+        self.get_gain(AudioSource::Input)
+    }
+}
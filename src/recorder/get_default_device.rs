@@ -1,6 +1,8 @@
-use cpal::{Device, traits::HostTrait};
+use cpal::{Device, HostId, traits::HostTrait};
 
 use super::errors::AudioRecorderError;
+#[cfg(target_os = "linux")]
+use super::linux_utils;
 
 pub fn get_default_input_device() -> Result<Device, AudioRecorderError> {
     let host = cpal::default_host();
@@ -14,10 +16,33 @@ pub fn get_default_input_device() -> Result<Device, AudioRecorderError> {
         }
     };
 
-    // TODO: implement linux device selection (this changes due to pipewire/alsa/pulseaudio)
+    // on Linux, the ALSA `default` device is routed to the default source by PulseAudio
+    // or PipeWire when they run, the monitor is only selected for the system stream
     Ok(device)
 }
 
+/// Gets the default input device of the host `host_id`, e.g. the JACK client whose
+/// ports are connected to the physical capture ports.
+pub fn get_host_input_device(host_id: HostId) -> Result<Device, AudioRecorderError> {
+    let host = match cpal::host_from_id(host_id) {
+        Ok(host) => host,
+        Err(e) => {
+            tracing::error!("Failed to open host {:?}: {}", host_id, e);
+            return Err(AudioRecorderError::DeviceError("Host unavailable"));
+        }
+    };
+
+    match host.default_input_device() {
+        Some(device) => Ok(device),
+        None => {
+            tracing::error!("Host {:?} has no default input device", host_id);
+            Err(AudioRecorderError::DeviceError(
+                "No default input device found",
+            ))
+        }
+    }
+}
+
 pub fn get_default_output_device() -> Result<Device, AudioRecorderError> {
     #[cfg(target_os = "macos")]
     {
@@ -74,8 +99,14 @@ pub fn get_default_output_device() -> Result<Device, AudioRecorderError> {
 
     #[cfg(target_os = "linux")]
     {
-        // TODO: implement linux device selection (this changes due to pipewire/alsa/pulseaudio)
-        // For Linux, we can use the default host
+        tracing::debug!("Trying to capture the monitor source of the default sink");
+        if let Some(device) = linux_utils::monitor_device() {
+            return Ok(device);
+        }
+
+        tracing::warn!(
+            "No PulseAudio or PipeWire monitor source found, falling back to the default output device"
+        );
         let host = cpal::default_host();
         let device = match host.default_output_device() {
             Some(d) => d,
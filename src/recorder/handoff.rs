@@ -0,0 +1,157 @@
+use std::sync::{Arc, Mutex, PoisonError, Weak, atomic::AtomicBool};
+
+use crossbeam_channel::SendError;
+
+use super::{
+    Recorder,
+    constants::TargetFormat,
+    delivery::{ChunkSender, StreamMessage},
+    errors::AudioRecorderError,
+    receiver::AudioReceiver,
+    worker::WorkerHandles,
+};
+
+/// The sending side of the data channel shared by the capture threads, so that it can
+/// be swapped for the channel of another recorder, see `Recorder::hand_off`.
+#[derive(Clone)]
+pub struct SharedSender(Arc<Mutex<ChunkSender>>);
+
+impl SharedSender {
+    pub fn send(&self, data: Vec<TargetFormat>) -> Result<(), SendError<StreamMessage>> {
+        // a poisoned sender is still consistent, a panic never happens mid-send
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(data)
+    }
+
+    /// Adapts the sender to a stream rebuilt on another device, see
+    /// `ChunkSender::reconfigure`.
+    pub fn reconfigure(&self, sample_rate: u32, channels: u16) -> Result<(), AudioRecorderError> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .reconfigure(sample_rate, channels)
+    }
+}
+
+/// Where the data channel of a running recording can be swapped.
+#[derive(Debug)]
+pub struct HandoffPoint {
+    /// The shared sender, weak so that the end marker is still sent once the capture
+    /// threads release it.
+    sender: Weak<Mutex<ChunkSender>>,
+    /// Layout of the stream fed to the sender, before any conversion.
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Recorder {
+    /// Creates the data channel for a stream captured with the given layout, like
+    /// `data_channel`, but shared so that the recording can be handed off.
+    pub fn shared_channel(
+        &self,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<(SharedSender, AudioReceiver), AudioRecorderError> {
+        let (sender, receiver) = self.data_channel(sample_rate, channels)?;
+        let sender = Arc::new(Mutex::new(sender));
+
+        *self.handoff.lock().unwrap_or_else(PoisonError::into_inner) = Some(HandoffPoint {
+            sender: Arc::downgrade(&sender),
+            sample_rate,
+            channels,
+        });
+
+        Ok((SharedSender(sender), receiver))
+    }
+
+    /// Hands the running recording over to `next`, e.g. after the user changed the
+    /// delivery settings, without stopping the devices or dropping a single frame.
+    ///
+    /// The data channel of this recorder is swapped for one built with the settings of
+    /// `next` (standard output, sample rate, pacing, fingerprint, analyzers, watchdog)
+    /// between two capture callbacks: the audio still in the converter and the paced
+    /// remainder end the stream of this recorder, followed by its end marker, and the
+    /// very next frame starts the stream returned here. The capture settings (devices,
+    /// gain, mix, noise reduction) and the events of the capture threads stay those of
+    /// this recorder.
+    ///
+    /// Afterwards `next` owns the recording and this recorder is stopped. Recordings of
+    /// separate sources cannot be handed off.
+    #[tracing::instrument(skip(next))]
+    pub fn hand_off(&mut self, next: &mut Recorder) -> Result<AudioReceiver, AudioRecorderError> {
+        tracing::info!("Handing off the recording");
+
+        if !self.get_is_recording() {
+            tracing::error!("No recording to hand off");
+            return Err(AudioRecorderError::HandOff("No recording to hand off"));
+        }
+
+        if next.get_is_recording() {
+            tracing::warn!("The next recorder is already recording");
+            return Err(AudioRecorderError::RecordingInProgress);
+        }
+
+        let point = self
+            .handoff
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let (point, sender) = match point {
+            Some(point) => match point.sender.upgrade() {
+                Some(sender) => (point, sender),
+                None => {
+                    tracing::error!("The capture threads already released the data channel");
+                    return Err(AudioRecorderError::HandOff(
+                        "The recording has already ended",
+                    ));
+                }
+            },
+            None => {
+                tracing::error!("The recording cannot be handed off");
+                return Err(AudioRecorderError::HandOff(
+                    "The recording cannot be handed off",
+                ));
+            }
+        };
+
+        // the capture threads keep running on the recording flag, which moves to `next`
+        let fresh_signal = Arc::new(AtomicBool::new(false));
+        let previous_signal =
+            std::mem::replace(&mut next.recording_signal, self.recording_signal.clone());
+        let previous_handles =
+            std::mem::replace(&mut next.worker_handles, self.worker_handles.clone());
+
+        let (new_sender, receiver) = match next.data_channel(point.sample_rate, point.channels) {
+            Ok(channel) => channel,
+            Err(e) => {
+                next.recording_signal = previous_signal;
+                next.worker_handles = previous_handles;
+                *self.handoff.lock().unwrap_or_else(PoisonError::into_inner) = Some(point);
+                return Err(e);
+            }
+        };
+        let (sample_rate, channels) = new_sender.layout();
+
+        let mut old_sender = {
+            let mut current = sender.lock().unwrap_or_else(PoisonError::into_inner);
+            std::mem::replace(&mut *current, new_sender)
+        };
+        old_sender.flush_converter();
+        // sends the end marker of this recorder's stream
+        drop(old_sender);
+
+        next.config = self.config.clone();
+        next.target_sample_rate = Some(sample_rate);
+        next.channels = Some(channels);
+        next.sample_size = self.sample_size;
+        *next.handoff.lock().unwrap_or_else(PoisonError::into_inner) = Some(point);
+
+        self.recording_signal = fresh_signal;
+        self.worker_handles = WorkerHandles::default();
+
+        tracing::info!("Recording handed off at {} Hz {}ch", sample_rate, channels);
+        Ok(receiver)
+    }
+}
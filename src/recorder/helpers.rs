@@ -3,12 +3,40 @@ use std::iter::Sum;
 use cpal::StreamError;
 use num_traits::{FromPrimitive, Num};
 
-use super::Recorder;
+use super::{Recorder, constants::AudioSource, errors::AudioRecorderError, events::RecorderEvent};
 
+/// The sample rate conversion of a captured source, see `Config::input_resampling`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RateConversion {
+    /// Sample rate the device captures at, in Hz.
+    pub from: u32,
+    /// Sample rate the source is delivered at, in Hz.
+    pub to: u32,
+}
+
+impl RateConversion {
+    /// The conversion from `from` to `to`, or `None` when the rates match.
+    pub fn between(from: u32, to: u32) -> Option<RateConversion> {
+        (from != to).then_some(RateConversion { from, to })
+    }
+}
+
+/// The layout of the delivered stream, see `Recorder::get_config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub struct Config {
+    /// Sample rate, in Hz.
     pub sample_rate: u32,
+    /// Number of interleaved channels.
     pub channels: u16,
+    /// Size of a sample of the capture format, in bytes.
     pub sample_size: u32,
+    /// How the input stream is resampled, `None` when it is delivered at its rate.
+    pub input_resampling: Option<RateConversion>,
+    /// How the system audio stream is resampled, `None` when it is delivered at its
+    /// rate or not recorded.
+    pub output_resampling: Option<RateConversion>,
 }
 
 impl Recorder {
@@ -40,25 +68,25 @@ impl Recorder {
     }
 
     #[tracing::instrument]
-    pub fn get_config(&self) -> Result<Config, String> {
+    pub fn get_config(&self) -> Result<Config, AudioRecorderError> {
         let sample_rate = match self.target_sample_rate {
             Some(rate) => rate,
             None => {
-                return Err("Sample rate not set".to_string());
+                return Err(AudioRecorderError::NotConfigured("Sample rate not set"));
             }
         };
 
         let channels = match self.channels {
             Some(channels) => channels,
             None => {
-                return Err("Channels not set".to_string());
+                return Err(AudioRecorderError::NotConfigured("Channels not set"));
             }
         };
 
         let sample_size = match self.sample_size {
             Some(size) => size,
             None => {
-                return Err("Sample size not set".to_string());
+                return Err(AudioRecorderError::NotConfigured("Sample size not set"));
             }
         };
 
@@ -66,9 +94,31 @@ impl Recorder {
             sample_rate,
             channels,
             sample_size,
+            input_resampling: self.input_resampling,
+            output_resampling: self.output_resampling,
         })
     }
 
+    /// Records whether `source` is resampled, reported by `get_config` and emitted as a
+    /// `RecorderEvent::Resampling` when it is.
+    pub fn report_resampling(&mut self, source: AudioSource, conversion: Option<RateConversion>) {
+        match source {
+            AudioSource::Input => self.input_resampling = conversion,
+            AudioSource::Output => self.output_resampling = conversion,
+        }
+
+        if let Some(RateConversion { from, to }) = conversion {
+            tracing::info!(
+                "Resampling the {:?} stream from {} Hz to {} Hz",
+                source,
+                from,
+                to
+            );
+            self.events
+                .emit(RecorderEvent::Resampling { source, from, to });
+        }
+    }
+
     /// Converts multi-channel audio data to mono by averaging the channels.
     ///
     /// # Parameters
@@ -81,9 +131,8 @@ impl Recorder {
     /// A vector containing the mono audio data, where each sample is the average of the corresponding
     /// samples from the input channels.
     ///
-    /// # Panics
-    ///
-    /// This function will panic if the length of `stereo_data` is not a multiple of `channels`.
+    /// A trailing partial frame, which a malformed callback may deliver, is averaged
+    /// over the samples it has, and a channel count of zero is treated as mono.
     ///
     /// # Type Parameters
     ///
@@ -92,54 +141,25 @@ impl Recorder {
     /// # Examples
     ///
     /// ```
-    /// use num_traits::{Num, FromPrimitive};
-    /// use std::iter::Sum;
-    ///
-    /// fn channels_to_mono<T>(stereo_data: Vec<T>, channels: u16) -> Vec<T>
-    /// where
-    ///     T: Num + Copy + Sum + FromPrimitive,
-    /// {
-    ///     let channels = channels as usize; // Convert u16 to usize for safe indexing and operations
+    /// use audio_recorder_rs::Recorder;
     ///
-    ///     // Ensure that the length of stereo_data is a multiple of the number of channels
-    ///     assert!(
-    ///         stereo_data.len() % channels == 0,
-    ///         "Data length must be a multiple of the number of channels"
-    ///     );
-    ///
-    ///     // Create a new vector to hold the mono data
-    ///     let mut mono_data = Vec::with_capacity(stereo_data.len() / channels);
-    ///
-    ///     // Iterate over the stereo data in chunks of 'channels'
-    ///     for chunk in stereo_data.chunks(channels) {
-    ///         // Compute the average of the chunk
-    ///         let mono = chunk.iter().copied().sum::<T>() / T::from_usize(channels).unwrap();
-    ///         mono_data.push(mono);
-    ///     }
-    ///
-    ///     mono_data
-    /// }
+    /// let mono = Recorder::channels_to_mono(vec![0.5f32, 0.25, 1.0], 2);
+    /// assert_eq!(mono, vec![0.375, 1.0]);
     /// ```
     pub fn channels_to_mono<T>(stereo_data: Vec<T>, channels: u16) -> Vec<T>
     where
         T: Num + Copy + Sum + FromPrimitive,
     {
-        let channels = channels as usize; // Convert u16 to usize for safe indexing and operations
-
-        // Ensure that the length of stereo_data is a multiple of the number of channels
-        assert!(
-            stereo_data.len().is_multiple_of(channels),
-            "Data length must be a multiple of the number of channels"
-        );
+        let channels = channels.max(1) as usize; // Convert u16 to usize for safe indexing and operations
 
         // Create a new vector to hold the mono data
-        let mut mono_data = Vec::with_capacity(stereo_data.len() / channels);
+        let mut mono_data = Vec::with_capacity(stereo_data.len().div_ceil(channels));
 
         // Iterate over the stereo data in chunks of 'channels'
         for chunk in stereo_data.chunks(channels) {
             // Compute the average of the chunk
-            let mono = chunk.iter().copied().sum::<T>() / T::from_usize(channels).unwrap();
-            mono_data.push(mono);
+            let len = T::from_usize(chunk.len()).unwrap_or_else(T::one);
+            mono_data.push(chunk.iter().copied().sum::<T>() / len);
         }
 
         mono_data
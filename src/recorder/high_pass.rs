@@ -0,0 +1,43 @@
+use super::{Recorder, dsp::high_pass::HighPass};
+
+/// Settings of the high-pass filter of the microphone, see `Recorder::set_high_pass`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighPassFilter {
+    /// Cutoff of the 2nd-order high-pass, in Hz, or `None` to only remove the DC
+    /// offset.
+    pub cutoff_hz: Option<f32>,
+}
+
+impl Default for HighPassFilter {
+    fn default() -> Self {
+        HighPassFilter {
+            cutoff_hz: Some(80.0),
+        }
+    }
+}
+
+impl HighPassFilter {
+    /// Only removes the DC offset, keeping all the audible frequencies.
+    pub fn dc_blocker() -> Self {
+        HighPassFilter { cutoff_hz: None }
+    }
+
+    /// Creates the filter of a microphone stream with the given layout.
+    pub fn filter(&self, sample_rate: u32, channels: u16) -> HighPass {
+        HighPass::new(sample_rate, channels, self.cutoff_hz)
+    }
+}
+
+impl Recorder {
+    /// Removes the DC offset and the rumble below the cutoff of `filter` from the
+    /// microphone (or `None` to keep them), e.g. the handling noise and hum of cheap
+    /// microphones, which otherwise pollute the recording and mislead the level
+    /// measurements. The default 80 Hz cutoff keeps all of the voice.
+    ///
+    /// The filter runs first, right after the gain.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_high_pass(&mut self, filter: Option<HighPassFilter>) {
+        self.high_pass = filter;
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use cpal::SupportedBufferSize;
+
+use super::{
+    Recorder,
+    constants::{ALIGNMENT_MAX_MS, LATENCY_PADDING_FALLBACK_MS, LATENCY_PADDING_MAX_MS},
+};
+
+/// Silence inserted ahead of the system audio when both streams run at the same rate,
+/// absorbing the scheduling skew between the two device callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum LatencyPadding {
+    /// Derived from the buffer sizes reported by the devices.
+    #[default]
+    Auto,
+    /// A fixed amount of padding, `Duration::ZERO` disables it.
+    Fixed(Duration),
+}
+
+/// Duration of the largest buffer a device reports, `None` when the device doesn't report
+/// its buffer sizes.
+fn max_buffer_duration(config: &cpal::SupportedStreamConfig) -> Option<Duration> {
+    match config.buffer_size() {
+        SupportedBufferSize::Range { max, .. } => Some(Duration::from_secs_f64(
+            *max as f64 / config.sample_rate().0.max(1) as f64,
+        )),
+        SupportedBufferSize::Unknown => None,
+    }
+}
+
+impl Recorder {
+    /// Sets the latency padding inserted ahead of the system audio.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_latency_padding(&mut self, padding: LatencyPadding) {
+        self.latency_padding = padding;
+    }
+
+    /// Offsets the system audio against the microphone when mixing both, in
+    /// milliseconds: a positive offset delays the system audio, a negative one delays
+    /// the microphone. Offsets are capped at one second either way.
+    ///
+    /// Measure the offset of a setup with `calibrate_alignment`.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_alignment_offset_ms(&mut self, offset_ms: i32) {
+        self.alignment_offset_ms = offset_ms.clamp(-ALIGNMENT_MAX_MS, ALIGNMENT_MAX_MS);
+    }
+
+    /// The offset set by `set_alignment_offset_ms`.
+    pub fn get_alignment_offset_ms(&self) -> i32 {
+        self.alignment_offset_ms
+    }
+
+    /// Resolves the alignment offset at `sample_rate`, as the frames of silence
+    /// inserted ahead of the system audio and the frames of system audio skipped at
+    /// its start (delaying the microphone instead).
+    pub fn alignment_frames(&self, sample_rate: u32) -> (usize, usize) {
+        let frames =
+            (self.alignment_offset_ms.unsigned_abs() as u64 * sample_rate as u64 / 1000) as usize;
+
+        tracing::debug!("Alignment offset: {} ms", self.alignment_offset_ms);
+        match self.alignment_offset_ms >= 0 {
+            true => (frames, 0),
+            false => (0, frames),
+        }
+    }
+
+    /// Resolves the latency padding, in frames at the input sample rate.
+    ///
+    /// `LatencyPadding::Auto` covers one callback buffer of each device, capped at
+    /// 150 ms, and falls back to 150 ms when a device doesn't report its buffer sizes.
+    pub fn latency_padding_frames(
+        &self,
+        input_config: &cpal::SupportedStreamConfig,
+        output_config: &cpal::SupportedStreamConfig,
+    ) -> usize {
+        let padding = match self.latency_padding {
+            LatencyPadding::Fixed(padding) => padding,
+            LatencyPadding::Auto => {
+                match (
+                    max_buffer_duration(input_config),
+                    max_buffer_duration(output_config),
+                ) {
+                    (Some(input), Some(output)) => {
+                        (input + output).min(Duration::from_millis(LATENCY_PADDING_MAX_MS))
+                    }
+                    _ => Duration::from_millis(LATENCY_PADDING_FALLBACK_MS),
+                }
+            }
+        };
+
+        tracing::debug!("Latency padding: {:?}", padding);
+        (padding.as_secs_f64() * input_config.sample_rate().0 as f64) as usize
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use super::{
+    Recorder,
+    constants::{
+        AudioSource, LEVEL_HISTOGRAM_BIN_DB, LEVEL_HISTOGRAM_BINS, LEVEL_HISTOGRAM_FLOOR_DB,
+        TargetFormat,
+    },
+    dsp::level::{rms, to_dbfs},
+};
+
+/// How long a source spent at each level over a recording, see
+/// `Recorder::get_level_histogram` and `StreamSummary::levels`.
+///
+/// The RMS level of every capture callback is counted, weighted by its frames, in bins of
+/// 3 dB from -90 dBFS to 0 dBFS; the first bin also counts everything quieter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LevelHistogram {
+    pub source: AudioSource,
+    /// Frames captured at the level of each bin, the quietest first.
+    pub frames: Vec<u64>,
+}
+
+impl LevelHistogram {
+    /// Total number of frames counted.
+    pub fn total_frames(&self) -> u64 {
+        self.frames.iter().sum()
+    }
+
+    /// Range of levels, in dBFS, of the bin at `index`.
+    pub fn bin_range(index: usize) -> (f32, f32) {
+        let low = LEVEL_HISTOGRAM_FLOOR_DB + index as f32 * LEVEL_HISTOGRAM_BIN_DB;
+        (low, low + LEVEL_HISTOGRAM_BIN_DB)
+    }
+
+    /// Share of the frames, from 0 to 1, captured below `db` (rounded to the bins), e.g.
+    /// `fraction_below(-60.0)` close to 1 when the microphone was silent all along.
+    pub fn fraction_below(&self, db: f32) -> f32 {
+        let total = self.total_frames();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let below: u64 = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| LevelHistogram::bin_range(*index).1 <= db)
+            .map(|(_, frames)| frames)
+            .sum();
+        below as f32 / total as f32
+    }
+
+    /// Level, in dBFS, below which `fraction` of the frames were captured (the top of
+    /// the bin reaching it), e.g. `percentile_db(0.5)` for the median level.
+    pub fn percentile_db(&self, fraction: f32) -> f32 {
+        let target = self.total_frames() as f64 * fraction.clamp(0.0, 1.0) as f64;
+
+        let mut counted = 0;
+        for (index, frames) in self.frames.iter().enumerate() {
+            counted += frames;
+            if counted as f64 >= target {
+                return LevelHistogram::bin_range(index).1;
+            }
+        }
+        0.0
+    }
+}
+
+/// Level histogram of a source, shared with its capture callback.
+#[derive(Debug, Clone)]
+pub struct LevelCounters(Arc<[AtomicU64; LEVEL_HISTOGRAM_BINS]>);
+
+impl Default for LevelCounters {
+    fn default() -> Self {
+        LevelCounters(Arc::new(std::array::from_fn(|_| AtomicU64::new(0))))
+    }
+}
+
+impl LevelCounters {
+    /// Counts `frames` frames at the level of `samples`.
+    pub fn record(&self, samples: &[TargetFormat], frames: usize) {
+        if frames == 0 {
+            return;
+        }
+
+        let level = to_dbfs(rms(samples));
+        let index = ((level - LEVEL_HISTOGRAM_FLOOR_DB) / LEVEL_HISTOGRAM_BIN_DB).floor();
+        let index = (index.max(0.0) as usize).min(LEVEL_HISTOGRAM_BINS - 1);
+        self.0[index].fetch_add(frames as u64, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.0
+            .iter()
+            .for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+
+    pub fn snapshot(&self, source: AudioSource) -> LevelHistogram {
+        LevelHistogram {
+            source,
+            frames: self
+                .0
+                .iter()
+                .map(|counter| counter.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+impl Recorder {
+    /// Returns the level histogram of `source` in the current (or last) recording, also
+    /// delivered in `StreamSummary::levels`, for quick answers such as whether the
+    /// microphone was silent the whole meeting without reading the audio again.
+    ///
+    /// Levels are measured after the processing of the source (gain, noise reduction,
+    /// automatic gain).
+    pub fn get_level_histogram(&self, source: AudioSource) -> LevelHistogram {
+        match source {
+            AudioSource::Input => self.input_levels.snapshot(source),
+            AudioSource::Output => self.output_levels.snapshot(source),
+        }
+    }
+
+    /// The level counters of both sources, summarized at the end of the stream.
+    pub fn level_histograms(&self) -> Vec<(AudioSource, LevelCounters)> {
+        vec![
+            (AudioSource::Input, self.input_levels.clone()),
+            (AudioSource::Output, self.output_levels.clone()),
+        ]
+    }
+}
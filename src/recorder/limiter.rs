@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use super::{Recorder, dsp::limiter::Limiter};
+
+/// Settings of the limiter protecting the delivered stream from clipping, see
+/// `Recorder::set_limiter`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftLimiter {
+    /// Level, in dBFS, no delivered sample exceeds. Clamped to 0 dBFS.
+    pub threshold_db: f32,
+    /// How long the gain takes to recover after a peak.
+    pub release: Duration,
+}
+
+impl Default for SoftLimiter {
+    fn default() -> Self {
+        SoftLimiter {
+            threshold_db: -1.0,
+            release: Duration::from_millis(50),
+        }
+    }
+}
+
+impl SoftLimiter {
+    /// Creates the limiter of a delivered stream with the given layout.
+    pub fn limiter(&self, sample_rate: u32, channels: u16) -> Limiter {
+        Limiter::new(sample_rate, channels, self.threshold_db, self.release)
+    }
+}
+
+impl Recorder {
+    /// Limits the delivered stream to the threshold of `limiter` (or `None` to deliver
+    /// it unchanged).
+    ///
+    /// Gains, sample format conversions and mixing both sources can push a float stream
+    /// past ±1.0, which clips once it is written as integers. The limiter lowers the
+    /// gain of such peaks instead.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_limiter(&mut self, limiter: Option<SoftLimiter>) {
+        self.limiter = limiter;
+    }
+}
@@ -0,0 +1,116 @@
+use std::process::Command;
+
+use cpal::{
+    Device,
+    traits::{DeviceTrait, HostTrait},
+};
+
+/// Runs `pactl` with `args`, returning its standard output when it succeeds.
+///
+/// `pactl` talks to PulseAudio and to the PulseAudio server of PipeWire alike.
+fn pactl(args: &[&str]) -> Option<String> {
+    let output = match Command::new("pactl").args(args).output() {
+        Ok(output) => output,
+        Err(e) => {
+            tracing::debug!("Failed to run pactl: {}", e);
+            return None;
+        }
+    };
+
+    if !output.status.success() {
+        tracing::debug!("pactl {:?} exited with {}", args, output.status);
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Name of the default sink (`kind` "sink") or source (`kind` "source"), e.g.
+/// `alsa_output.pci-0000_00_1f.3.analog-stereo`.
+pub fn default_node(kind: &str) -> Option<String> {
+    let node = pactl(&[&format!("get-default-{kind}")])
+        .map(|out| out.trim().to_string())
+        .filter(|node| !node.is_empty());
+    if node.is_some() {
+        return node;
+    }
+
+    // servers older than PulseAudio 15 have no `get-default-sink` nor `get-default-source`
+    let prefix = match kind {
+        "source" => "Default Source:",
+        _ => "Default Sink:",
+    };
+    pactl(&["info"])?.lines().find_map(|line| {
+        line.strip_prefix(prefix)
+            .map(|node| node.trim().to_string())
+            .filter(|node| !node.is_empty())
+    })
+}
+
+/// Makes `name` the default sink (`kind` "sink") or source (`kind` "source"),
+/// returning whether the server accepted it.
+pub fn set_default_node(kind: &str, name: &str) -> bool {
+    pactl(&[&format!("set-default-{kind}"), name]).is_some()
+}
+
+/// Name of the monitor source of the default sink, which carries the system audio.
+pub fn default_monitor_source() -> Option<String> {
+    let monitor = format!("{}.monitor", default_node("sink")?);
+
+    let listed = pactl(&["list", "short", "sources"])?
+        .lines()
+        .any(|line| line.split_whitespace().nth(1) == Some(monitor.as_str()));
+    if !listed {
+        tracing::debug!("Default sink has no monitor source {}", monitor);
+        return None;
+    }
+
+    Some(monitor)
+}
+
+/// Whether `device` is the `pulse` or `pipewire` device of the ALSA host, i.e. a
+/// client of the sound server.
+fn is_sound_server(device: &Device) -> bool {
+    device
+        .name()
+        .is_ok_and(|name| name == "pulse" || name == "pipewire")
+}
+
+/// The monitor source to capture as the system audio of `device`, when it is the
+/// device of the sound server.
+pub fn monitor_source(device: &Device) -> Option<String> {
+    match is_sound_server(device) {
+        true => default_monitor_source(),
+        false => None,
+    }
+}
+
+/// The device of the ALSA host standing for the monitor source of the default sink.
+///
+/// The system stream built on it records the monitor by name, see
+/// `pulse_monitor::MonitorStream`, so the default source of the server and the
+/// environment are left untouched. Returns `None` when there is no sound server or no
+/// such plugin.
+pub fn monitor_device() -> Option<Device> {
+    let monitor = default_monitor_source()?;
+
+    let host = cpal::default_host();
+    let mut devices = match host.input_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            tracing::warn!("Failed to list input devices: {}", e);
+            return None;
+        }
+    };
+
+    let device = match devices.find(is_sound_server) {
+        Some(device) => device,
+        None => {
+            tracing::debug!("No pulse or pipewire ALSA device to capture {}", monitor);
+            return None;
+        }
+    };
+
+    tracing::debug!("Capturing the monitor source {}", monitor);
+    Some(device)
+}
@@ -0,0 +1,237 @@
+use cpal::Sample;
+
+use super::{Recorder, constants::TargetFormat};
+
+/// How the input (microphone) and output (system audio) streams are combined into the
+/// delivered stereo stream.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[non_exhaustive]
+pub enum MixMode {
+    /// Microphone downmixed to mono on the left channel, system audio downmixed to mono
+    /// on the right channel.
+    #[default]
+    DualMono,
+    /// System audio kept in stereo, with the mono microphone overlaid into both channels
+    /// at `mic_level` (linear). This is the layout screen-recording tools typically want.
+    StereoOverlay { mic_level: f32 },
+}
+
+impl MixMode {
+    /// Number of interleaved channels the system audio is kept in before mixing.
+    pub fn system_channels(&self) -> usize {
+        match self {
+            MixMode::DualMono => 1,
+            MixMode::StereoOverlay { .. } => 2,
+        }
+    }
+
+    /// Converts interleaved system audio with `channels` channels to the layout used
+    /// before mixing.
+    pub fn system_layout(&self, data: Vec<TargetFormat>, channels: u16) -> Vec<TargetFormat> {
+        match self {
+            MixMode::DualMono => Recorder::channels_to_mono(data, channels),
+            MixMode::StereoOverlay { .. } => to_stereo(data, channels),
+        }
+    }
+
+    /// Mixes one microphone sample with one system audio frame (in the layout returned by
+    /// `system_layout`) into `out`.
+    pub fn mix_frame(
+        &self,
+        mic: TargetFormat,
+        system: &[TargetFormat],
+        out: &mut Vec<TargetFormat>,
+    ) {
+        match self {
+            MixMode::DualMono => {
+                out.push(mic);
+                out.push(system.first().copied().unwrap_or(TargetFormat::EQUILIBRIUM));
+            }
+            MixMode::StereoOverlay { mic_level } => {
+                let left = system.first().copied().unwrap_or(TargetFormat::EQUILIBRIUM);
+                let right = system.get(1).copied().unwrap_or(left);
+                out.push(left + mic * mic_level);
+                out.push(right + mic * mic_level);
+            }
+        }
+    }
+}
+
+/// The channel layout of the delivered stream when recording from both devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ChannelMode {
+    /// The stereo stream produced by the `MixMode`, downmixed to a single channel.
+    Mono,
+    /// The stereo stream produced by the `MixMode`.
+    #[default]
+    Stereo,
+    /// Every channel of both devices kept as is: the microphone channels first, followed
+    /// by the system audio channels. The `MixMode` is ignored.
+    Passthrough,
+}
+
+/// How the captured streams are laid out before, and combined while, mixing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MixLayout {
+    mix_mode: MixMode,
+    channel_mode: ChannelMode,
+    mic_channels: usize,
+    system_channels: usize,
+}
+
+impl MixLayout {
+    pub fn new(
+        mix_mode: MixMode,
+        channel_mode: ChannelMode,
+        input_channels: u16,
+        output_channels: u16,
+    ) -> Self {
+        let (mic_channels, system_channels) = match channel_mode {
+            ChannelMode::Passthrough => (input_channels as usize, output_channels as usize),
+            _ => (1, mix_mode.system_channels()),
+        };
+
+        MixLayout {
+            mix_mode,
+            channel_mode,
+            mic_channels: mic_channels.max(1),
+            system_channels: system_channels.max(1),
+        }
+    }
+
+    /// Number of interleaved channels the microphone is kept in before mixing.
+    pub fn mic_channels(&self) -> usize {
+        self.mic_channels
+    }
+
+    /// Number of interleaved channels the system audio is kept in before mixing.
+    pub fn system_channels(&self) -> usize {
+        self.system_channels
+    }
+
+    /// Number of interleaved channels of the mixed stream.
+    pub fn output_channels(&self) -> u16 {
+        match self.channel_mode {
+            ChannelMode::Mono => 1,
+            ChannelMode::Stereo => 2,
+            ChannelMode::Passthrough => (self.mic_channels + self.system_channels) as u16,
+        }
+    }
+
+    /// Converts interleaved microphone data with `channels` channels to the layout used
+    /// before mixing.
+    pub fn mic_layout(&self, data: Vec<TargetFormat>, channels: u16) -> Vec<TargetFormat> {
+        match self.channel_mode {
+            ChannelMode::Passthrough => data,
+            _ => Recorder::channels_to_mono(data, channels),
+        }
+    }
+
+    /// Converts interleaved system audio data with `channels` channels to the layout used
+    /// before mixing.
+    pub fn system_layout(&self, data: Vec<TargetFormat>, channels: u16) -> Vec<TargetFormat> {
+        match self.channel_mode {
+            ChannelMode::Passthrough => data,
+            _ => self.mix_mode.system_layout(data, channels),
+        }
+    }
+
+    /// Mixes one microphone frame with one system audio frame (in the layouts returned by
+    /// `mic_layout` and `system_layout`) into `out`.
+    pub fn mix_frame(
+        &self,
+        mic: &[TargetFormat],
+        system: &[TargetFormat],
+        out: &mut Vec<TargetFormat>,
+    ) {
+        let first = mic.first().copied().unwrap_or(TargetFormat::EQUILIBRIUM);
+
+        match self.channel_mode {
+            ChannelMode::Mono => {
+                self.mix_mode.mix_frame(first, system, out);
+                let right = out.pop().unwrap_or(TargetFormat::EQUILIBRIUM);
+                let left = out.pop().unwrap_or(TargetFormat::EQUILIBRIUM);
+                out.push((left + right) * 0.5);
+            }
+            ChannelMode::Stereo => self.mix_mode.mix_frame(first, system, out),
+            ChannelMode::Passthrough => {
+                out.extend_from_slice(mic);
+                out.extend_from_slice(system);
+            }
+        }
+    }
+}
+
+/// Converts interleaved data with `channels` channels to stereo, duplicating mono and
+/// keeping the front left/right channels of multichannel layouts. A channel count of
+/// zero is treated as mono.
+pub fn to_stereo(data: Vec<TargetFormat>, channels: u16) -> Vec<TargetFormat> {
+    match channels {
+        2 => data,
+        0 | 1 => data.iter().flat_map(|s| [*s, *s]).collect(),
+        _ => data
+            .chunks(channels as usize)
+            .flat_map(|frame| [frame[0], frame.get(1).copied().unwrap_or(frame[0])])
+            .collect(),
+    }
+}
+
+/// Remixes interleaved data with `channels` channels to `target` channels.
+///
+/// Mono is downmixed by averaging and spread to every channel when upmixing, stereo
+/// uses `to_stereo`; other layouts keep their first channels, with the channels they
+/// lack left silent. A channel count of zero is treated as mono.
+pub fn remix(data: Vec<TargetFormat>, channels: u16, target: u16) -> Vec<TargetFormat> {
+    let channels = channels.max(1);
+    let target = target.max(1) as usize;
+
+    match (channels, target) {
+        (from, to) if from as usize == to => data,
+        (_, 1) => Recorder::channels_to_mono(data, channels),
+        (_, 2) => to_stereo(data, channels),
+        (1, _) => data
+            .iter()
+            .flat_map(|s| std::iter::repeat_n(*s, target))
+            .collect(),
+        _ => data
+            .chunks(channels as usize)
+            .flat_map(|frame| (0..target).map(|c| frame.get(c).copied().unwrap_or(0.0)))
+            .collect(),
+    }
+}
+
+impl Recorder {
+    /// Sets how the microphone and system audio are combined when recording from
+    /// multiple devices.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_mix_mode(&mut self, mix_mode: MixMode) {
+        self.mix_mode = mix_mode;
+    }
+
+    /// Sets the channel layout of the delivered stream when recording from multiple
+    /// devices.
+    ///
+    /// `ChannelMode::Passthrough` skips the lossy downmix entirely, e.g. to record music
+    /// or to keep separate microphone and system audio tracks.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_channel_mode(&mut self, channel_mode: ChannelMode) {
+        self.channel_mode = channel_mode;
+    }
+
+    /// Resolves the mix layout for the given device configs.
+    pub fn mix_layout(
+        &self,
+        input_config: &cpal::SupportedStreamConfig,
+        output_config: &cpal::SupportedStreamConfig,
+    ) -> MixLayout {
+        MixLayout::new(
+            self.mix_mode,
+            self.channel_mode,
+            input_config.channels(),
+            output_config.channels(),
+        )
+    }
+}
@@ -0,0 +1,737 @@
+use std::sync::atomic::Ordering;
+
+use cpal::traits::DeviceTrait;
+use crossbeam_channel::{Receiver, Sender};
+use ringbuf::{
+    HeapCons, HeapProd, HeapRb,
+    traits::{Consumer, Observer, Producer, Split},
+};
+
+use super::{
+    Recorder,
+    constants::{AudioSource, CustomSample, TargetFormat},
+    convert::StreamConverter,
+    delivery::ChunkSender,
+    devices::{AudioDevice, DeviceType},
+    dsp::{level::db_to_linear, loudness::LoudnessMatcher},
+    errors::AudioRecorderError,
+    receiver::AudioReceiver,
+    system_stream::{SystemStream, build_system_stream},
+    wakeup::Wakeup,
+    worker,
+};
+
+/// Channels of the mix delivered by `SessionMode::Mix`.
+const MIX_CHANNELS: u16 = 2;
+
+/// How a `MixerSession` delivers its sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum SessionMode {
+    /// Mixes every source into a single stereo stream.
+    #[default]
+    Mix,
+    /// Delivers every source as a separate track, in the channels of its device.
+    Tracks,
+}
+
+/// Audio pushed by the application into a `MixerSession`, e.g. a "recording started"
+/// announcement, text-to-speech prompts or the far end of a call it already receives.
+///
+/// The pushed audio is resampled, mixed and timestamped like the device sources, and
+/// plays out in real time: audio pushed faster is queued, and silence fills the time
+/// nothing was pushed. Audio pushed before the session starts plays at its start.
+///
+/// Clones push into the same source.
+#[derive(Debug, Clone)]
+pub struct AudioInjector {
+    sender: Sender<Vec<TargetFormat>>,
+    receiver: Receiver<Vec<TargetFormat>>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl AudioInjector {
+    /// Creates a source of interleaved `TargetFormat` audio with the given layout.
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        AudioInjector {
+            sender,
+            receiver,
+            sample_rate,
+            channels: channels.max(1),
+        }
+    }
+
+    /// Queues interleaved samples to be mixed after those pushed before.
+    pub fn push(&self, samples: Vec<TargetFormat>) {
+        if samples.is_empty() {
+            return;
+        }
+
+        // the injector holds the receiver, so the channel never disconnects
+        let _ = self.sender.send(samples);
+    }
+
+    /// Drops the audio queued and not mixed yet, e.g. to interrupt a prompt.
+    pub fn clear(&self) {
+        let dropped: usize = self.receiver.try_iter().map(|samples| samples.len()).sum();
+        tracing::debug!("Cleared {} injected samples", dropped);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+}
+
+/// Where the audio of a `MixerSession` source comes from.
+#[derive(Debug, Clone)]
+enum SourceInput {
+    Device(AudioDevice),
+    Injected(AudioInjector),
+}
+
+/// A source of a `MixerSession`.
+#[derive(Debug, Clone)]
+struct SessionSource {
+    name: String,
+    input: SourceInput,
+    gain_db: f32,
+    /// Whether the source keeps its gain when matching the loudness.
+    fixed_gain: bool,
+}
+
+/// A recording of any number of devices, e.g. two microphones and the system audio of
+/// a podcast, mixed together or delivered as separate tracks.
+///
+/// Input devices are captured as is and output devices as their system audio, and the
+/// application can push audio of its own with an `AudioInjector`. Every source is
+/// resampled to the session rate (`Recorder::set_sample_rate`, or the lowest device
+/// rate), so the sources stay aligned whatever their devices.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{AudioDevice, DeviceType, MixerSession, Recorder, SessionMode};
+///
+/// let host = AudioDevice::find("Host Mic", &[DeviceType::Input]).expect("No host mic");
+/// let guest = AudioDevice::find("Guest Mic", &[DeviceType::Input]).expect("No guest mic");
+/// let system = AudioDevice::find("Speakers", &[DeviceType::Output]).expect("No speakers");
+///
+/// let session = MixerSession::new()
+///     .source("host", host, 0.0)
+///     .source("guest", guest, 3.0)
+///     .source("system", system, -6.0)
+///     .mode(SessionMode::Tracks);
+///
+/// let mut recorder = Recorder::new();
+/// let receivers = recorder.start_session(session).expect("Failed to start session");
+/// for track in &receivers.tracks {
+///     println!("Recording {}", track.name);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MixerSession {
+    sources: Vec<SessionSource>,
+    mode: SessionMode,
+    match_loudness: bool,
+}
+
+impl MixerSession {
+    pub fn new() -> Self {
+        MixerSession::default()
+    }
+
+    /// Adds `device` as the source named `name`, amplified by `gain_db`.
+    pub fn source(mut self, name: impl Into<String>, device: AudioDevice, gain_db: f32) -> Self {
+        self.sources.push(SessionSource {
+            name: name.into(),
+            input: SourceInput::Device(device),
+            gain_db,
+            fixed_gain: false,
+        });
+        self
+    }
+
+    /// Adds the audio pushed into `injector` as the source named `name`, amplified by
+    /// `gain_db`.
+    ///
+    /// A session needs at least one device source, which sets its pace.
+    pub fn inject(
+        mut self,
+        name: impl Into<String>,
+        injector: &AudioInjector,
+        gain_db: f32,
+    ) -> Self {
+        self.sources.push(SessionSource {
+            name: name.into(),
+            input: SourceInput::Injected(injector.clone()),
+            gain_db,
+            fixed_gain: false,
+        });
+        self
+    }
+
+    /// How the sources are delivered, mixed by default.
+    pub fn mode(mut self, mode: SessionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Matches the short-term loudness of the sources in the mix, so no single source
+    /// dominates it, e.g. a guest close to their microphone.
+    ///
+    /// The loudness of every source is measured over the last few seconds, ignoring its
+    /// pauses, and its gain is adjusted slowly towards the common level, by at most
+    /// 12 dB. The `gain_db` of a source then offsets it from the common level, e.g.
+    /// 3 dB to make the host stand out. Only applies to `SessionMode::Mix`.
+    pub fn match_loudness(mut self, match_loudness: bool) -> Self {
+        self.match_loudness = match_loudness;
+        self
+    }
+
+    /// Keeps the source named `name` at its `gain_db` when matching the loudness, e.g.
+    /// a music bed meant to stay under the voices.
+    pub fn fixed_gain(mut self, name: &str) -> Self {
+        match self.sources.iter_mut().find(|source| source.name == name) {
+            Some(source) => source.fixed_gain = true,
+            None => tracing::warn!("No source named {} to fix the gain of", name),
+        }
+        self
+    }
+}
+
+/// The stream of one source of a `MixerSession`, tagged with the name of the source.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TrackReceiver {
+    pub name: String,
+    pub receiver: AudioReceiver,
+}
+
+/// The streams returned by `Recorder::start_session`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SessionReceivers {
+    /// The mix of every source, with `SessionMode::Mix`.
+    pub mix: Option<AudioReceiver>,
+    /// Every source in the order added, with `SessionMode::Tracks`.
+    pub tracks: Vec<TrackReceiver>,
+}
+
+/// A source opened for capture.
+struct OpenSource {
+    name: String,
+    device: cpal::Device,
+    device_type: DeviceType,
+    config: cpal::SupportedStreamConfig,
+    gain: f32,
+}
+
+/// Where a `Track` reads its audio from.
+enum TrackInput {
+    /// The ring buffer filled by the capture callback, already amplified.
+    Device(HeapCons<TargetFormat>),
+    /// The queue of an `AudioInjector`, amplified when read.
+    Injected(Receiver<Vec<TargetFormat>>, f32),
+}
+
+/// The audio of a source, converted to the session layout by the mixer thread.
+struct Track {
+    input: TrackInput,
+    converter: StreamConverter,
+    pending: Vec<TargetFormat>,
+    channels: usize,
+}
+
+impl Track {
+    /// Converts what the source buffered since the last call.
+    fn poll(&mut self) {
+        let data = match &mut self.input {
+            TrackInput::Device(consumer) => {
+                let mut data = vec![0.0; consumer.occupied_len()];
+                consumer.pop_slice(&mut data);
+                data
+            }
+            TrackInput::Injected(receiver, gain) => {
+                let mut data: Vec<TargetFormat> = receiver.try_iter().flatten().collect();
+                for sample in &mut data {
+                    *sample *= *gain;
+                }
+                data
+            }
+        };
+        self.pending.extend(self.converter.process(data));
+    }
+
+    /// Whether the track follows a device, rather than the pace of the devices.
+    fn is_device(&self) -> bool {
+        matches!(self.input, TrackInput::Device(_))
+    }
+
+    fn frames(&self) -> usize {
+        self.pending.len() / self.channels
+    }
+
+    /// Removes `frames` frames, padded with silence when fewer were pushed.
+    fn take(&mut self, frames: usize) -> Vec<TargetFormat> {
+        let samples = frames * self.channels;
+        let available = self.pending.len().min(samples);
+
+        let mut data: Vec<TargetFormat> = self.pending.drain(..available).collect();
+        data.resize(samples, 0.0);
+        data
+    }
+}
+
+/// Builds the stream of `source`, pushing its samples, amplified, into `producer` and
+/// waking the mixer with `wakeup`.
+fn build_source_stream<S>(
+    source: &OpenSource,
+    stream_config: &cpal::StreamConfig,
+    mut producer: HeapProd<TargetFormat>,
+    wakeup: Wakeup,
+    errors: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<SystemStream, AudioRecorderError>
+where
+    S: CustomSample + 'static,
+{
+    let gain = source.gain;
+    let name = source.name.clone();
+    let mut write = move |data: &[S]| {
+        let amplified: Vec<TargetFormat> = data
+            .iter()
+            .map(|s| s.to_sample::<TargetFormat>() * gain)
+            .collect();
+
+        let pushed = producer.push_slice(&amplified);
+        if pushed < amplified.len() {
+            tracing::debug!(
+                "Dropped {} samples of source {}",
+                amplified.len() - pushed,
+                name
+            );
+        }
+        wakeup.notify();
+    };
+
+    match source.device_type {
+        DeviceType::Input => source
+            .device
+            .build_input_stream(
+                stream_config,
+                move |data: &[S], _: &_| write(data),
+                errors,
+                None,
+            )
+            .map(SystemStream::Device)
+            .map_err(|e| {
+                tracing::error!("Failed to build stream of source {}: {}", source.name, e);
+                AudioRecorderError::BuildStream {
+                    device: source.name.clone(),
+                    source: e,
+                }
+            }),
+        DeviceType::Output => build_system_stream(
+            &source.device,
+            stream_config,
+            move |data: &[S], _| write(data),
+            errors,
+        ),
+    }
+}
+
+/// Builds the stream of `source`, dispatching on its sample format.
+fn build_stream(
+    source: &OpenSource,
+    stream_config: &cpal::StreamConfig,
+    producer: HeapProd<TargetFormat>,
+    wakeup: Wakeup,
+    errors: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<SystemStream, AudioRecorderError> {
+    match source.config.sample_format() {
+        cpal::SampleFormat::I8 => {
+            build_source_stream::<i8>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::I16 => {
+            build_source_stream::<i16>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::I32 => {
+            build_source_stream::<i32>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::U8 => {
+            build_source_stream::<u8>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::U16 => {
+            build_source_stream::<u16>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::U32 => {
+            build_source_stream::<u32>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::F32 => {
+            build_source_stream::<f32>(source, stream_config, producer, wakeup, errors)
+        }
+        cpal::SampleFormat::F64 => {
+            build_source_stream::<f64>(source, stream_config, producer, wakeup, errors)
+        }
+        other => {
+            tracing::error!("Unsupported sample format: {:?}", other);
+            Err(AudioRecorderError::UnsupportedSampleFormat {
+                device: source.name.clone(),
+                format: other,
+            })
+        }
+    }
+}
+
+/// Mixes `frames` stereo frames of every track, padding the tracks that have fewer with
+/// silence, and removes them from the tracks.
+fn mix_tracks(
+    tracks: &mut [Track],
+    frames: usize,
+    matcher: Option<&mut LoudnessMatcher>,
+) -> Vec<TargetFormat> {
+    let mut blocks: Vec<Vec<TargetFormat>> =
+        tracks.iter_mut().map(|track| track.take(frames)).collect();
+    if let Some(matcher) = matcher {
+        matcher.process(&mut blocks);
+    }
+
+    let mut mix = vec![0.0; frames * MIX_CHANNELS as usize];
+    for block in &blocks {
+        for (out, sample) in mix.iter_mut().zip(block) {
+            *out += sample;
+        }
+    }
+
+    for sample in &mut mix {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+    mix
+}
+
+impl Recorder {
+    /// Records every source of `session`, see `MixerSession`.
+    ///
+    /// `get_config` describes the mix, or the first track with `SessionMode::Tracks`.
+    /// The recording is stopped with `stop` like any other.
+    #[tracing::instrument]
+    pub fn start_session(
+        &mut self,
+        session: MixerSession,
+    ) -> Result<SessionReceivers, AudioRecorderError> {
+        tracing::info!("Starting a session of {} sources", session.sources.len());
+        self.begin()?;
+
+        let receivers = self.record_session(session);
+        if receivers.is_err() {
+            self.stop();
+        }
+        receivers
+    }
+
+    fn record_session(
+        &mut self,
+        session: MixerSession,
+    ) -> Result<SessionReceivers, AudioRecorderError> {
+        // the device sources, and every source in the order added with its injector
+        let mut sources = Vec::with_capacity(session.sources.len());
+        let mut inputs = Vec::with_capacity(session.sources.len());
+        // the manual gains the loudness is matched around
+        let offsets_db: Vec<Option<f32>> = session
+            .sources
+            .iter()
+            .map(|source| match source.fixed_gain {
+                true => None,
+                false => Some(source.gain_db),
+            })
+            .collect();
+        for source in session.sources {
+            let gain = db_to_linear(source.gain_db);
+            let info = match source.input {
+                SourceInput::Device(info) => info,
+                SourceInput::Injected(injector) => {
+                    tracing::debug!(
+                        "Source {}: injected at {} Hz {}ch",
+                        source.name,
+                        injector.sample_rate(),
+                        injector.channels()
+                    );
+                    inputs.push((source.name, Some((injector, gain))));
+                    continue;
+                }
+            };
+
+            let device = info.open()?;
+            let config = match info.device_type {
+                DeviceType::Input => self.input_config(&device)?,
+                DeviceType::Output => self.output_config(&device)?,
+            };
+            tracing::debug!(
+                "Source {}: {:?} at {} Hz {}ch",
+                source.name,
+                info.name,
+                config.sample_rate().0,
+                config.channels()
+            );
+
+            sources.push(OpenSource {
+                name: source.name.clone(),
+                device,
+                device_type: info.device_type,
+                config,
+                gain,
+            });
+            inputs.push((source.name, None));
+        }
+
+        if sources.is_empty() {
+            tracing::error!("A mixer session needs at least one device source");
+            return Err(AudioRecorderError::InvalidArgument(
+                "A mixer session needs at least one device source",
+            ));
+        }
+
+        let target_rate = match self.sample_rate {
+            Some(rate) => rate,
+            None => sources
+                .iter()
+                .map(|source| source.config.sample_rate().0)
+                .min()
+                .unwrap_or_default(),
+        };
+
+        let mut tracks = Vec::with_capacity(inputs.len());
+        let mut producers = Vec::with_capacity(sources.len());
+        let mut devices = sources.iter();
+        for (_, injected) in &inputs {
+            let (rate, channels, input) = match injected {
+                Some((injector, gain)) => (
+                    injector.sample_rate(),
+                    injector.channels(),
+                    TrackInput::Injected(injector.receiver.clone(), *gain),
+                ),
+                None => {
+                    let config = match devices.next() {
+                        Some(source) => &source.config,
+                        None => continue,
+                    };
+                    let (rate, channels) = (config.sample_rate().0, config.channels());
+
+                    // two seconds of capture between the callback and the mixer thread
+                    let (producer, consumer) =
+                        HeapRb::<TargetFormat>::new(rate as usize * channels as usize * 2).split();
+                    producers.push(producer);
+                    (rate, channels, TrackInput::Device(consumer))
+                }
+            };
+
+            let track_channels = match session.mode {
+                SessionMode::Mix => MIX_CHANNELS,
+                SessionMode::Tracks => channels,
+            };
+            tracks.push(Track {
+                input,
+                converter: StreamConverter::with_quality(
+                    rate,
+                    channels,
+                    target_rate,
+                    track_channels,
+                    self.config.resampler_quality(),
+                    self.config.resampler_chunk_size(),
+                )?,
+                pending: Vec::new(),
+                channels: track_channels as usize,
+            });
+        }
+
+        tracing::debug!("Setting up the recorder");
+        self.target_sample_rate = Some(target_rate);
+        self.channels = Some(match session.mode {
+            SessionMode::Mix => MIX_CHANNELS,
+            SessionMode::Tracks => tracks[0].channels as u16,
+        });
+        self.sample_size = Some(sources[0].config.sample_format().sample_size() as u32);
+        self.apply_standard_output();
+        tracing::debug!("Config: {:?}", self);
+
+        let mut receivers = SessionReceivers {
+            mix: None,
+            tracks: Vec::new(),
+        };
+        let mut senders: Vec<ChunkSender> = Vec::new();
+        match session.mode {
+            SessionMode::Mix => {
+                let (sender, receiver) = self.data_channel(target_rate, MIX_CHANNELS)?;
+                senders.push(sender);
+                receivers.mix = Some(receiver);
+            }
+            SessionMode::Tracks => {
+                for ((name, _), track) in inputs.into_iter().zip(&tracks) {
+                    let (sender, receiver) =
+                        self.data_channel(target_rate, track.channels as u16)?;
+                    senders.push(sender);
+                    receivers.tracks.push(TrackReceiver { name, receiver });
+                }
+            }
+        }
+
+        let stream_configs: Vec<cpal::StreamConfig> = sources
+            .iter()
+            .map(|source| self.stream_config(&source.config))
+            .collect();
+        let mode = session.mode;
+        let mut matcher = match (session.match_loudness, mode) {
+            (true, SessionMode::Mix) => {
+                tracing::debug!("Matching the loudness of the sources");
+                Some(LoudnessMatcher::new(target_rate, MIX_CHANNELS, &offsets_db))
+            }
+            (true, _) => {
+                tracing::warn!("Loudness matching only applies to a mix, ignoring it");
+                None
+            }
+            (false, _) => None,
+        };
+        let mix_frames = match self.config.mix_chunk() {
+            Some(d) => ((d.as_secs_f64() * target_rate as f64) as usize).max(1),
+            None => target_rate as usize / 10,
+        }
+        .max(1);
+        // a source lagging this far behind is mixed as silence instead of holding the mix
+        let max_skew_frames = target_rate as usize;
+        let poll_interval = self.config.poll_interval();
+        // woken by every device callback, the injected sources follow their pace
+        let wakeup = Wakeup::default();
+        let recording_signal = self.recording_signal.clone();
+        let flush_signal = self.flush_signal.clone();
+        let errors = self.stream_errors(AudioSource::Input);
+
+        let (startup, started) = worker::startup();
+        let mut startup = Some(startup);
+
+        tracing::debug!("Spawning session mixer thread...");
+        let workers = self.workers();
+        self.workers().spawn("arec-session", move || {
+            let mut streams = Vec::with_capacity(sources.len());
+            for ((source, config), producer) in sources.iter().zip(&stream_configs).zip(producers) {
+                match build_stream(source, config, producer, wakeup.clone(), errors.clone()) {
+                    Ok(stream) => streams.push(stream),
+                    Err(e) => {
+                        workers.fail("arec-session", &mut startup, e);
+                        return;
+                    }
+                }
+            }
+
+            for (source, stream) in sources.iter().zip(&streams) {
+                if let Err(e) = stream.play() {
+                    tracing::error!("Failed to play stream of source {}: {}", source.name, e);
+                    workers.fail(
+                        "arec-session",
+                        &mut startup,
+                        AudioRecorderError::PlayStream {
+                            device: source.name.clone(),
+                            source: e,
+                        },
+                    );
+                    return;
+                }
+            }
+
+            if let Some(startup) = startup.take() {
+                startup.ready();
+            }
+
+            let mut deliver =
+                |tracks: &mut [Track], senders: &mut [ChunkSender], last: bool| match mode {
+                    SessionMode::Mix => {
+                        // the injected tracks follow the pace of the devices
+                        let devices = || tracks.iter().filter(|track| track.is_device());
+                        let ready = devices().map(Track::frames).min().unwrap_or_default();
+                        let ahead = devices().map(Track::frames).max().unwrap_or_default();
+
+                        let frames = match (last, ready >= mix_frames) {
+                            (true, _) => ahead,
+                            (false, true) => ready,
+                            (false, false) if ahead >= mix_frames + max_skew_frames => mix_frames,
+                            (false, false) => return,
+                        };
+                        if frames == 0 {
+                            return;
+                        }
+
+                        let mix = mix_tracks(tracks, frames, matcher.as_mut());
+                        if let Err(e) = senders[0].send(mix) {
+                            tracing::error!("Failed to send data: {}", e);
+                        }
+                    }
+                    SessionMode::Tracks => {
+                        // the injected tracks are padded to stay aligned with the devices
+                        let frames = tracks
+                            .iter()
+                            .filter(|track| track.is_device())
+                            .map(Track::frames)
+                            .max()
+                            .unwrap_or_default();
+
+                        for (track, sender) in tracks.iter_mut().zip(senders.iter_mut()) {
+                            let data = match track.is_device() {
+                                true => std::mem::take(&mut track.pending),
+                                false => track.take(frames),
+                            };
+                            if data.is_empty() {
+                                continue;
+                            }
+
+                            if let Err(e) = sender.send(data) {
+                                tracing::error!("Failed to send data: {}", e);
+                            }
+                        }
+                    }
+                };
+
+            while recording_signal.load(Ordering::SeqCst) {
+                for track in &mut tracks {
+                    track.poll();
+                }
+                deliver(&mut tracks, &mut senders, false);
+
+                wakeup.wait(poll_interval);
+            }
+
+            tracing::debug!("Pausing streams");
+            for stream in &streams {
+                if let Err(e) = stream.pause() {
+                    tracing::error!("Failed to pause session stream: {}", e);
+                }
+            }
+
+            if flush_signal.load(Ordering::SeqCst) {
+                tracing::debug!("Flushing the session");
+                for track in &mut tracks {
+                    track.poll();
+                    let tail = track.converter.flush();
+                    track.pending.extend(tail);
+                }
+                deliver(&mut tracks, &mut senders, true);
+            }
+
+            if let Some(matcher) = &matcher {
+                tracing::debug!("Loudness corrections: {:?} dB", matcher.gains_db());
+            }
+
+            tracing::debug!("Dropping streams");
+            drop(streams);
+            tracing::info!("Session stopped");
+        })?;
+
+        started.wait()?;
+
+        Ok(receivers)
+    }
+}
@@ -1,25 +1,110 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
-use constants::TargetFormat;
+use buffer_occupancy::OccupancyCounters;
+use callback_stats::CallbackCounters;
 use cpal::traits::DeviceTrait;
-use crossbeam_channel::Receiver;
-use errors::AudioRecorderError;
-use get_default_device::{get_default_input_device, get_default_output_device};
+use events::EventBus;
+use get_default_device::{
+    get_default_input_device, get_default_output_device, get_host_input_device,
+};
+use handoff::HandoffPoint;
+use level_histogram::LevelCounters;
+use processor::ProcessorChain;
+use shared::SharedF32;
+use wakeup::Wakeup;
+use worker::WorkerHandles;
+
+/// Module for the chunks cut at the pauses of the speech.
+mod adaptive_chunking;
+
+/// Module for the automatic gain control of the microphone.
+mod auto_gain;
+
+/// Module for the fill level of the ring buffers.
+mod buffer_occupancy;
+
+/// Module for the statistics of the capture callbacks.
+mod callback_stats;
+
+/// Module for the input level calibration routine.
+mod calibration;
+
+/// Module for synchronizing the recording to an external clock.
+mod clock;
+
+/// Module for the clock the delivered chunks are timestamped with.
+mod clock_source;
+
+/// Module for the handle sharing the capture clock with other media.
+mod capture_clock;
+
+/// Module for the CoreAudio aggregate device of a microphone and a loopback device.
+#[cfg(target_os = "macos")]
+mod aggregate_device;
+
+/// Module for the builder-style configuration of a recording.
+mod config;
+
+/// Module for the per-thread COM initialization needed by WASAPI.
+mod com;
 
 /// Module for handling constants used in the audio recorder.
 mod constants;
 
+/// Module for the signal processing building blocks.
+mod dsp;
+
+/// Module for the graceful degradation of a recording that cannot keep up.
+mod degradation;
+
+/// Module for following the default devices while recording.
+mod device_monitor;
+
+/// Module for the cache of the enumerated devices.
+mod device_registry;
+
+/// Module for cancelling the echo of the speakers in the microphone.
+mod echo_cancellation;
+
+/// Module for enumerating the devices of every available host.
+mod devices;
+
+/// Module for the speaker and microphone tests of the devices.
+mod device_test;
+
+/// Module for the noise profile removed from the input stream.
+mod noise_profile;
+
 /// Module for error handling in the audio recorder.
 mod errors;
+/// Module for exporting recorded segments.
+mod export;
+
 /// Module for handling the default device i/o selection.
 mod get_default_device;
 
+/// Module for the gain applied to the captured streams.
+mod gain;
+
+/// Module for handing a running recording over to another recorder.
+mod handoff;
+
 /// Helper functions for the recorder module.
 mod helpers;
 
+/// Module for the high-pass filter of the microphone.
+mod high_pass;
+
+/// Module for recording any number of devices in one session.
+mod mixer_session;
+
 /// Module for handling recording with a resampler.
 mod multiple_w_resampler;
 
@@ -32,11 +117,201 @@ mod record_multiple_spawner;
 /// Module for recording from a single device.
 mod record_single_device;
 
+/// Module for recording both devices as separate streams.
+mod record_separate;
+
+/// Module for finding the PulseAudio/PipeWire monitor source on Linux.
+#[cfg(target_os = "linux")]
+mod linux_utils;
+
+/// Module for capturing a PulseAudio/PipeWire monitor source with `parec` on Linux.
+#[cfg(target_os = "linux")]
+mod pulse_monitor;
+
+/// Module for the latency padding between the captured streams.
+mod latency;
+
+/// Module for the level histograms of the sources.
+mod level_histogram;
+
+/// Module for the limiter protecting the delivered stream from clipping.
+mod limiter;
+
+/// Module for combining the captured streams.
+mod mix;
+
+/// Module for negotiating the stream configs used for capture.
+mod negotiate_config;
+
+/// Module for the recording quotas.
+mod quota;
+
+/// Module for the custom processors of the delivered stream.
+mod processor;
+
+/// Module for changing the default devices and restoring them.
+mod routing;
+
+/// Module for driving the resampler between ring buffers.
+mod resampler_driver;
+
+/// Module for teeing raw source streams to disk for debugging.
+mod raw_capture;
+
+/// Module for values shared with the capture callbacks.
+mod shared;
+
+/// Module for the silence-gated recording mode.
+mod silence_gate;
+
+/// Module for the stream capturing the system audio.
+mod system_stream;
+
+/// Module for the WASAPI loopback capture of the default render endpoint.
+#[cfg(target_os = "windows")]
+mod wasapi_loopback;
+
+/// Module for the privacy permissions needed to capture audio.
+mod permissions;
+
+/// Module for the per-source processing done in the capture callbacks.
+mod source_tap;
+
+/// Module for standardizing the layout of the delivered stream.
+mod convert;
+
+/// Module for the pre-flight validation of the recorder settings.
+mod validate;
+
+/// Module for the events reported while recording.
+mod events;
+
+/// Module for detecting recordings that stopped delivering audio.
+mod watchdog;
+
+/// Module for waking the worker threads when there is work.
+mod wakeup;
+
+/// Module for spawning the worker threads of a recording.
+mod worker;
+
+/// Module for the chunks delivered on the data channel.
+mod chunk;
+
+/// Module for the serialization of chunks shipped between processes.
+#[cfg(feature = "serde")]
+mod chunk_codec;
+
+/// Module for capturing in a child process.
+#[cfg(feature = "helper")]
+mod capture_helper;
+
+/// Module for the annotations of the recording timeline.
+mod annotations;
+
+/// Module for the backpressure policy of the bounded data channel.
+mod backpressure;
+
+/// Module for the analyzers observing the delivered stream.
+mod analysis;
+
+/// Module for the sending side of the data channel.
+mod delivery;
+
+/// Module for the receiver wrapping the recorded stream.
+mod receiver;
+
+/// Module for the typestate builder of recordings with a known layout.
+mod typed_session;
+
+/// Module for sinks consuming the recorded stream.
+mod sink;
+
+/// Module for consuming the recorded stream from async code.
+#[cfg(feature = "async")]
+mod stream;
+
+/// Module for decoding audio files.
+#[cfg(feature = "decode")]
+mod decode;
+
+/// Module for encoding the recorded stream to compressed formats.
+#[cfg(any(feature = "aac", feature = "mp3", feature = "opus"))]
+mod encoder;
+
+pub use adaptive_chunking::AdaptiveChunking;
+#[cfg(target_os = "macos")]
+pub use aggregate_device::AggregateDevice;
+pub use analysis::{ChunkFeatures, PitchEstimate};
+pub use annotations::{Annotation, AnnotationKind, AnnotationLog};
+pub use auto_gain::AutoGainControl;
+pub use backpressure::BackpressurePolicy;
+pub use buffer_occupancy::BufferOccupancy;
+pub use calibration::{AlignmentReport, CalibrationReport};
+pub use callback_stats::CallbackStats;
+pub use capture_clock::CaptureClock;
+#[cfg(feature = "helper")]
+pub use capture_helper::{CaptureHelper, HelperSettings, run_capture_helper};
+pub use chunk::AudioChunk;
+#[cfg(feature = "serde")]
+pub use chunk_codec::{read_chunk, write_chunk};
+pub use clock::ExternalClock;
+pub use clock_source::{ClockMapping, ClockSource};
+pub use config::{LatencyMode, RecorderConfig, RecorderConfigBuilder, ResamplerQuality};
+pub use constants::{AudioSource, TargetFormat};
+#[cfg(feature = "decode")]
+pub use decode::{DecodedAudio, decode_file, decode_to_wav};
+pub use degradation::DegradationStep;
+pub use delivery::StreamSummary;
+pub use device_registry::DeviceRegistry;
+pub use device_test::InputLevel;
+pub use devices::{AudioDevice, DeviceConfig, DeviceType};
+pub use dsp::{classifier::AudioClass, vad::VadMode};
+#[cfg(feature = "aac")]
+pub use encoder::{AacSettings, AacSink};
+#[cfg(feature = "mp3")]
+pub use encoder::{Mp3Settings, Mp3Sink};
+#[cfg(feature = "opus")]
+pub use encoder::{OpusEncoderSink, OpusSettings};
+pub use errors::AudioRecorderError;
+pub use events::RecorderEvent;
+pub use export::stitch_wav_segments;
+pub use helpers::{Config, RateConversion};
+pub use high_pass::HighPassFilter;
+pub use latency::LatencyPadding;
+pub use level_histogram::LevelHistogram;
+pub use limiter::SoftLimiter;
+pub use mix::{ChannelMode, MixMode};
+pub use mixer_session::{
+    AudioInjector, MixerSession, SessionMode, SessionReceivers, TrackReceiver,
+};
+pub use noise_profile::NoiseProfile;
+pub use permissions::Permission;
+pub use processor::AudioProcessor;
+pub use quota::RecordingQuota;
+pub use receiver::{AudioReceiver, AudioRecvError};
+pub use record_separate::SourceReceivers;
+pub use silence_gate::SilenceGate;
+pub use sink::{
+    MemorySink, PipeFrame, PipeSink, PullReader, PunchFill, SinkOutcome, SinkPolicy, SyncPolicy,
+    WavRotation, WavSink, WriterStats, read_pipe_frame,
+};
+#[cfg(feature = "async")]
+pub use stream::AudioStream;
+pub use typed_session::{
+    CaptureBuilder, ChunkLayout, DualMonoLayout, MonoLayout, TypedChunk, TypedReceiver,
+    TypedSession,
+};
+pub use validate::ValidationIssue;
+pub use worker::ShutdownReport;
+
 /// Expands to the correct `self.record_multiple::<In, Out>(…)` call
 /// for every (input, output) sample-format pair.
 ///
 /// Usage:
 /// `record_multiple_expansion!(self, input_config, output_config, input_device, output_device)`
+///
+/// The configs and devices are moved into the selected call.
 macro_rules! record_multiple_expansion {
     // ── entry point ───────────────────────────────────────────────────────────
     ($self_:expr, $in_cfg:expr, $out_cfg:expr, $in_dev:expr, $out_dev:expr) => {{
@@ -45,38 +320,39 @@ macro_rules! record_multiple_expansion {
             ($in_ty:ty) => {{
                 match $out_cfg.sample_format() {
                     cpal::SampleFormat::I8 => {
-                        $self_.record_multiple::<$in_ty, i8>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, i8>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::I16 => {
-                        $self_.record_multiple::<$in_ty, i16>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, i16>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::I32 => {
-                        $self_.record_multiple::<$in_ty, i32>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, i32>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::I64 => {
-                        $self_.record_multiple::<$in_ty, i64>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, i64>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::U8 => {
-                        $self_.record_multiple::<$in_ty, u8>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, u8>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::U16 => {
-                        $self_.record_multiple::<$in_ty, u16>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, u16>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::U32 => {
-                        $self_.record_multiple::<$in_ty, u32>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, u32>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::U64 => {
-                        $self_.record_multiple::<$in_ty, u64>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, u64>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::F32 => {
-                        $self_.record_multiple::<$in_ty, f32>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, f32>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
                     cpal::SampleFormat::F64 => {
-                        $self_.record_multiple::<$in_ty, f64>($in_dev, $out_dev)
+                        $self_.record_multiple::<$in_ty, f64>($in_dev, $out_dev, $in_cfg, $out_cfg)
                     }
-                    sf => Err(AudioRecorderError::SignalError(format!(
-                        "Unsupported sample format '{sf:?}'"
-                    ))),
+                    sf => Err(AudioRecorderError::UnsupportedSampleFormat {
+                        device: errors::device_name(&$out_dev),
+                        format: sf,
+                    }),
                 }
             }};
         }
@@ -93,13 +369,25 @@ macro_rules! record_multiple_expansion {
             cpal::SampleFormat::U64 => match_output!(u64),
             cpal::SampleFormat::F32 => match_output!(f32),
             cpal::SampleFormat::F64 => match_output!(f64),
-            sf => Err(AudioRecorderError::SignalError(format!(
-                "Unsupported sample format '{sf:?}'"
-            ))),
+            sf => Err(AudioRecorderError::UnsupportedSampleFormat {
+                device: errors::device_name(&$in_dev),
+                format: sf,
+            }),
         }
     }};
 }
 
+/// The devices a recording starts from, see `Recorder::start_from`.
+#[derive(Debug, Clone, Copy)]
+enum DeviceSelection<'a> {
+    /// The devices of the config, or the default ones.
+    Config,
+    /// A picked input device, recorded on its own.
+    Input(&'a AudioDevice),
+    /// A picked input device mixed with a picked system audio device.
+    Pair(&'a AudioDevice, &'a AudioDevice),
+}
+
 /// A recorder for recording audio - It should be consumed using a singleton pattern
 #[derive(Debug)]
 pub struct Recorder {
@@ -111,16 +399,190 @@ pub struct Recorder {
     channels: Option<u16>,
     /// The sample size for recording.
     sample_size: Option<u32>,
+    /// Directory where the raw per-source streams are teed, if enabled.
+    raw_capture_dir: Option<PathBuf>,
+    /// Whether `f32` stream configs are requested from the devices.
+    prefer_f32: bool,
+    /// Gain applied to the input stream, shared with the capture callbacks.
+    input_gain: SharedF32,
+    /// The input gain in dB, as set by the user.
+    input_gain_db: f32,
+    /// Gain applied to the output stream, shared with the capture callbacks.
+    output_gain: SharedF32,
+    /// The output gain in dB, as set by the user.
+    output_gain_db: f32,
+    /// Noise floor estimate of the input stream, `NaN` until estimated.
+    input_noise_floor_db: SharedF32,
+    /// Noise floor estimate of the output stream, `NaN` until estimated.
+    output_noise_floor_db: SharedF32,
+    /// Statistics of the input capture callbacks.
+    input_callbacks: CallbackCounters,
+    /// Statistics of the output capture callbacks.
+    output_callbacks: CallbackCounters,
+    /// Occupancy of the ring buffer of the input stream.
+    input_buffer: OccupancyCounters,
+    /// Occupancy of the ring buffer of the output stream.
+    output_buffer: OccupancyCounters,
+    /// Level histogram of the input stream.
+    input_levels: LevelCounters,
+    /// Level histogram of the output stream.
+    output_levels: LevelCounters,
+    /// How the input and output streams are combined.
+    mix_mode: MixMode,
+    /// Channel layout of the delivered stream when recording from both devices.
+    channel_mode: ChannelMode,
+    /// Silence inserted ahead of the system audio when no resampling is needed.
+    latency_padding: LatencyPadding,
+    /// Offset of the system audio against the microphone, see `set_alignment_offset_ms`.
+    alignment_offset_ms: i32,
+    /// Whether the delivered stream is standardized to 48 kHz stereo.
+    standard_output: bool,
+    /// Channel count the delivered stream is remixed to, if chosen by the user.
+    output_channels: Option<u16>,
+    /// Sample rate every source is resampled to, if chosen by the user.
+    sample_rate: Option<u32>,
+    /// How the input stream of the current recording is resampled, if it is.
+    input_resampling: Option<RateConversion>,
+    /// How the system audio stream of the current recording is resampled, if it is.
+    output_resampling: Option<RateConversion>,
+    /// Subscribers to the recorder events.
+    events: EventBus,
+    /// Duration of audio in each delivered chunk, if paced.
+    chunk_duration: Option<Duration>,
+    /// Whether an acoustic fingerprint of the recording is computed.
+    fingerprint: bool,
+    /// Whether the delivered stream is classified as speech, music or noise.
+    classification: bool,
+    /// Subscribers to the pitch estimates.
+    pitch: EventBus<PitchEstimate>,
+    /// Config of the current (or last) recording.
+    config: RecorderConfig,
+    /// Delivery timeout after which the recording is reported as stalled.
+    watchdog: Option<Duration>,
+    /// Whether a stalled recording is stopped.
+    watchdog_stop: bool,
+    /// Whether the default devices are followed while recording.
+    device_monitor: bool,
+    /// Subscribers to the per-chunk features of the delivered stream.
+    features: EventBus<ChunkFeatures>,
+    /// Whether the buffered audio is delivered once the recording stops.
+    flush_signal: Arc<AtomicBool>,
+    /// Wakes the capture thread waiting for the recording to stop.
+    stop_wakeup: Wakeup,
+    /// Noise removed from the input stream by spectral subtraction.
+    noise_profile: Option<NoiseProfile>,
+    /// Automatic gain control of the input stream, if enabled.
+    auto_gain: Option<AutoGainControl>,
+    /// High-pass filter of the microphone, if enabled.
+    high_pass: Option<HighPassFilter>,
+    /// Join handles of the worker threads, see `stop_blocking`.
+    worker_handles: WorkerHandles,
+    /// Summary of the recording the next one continues, see `set_resume_from`.
+    resume_from: Option<StreamSummary>,
+    /// Where the running recording can be handed off, see `hand_off`.
+    handoff: Mutex<Option<HandoffPoint>>,
+    /// How the delivered chunks are annotated with speech, if at all.
+    voice_detection: Option<VadMode>,
+    /// Threshold below which the audio is not delivered, if enabled.
+    silence_gate: Option<SilenceGate>,
+    /// How the delivered chunks are cut at the pauses of the speech, if they are.
+    adaptive_chunking: Option<AdaptiveChunking>,
+    /// Clock the delivered chunks are timestamped with, if any.
+    external_clock: Option<ExternalClock>,
+    /// Clock of `AudioChunk::time`.
+    clock_source: ClockSource,
+    /// Latest reading of the clocks of the recording, see `get_clock_mapping`.
+    clock_mapping: Arc<Mutex<Option<ClockMapping>>>,
+    /// Limits of the delivered stream, see `set_quota`.
+    quota: Option<RecordingQuota>,
+    /// Limiter of the delivered stream, if enabled.
+    limiter: Option<SoftLimiter>,
+    /// Longest echo cancelled from the microphone, if enabled.
+    echo_tail: Option<Duration>,
+    /// Steps taken, in order, when the recording cannot keep up.
+    degradation: Vec<DegradationStep>,
+    /// Custom processors of the delivered chunks, see `add_processor`.
+    processors: ProcessorChain,
+    /// Raised once the resamplers should switch to `ResamplerQuality::Fast`.
+    resampler_degraded: Arc<AtomicBool>,
+    /// Chunks the data channel holds at most, `None` when unbounded.
+    channel_capacity: Option<usize>,
+    /// What happens to the chunks delivered while the data channel is full.
+    backpressure: BackpressurePolicy,
+    /// Chunks dropped by the backpressure policy in the current (or last) recording.
+    dropped_chunks: Arc<AtomicU64>,
+    /// Annotations of the current (or last) recording, see `annotations`.
+    annotations: AnnotationLog,
 }
 
 impl Recorder {
     /// Creates a new instance of the Recorder.
+    ///
+    /// The system routing is left untouched, call `Recorder::restore_orphaned_routing`
+    /// at startup to restore the default devices left changed by a crashed process.
     pub fn new() -> Self {
         Recorder {
             recording_signal: Arc::new(AtomicBool::new(false)),
             target_sample_rate: None,
             channels: None,
             sample_size: None,
+            raw_capture_dir: None,
+            prefer_f32: false,
+            input_gain: SharedF32::new(1.0),
+            input_gain_db: 0.0,
+            output_gain: SharedF32::new(1.0),
+            output_gain_db: 0.0,
+            input_noise_floor_db: SharedF32::new(f32::NAN),
+            output_noise_floor_db: SharedF32::new(f32::NAN),
+            input_callbacks: CallbackCounters::default(),
+            output_callbacks: CallbackCounters::default(),
+            input_buffer: OccupancyCounters::default(),
+            output_buffer: OccupancyCounters::default(),
+            input_levels: LevelCounters::default(),
+            output_levels: LevelCounters::default(),
+            mix_mode: MixMode::default(),
+            channel_mode: ChannelMode::default(),
+            latency_padding: LatencyPadding::default(),
+            alignment_offset_ms: 0,
+            standard_output: false,
+            output_channels: None,
+            sample_rate: None,
+            input_resampling: None,
+            output_resampling: None,
+            events: EventBus::default(),
+            chunk_duration: None,
+            fingerprint: false,
+            classification: false,
+            pitch: EventBus::default(),
+            config: RecorderConfig::default(),
+            watchdog: None,
+            watchdog_stop: false,
+            device_monitor: false,
+            features: EventBus::default(),
+            flush_signal: Arc::new(AtomicBool::new(false)),
+            stop_wakeup: Wakeup::default(),
+            noise_profile: None,
+            auto_gain: None,
+            high_pass: None,
+            worker_handles: WorkerHandles::default(),
+            resume_from: None,
+            handoff: Mutex::new(None),
+            voice_detection: None,
+            silence_gate: None,
+            adaptive_chunking: None,
+            external_clock: None,
+            clock_source: ClockSource::default(),
+            clock_mapping: Arc::new(Mutex::new(None)),
+            quota: None,
+            limiter: None,
+            echo_tail: None,
+            degradation: Vec::new(),
+            processors: ProcessorChain::default(),
+            resampler_degraded: Arc::new(AtomicBool::new(false)),
+            channel_capacity: None,
+            backpressure: BackpressurePolicy::default(),
+            dropped_chunks: Arc::new(AtomicU64::new(0)),
+            annotations: AnnotationLog::default(),
         }
     }
 
@@ -136,29 +598,128 @@ impl Recorder {
 
         tracing::debug!("Resetting recording signal");
         self.recording_signal.store(false, Ordering::SeqCst);
+        self.stop_wakeup.notify();
         tracing::info!("Recorder stopped successfully");
     }
 
+    /// Stops the recording like `stop`, but delivers the audio still buffered in the
+    /// mixer and the resamplers (up to about half a second) as the last chunks before the
+    /// end marker, instead of dropping it.
     #[tracing::instrument]
-    pub fn start(
-        &mut self,
-        input_only: bool,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError> {
-        tracing::info!("Starting audio recording");
+    pub fn stop_and_flush(&mut self) {
+        tracing::info!("Stopping the recorder, flushing the buffered audio");
+        self.flush_signal.store(true, Ordering::SeqCst);
+        self.stop();
+    }
 
+    /// Raises the recording flag, failing if a recording is already in progress.
+    pub fn begin(&mut self) -> Result<(), AudioRecorderError> {
         tracing::debug!("Checking if recording is already in progress");
         if self.recording_signal.load(Ordering::SeqCst) {
             tracing::warn!("Recording is already in progress");
             return Err(AudioRecorderError::RecordingInProgress);
         }
 
+        worker::prune(&self.worker_handles);
+
         tracing::debug!("Initializing flag for recording");
         self.recording_signal.store(true, Ordering::SeqCst);
+        self.flush_signal.store(false, Ordering::SeqCst);
         self.target_sample_rate = None;
         self.channels = None;
         self.sample_size = None;
+        // sessions capture without taps, and must not report the last recording's levels
+        self.input_levels.reset();
+        self.output_levels.reset();
+        self.dropped_chunks.store(0, Ordering::Relaxed);
+        self.annotations.clear();
+        Ok(())
+    }
+
+    /// Starts recording with the given config, see `RecorderConfig::builder`.
+    #[tracing::instrument]
+    pub fn start(&mut self, config: RecorderConfig) -> Result<AudioReceiver, AudioRecorderError> {
+        tracing::info!("Starting audio recording");
+        self.start_from(Some(config), DeviceSelection::Config)
+    }
+
+    /// Starts recording from a specific input device, e.g. a microphone or a monitor
+    /// source picked from `AudioDevice::list_all`, with the config of the last recording.
+    ///
+    /// The device is opened and its input config resolved before any capture thread is
+    /// spawned, so a device that disappeared or cannot capture is reported immediately.
+    #[tracing::instrument]
+    pub fn start_with_device(
+        &mut self,
+        device: &AudioDevice,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
+        tracing::info!("Starting audio recording from {:?}", device.name);
+        self.start_from(None, DeviceSelection::Input(device))
+    }
 
-        let input_device = match get_default_input_device() {
+    /// Starts recording from a specific input device mixed with a specific system audio
+    /// device, with the config of the last recording.
+    ///
+    /// `output` may be an output device captured in loopback mode or an input device such
+    /// as a monitor source. Both devices are validated before any capture thread is
+    /// spawned.
+    #[tracing::instrument]
+    pub fn start_with_devices(
+        &mut self,
+        input: &AudioDevice,
+        output: &AudioDevice,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
+        tracing::info!(
+            "Starting audio recording from {:?} and {:?}",
+            input.name,
+            output.name
+        );
+        self.start_from(None, DeviceSelection::Pair(input, output))
+    }
+
+    /// Starts a recording from `devices`, applying `config` first if given.
+    ///
+    /// The default devices are only followed when the config names no device or host
+    /// and none was picked.
+    fn start_from(
+        &mut self,
+        config: Option<RecorderConfig>,
+        devices: DeviceSelection,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
+        self.begin()?;
+        if let Some(config) = config {
+            self.apply_config(config);
+        }
+
+        let input_only = match devices {
+            DeviceSelection::Config => self.config.input_only(),
+            DeviceSelection::Input(_) => true,
+            DeviceSelection::Pair(..) => false,
+        };
+        let follows_defaults = matches!(devices, DeviceSelection::Config)
+            && self.config.input_device().is_none()
+            && self.config.host().is_none();
+
+        // a picked system audio device is opened like a named one, without ScreenCaptureKit
+        let picked_output = matches!(devices, DeviceSelection::Pair(..));
+        if let Err(e) = self.check_permissions(input_only || picked_output) {
+            self.stop();
+            return Err(e);
+        }
+
+        let input_device = match devices {
+            DeviceSelection::Config => match self.config.input_device() {
+                Some(name) => AudioDevice::find(name, &[DeviceType::Input]).and_then(|d| d.open()),
+                None => match self.config.host() {
+                    Some(host) => get_host_input_device(host),
+                    None => get_default_input_device(),
+                },
+            },
+            DeviceSelection::Input(device) | DeviceSelection::Pair(device, _) => {
+                self.picked_input_device(device)
+            }
+        };
+        let input_device = match input_device {
             Ok(device) => device,
             Err(e) => {
                 tracing::error!("{}", e);
@@ -169,17 +730,108 @@ impl Recorder {
 
         if input_only {
             tracing::info!("Recording from a single device");
-            return self.record_single_device(input_device);
+            let monitor = match follows_defaults {
+                true => self.device_monitor(AudioSource::Input),
+                false => None,
+            };
+            return self.record_single_device(input_device, monitor);
         }
 
-        let output_device = match get_default_output_device() {
-            Ok(device) => device,
+        let output = match devices {
+            DeviceSelection::Pair(_, output) => self.system_device(output),
+            _ => match self.config.output_device().map(str::to_string) {
+                Some(name) => self.named_system_device(&name),
+                None => get_default_output_device().and_then(|device| {
+                    let config = self.output_config(&device)?;
+                    Ok((device, config))
+                }),
+            },
+        };
+        let (output_device, output_config) = match output {
+            Ok(output) => output,
             Err(e) => {
                 tracing::error!("{}", e);
                 self.stop();
                 return Err(e);
             }
         };
+
+        let receiver = self.record_devices(input_device, output_device, output_config)?;
+
+        if follows_defaults && self.config.output_device().is_none() {
+            if let Err(e) = self.watch_devices() {
+                self.stop();
+                return Err(e);
+            }
+        }
+
+        Ok(receiver)
+    }
+
+    /// Opens a picked input device, resolving its input config so that a device that
+    /// cannot capture fails before the recording starts.
+    fn picked_input_device(
+        &self,
+        device: &AudioDevice,
+    ) -> Result<cpal::Device, AudioRecorderError> {
+        if device.device_type != DeviceType::Input {
+            tracing::error!("Device {:?} is not an input device", device.name);
+            return Err(AudioRecorderError::NotAnInputDevice {
+                device: device.name.clone(),
+            });
+        }
+
+        let input_device = device.open()?;
+        self.input_config(&input_device)?;
+        Ok(input_device)
+    }
+
+    /// Opens the system audio device with the given name, preferring output devices
+    /// (captured in loopback mode) over input devices such as monitor sources.
+    fn named_system_device(
+        &self,
+        name: &str,
+    ) -> Result<(cpal::Device, cpal::SupportedStreamConfig), AudioRecorderError> {
+        let device = AudioDevice::find(name, &[DeviceType::Output, DeviceType::Input])?;
+        self.system_device(&device)
+    }
+
+    /// Opens a system audio device, resolving the config of its type.
+    fn system_device(
+        &self,
+        device: &AudioDevice,
+    ) -> Result<(cpal::Device, cpal::SupportedStreamConfig), AudioRecorderError> {
+        let output_device = device.open()?;
+
+        let output_config = match device.device_type {
+            DeviceType::Input => self.input_config(&output_device)?,
+            DeviceType::Output => self.output_config(&output_device)?,
+        };
+
+        Ok((output_device, output_config))
+    }
+
+    /// Records from both devices, stopping the recorder if it fails to start.
+    fn record_devices(
+        &mut self,
+        input_device: cpal::Device,
+        output_device: cpal::Device,
+        output_config: cpal::SupportedStreamConfig,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
+        let receiver = self.dispatch_devices(input_device, output_device, output_config);
+        if receiver.is_err() {
+            self.stop();
+        }
+        receiver
+    }
+
+    /// Records from both devices, dispatching on their sample formats.
+    fn dispatch_devices(
+        &mut self,
+        input_device: cpal::Device,
+        output_device: cpal::Device,
+        output_config: cpal::SupportedStreamConfig,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
         tracing::info!("Recording from multiple devices");
 
         tracing::debug!(
@@ -191,25 +843,7 @@ impl Recorder {
             output_device.name().unwrap_or(String::from("Unknown"))
         );
 
-        let input_config = match input_device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get input config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get input config",
-                ));
-            }
-        };
-
-        let output_config = match output_device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get output config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get output config",
-                ));
-            }
-        };
+        let input_config = self.input_config(&input_device)?;
 
         record_multiple_expansion!(
             self,
@@ -1,212 +1,365 @@
 use std::{
     sync::atomic::Ordering,
-    thread::{self, sleep},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use cpal::{
     Sample,
     traits::{DeviceTrait, StreamTrait},
 };
-use crossbeam_channel::Receiver;
 use ringbuf::{
-    HeapRb,
+    HeapCons, HeapProd, HeapRb,
     traits::{Consumer, Observer, Producer, Split},
 };
-use rubato::{FftFixedIn, Resampler};
 
 use super::{
-    constants::{CustomSample, RESAMPLER_CHUNK_SIZE, RESAMPLER_SLEEP_DELAY, TargetFormat},
-    errors::AudioRecorderError,
+    config::{LatencyMode, ResamplerQuality},
+    constants::{
+        AudioSource, CustomSample, RESAMPLER_CHUNK_SIZE, ROBUST_RING_FACTOR, TargetFormat,
+    },
+    errors::{AudioRecorderError, device_name},
+    receiver::AudioReceiver,
+    resampler_driver::ResamplerDriver,
+    source_tap::guard_callback,
+    system_stream::build_system_stream,
+    wakeup::Wakeup,
+    worker,
 };
 
 use super::Recorder;
 
+/// Resamples one source from its capture ring buffer into the mixer's ring buffer.
+struct SourceResampler {
+    driver: ResamplerDriver,
+    consumer: HeapCons<TargetFormat>,
+    producer: HeapProd<TargetFormat>,
+}
+
+/// Routes a source captured at `origin_rate` to the `mixer` ring buffer, through a
+/// resampler when it is not captured at `target_rate`.
+///
+/// # Returns
+///
+/// The writer used by the capture callback and the resampler of the source, if any.
+fn route_source(
+    origin_rate: usize,
+    target_rate: usize,
+    channels: usize,
+    buffer_size: usize,
+    quality: ResamplerQuality,
+    chunk_size: usize,
+    mixer: HeapProd<TargetFormat>,
+) -> Result<(HeapProd<TargetFormat>, Option<SourceResampler>), AudioRecorderError> {
+    if origin_rate == target_rate {
+        return Ok((mixer, None));
+    }
+
+    tracing::debug!(
+        "Resampling {}ch source from {} Hz to {} Hz",
+        channels,
+        origin_rate,
+        target_rate
+    );
+    let driver =
+        ResamplerDriver::with_quality(origin_rate, target_rate, channels, quality, chunk_size)?;
+    let (producer, consumer) = HeapRb::<TargetFormat>::new(buffer_size).split();
+
+    Ok((
+        producer,
+        Some(SourceResampler {
+            driver,
+            consumer,
+            producer: mixer,
+        }),
+    ))
+}
+
 impl Recorder {
-    pub fn with_input_resampler<T, U>(
+    /// Records from both devices, independently resampling every source that is not
+    /// captured at `target_rate` before mixing.
+    pub fn with_resampler<T, U>(
         &self,
         input_device: cpal::Device,
         output_device: cpal::Device,
+        input_config: cpal::SupportedStreamConfig,
+        output_config: cpal::SupportedStreamConfig,
         target_rate: usize,
-        origin_rate: usize,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError>
+    ) -> Result<AudioReceiver, AudioRecorderError>
     where
         T: CustomSample + 'static,
         U: CustomSample + 'static,
     {
-        tracing::info!("Starting the recorder with input resampler");
-        // using the same config for input and output
-        let input_config = match input_device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get input config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get input config",
-                ));
-            }
+        tracing::info!("Starting the recorder with resampler");
+
+        let mut input_tap = self.source_tap(AudioSource::Input, &input_config)?;
+        let mut output_tap = self.source_tap(AudioSource::Output, &output_config)?;
+
+        let layout = self.mix_layout(&input_config, &output_config);
+        let mic_channels = layout.mic_channels();
+        let system_channels = layout.system_channels();
+        let input_rate = input_config.sample_rate().0 as usize;
+        let output_rate = output_config.sample_rate().0 as usize;
+
+        // frames accumulated and mixed at once by the mixer
+        let mix_frames = match self.config.mix_chunk() {
+            Some(d) => ((d.as_secs_f64() * target_rate as f64) as usize).max(1),
+            None => target_rate,
         };
-        let output_config = match output_device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get output config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get output config",
-                ));
+        let quality = self.config.resampler_quality();
+        let chunk_size = self.config.resampler_chunk_size();
+
+        // a few resampler chunks are enough when low latency keeps the buffers drained
+        let min_frames = match self.config.latency_mode() {
+            LatencyMode::Low => chunk_size * 4,
+            LatencyMode::Robust => {
+                RESAMPLER_CHUNK_SIZE.max(input_rate).max(output_rate) * ROBUST_RING_FACTOR
             }
+            _ => RESAMPLER_CHUNK_SIZE.max(input_rate).max(output_rate),
         };
-
-        let buffer_size = RESAMPLER_CHUNK_SIZE * 2;
+        let (delay_frames, skip_frames) = self.alignment_frames(output_rate as u32);
+        let buffer_size =
+            (min_frames.max(mix_frames) + delay_frames) * 2 * mic_channels.max(system_channels);
 
         tracing::debug!("Creating ring buffers...");
         let ring_output = HeapRb::<TargetFormat>::new(buffer_size);
         let ring_input = HeapRb::<TargetFormat>::new(buffer_size);
-        let ring_resampler = HeapRb::<TargetFormat>::new(buffer_size);
 
         tracing::debug!("Splitting ring buffers...");
-        let (mut producer_input, mut consumer_input) = ring_input.split();
-        let (mut producer_output, mut consumer_output) = ring_output.split();
-        let (mut producer_resampler, mut consumer_resampler) = ring_resampler.split();
+        let (producer_input, mut consumer_input) = ring_input.split();
+        let (producer_output, mut consumer_output) = ring_output.split();
+
+        // route the streams that need resampling through their own resampler
+        let (mut input_writer, input_resampler) = route_source(
+            input_rate,
+            target_rate,
+            mic_channels,
+            buffer_size,
+            quality,
+            chunk_size,
+            producer_input,
+        )?;
+        let (mut output_writer, output_resampler) = route_source(
+            output_rate,
+            target_rate,
+            system_channels,
+            buffer_size,
+            quality,
+            chunk_size,
+            producer_output,
+        )?;
+
+        // the alignment delay is captured at the output rate, ahead of its resampler
+        for _ in 0..delay_frames * system_channels {
+            if let Err(e) = output_writer.try_push(TargetFormat::EQUILIBRIUM) {
+                tracing::error!("Failed to push alignment sample: {}", e);
+                break;
+            }
+        }
+        let mut skip_samples = skip_frames * system_channels;
+
+        let mut resamplers: Vec<SourceResampler> = [input_resampler, output_resampler]
+            .into_iter()
+            .flatten()
+            .collect();
 
         // A signal to pass on the stream
         tracing::debug!("Creating sync channel...");
-        let (sync_tx, sync_rx) = crossbeam_channel::unbounded();
+        let (sync_tx, sync_rx) =
+            self.shared_channel(target_rate as u32, layout.output_channels())?;
 
         // A flag to indicate that recording is in progress.
         tracing::debug!("Begin recording...");
 
         // Run the input stream on a separate thread.
         let recording_signal = self.recording_signal.clone();
-
+        let poll_interval = self.config.poll_interval();
+        // the callbacks wake the resampler thread and the mixer, which the resampler
+        // wakes in turn once it produced frames
+        let (resample_wakeup, mix_wakeup) = (Wakeup::default(), Wakeup::default());
+        let (input_wakeups, output_wakeups) = (
+            (resample_wakeup.clone(), mix_wakeup.clone()),
+            (resample_wakeup.clone(), mix_wakeup.clone()),
+        );
+        let mut echo_canceller = self.echo_canceller(target_rate as u32, &layout);
+        let resampler_degraded = self.resampler_degraded.clone();
+
+        let input_stream_config = self.stream_config(&input_config);
+        let output_stream_config = self.stream_config(&output_config);
         let output_channels = output_config.channels();
         let input_channels = input_config.channels();
 
         // ring buffer writers for input and output
-        let write_output_data = move |data: &[U], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), output_channels);
+        let write_output_data = move |data: &[U], info: Option<&cpal::InputCallbackInfo>| {
+            let (started, samples) = (Instant::now(), data.len());
 
-            for sample in data {
-                if producer_output
-                    .try_push(sample.to_sample::<TargetFormat>())
-                    .is_err()
-                {
-                    tracing::error!("output stream 1 fell behind: try increasing CHUNK_SIZE");
-                }
-            }
+            guard_callback(AudioSource::Output, || {
+                let data = layout.system_layout(output_tap.process(data, info), output_channels);
+                let skipped = skip_samples.min(data.len());
+                skip_samples -= skipped;
+
+                let pushed = output_writer.push_slice(&data[skipped..]);
+                output_tap.overrun(data.len() - skipped - pushed);
+                output_tap.occupancy(output_writer.occupied_len(), output_writer.capacity().get());
+            });
+            output_wakeups.0.notify();
+            output_wakeups.1.notify();
+            output_tap.finish_callback(started, samples);
         };
 
-        let write_input_data = move |data: &[T], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), input_channels);
+        let write_input_data = move |data: &[T], info: &_| {
+            let (started, samples) = (Instant::now(), data.len());
 
-            for sample in data {
-                if producer_resampler
-                    .try_push(sample.to_sample::<TargetFormat>())
-                    .is_err()
-                {
-                    tracing::error!("output stream 2 fell behind: try increasing CHUNK_SIZE");
-                }
-            }
+            guard_callback(AudioSource::Input, || {
+                let data = layout.mic_layout(input_tap.process(data, Some(info)), input_channels);
+
+                let pushed = input_writer.push_slice(&data);
+                input_tap.overrun(data.len() - pushed);
+                input_tap.occupancy(input_writer.occupied_len(), input_writer.capacity().get());
+            });
+            input_wakeups.0.notify();
+            input_wakeups.1.notify();
+            input_tap.finish_callback(started, samples);
         };
 
+        let input_errors = self.stream_errors(AudioSource::Input);
+        let output_errors = self.stream_errors(AudioSource::Output);
+
+        let (startup, started) = worker::startup();
+        let mut startup = Some(startup);
+        let flush_signal = self.flush_signal.clone();
+
         tracing::debug!("Spawning input stream thread...");
-        thread::spawn(move || {
+        let workers = self.workers();
+        self.workers().spawn("arec-mixer", move || {
             let input_stream = match input_device.build_input_stream(
-                &input_config.into(),
+                &input_stream_config,
                 write_input_data,
-                Recorder::err_fn,
+                input_errors,
                 None,
             ) {
                 Ok(s) => s,
                 Err(e) => {
                     tracing::error!("Failed to build input stream: {}", e);
+                    workers.fail(
+                        "arec-mixer",
+                        &mut startup,
+                        AudioRecorderError::BuildStream {
+                            device: device_name(&input_device),
+                            source: e,
+                        },
+                    );
                     return;
                 }
             };
 
-            let output_stream = match output_device.build_input_stream(
-                &output_config.into(),
+            let output_stream = match build_system_stream(
+                &output_device,
+                &output_stream_config,
                 write_output_data,
-                Recorder::err_fn,
-                None,
+                output_errors,
             ) {
                 Ok(s) => s,
                 Err(e) => {
-                    tracing::error!("Failed to build output stream: {}", e);
+                    workers.fail("arec-mixer", &mut startup, e);
                     return;
                 }
             };
 
             let recording_signal_2 = recording_signal.clone();
-
-            // resampler thread
-            thread::spawn(move || {
-                let mut resampler =
-                    match FftFixedIn::<TargetFormat>::new(origin_rate, target_rate, 1024, 2, 1) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            return Err(format!("Failed to create resampler: {e}"));
+            let resampled_wakeup = mix_wakeup.clone();
+            let (resamplers_tx, resamplers_rx) = crossbeam_channel::bounded(1);
+
+            // resampler thread, handing the resamplers back to be flushed once stopped
+            let resampler = workers.spawn("arec-resampler", move || {
+                let mut lowered = false;
+                while recording_signal_2.load(Ordering::SeqCst) {
+                    if !lowered && resampler_degraded.load(Ordering::SeqCst) {
+                        tracing::info!("Lowering the resampler quality");
+                        lowered = true;
+                        let quality = ResamplerQuality::Fast;
+                        for resampler in &mut resamplers {
+                            let chunk_size = quality.chunk_size();
+                            if let Err(e) = resampler.driver.set_quality(quality, chunk_size) {
+                                tracing::error!("Failed to lower the resampler quality: {}", e);
+                            }
                         }
-                    };
-
-                let mut resampler_output_buffer = resampler.output_buffer_allocate(true);
-                let mut next_input_frames = resampler.input_frames_next();
-
-                loop {
-                    if !recording_signal_2.load(Ordering::SeqCst) {
-                        break;
                     }
 
-                    while consumer_resampler.occupied_len() >= next_input_frames {
-                        let mut data_buffer = vec![0.0; next_input_frames];
-
-                        consumer_resampler.pop_slice(&mut data_buffer);
-
-                        match resampler.process_into_buffer(
-                            &[&data_buffer],
-                            &mut resampler_output_buffer,
-                            None,
-                        ) {
-                            Ok(_) => {
-                                next_input_frames = resampler.input_frames_next();
-                                let output_data = &resampler_output_buffer[0];
-                                // drain the output buffer
-                                producer_input.push_slice(output_data);
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to resample: {}", e);
-                            }
-                        };
+                    let mut pushed = 0;
+                    for resampler in &mut resamplers {
+                        pushed += resampler
+                            .driver
+                            .drain(&mut resampler.consumer, &mut resampler.producer);
                     }
-
-                    sleep(Duration::from_millis(RESAMPLER_SLEEP_DELAY as _));
+                    if pushed > 0 {
+                        resampled_wakeup.notify();
+                    }
+                    resample_wakeup.wait(poll_interval);
                 }
 
-                Ok(())
+                if resamplers_tx.send(resamplers).is_err() {
+                    tracing::debug!("Mixer exited before the resamplers");
+                }
             });
 
+            if let Err(e) = resampler {
+                workers.fail("arec-mixer", &mut startup, e);
+                return;
+            }
+
             if let Err(e) = input_stream.play() {
                 tracing::error!("Failed to play input stream: {}", e);
+                workers.fail(
+                    "arec-mixer",
+                    &mut startup,
+                    AudioRecorderError::PlayStream {
+                        device: device_name(&input_device),
+                        source: e,
+                    },
+                );
                 return;
             };
             if let Err(e) = output_stream.play() {
                 tracing::error!("Failed to play output stream: {}", e);
+                workers.fail(
+                    "arec-mixer",
+                    &mut startup,
+                    AudioRecorderError::PlayStream {
+                        device: device_name(&output_device),
+                        source: e,
+                    },
+                );
                 return;
             };
 
+            if let Some(startup) = startup.take() {
+                startup.ready();
+            }
+
             while recording_signal.load(Ordering::SeqCst) {
-                if consumer_output.occupied_len() >= target_rate
-                    || consumer_input.occupied_len() >= target_rate
+                if consumer_output.occupied_len() >= mix_frames * system_channels
+                    || consumer_input.occupied_len() >= mix_frames * mic_channels
                 {
-                    let mut input_buffer = vec![TargetFormat::EQUILIBRIUM; target_rate];
-                    let mut output_buffer = vec![TargetFormat::EQUILIBRIUM; target_rate];
+                    let mut input_buffer =
+                        vec![TargetFormat::EQUILIBRIUM; mix_frames * mic_channels];
+                    let mut output_buffer =
+                        vec![TargetFormat::EQUILIBRIUM; mix_frames * system_channels];
 
                     consumer_input.pop_slice(&mut input_buffer);
                     consumer_output.pop_slice(&mut output_buffer);
+                    let (input_buffer, output_buffer) = match &mut echo_canceller {
+                        Some(canceller) => canceller.process(input_buffer, output_buffer),
+                        None => (input_buffer, output_buffer),
+                    };
 
-                    let mut data: Vec<TargetFormat> = Vec::with_capacity(target_rate * 2);
+                    let mut data: Vec<TargetFormat> =
+                        Vec::with_capacity(mix_frames * layout.output_channels() as usize);
 
-                    for (i, o) in input_buffer.iter().zip(output_buffer.iter()) {
-                        data.push(*i);
-                        data.push(*o);
+                    for (i, o) in input_buffer
+                        .chunks(mic_channels)
+                        .zip(output_buffer.chunks(system_channels))
+                    {
+                        layout.mix_frame(i, o, &mut data);
                     }
 
                     if let Err(e) = sync_tx.send(data) {
@@ -214,7 +367,7 @@ impl Recorder {
                     }
                 }
 
-                sleep(Duration::from_millis(RESAMPLER_SLEEP_DELAY as _));
+                mix_wakeup.wait(poll_interval);
             }
 
             tracing::debug!("Pausing streams");
@@ -225,220 +378,54 @@ impl Recorder {
             if let Err(e) = output_stream.pause() {
                 tracing::error!("Failed to pause output stream: {}", e);
             };
-            tracing::debug!("Dropping stream");
-            drop(input_stream);
-            drop(output_stream);
-            tracing::info!("Recording stopped");
-        });
-
-        Ok(sync_rx)
-    }
-
-    pub fn with_output_resampler<T, U>(
-        &self,
-        input_device: cpal::Device,
-        output_device: cpal::Device,
-        target_rate: usize,
-        origin_rate: usize,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError>
-    where
-        T: CustomSample + 'static,
-        U: CustomSample + 'static,
-    {
-        tracing::info!("Recording with output resampler");
-        // using the same config for input and output
-        let input_config = match input_device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get input config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get input config",
-                ));
-            }
-        };
-        let output_config = match output_device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get output config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get output config",
-                ));
-            }
-        };
-
-        let buffer_size = RESAMPLER_CHUNK_SIZE * 2;
-
-        tracing::debug!("Creating ring buffers...");
-        let ring_output = HeapRb::<TargetFormat>::new(buffer_size);
-        let ring_input = HeapRb::<TargetFormat>::new(buffer_size);
-        let ring_resampler = HeapRb::<TargetFormat>::new(buffer_size);
-
-        tracing::debug!("Splitting ring buffers...");
-        let (mut producer_input, mut consumer_input) = ring_input.split();
-        let (mut producer_output, mut consumer_output) = ring_output.split();
-        let (mut producer_resampler, mut consumer_resampler) = ring_resampler.split();
-
-        // A signal to pass on the stream
-        let (sync_tx, sync_rx) = crossbeam_channel::unbounded();
-
-        // A flag to indicate that recording is in progress.
-        tracing::debug!("Begin recording...");
-
-        // Run the input stream on a separate thread.
-        let recording_signal = self.recording_signal.clone();
-
-        let output_channels = output_config.channels();
-        let input_channels = input_config.channels();
-
-        // ring buffer writers for input and output
-        let write_output_data = move |data: &[TargetFormat], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), output_channels);
-
-            for sample in data {
-                if producer_resampler
-                    .try_push(sample.to_sample::<TargetFormat>())
-                    .is_err()
-                {
-                    tracing::error!("output stream 2 fell behind: try increasing CHUNK_SIZE");
-                }
-            }
-        };
-
-        let write_input_data = move |data: &[TargetFormat], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), input_channels);
-
-            for sample in data {
-                if producer_input
-                    .try_push(sample.to_sample::<TargetFormat>())
-                    .is_err()
-                {
-                    tracing::error!("output stream 2 fell behind: try increasing CHUNK_SIZE");
-                }
-            }
-        };
-
-        tracing::debug!("Spawning input stream thread...");
-        thread::spawn(move || {
-            let input_stream = match input_device.build_input_stream(
-                &input_config.into(),
-                write_input_data,
-                Recorder::err_fn,
-                None,
-            ) {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!("Failed to build input stream: {}", e);
-                    return;
-                }
-            };
-
-            let output_stream = match output_device.build_input_stream(
-                &output_config.into(),
-                write_output_data,
-                Recorder::err_fn,
-                None,
-            ) {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!("Failed to build output stream: {}", e);
-                    return;
-                }
-            };
-
-            let recording_signal_2 = recording_signal.clone();
 
-            // resampler thread
-            thread::spawn(move || {
-                let mut resampler =
-                    match FftFixedIn::<TargetFormat>::new(origin_rate, target_rate, 1024, 2, 1) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            tracing::error!("Failed to create resampler: {}", e);
-                            return;
+            if flush_signal.load(Ordering::SeqCst) {
+                tracing::debug!("Flushing the resamplers and the mixer");
+                match resamplers_rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(mut resamplers) => {
+                        for resampler in &mut resamplers {
+                            resampler
+                                .driver
+                                .flush(&mut resampler.consumer, &mut resampler.producer);
                         }
-                    };
-
-                let mut resampler_output_buffer = resampler.output_buffer_allocate(true);
-                let mut next_input_frames = resampler.input_frames_next();
-
-                loop {
-                    if !recording_signal_2.load(Ordering::SeqCst) {
-                        break;
-                    }
-
-                    while consumer_resampler.occupied_len() >= next_input_frames {
-                        let mut data_buffer = vec![0.0; next_input_frames];
-
-                        consumer_resampler.pop_slice(&mut data_buffer);
-
-                        match resampler.process_into_buffer(
-                            &[&data_buffer],
-                            &mut resampler_output_buffer,
-                            None,
-                        ) {
-                            Ok(_) => {
-                                next_input_frames = resampler.input_frames_next();
-                                let output_data = &resampler_output_buffer[0];
-                                // drain the output buffer
-                                producer_output.push_slice(output_data);
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to resample: {}", e);
-                            }
-                        };
                     }
-
-                    sleep(Duration::from_millis(RESAMPLER_SLEEP_DELAY as _));
+                    Err(e) => tracing::error!("Failed to get the resamplers back: {}", e),
                 }
-            });
-
-            if let Err(e) = input_stream.play() {
-                tracing::error!("Failed to play input stream: {}", e);
-                return;
-            };
-            if let Err(e) = output_stream.play() {
-                tracing::error!("Failed to play output stream: {}", e);
-                return;
-            };
 
-            while recording_signal.load(Ordering::SeqCst) {
-                if consumer_output.occupied_len() >= target_rate
-                    || consumer_input.occupied_len() >= target_rate
+                // the shorter source is padded with silence
+                let frames = (consumer_input.occupied_len() / mic_channels)
+                    .max(consumer_output.occupied_len() / system_channels);
+                let mut input_buffer = vec![TargetFormat::EQUILIBRIUM; frames * mic_channels];
+                let mut output_buffer = vec![TargetFormat::EQUILIBRIUM; frames * system_channels];
+
+                consumer_input.pop_slice(&mut input_buffer);
+                consumer_output.pop_slice(&mut output_buffer);
+                let (input_buffer, output_buffer) = match &mut echo_canceller {
+                    Some(canceller) => canceller.process(input_buffer, output_buffer),
+                    None => (input_buffer, output_buffer),
+                };
+
+                let mut data: Vec<TargetFormat> =
+                    Vec::with_capacity(frames * layout.output_channels() as usize);
+                for (i, o) in input_buffer
+                    .chunks(mic_channels)
+                    .zip(output_buffer.chunks(system_channels))
                 {
-                    let mut input_buffer = vec![TargetFormat::EQUILIBRIUM; target_rate];
-                    let mut output_buffer = vec![TargetFormat::EQUILIBRIUM; target_rate];
-
-                    consumer_input.pop_slice(&mut input_buffer);
-                    consumer_output.pop_slice(&mut output_buffer);
-
-                    let mut data: Vec<TargetFormat> = Vec::with_capacity(target_rate * 2);
-
-                    for (i, o) in input_buffer.iter().zip(output_buffer.iter()) {
-                        data.push(*i);
-                        data.push(*o);
-                    }
-
-                    if let Err(e) = sync_tx.send(data) {
-                        tracing::error!("Failed to send data: {}", e);
-                    }
+                    layout.mix_frame(i, o, &mut data);
                 }
 
-                sleep(Duration::from_millis(RESAMPLER_SLEEP_DELAY as _));
+                if let Err(e) = sync_tx.send(data) {
+                    tracing::error!("Failed to send flushed data: {}", e);
+                }
             }
 
-            tracing::debug!("Pausing streams");
-            if let Err(e) = input_stream.pause() {
-                tracing::error!("Failed to pause input stream: {}", e);
-            };
-
-            if let Err(e) = output_stream.pause() {
-                tracing::error!("Failed to pause output stream: {}", e);
-            };
             tracing::debug!("Dropping stream");
             drop(input_stream);
             drop(output_stream);
             tracing::info!("Recording stopped");
-        });
+        })?;
+
+        started.wait()?;
 
         Ok(sync_rx)
     }
@@ -1,20 +1,21 @@
-use std::{
-    thread::{self, sleep},
-    time::Duration,
-};
+use std::time::Instant;
 
 use cpal::traits::{DeviceTrait, StreamTrait};
-use crossbeam_channel::Receiver;
 use dasp_sample::Sample;
 use ringbuf::{
     HeapRb,
-    traits::{Consumer, Producer, Split},
+    traits::{Consumer, Observer, Producer, Split},
 };
 
 use super::{
     Recorder,
-    constants::{CLOCK_DELAY, CustomSample, TargetFormat},
-    errors::AudioRecorderError,
+    config::LatencyMode,
+    constants::{AudioSource, CustomSample, ROBUST_RING_FACTOR, TargetFormat},
+    errors::{AudioRecorderError, device_name},
+    receiver::AudioReceiver,
+    source_tap::guard_callback,
+    system_stream::build_system_stream,
+    worker,
 };
 
 impl Recorder {
@@ -22,58 +23,58 @@ impl Recorder {
         &self,
         input_device: cpal::Device,
         output_device: cpal::Device,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError>
+        input_config: cpal::SupportedStreamConfig,
+        output_config: cpal::SupportedStreamConfig,
+    ) -> Result<AudioReceiver, AudioRecorderError>
     where
         T: CustomSample + 'static,
         U: CustomSample + 'static,
     {
         tracing::info!("Starting the recorder without resampler");
-        tracing::debug!("Collecting input and output configs");
-        // using the same config for input and output
-        let input_config = match input_device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get input config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get input config",
-                ));
-            }
-        };
-        let output_config = match output_device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get output config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get output config",
-                ));
-            }
-        };
+
+        let mut input_tap = self.source_tap(AudioSource::Input, &input_config)?;
+        let mut output_tap = self.source_tap(AudioSource::Output, &output_config)?;
 
         // We'll try and use the same configuration between streams to keep it simple.
         let config: cpal::StreamConfig = input_config.clone().into();
 
         // Create a delay in case the input and output devices aren't synced.
-        let latency_frames = (150.0 / 1_000.0) * config.sample_rate.0 as f32;
-        let latency_samples = latency_frames as usize * config.channels as usize;
+        let latency_frames = self.latency_padding_frames(&input_config, &output_config);
+        let layout = self.mix_layout(&input_config, &output_config);
+        let mic_channels = layout.mic_channels();
+        let system_channels = layout.system_channels();
+        let latency_samples = latency_frames * system_channels;
+        let (delay_frames, skip_frames) = self.alignment_frames(config.sample_rate.0);
+        let delay_samples = delay_frames * system_channels;
 
         tracing::debug!("Latency samples: {}", latency_samples);
         tracing::debug!("Latency frames: {}", latency_frames);
 
         // The buffer to share samples
         tracing::debug!("Creating ring buffer...");
-        let ring = HeapRb::<TargetFormat>::new(latency_samples * 2);
+        // one second of headroom on top of the padding, 100 ms in low latency mode
+        let headroom_frames = match self.config.latency_mode() {
+            LatencyMode::Low => config.sample_rate.0 as usize / 10,
+            LatencyMode::Robust => config.sample_rate.0 as usize * ROBUST_RING_FACTOR,
+            _ => config.sample_rate.0 as usize,
+        };
+        let ring = HeapRb::<TargetFormat>::new(
+            latency_samples + delay_samples + headroom_frames * system_channels,
+        );
 
         tracing::debug!("Splitting ring buffers...");
         let (mut producer, mut consumer) = ring.split();
 
         // A signal to pass on the stream
         tracing::debug!("Creating sync channel...");
-        let (sync_tx, sync_rx) = crossbeam_channel::unbounded();
+        let (sync_tx, sync_rx) =
+            self.shared_channel(config.sample_rate.0, layout.output_channels())?;
 
-        // Fill the samples with 0.0 equal to the length of the delay.
+        // Fill the samples with 0.0 equal to the length of the delay, the alignment delay
+        // past the trimmed padding is kept
         tracing::debug!("Filling ring buffer with EQUILIBRIUM samples");
-        for _ in 0..latency_samples {
-            // The ring buffer has twice as much space as necessary to add latency here,
+        for _ in 0..latency_samples + delay_samples {
+            // The ring buffer has more space than necessary to add latency here,
             // so this should never fail
             if let Err(e) = producer.try_push(TargetFormat::EQUILIBRIUM) {
                 tracing::error!("Failed to push equilibrium sample: {}", e);
@@ -84,68 +85,128 @@ impl Recorder {
         tracing::debug!("Clone recording signal mutex...");
         let recording_signal = self.recording_signal.clone();
 
+        let input_stream_config = self.stream_config(&input_config);
+        let output_stream_config = self.stream_config(&output_config);
+        let poll_interval = self.config.poll_interval();
+        let stop_wakeup = self.stop_wakeup.clone();
         let output_channels = output_config.channels();
         let input_channels = input_config.channels();
 
+        // system audio dropped at the start, delaying the microphone against it
+        let mut skip_samples = skip_frames * system_channels;
+
         // ring buffer writers for input and output
-        let write_output_data = move |data: &[U], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), output_channels);
+        let write_output_data = move |data: &[U], info: Option<&cpal::InputCallbackInfo>| {
+            let (started, samples) = (Instant::now(), data.len());
 
-            for sample in data {
-                if producer
-                    .try_push(sample.to_sample::<TargetFormat>())
-                    .is_err()
-                {
-                    tracing::warn!("output stream fell behind: increase buffer size");
-                }
-            }
+            guard_callback(AudioSource::Output, || {
+                let data = layout.system_layout(output_tap.process(data, info), output_channels);
+                let skipped = skip_samples.min(data.len());
+                skip_samples -= skipped;
+
+                let pushed = producer.push_slice(&data[skipped..]);
+                output_tap.overrun(data.len() - skipped - pushed);
+                output_tap.occupancy(producer.occupied_len(), producer.capacity().get());
+            });
+            output_tap.finish_callback(started, samples);
         };
 
-        let write_input_data = move |data: &[T], _: &_| {
-            let data = Recorder::channels_to_mono(data.to_vec(), input_channels);
-            let mut parsed_data: Vec<TargetFormat> = Vec::new();
+        // padding samples still queued ahead of the system audio
+        let mut padding_to_trim = latency_samples;
+        let mut echo_canceller = self.echo_canceller(config.sample_rate.0, &layout);
+
+        let write_input_data = move |data: &[T], info: &_| {
+            let (started, samples) = (Instant::now(), data.len());
+
+            guard_callback(AudioSource::Input, || {
+                let data = layout.mic_layout(input_tap.process(data, Some(info)), input_channels);
+                let mut parsed_data: Vec<TargetFormat> = Vec::with_capacity(
+                    data.len() / mic_channels * layout.output_channels() as usize,
+                );
+                let mut mic_data: Vec<TargetFormat> = Vec::with_capacity(data.len());
+                let mut system_data: Vec<TargetFormat> =
+                    Vec::with_capacity(data.len() / mic_channels * system_channels);
+                let mut system_frame = vec![TargetFormat::EQUILIBRIUM; system_channels];
+
+                for s_i in data.chunks(mic_channels) {
+                    for s_o in system_frame.iter_mut() {
+                        *s_o = consumer.try_pop().unwrap_or(TargetFormat::EQUILIBRIUM);
+                    }
 
-            for s_i in data {
-                parsed_data.push(s_i.to_sample::<TargetFormat>());
-                if let Some(s_o) = consumer.try_pop() {
-                    parsed_data.push(s_o.to_sample::<TargetFormat>());
-                } else {
-                    parsed_data.push(TargetFormat::EQUILIBRIUM.to_sample());
+                    // frames whose system audio is still the padding are trimmed, so the
+                    // delivered stream starts with both sources together
+                    if padding_to_trim > 0 {
+                        padding_to_trim = padding_to_trim.saturating_sub(system_channels);
+                        continue;
+                    }
+
+                    mic_data.extend_from_slice(s_i);
+                    system_data.extend_from_slice(&system_frame);
                 }
-            }
 
-            if let Err(e) = sync_tx.send(parsed_data) {
-                tracing::error!("Failed to send data: {}", e);
-            }
+                let (mic_data, system_data) = match &mut echo_canceller {
+                    Some(canceller) => canceller.process(mic_data, system_data),
+                    None => (mic_data, system_data),
+                };
+                for (s_i, s_o) in mic_data
+                    .chunks(mic_channels)
+                    .zip(system_data.chunks(system_channels))
+                {
+                    layout.mix_frame(s_i, s_o, &mut parsed_data);
+                }
+
+                if parsed_data.is_empty() {
+                    return;
+                }
+
+                if let Err(e) = sync_tx.send(parsed_data) {
+                    tracing::error!("Failed to send data: {}", e);
+                }
+            });
+            input_tap.finish_callback(started, samples);
         };
 
+        let input_errors = self.stream_errors(AudioSource::Input);
+        let output_errors = self.stream_errors(AudioSource::Output);
         let record_signal_clone_1 = recording_signal.clone();
+        let (startup, started) = worker::startup();
+        let mut startup = Some(startup);
+        let workers = self.workers();
+
         tracing::debug!("Spawning stream thread...");
-        thread::spawn(move || {
+        self.workers().spawn("arec-input", move || {
             // Build the input stream
             let input_stream = match input_device.build_input_stream(
-                &input_config.into(),
+                &input_stream_config,
                 write_input_data,
-                Recorder::err_fn,
+                input_errors,
                 None,
             ) {
                 Ok(s) => s,
                 Err(e) => {
                     tracing::error!("Failed to build input stream: {}", e);
+                    workers.fail(
+                        "arec-input",
+                        &mut startup,
+                        AudioRecorderError::BuildStream {
+                            device: device_name(&input_device),
+                            source: e,
+                        },
+                    );
                     return;
                 }
             };
 
             // Build the output stream
-            let output_stream = match output_device.build_input_stream(
-                &output_config.into(),
+            let output_stream = match build_system_stream(
+                &output_device,
+                &output_stream_config,
                 write_output_data,
-                Recorder::err_fn,
-                None,
+                output_errors,
             ) {
                 Ok(s) => s,
                 Err(e) => {
-                    tracing::error!("Failed to build output stream: {}", e);
+                    workers.fail("arec-input", &mut startup, e);
                     return;
                 }
             };
@@ -155,15 +216,35 @@ impl Recorder {
             // Start the streams
             if let Err(e) = input_stream.play() {
                 tracing::error!("Failed to play input stream: {}", e);
+                workers.fail(
+                    "arec-input",
+                    &mut startup,
+                    AudioRecorderError::PlayStream {
+                        device: device_name(&input_device),
+                        source: e,
+                    },
+                );
                 return;
             };
             if let Err(e) = output_stream.play() {
                 tracing::error!("Failed to play output stream: {}", e);
+                workers.fail(
+                    "arec-input",
+                    &mut startup,
+                    AudioRecorderError::PlayStream {
+                        device: device_name(&output_device),
+                        source: e,
+                    },
+                );
                 return;
             };
 
+            if let Some(startup) = startup.take() {
+                startup.ready();
+            }
+
             while record_signal_clone_1.load(std::sync::atomic::Ordering::SeqCst) {
-                sleep(Duration::from_millis(CLOCK_DELAY as _));
+                stop_wakeup.wait(poll_interval);
             }
 
             tracing::debug!("Dropping streams");
@@ -182,7 +263,9 @@ impl Recorder {
             // drop audio streams
             drop(output_stream);
             tracing::debug!("output stream dropped");
-        });
+        })?;
+
+        started.wait()?;
 
         Ok(sync_rx)
     }
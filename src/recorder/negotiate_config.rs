@@ -0,0 +1,132 @@
+use cpal::{SampleFormat, SupportedStreamConfig, SupportedStreamConfigRange, traits::DeviceTrait};
+
+use super::{
+    Recorder,
+    errors::{AudioRecorderError, device_name},
+};
+
+/// Picks an `f32` variant of `default` from `supported` when the device offers one with the
+/// same channel count and sample rate, otherwise returns `default` unchanged.
+fn negotiate_f32(
+    default: SupportedStreamConfig,
+    mut supported: impl Iterator<Item = SupportedStreamConfigRange>,
+) -> SupportedStreamConfig {
+    if default.sample_format() == SampleFormat::F32 {
+        return default;
+    }
+
+    let sample_rate = default.sample_rate();
+    match supported.find(|c| {
+        c.sample_format() == SampleFormat::F32
+            && c.channels() == default.channels()
+            && c.min_sample_rate() <= sample_rate
+            && sample_rate <= c.max_sample_rate()
+    }) {
+        Some(range) => {
+            tracing::debug!("Negotiated f32 stream config");
+            range.with_sample_rate(sample_rate)
+        }
+        None => {
+            tracing::debug!(
+                "Device does not support f32 at {} Hz, using {:?}",
+                sample_rate.0,
+                default.sample_format()
+            );
+            default
+        }
+    }
+}
+
+/// Resolves the stream config used to capture from an input device, requesting `f32` when
+/// `prefer_f32` is set.
+pub fn resolve_input_config(
+    device: &cpal::Device,
+    prefer_f32: bool,
+) -> Result<SupportedStreamConfig, AudioRecorderError> {
+    let config = match device.default_input_config() {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!("Failed to get input config: {}", e);
+            return Err(AudioRecorderError::StreamConfig {
+                device: device_name(device),
+                source: e,
+            });
+        }
+    };
+
+    if !prefer_f32 {
+        return Ok(config);
+    }
+
+    match device.supported_input_configs() {
+        Ok(supported) => Ok(negotiate_f32(config, supported)),
+        Err(e) => {
+            tracing::warn!("Failed to list supported input configs: {}", e);
+            Ok(config)
+        }
+    }
+}
+
+/// Resolves the stream config used to capture from an output device, requesting `f32` when
+/// `prefer_f32` is set.
+pub fn resolve_output_config(
+    device: &cpal::Device,
+    prefer_f32: bool,
+) -> Result<SupportedStreamConfig, AudioRecorderError> {
+    let config = match device.default_output_config() {
+        Ok(c) => c,
+        // monitor sources stand in for the output as capture devices
+        Err(_) if device.default_input_config().is_ok() => {
+            tracing::debug!("Output device is a capture device, using its input config");
+            return resolve_input_config(device, prefer_f32);
+        }
+        Err(e) => {
+            tracing::error!("Failed to get output config: {}", e);
+            return Err(AudioRecorderError::StreamConfig {
+                device: device_name(device),
+                source: e,
+            });
+        }
+    };
+
+    if !prefer_f32 {
+        return Ok(config);
+    }
+
+    match device.supported_output_configs() {
+        Ok(supported) => Ok(negotiate_f32(config, supported)),
+        Err(e) => {
+            tracing::warn!("Failed to list supported output configs: {}", e);
+            Ok(config)
+        }
+    }
+}
+
+impl Recorder {
+    /// Requests `f32` stream configs from the devices when they support them.
+    ///
+    /// Only the stream config negotiation is affected; samples still go through the
+    /// usual conversion to `TargetFormat`. Devices that do not offer an `f32` config
+    /// at their default sample rate keep using their default config.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_prefer_f32(&mut self, prefer_f32: bool) {
+        self.prefer_f32 = prefer_f32;
+    }
+
+    /// Resolves the stream config used to capture from an input device.
+    pub fn input_config(
+        &self,
+        device: &cpal::Device,
+    ) -> Result<SupportedStreamConfig, AudioRecorderError> {
+        resolve_input_config(device, self.prefer_f32)
+    }
+
+    /// Resolves the stream config used to capture from an output device.
+    pub fn output_config(
+        &self,
+        device: &cpal::Device,
+    ) -> Result<SupportedStreamConfig, AudioRecorderError> {
+        resolve_output_config(device, self.prefer_f32)
+    }
+}
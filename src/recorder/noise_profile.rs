@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use super::{
+    Recorder,
+    config::RecorderConfig,
+    constants::{NOISE_PROFILE_FRAME_SIZE, TargetFormat},
+    dsp::spectral_subtraction::{SpectralSubtractor, mean_spectrum},
+    errors::AudioRecorderError,
+    receiver::AudioRecvError,
+};
+
+/// The spectrum of the "room tone" of the input device, captured with
+/// `Recorder::capture_noise_profile` and removed from the following recordings.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct NoiseProfile {
+    /// Sample rate the profile was captured at.
+    pub sample_rate: u32,
+    /// Mean noise magnitude of every bin of a `NOISE_PROFILE_FRAME_SIZE` frame.
+    spectrum: Vec<f32>,
+}
+
+impl NoiseProfile {
+    /// Computes the profile of interleaved noise `samples`.
+    fn analyze(samples: &[TargetFormat], sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        let mono: Vec<TargetFormat> = samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<TargetFormat>() / frame.len() as TargetFormat)
+            .collect();
+
+        NoiseProfile {
+            sample_rate,
+            spectrum: mean_spectrum(&mono),
+        }
+    }
+
+    /// The noise magnitude of every bin for a stream at `sample_rate`, interpolating the
+    /// profile when it was captured at another rate.
+    fn spectrum_at(&self, sample_rate: u32) -> Vec<f32> {
+        if sample_rate == self.sample_rate {
+            return self.spectrum.clone();
+        }
+
+        let last = self.spectrum.len() - 1;
+        let ratio = sample_rate as f32 / self.sample_rate as f32;
+
+        (0..=last)
+            .map(|bin| {
+                let source = bin as f32 * ratio;
+                let low = source.floor() as usize;
+                if low >= last {
+                    return 0.0;
+                }
+
+                let fraction = source - low as f32;
+                self.spectrum[low] * (1.0 - fraction) + self.spectrum[low + 1] * fraction
+            })
+            .collect()
+    }
+
+    /// Creates the subtractor removing this noise from a stream with the given layout.
+    pub fn subtractor(&self, sample_rate: u32, channels: u16) -> SpectralSubtractor {
+        SpectralSubtractor::new(self.spectrum_at(sample_rate), channels)
+    }
+}
+
+impl Recorder {
+    /// Records `duration` of the input device without any noise reduction and returns the
+    /// spectrum of its noise. The room should be silent apart from the stationary noise
+    /// to remove (fans, hum, hiss), a second or two is enough.
+    #[tracing::instrument]
+    pub fn capture_noise_profile(
+        &mut self,
+        duration: Duration,
+    ) -> Result<NoiseProfile, AudioRecorderError> {
+        tracing::info!("Capturing noise profile");
+
+        if self.get_is_recording() {
+            tracing::warn!("Recording is already in progress");
+            return Err(AudioRecorderError::RecordingInProgress);
+        }
+
+        let previous_profile = self.noise_profile.take();
+        // the automatic gain would boost the noise while profiling it
+        let previous_agc = self.auto_gain.take();
+
+        let receiver = match self.start(RecorderConfig::builder().input_only(true).build()) {
+            Ok(r) => r,
+            Err(e) => {
+                self.noise_profile = previous_profile;
+                self.auto_gain = previous_agc;
+                return Err(e);
+            }
+        };
+
+        let config = match self.get_config() {
+            Ok(c) => c,
+            Err(e) => {
+                self.stop();
+                self.noise_profile = previous_profile;
+                self.auto_gain = previous_agc;
+                return Err(e);
+            }
+        };
+
+        let mut samples: Vec<TargetFormat> = Vec::new();
+        let instant = Instant::now();
+
+        while instant.elapsed() < duration {
+            match receiver.recv_timeout(duration.saturating_sub(instant.elapsed())) {
+                Ok(chunk) => samples.extend(chunk.samples),
+                Err(AudioRecvError::Ended) => break,
+                Err(_) => {}
+            }
+        }
+
+        self.stop();
+        self.noise_profile = previous_profile;
+        self.auto_gain = previous_agc;
+
+        if samples.len() / (config.channels.max(1) as usize) < NOISE_PROFILE_FRAME_SIZE {
+            tracing::error!("Not enough audio captured for a noise profile");
+            return Err(AudioRecorderError::NotEnoughAudio(
+                "a noise profile needs at least one frame",
+            ));
+        }
+
+        let profile = NoiseProfile::analyze(&samples, config.sample_rate, config.channels);
+        tracing::info!("Noise profile captured at {} Hz", profile.sample_rate);
+
+        Ok(profile)
+    }
+
+    /// Removes the noise of `profile` from the input stream by spectral subtraction (or
+    /// `None` to disable it), adding the latency of one 512 sample hop.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_noise_profile(&mut self, profile: Option<NoiseProfile>) {
+        self.noise_profile = profile;
+    }
+
+    /// Returns the noise profile removed from the input stream, if any.
+    pub fn get_noise_profile(&self) -> Option<&NoiseProfile> {
+        self.noise_profile.as_ref()
+    }
+}
@@ -0,0 +1,269 @@
+use super::{Recorder, errors::AudioRecorderError};
+
+/// A privacy permission the operating system requires for capturing audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Permission {
+    /// Capturing the microphone.
+    Microphone,
+    /// Capturing the system audio with ScreenCaptureKit, which macOS grants as part of
+    /// the screen recording permission.
+    ScreenRecording,
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void};
+
+    use core_foundation::{
+        base::{CFType, TCFType},
+        boolean::CFBoolean,
+        bundle::CFBundle,
+        string::{CFString, CFStringRef},
+    };
+    use core_foundation_sys::base::CFRelease;
+
+    /// `CS_RUNTIME`, set in the code signing status of hardened runtime processes.
+    const CS_RUNTIME: u32 = 0x10000;
+
+    /// `AVAuthorizationStatusDenied` and `AVAuthorizationStatusRestricted`.
+    const AV_AUTHORIZATION_RESTRICTED: isize = 1;
+    const AV_AUTHORIZATION_DENIED: isize = 2;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    unsafe extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    unsafe extern "C" {
+        static AVMediaTypeAudio: *mut c_void;
+    }
+
+    #[link(name = "Security", kind = "framework")]
+    unsafe extern "C" {
+        fn SecTaskCreateFromSelf(allocator: *const c_void) -> *mut c_void;
+        fn SecTaskCopyValueForEntitlement(
+            task: *mut c_void,
+            entitlement: CFStringRef,
+            error: *mut *mut c_void,
+        ) -> *const c_void;
+        fn SecTaskGetCodeSignStatus(task: *mut c_void) -> u32;
+    }
+
+    #[link(name = "objc")]
+    unsafe extern "C" {
+        fn objc_getClass(name: *const c_char) -> *mut c_void;
+        fn sel_registerName(name: *const c_char) -> *mut c_void;
+        fn objc_msgSend();
+    }
+
+    pub fn screen_recording_granted() -> bool {
+        // SAFETY: plain CoreGraphics queries without arguments.
+        unsafe { CGPreflightScreenCaptureAccess() }
+    }
+
+    /// Shows the system prompt the first time, later calls only report the choice.
+    pub fn request_screen_recording() -> bool {
+        // SAFETY: plain CoreGraphics call without arguments.
+        unsafe { CGRequestScreenCaptureAccess() }
+    }
+
+    /// The `AVAuthorizationStatus` of audio capture devices.
+    fn microphone_status() -> isize {
+        // SAFETY: `authorizationStatusForMediaType:` is a class method of
+        // `AVCaptureDevice` taking an `AVMediaType` and returning an `NSInteger`, which
+        // is the signature `objc_msgSend` is cast to.
+        unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            let selector = sel_registerName(c"authorizationStatusForMediaType:".as_ptr());
+            if class.is_null() || selector.is_null() {
+                return 0;
+            }
+
+            let send: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void) -> isize =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send(class, selector, AVMediaTypeAudio)
+        }
+    }
+
+    /// The code signing task of this process, released when dropped.
+    struct Task(*mut c_void);
+
+    impl Task {
+        fn current() -> Option<Task> {
+            // SAFETY: creates a task reference owned by the returned value.
+            let task = unsafe { SecTaskCreateFromSelf(std::ptr::null()) };
+            (!task.is_null()).then_some(Task(task))
+        }
+
+        /// Whether the boolean entitlement `name` is set.
+        fn entitlement(&self, name: &str) -> bool {
+            let name = CFString::new(name);
+
+            // SAFETY: the value is returned under the create rule, or null when the
+            // entitlement is missing.
+            unsafe {
+                let value = SecTaskCopyValueForEntitlement(
+                    self.0,
+                    name.as_concrete_TypeRef(),
+                    std::ptr::null_mut(),
+                );
+                if value.is_null() {
+                    return false;
+                }
+
+                CFType::wrap_under_create_rule(value)
+                    .downcast::<CFBoolean>()
+                    .is_some_and(bool::from)
+            }
+        }
+
+        fn hardened_runtime(&self) -> bool {
+            // SAFETY: the task is valid for the lifetime of `self`.
+            unsafe { SecTaskGetCodeSignStatus(self.0) & CS_RUNTIME != 0 }
+        }
+    }
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            // SAFETY: the task was created by `current` and is released once.
+            unsafe { CFRelease(self.0 as *const c_void) }
+        }
+    }
+
+    /// Whether the process runs from an app bundle whose `Info.plist` lacks `key`.
+    ///
+    /// Command line tools have no bundle and are attributed to their terminal, which
+    /// holds the usage descriptions itself.
+    pub fn bundle_lacks(key: &str) -> bool {
+        let info = CFBundle::main_bundle().info_dictionary();
+
+        info.find(&CFString::new("CFBundleIdentifier")).is_some()
+            && info.find(&CFString::new(key)).is_none()
+    }
+
+    /// Whether the process is sandboxed or uses the hardened runtime without the
+    /// entitlement `name`, without which macOS silently denies the device.
+    pub fn lacks_entitlement(name: &str) -> bool {
+        let task = match Task::current() {
+            Some(task) => task,
+            None => return false,
+        };
+
+        let restricted =
+            task.entitlement("com.apple.security.app-sandbox") || task.hardened_runtime();
+        restricted && !task.entitlement(name)
+    }
+
+    /// Whether the microphone was not denied; an undecided permission is asked by the
+    /// system when the capture starts.
+    pub fn microphone_allowed() -> bool {
+        !matches!(
+            microphone_status(),
+            AV_AUTHORIZATION_RESTRICTED | AV_AUTHORIZATION_DENIED
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn granted(permission: Permission, prompt: bool) -> bool {
+    match permission {
+        Permission::Microphone => macos::microphone_allowed(),
+        Permission::ScreenRecording if prompt => macos::request_screen_recording(),
+        Permission::ScreenRecording => macos::screen_recording_granted(),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn granted(_: Permission, _: bool) -> bool {
+    true
+}
+
+impl Permission {
+    /// Whether the permission is granted, without prompting the user.
+    ///
+    /// Always `true` on platforms without such permissions. An undecided microphone
+    /// permission counts as granted, as the system asks for it when the capture starts.
+    pub fn is_granted(self) -> bool {
+        granted(self, false)
+    }
+
+    /// Like `is_granted`, but shows the system prompt for the screen recording
+    /// permission when the user was never asked.
+    ///
+    /// macOS only applies a screen recording permission granted from the prompt after
+    /// the application restarts.
+    pub fn request(self) -> bool {
+        granted(self, true)
+    }
+}
+
+/// Usage description macOS requires in the `Info.plist` of apps capturing the microphone.
+#[cfg(target_os = "macos")]
+const MICROPHONE_USAGE_KEY: &str = "NSMicrophoneUsageDescription";
+/// Entitlement sandboxed and hardened runtime apps need to capture the microphone.
+#[cfg(target_os = "macos")]
+const AUDIO_INPUT_ENTITLEMENT: &str = "com.apple.security.device.audio-input";
+
+/// Reports the packaging mistakes of macOS apps that make the system deny the
+/// microphone without a prompt, which otherwise surface as generic stream failures.
+#[cfg(target_os = "macos")]
+fn check_packaging() -> Result<(), AudioRecorderError> {
+    if macos::bundle_lacks(MICROPHONE_USAGE_KEY) {
+        tracing::error!("The app bundle has no {}", MICROPHONE_USAGE_KEY);
+        return Err(AudioRecorderError::MissingUsageDescription(
+            MICROPHONE_USAGE_KEY,
+        ));
+    }
+
+    if macos::lacks_entitlement(AUDIO_INPUT_ENTITLEMENT) {
+        tracing::error!("The app is not signed with {}", AUDIO_INPUT_ENTITLEMENT);
+        return Err(AudioRecorderError::MissingEntitlement(
+            AUDIO_INPUT_ENTITLEMENT,
+        ));
+    }
+
+    Ok(())
+}
+
+impl Recorder {
+    /// Checks the permissions needed to record, so that a denied permission fails with
+    /// `AudioRecorderError::PermissionDenied` instead of recording silence.
+    ///
+    /// On macOS, an app bundle without `NSMicrophoneUsageDescription`, or a sandboxed
+    /// or hardened runtime app without the `com.apple.security.device.audio-input`
+    /// entitlement, fails with `MissingUsageDescription` or `MissingEntitlement`
+    /// first, as the system denies those apps without asking the user.
+    ///
+    /// The screen recording permission is only needed for the system audio when it is
+    /// captured with ScreenCaptureKit, i.e. on macOS without a named output device.
+    pub fn check_permissions(&self, input_only: bool) -> Result<(), AudioRecorderError> {
+        tracing::debug!("Checking the capture permissions");
+
+        #[cfg(target_os = "macos")]
+        check_packaging()?;
+
+        if !Permission::Microphone.request() {
+            tracing::error!("Microphone permission denied");
+            return Err(AudioRecorderError::PermissionDenied(Permission::Microphone));
+        }
+
+        #[cfg(target_os = "macos")]
+        if !input_only
+            && self.config.output_device().is_none()
+            && cpal::host_from_id(cpal::HostId::ScreenCaptureKit).is_ok()
+            && !Permission::ScreenRecording.request()
+        {
+            tracing::error!("Screen recording permission denied");
+            return Err(AudioRecorderError::PermissionDenied(
+                Permission::ScreenRecording,
+            ));
+        }
+        #[cfg(not(target_os = "macos"))]
+        let _ = input_only;
+
+        Ok(())
+    }
+}
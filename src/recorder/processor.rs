@@ -0,0 +1,88 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use super::{Recorder, chunk::AudioChunk};
+
+/// A custom filter or effect run on every chunk before it is delivered, see
+/// `Recorder::add_processor`.
+///
+/// Processors may rewrite the samples of the chunk in place, or change how many frames
+/// it holds (an emptied chunk is not delivered), but not its layout: the `channels` and
+/// `sample_rate` of the chunk are restored afterwards. They run on the delivery thread,
+/// so a slow processor delays the stream.
+pub trait AudioProcessor: Send {
+    fn process(&mut self, chunk: &mut AudioChunk);
+}
+
+/// The ordered processors of a recorder, shared with the data channel of its recording.
+#[derive(Clone, Default)]
+pub struct ProcessorChain {
+    processors: Arc<Mutex<Vec<Box<dyn AudioProcessor>>>>,
+}
+
+impl fmt::Debug for ProcessorChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessorChain")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl ProcessorChain {
+    pub fn len(&self) -> usize {
+        self.processors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs the chunk through every processor, in the order they were added.
+    pub fn process(&self, chunk: &mut AudioChunk) {
+        let (channels, sample_rate) = (chunk.channels, chunk.sample_rate);
+
+        for processor in self
+            .processors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter_mut()
+        {
+            processor.process(chunk);
+        }
+
+        if (chunk.channels, chunk.sample_rate) != (channels, sample_rate) {
+            tracing::warn!("A processor changed the layout of the chunk, restoring it");
+            chunk.channels = channels;
+            chunk.sample_rate = sample_rate;
+        }
+    }
+}
+
+impl Recorder {
+    /// Appends `processor` to the chain every delivered chunk runs through, after the
+    /// built-in processing (conversion, limiter, silence gate) and before the fingerprint,
+    /// voice detection, analyzers and the data channel.
+    ///
+    /// Processors added while recording join the running recording from its next chunk.
+    pub fn add_processor(&mut self, processor: impl AudioProcessor + 'static) {
+        tracing::debug!("Adding processor {}", self.processors.len());
+        self.processors
+            .processors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(processor));
+    }
+
+    /// Removes all the processors added with `add_processor`.
+    ///
+    /// Takes effect on the next call to `start`; a running recording keeps its
+    /// processors.
+    pub fn clear_processors(&mut self) {
+        self.processors = ProcessorChain::default();
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    io::{ErrorKind, Read},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+use super::errors::AudioRecorderError;
+
+/// Latency requested from the sound server for the record stream.
+const MONITOR_LATENCY_MS: u32 = 20;
+/// Frames read from `parec` at a time.
+const MONITOR_READ_FRAMES: usize = 480;
+
+/// Capture of a PulseAudio/PipeWire monitor source by `parec`, read on its own thread.
+///
+/// `parec` connects its record stream to the source it is given, so the monitor is
+/// selected for this stream only: the environment of the process and its other streams,
+/// e.g. the microphone, keep the default source. The server converts the monitor to the
+/// requested layout.
+pub struct MonitorStream {
+    playing: Arc<AtomicBool>,
+    alive: Arc<AtomicBool>,
+    child: Child,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MonitorStream {
+    /// Starts recording `source`, delivering interleaved samples to `callback` once
+    /// playing.
+    pub fn build(
+        source: &str,
+        sample_rate: u32,
+        channels: u16,
+        mut callback: impl FnMut(&[f32]) + Send + 'static,
+        mut errors: impl FnMut(cpal::StreamError) + Send + 'static,
+    ) -> Result<Self, AudioRecorderError> {
+        let mut child = Command::new("parec")
+            .arg(format!("--device={source}"))
+            .arg(format!("--rate={sample_rate}"))
+            .arg(format!("--channels={channels}"))
+            .arg(format!("--latency-msec={MONITOR_LATENCY_MS}"))
+            .args(["--format=float32le", "--raw"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                tracing::error!("Failed to run parec: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        let mut stdout = match child.stdout.take() {
+            Some(stdout) => stdout,
+            None => {
+                tracing::error!("parec has no standard output");
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AudioRecorderError::DeviceError(
+                    "Failed to read the monitor source",
+                ));
+            }
+        };
+
+        let playing = Arc::new(AtomicBool::new(false));
+        let alive = Arc::new(AtomicBool::new(true));
+        let thread_playing = playing.clone();
+        let thread_alive = alive.clone();
+        let source = source.to_string();
+
+        let spawned = thread::Builder::new()
+            .name("arec-monitor".to_string())
+            .spawn(move || {
+                let frame_size = channels.max(1) as usize * size_of::<f32>();
+                let mut bytes = vec![0u8; MONITOR_READ_FRAMES * frame_size];
+                let mut filled = 0;
+                let mut samples: Vec<f32> = Vec::new();
+
+                loop {
+                    let read = match stdout.read(&mut bytes[filled..]) {
+                        Ok(read) => read,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            tracing::error!("Failed to read the monitor source: {}", e);
+                            0
+                        }
+                    };
+
+                    // parec only exits on its own when the source or the server went away
+                    if read == 0 {
+                        if thread_alive.load(Ordering::SeqCst) {
+                            tracing::error!("Capture of the monitor source {} ended", source);
+                            errors(cpal::StreamError::DeviceNotAvailable);
+                        }
+                        break;
+                    }
+
+                    // a read may end inside a frame, the rest is kept for the next one
+                    filled += read;
+                    let complete = filled - filled % frame_size;
+                    if thread_playing.load(Ordering::SeqCst) {
+                        samples.clear();
+                        samples.extend(
+                            bytes[..complete]
+                                .chunks_exact(size_of::<f32>())
+                                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                        );
+                        callback(&samples);
+                    }
+                    bytes.copy_within(complete..filled, 0);
+                    filled -= complete;
+                }
+            });
+
+        let handle = match spawned {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::error!("Failed to spawn monitor thread: {}", e);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AudioRecorderError::IoError(e));
+            }
+        };
+
+        Ok(MonitorStream {
+            playing,
+            alive,
+            child,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn play(&self) {
+        self.playing.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops delivering the monitor; it keeps being read so it resumes with live audio.
+    pub fn pause(&self) {
+        self.playing.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for MonitorStream {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::SeqCst);
+
+        // the reader thread sees the end of the output once parec is gone
+        if let Err(e) = self.child.kill() {
+            tracing::debug!("Failed to kill parec: {}", e);
+        }
+        if let Err(e) = self.child.wait() {
+            tracing::error!("Failed to wait for parec: {}", e);
+        }
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                tracing::error!("Monitor thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use super::{
+    Recorder,
+    constants::TargetFormat,
+    delivery::ChunkSender,
+    events::{EventBus, RecorderEvent},
+    wakeup::Wakeup,
+};
+
+/// Limits enforced on the delivered stream of a recording, see `Recorder::set_quota`.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use audio_recorder_rs::RecordingQuota;
+///
+/// // a free plan: one hour, warned at 80% and 95%
+/// let quota = RecordingQuota::new()
+///     .max_duration(Duration::from_secs(3600))
+///     .warn_at(0.8)
+///     .warn_at(0.95);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct RecordingQuota {
+    /// Longest recording delivered.
+    pub max_duration: Option<Duration>,
+    /// Largest recording delivered, counted as `f32` samples.
+    pub max_bytes: Option<u64>,
+    /// Fractions of the limit at which a `RecorderEvent::QuotaWarning` is emitted.
+    pub warnings: Vec<f32>,
+}
+
+impl RecordingQuota {
+    pub fn new() -> Self {
+        RecordingQuota::default()
+    }
+
+    /// Stops the recording once it delivered `duration` of audio.
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Stops the recording once it delivered `bytes` of `f32` samples.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Emits a `RecorderEvent::QuotaWarning` once `fraction` (e.g. `0.9`) of the limit
+    /// is used.
+    pub fn warn_at(mut self, fraction: f32) -> Self {
+        self.warnings.push(fraction);
+        self
+    }
+}
+
+/// Enforces a `RecordingQuota` on the chunks of a data channel.
+pub struct QuotaTracker {
+    /// Frames the quota allows, the lower of its limits.
+    limit: u64,
+    /// Frames delivered so far on this data channel.
+    frames: u64,
+    /// Frame counts at which a warning is still due, in order.
+    warnings: Vec<u64>,
+    sample_rate: u32,
+    channels: usize,
+    events: EventBus,
+    recording_signal: Arc<AtomicBool>,
+    stop_wakeup: Wakeup,
+}
+
+impl QuotaTracker {
+    fn duration(&self, frames: u64) -> Duration {
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
+
+    /// Truncates `data` to what the quota still allows, reporting the warnings crossed
+    /// and stopping the recording once the quota is used up.
+    pub fn admit(&mut self, data: &mut Vec<TargetFormat>) {
+        if self.frames >= self.limit {
+            data.clear();
+            return;
+        }
+
+        let allowed = ((self.limit - self.frames) as usize).saturating_mul(self.channels);
+        data.truncate(allowed);
+        self.frames += (data.len() / self.channels) as u64;
+
+        let frames = self.frames;
+        let crossed = self.warnings.iter().take_while(|w| **w <= frames).count();
+        if crossed > 0 {
+            self.warnings.drain(..crossed);
+            tracing::warn!("Recording quota almost used: {} frames", frames);
+            self.events.emit(RecorderEvent::QuotaWarning {
+                delivered: self.duration(frames),
+                limit: self.duration(self.limit),
+            });
+        }
+
+        if self.frames >= self.limit {
+            tracing::warn!("Recording quota reached, stopping the recording");
+            self.recording_signal.store(false, Ordering::SeqCst);
+            self.stop_wakeup.notify();
+            self.events.emit(RecorderEvent::QuotaReached {
+                limit: self.duration(self.limit),
+            });
+        }
+    }
+}
+
+impl Recorder {
+    /// Enforces `quota` on the delivered stream of every recording (or `None` to record
+    /// without limits), so products can enforce plan limits at the capture layer.
+    ///
+    /// The stream ends exactly at the limit: the last chunk is cut short, the recording
+    /// is stopped and a `RecorderEvent::QuotaReached` is emitted. With separate sources,
+    /// each stream is limited on its own.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_quota(&mut self, quota: Option<RecordingQuota>) {
+        self.quota = quota;
+    }
+
+    /// Attaches the quota tracker to the data channel of a stream with this layout.
+    pub fn apply_quota(&self, sender: &mut ChunkSender, sample_rate: u32, channels: u16) {
+        let quota = match &self.quota {
+            Some(quota) => quota,
+            None => return,
+        };
+
+        let frame_bytes = (size_of::<TargetFormat>() * channels.max(1) as usize) as u64;
+        let limits = [
+            quota
+                .max_duration
+                .map(|d| (d.as_secs_f64() * sample_rate as f64) as u64),
+            quota.max_bytes.map(|bytes| bytes / frame_bytes),
+        ];
+        let limit = match limits.into_iter().flatten().min() {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let mut warnings: Vec<u64> = quota
+            .warnings
+            .iter()
+            .filter(|fraction| (0.0..1.0).contains(*fraction))
+            .map(|fraction| (limit as f64 * *fraction as f64) as u64)
+            .collect();
+        warnings.sort_unstable();
+
+        tracing::debug!("Limiting the recording to {} frames", limit);
+        sender.quota = Some(QuotaTracker {
+            limit,
+            frames: 0,
+            warnings,
+            sample_rate,
+            channels: channels.max(1) as usize,
+            events: self.events.clone(),
+            recording_signal: self.recording_signal.clone(),
+            stop_wakeup: self.stop_wakeup.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stereo tracker at 1 kHz allowing `limit` frames.
+    fn tracker(limit: u64, warnings: Vec<u64>) -> QuotaTracker {
+        QuotaTracker {
+            limit,
+            frames: 0,
+            warnings,
+            sample_rate: 1000,
+            channels: 2,
+            events: EventBus::default(),
+            recording_signal: Arc::new(AtomicBool::new(true)),
+            stop_wakeup: Wakeup::default(),
+        }
+    }
+
+    fn millis(duration: Duration) -> u128 {
+        (duration.as_secs_f64() * 1000.0).round() as u128
+    }
+
+    #[test]
+    fn cuts_the_last_chunk_at_the_limit() {
+        let mut quota = tracker(100, Vec::new());
+        let events = quota.events.subscribe();
+
+        let mut data = vec![0.5; 60 * 2];
+        quota.admit(&mut data);
+        assert_eq!(data.len(), 60 * 2);
+        assert!(quota.recording_signal.load(Ordering::SeqCst));
+
+        let mut data = vec![0.5; 60 * 2];
+        quota.admit(&mut data);
+        assert_eq!(data.len(), 40 * 2);
+        assert!(!quota.recording_signal.load(Ordering::SeqCst));
+        assert!(quota.stop_wakeup.wait(Duration::ZERO));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(RecorderEvent::QuotaReached { limit }) if millis(limit) == 100
+        ));
+
+        let mut data = vec![0.5; 10 * 2];
+        quota.admit(&mut data);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn chunk_ending_exactly_at_the_limit_is_kept_whole() {
+        let mut quota = tracker(100, Vec::new());
+
+        let mut data = vec![0.5; 100 * 2];
+        quota.admit(&mut data);
+
+        assert_eq!(data.len(), 100 * 2);
+        assert!(!quota.recording_signal.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn warns_once_per_crossed_threshold() {
+        let mut quota = tracker(100, vec![50, 90]);
+        let events = quota.events.subscribe();
+
+        for frames in [60, 20] {
+            quota.admit(&mut vec![0.5; frames * 2]);
+        }
+        let warnings: Vec<u128> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                RecorderEvent::QuotaWarning { delivered, .. } => Some(millis(delivered)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(warnings, vec![60]);
+
+        quota.admit(&mut vec![0.5; 20 * 2]);
+        let events: Vec<RecorderEvent> = events.try_iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                RecorderEvent::QuotaWarning { .. },
+                RecorderEvent::QuotaReached { .. }
+            ]
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use cpal::Sample;
+use crossbeam_channel::Sender;
+
+use super::{
+    Recorder,
+    constants::{CustomSample, TargetFormat},
+    errors::AudioRecorderError,
+};
+
+/// Tees an untouched source stream into a raw file.
+///
+/// Samples are converted to `TargetFormat` and written as interleaved little-endian
+/// values by a dedicated writer thread, so the capture callback never touches the disk.
+/// The file name carries the sample rate and channel count needed to replay it, e.g.
+/// `input_48000hz_2ch.f32`.
+pub struct RawTee {
+    tx: Sender<Vec<TargetFormat>>,
+}
+
+impl RawTee {
+    pub fn write<S: CustomSample>(&self, data: &[S]) {
+        let parsed: Vec<TargetFormat> =
+            data.iter().map(|s| s.to_sample::<TargetFormat>()).collect();
+
+        if let Err(e) = self.tx.send(parsed) {
+            tracing::error!("Failed to send raw capture data: {}", e);
+        }
+    }
+}
+
+impl Recorder {
+    /// Enables (or disables with `None`) the raw capture debug mode.
+    ///
+    /// When enabled, every source stream is additionally written to `dir` exactly as
+    /// delivered by the device (before channel downmix, resampling and mixing), so
+    /// mixing and resampling issues can be reproduced offline from submitted captures.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_raw_capture_dir(&mut self, dir: Option<PathBuf>) {
+        self.raw_capture_dir = dir;
+    }
+
+    /// Creates a raw tee for the given source if the raw capture debug mode is enabled.
+    pub fn raw_tee(
+        &self,
+        source: &str,
+        config: &cpal::SupportedStreamConfig,
+    ) -> Result<Option<RawTee>, AudioRecorderError> {
+        let dir = match &self.raw_capture_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        std::fs::create_dir_all(dir).map_err(|e| {
+            tracing::error!("Failed to create raw capture directory: {}", e);
+            AudioRecorderError::IoError(e)
+        })?;
+
+        let path = dir.join(format!(
+            "{source}_{}hz_{}ch.f32",
+            config.sample_rate().0,
+            config.channels()
+        ));
+        tracing::debug!("Teeing raw {} stream to {:?}", source, path);
+
+        let file = File::create(&path).map_err(|e| {
+            tracing::error!("Failed to create raw capture file: {}", e);
+            AudioRecorderError::IoError(e)
+        })?;
+
+        let (tx, rx) = crossbeam_channel::unbounded::<Vec<TargetFormat>>();
+
+        self.workers().spawn("arec-raw-capture", move || {
+            let mut writer = BufWriter::new(file);
+
+            while let Ok(data) = rx.recv() {
+                for sample in data {
+                    if let Err(e) = writer.write_all(&sample.to_le_bytes()) {
+                        tracing::error!("Failed to write raw capture data: {}", e);
+                        return;
+                    }
+                }
+            }
+
+            if let Err(e) = writer.flush() {
+                tracing::error!("Failed to flush raw capture file: {}", e);
+            }
+        })?;
+
+        Ok(Some(RawTee { tx }))
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, OnceLock, Weak},
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, TryRecvError};
+#[cfg(feature = "async")]
+use futures::task::AtomicWaker;
+
+use super::{
+    chunk::AudioChunk,
+    delivery::{StreamMessage, StreamSummary},
+};
+
+/// Why a receive on an `AudioReceiver` returned without a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AudioRecvError {
+    /// No chunk is available yet, but the recording is still running.
+    Empty,
+    /// No chunk arrived before the timeout elapsed, but the recording is still running.
+    Timeout,
+    /// The recording has stopped and every chunk has been received.
+    Ended,
+}
+
+impl Display for AudioRecvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioRecvError::Empty => write!(f, "No audio data available yet"),
+            AudioRecvError::Timeout => write!(f, "Timed out waiting for audio data"),
+            AudioRecvError::Ended => write!(f, "Recording has ended"),
+        }
+    }
+}
+
+/// The stream of recorded audio returned by `Recorder::start`.
+///
+/// # End of stream
+///
+/// Once the recorder is stopped, the capture threads flush and release their streams,
+/// then send a final marker carrying the `StreamSummary` of the recording. Chunks still
+/// in flight can be received as usual, after which every receive returns
+/// `AudioRecvError::Ended` and `summary` reports the total frames and duration. This lets
+/// consumers tell "no data yet" apart from "recording ended" without polling
+/// `Recorder::get_is_recording`, and file writers know they received everything before
+/// finalizing.
+#[derive(Debug, Clone)]
+pub struct AudioReceiver {
+    receiver: Receiver<StreamMessage>,
+    summary: Arc<OnceLock<StreamSummary>>,
+    /// Shared by every clone, so the sending side can tell once they are all dropped.
+    consumer: Arc<()>,
+    #[cfg(feature = "async")]
+    waker: Arc<AtomicWaker>,
+}
+
+impl AudioReceiver {
+    pub fn new(receiver: Receiver<StreamMessage>) -> Self {
+        AudioReceiver {
+            receiver,
+            summary: Arc::new(OnceLock::new()),
+            consumer: Arc::new(()),
+            #[cfg(feature = "async")]
+            waker: Arc::new(AtomicWaker::new()),
+        }
+    }
+
+    /// A handle that is dead once this receiver and all its clones are dropped.
+    ///
+    /// The sending side may hold a clone of the channel receiver (e.g. to evict chunks),
+    /// which keeps the channel connected, so it checks this instead.
+    pub fn consumer(&self) -> Weak<()> {
+        Arc::downgrade(&self.consumer)
+    }
+
+    /// The waker woken by the sending side whenever a message is sent.
+    #[cfg(feature = "async")]
+    pub fn waker(&self) -> Arc<AtomicWaker> {
+        self.waker.clone()
+    }
+
+    /// Blocks until a chunk is available or the recording has ended.
+    pub fn recv(&self) -> Result<AudioChunk, AudioRecvError> {
+        match self.receiver.recv() {
+            Ok(message) => self.unpack(message),
+            Err(_) => Err(AudioRecvError::Ended),
+        }
+    }
+
+    /// Blocks until a chunk is available, the recording has ended, or `timeout` elapses.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<AudioChunk, AudioRecvError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(message) => self.unpack(message),
+            Err(RecvTimeoutError::Timeout) => Err(AudioRecvError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(AudioRecvError::Ended),
+        }
+    }
+
+    /// Returns a chunk if one is available, without blocking.
+    pub fn try_recv(&self) -> Result<AudioChunk, AudioRecvError> {
+        match self.receiver.try_recv() {
+            Ok(message) => self.unpack(message),
+            Err(TryRecvError::Empty) => Err(AudioRecvError::Empty),
+            Err(TryRecvError::Disconnected) => Err(AudioRecvError::Ended),
+        }
+    }
+
+    /// Iterates over the chunks available right now, without blocking.
+    ///
+    /// The iterator can be created again later to resume where it stopped.
+    pub fn try_iter(&self) -> impl Iterator<Item = AudioChunk> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    /// Iterates over every chunk, blocking between them, until the recording has ended.
+    pub fn iter(&self) -> impl Iterator<Item = AudioChunk> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Number of chunks sent but not received yet, e.g. the backlog of a file writer.
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Whether no chunk is waiting to be received.
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
+    /// Summary of the recording, available once the end of stream has been received.
+    pub fn summary(&self) -> Option<StreamSummary> {
+        self.summary.get().cloned()
+    }
+
+    fn unpack(&self, message: StreamMessage) -> Result<AudioChunk, AudioRecvError> {
+        match message {
+            StreamMessage::Data(data) => Ok(data),
+            StreamMessage::End(summary) => {
+                self.summary.get_or_init(|| summary);
+                Err(AudioRecvError::Ended)
+            }
+        }
+    }
+}
+
+/// Blocking iterator over the chunks of an `AudioReceiver`.
+pub struct IntoIter {
+    receiver: AudioReceiver,
+}
+
+impl Iterator for IntoIter {
+    type Item = AudioChunk;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl IntoIterator for AudioReceiver {
+    type Item = AudioChunk;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { receiver: self }
+    }
+}
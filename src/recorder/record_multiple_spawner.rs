@@ -1,93 +1,86 @@
-use cpal::traits::DeviceTrait;
-use crossbeam_channel::Receiver;
-
 use super::{
     Recorder,
-    constants::{CustomSample, ResampleTargetStream, TargetFormat},
+    constants::{AudioSource, CustomSample},
     errors::AudioRecorderError,
+    helpers::RateConversion,
+    receiver::AudioReceiver,
 };
 
 impl Recorder {
+    /// Sets the sample rate the delivered stream is resampled to (or `None` to deliver
+    /// the device rate, or the lowest of the device rates when recording from both).
+    ///
+    /// Each source is resampled independently, so the delivered rate does not depend on
+    /// which device happens to run slower.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Resamples the delivered stream to `sample_rate` on every recording path, e.g.
+    /// 16 kHz for speech recognition or 48 kHz for broadcast.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_target_sample_rate(&mut self, sample_rate: u32) {
+        self.set_sample_rate(Some(sample_rate));
+    }
+
     pub fn record_multiple<T, U>(
         &mut self,
         input_device: cpal::Device,
         output_device: cpal::Device,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError>
+        input_config: cpal::SupportedStreamConfig,
+        output_config: cpal::SupportedStreamConfig,
+    ) -> Result<AudioReceiver, AudioRecorderError>
     where
         T: CustomSample + 'static,
         U: CustomSample + 'static,
     {
         tracing::debug!("Record multiple started");
 
-        tracing::debug!("Collecting input and output configs");
-        // using the same config for input and output
-        let input_config = match input_device.default_input_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get input config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get input config",
-                ));
-            }
-        };
-        let output_config = match output_device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                tracing::error!("Failed to get output config: {}", e);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get output config",
-                ));
-            }
-        };
-
         tracing::debug!("Calculating resampling target");
-        // calculate the resampling target
         let input_sample_rate = input_config.sample_rate().0;
         let output_sample_rate = output_config.sample_rate().0;
 
-        let (resampler_target, target_rate, origin_rate) =
-            match input_sample_rate.cmp(&output_sample_rate) {
-                std::cmp::Ordering::Less => (
-                    ResampleTargetStream::Output,
-                    input_sample_rate as usize,
-                    output_sample_rate as usize,
-                ), // resample output to achieve input rate
-                std::cmp::Ordering::Equal => (
-                    ResampleTargetStream::None,
-                    input_sample_rate as usize,
-                    output_sample_rate as usize,
-                ), // no resampling
-                std::cmp::Ordering::Greater => (
-                    ResampleTargetStream::Input,
-                    output_sample_rate as usize,
-                    input_sample_rate as usize,
-                ), // resample input to achieve output rate
-            };
+        // resample both sources to the chosen rate, or the slower source's rate by default
+        let target_rate = self
+            .sample_rate
+            .unwrap_or(input_sample_rate.min(output_sample_rate));
 
         tracing::debug!("Setting up the recorder");
-        self.target_sample_rate = Some(target_rate as u32);
-        self.channels = Some(2);
+        self.target_sample_rate = Some(target_rate);
+        self.channels = Some(
+            self.mix_layout(&input_config, &output_config)
+                .output_channels(),
+        );
         self.sample_size = Some(input_config.sample_format().sample_size() as u32);
+        self.apply_standard_output();
+
+        // the standard output converts the mix again, so report the delivered rate
+        let delivered_rate = self.target_sample_rate.unwrap_or(target_rate);
+        self.report_resampling(
+            AudioSource::Input,
+            RateConversion::between(input_sample_rate, delivered_rate),
+        );
+        self.report_resampling(
+            AudioSource::Output,
+            RateConversion::between(output_sample_rate, delivered_rate),
+        );
 
         tracing::debug!("Config: {:?}", self);
 
         // start recording
-        match resampler_target {
-            ResampleTargetStream::None => {
-                self.without_resampler::<T, U>(input_device, output_device)
-            }
-            ResampleTargetStream::Output => self.with_output_resampler::<T, U>(
-                input_device,
-                output_device,
-                target_rate,
-                origin_rate,
-            ),
-            ResampleTargetStream::Input => self.with_input_resampler::<T, U>(
+        if input_sample_rate == target_rate && output_sample_rate == target_rate {
+            self.without_resampler::<T, U>(input_device, output_device, input_config, output_config)
+        } else {
+            self.with_resampler::<T, U>(
                 input_device,
                 output_device,
-                target_rate,
-                origin_rate,
-            ),
+                input_config,
+                output_config,
+                target_rate as usize,
+            )
         }
     }
 }
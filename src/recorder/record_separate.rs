@@ -0,0 +1,103 @@
+use std::sync::PoisonError;
+
+use cpal::traits::DeviceTrait;
+
+use super::{
+    Recorder,
+    constants::{AudioSource, STANDARD_SAMPLE_RATE},
+    errors::AudioRecorderError,
+    get_default_device::{get_default_input_device, get_default_output_device},
+    helpers::RateConversion,
+    receiver::AudioReceiver,
+};
+
+/// The independent streams returned by `Recorder::start_separate`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SourceReceivers {
+    /// The microphone, in its device layout.
+    pub mic: AudioReceiver,
+    /// The system audio, in its device layout.
+    pub system: AudioReceiver,
+}
+
+impl Recorder {
+    /// Records the default input and output devices as two independent streams instead of
+    /// mixing them, e.g. to run diarization or speech recognition on the microphone and
+    /// the system audio separately.
+    ///
+    /// Each stream keeps its device sample rate and channels, reported by its chunks;
+    /// `get_config` describes the microphone stream.
+    #[tracing::instrument]
+    pub fn start_separate(&mut self) -> Result<SourceReceivers, AudioRecorderError> {
+        tracing::info!("Starting separate audio recording");
+        self.begin()?;
+
+        if let Err(e) = self.check_permissions(false) {
+            self.stop();
+            return Err(e);
+        }
+
+        let receivers = self.record_separate();
+        if receivers.is_err() {
+            self.stop();
+        }
+        receivers
+    }
+
+    fn record_separate(&mut self) -> Result<SourceReceivers, AudioRecorderError> {
+        let input_device = get_default_input_device()?;
+        let output_device = get_default_output_device()?;
+
+        tracing::debug!(
+            "Using input device: {:?}",
+            input_device.name().unwrap_or(String::from("Unknown"))
+        );
+        tracing::debug!(
+            "Using output device: {:?}",
+            output_device.name().unwrap_or(String::from("Unknown"))
+        );
+
+        let input_config = self.input_config(&input_device)?;
+        let output_config = self.output_config(&output_device)?;
+
+        tracing::debug!("Setting up the recorder");
+        self.target_sample_rate = Some(self.sample_rate.unwrap_or(input_config.sample_rate().0));
+        self.channels = Some(input_config.channels());
+        self.sample_size = Some(input_config.sample_format().sample_size() as u32);
+        self.apply_standard_output();
+
+        // each source is delivered on its own channel, at its own rate unless converted
+        let delivered_rate = |rate: u32| match self.standard_output {
+            true => STANDARD_SAMPLE_RATE,
+            false => self.sample_rate.unwrap_or(rate),
+        };
+        let input_rate = input_config.sample_rate().0;
+        let output_rate = output_config.sample_rate().0;
+        let input_resampling = RateConversion::between(input_rate, delivered_rate(input_rate));
+        let output_resampling = RateConversion::between(output_rate, delivered_rate(output_rate));
+        self.report_resampling(AudioSource::Input, input_resampling);
+        self.report_resampling(AudioSource::Output, output_resampling);
+        tracing::debug!("Config: {:?}", self);
+
+        let mic = self.capture_source(
+            AudioSource::Input,
+            "arec-input",
+            input_device,
+            input_config,
+            self.device_monitor(AudioSource::Input),
+        )?;
+        let system = self.capture_source(
+            AudioSource::Output,
+            "arec-system",
+            output_device,
+            output_config,
+            self.device_monitor(AudioSource::Output),
+        )?;
+
+        // two data channels cannot be handed off as one
+        *self.handoff.lock().unwrap_or_else(PoisonError::into_inner) = None;
+
+        Ok(SourceReceivers { mic, system })
+    }
+}
@@ -1,67 +1,116 @@
 use std::{
-    thread::{self},
-    time::Duration,
+    sync::{
+        Arc, Mutex, PoisonError, TryLockError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Instant,
 };
 
-use cpal::{
-    Sample,
-    traits::{DeviceTrait, StreamTrait},
-};
-use crossbeam_channel::Receiver;
+use cpal::traits::{DeviceTrait, StreamTrait};
 
 use super::{
-    constants::{CLOCK_DELAY, TargetFormat},
-    errors::AudioRecorderError,
+    config::LatencyMode,
+    constants::{AudioSource, CustomSample, ROBUST_RETRY_BACKOFF, ROBUST_STREAM_RETRIES},
+    device_monitor::DeviceMonitor,
+    errors::{AudioRecorderError, device_name},
+    events::RecorderEvent,
+    handoff::SharedSender,
+    helpers::RateConversion,
+    receiver::AudioReceiver,
+    source_tap::{SourceTap, guard_callback},
+    worker,
 };
 
 use super::Recorder;
 
+/// The state fed by the capture callback of a source, kept across stream rebuilds.
+struct SourceCapture {
+    tap: SourceTap,
+    tx: SharedSender,
+}
+
+impl SourceCapture {
+    fn process<S: CustomSample>(&mut self, data: &[S], info: &cpal::InputCallbackInfo) {
+        let started = Instant::now();
+
+        guard_callback(self.tap.source(), || {
+            let parsed = self.tap.process(data, Some(info));
+            if let Err(e) = self.tx.send(parsed) {
+                tracing::error!("Failed to send data: {}", e);
+            }
+        });
+
+        self.tap.finish_callback(started, data.len());
+    }
+
+    /// Adapts the capture to a stream rebuilt on another device.
+    fn reconfigure(
+        &mut self,
+        config: &cpal::SupportedStreamConfig,
+    ) -> Result<(), AudioRecorderError> {
+        self.tap.reconfigure(config);
+        self.tx
+            .reconfigure(config.sample_rate().0, config.channels())
+    }
+}
+
 /// Macro: build an input stream for every numeric CPAL `SampleFormat` you list.
 ///
 /// * `$device`   – the `cpal::Device`.
-/// * `$config`   – the (mutable/owned) `cpal::StreamConfig`.
+/// * `$config`   – the `cpal::StreamConfig` requested from the device.
 /// * `$fmt`      – the **runtime** sample-format you want to match on.
-/// * `$tx`       – an `mpsc::Sender<Vec<TargetFormat>>`.
+/// * `$capture`  – the shared `SourceCapture` processing the device data.
+/// * `$errors`   – the error callback of the stream.
 ///
-/// After those four, give the *compile-time* mapping from enum variant → Rust
+/// After those five, give the *compile-time* mapping from enum variant → Rust
 /// primitive type (`I16 => i16`, etc.).  
-/// It expands to an **expression** that evaluates to `Result<cpal::Stream, String>`.
+/// It expands to an **expression** that evaluates to
+/// `Result<cpal::Stream, AudioRecorderError>`.
 ///
 macro_rules! build_input_stream_for {
     (
         $device:expr,            // input  CPAL device
         $config:expr,            // config
         $fmt:expr,               // runtime SampleFormat
-        $tx:expr,                // mpsc::Sender<Vec<TargetFormat>>
+        $capture:expr,           // Arc<Mutex<SourceCapture>>
+        $errors:expr,            // stream error callback
         $( $variant:ident => $ty:ty ),+ $(,)?   // mapping table
     ) => {{
         match $fmt {
             $(
                 cpal::SampleFormat::$variant => {
                     // Each branch has the right slice type automatically.
-                    let tx_clone = $tx.clone();
+                    let capture = $capture;
                     $device.build_input_stream(
-                        &($config).clone().into(),
-                        move |data: &[$ty], _| {
-                            // fast, idiomatic conversion
-                            let parsed: Vec<TargetFormat> =
-                                data.iter().map(|s| s.to_sample::<TargetFormat>()).collect();
-                            if let Err(e) = tx_clone.send(parsed) {
-                                tracing::error!("Failed to send data: {}", e);
+                        &$config,
+                        // never wait on the real-time thread: the capture is only
+                        // locked elsewhere to reconfigure it, once this stream is dropped
+                        move |data: &[$ty], info: &cpal::InputCallbackInfo| match capture.try_lock() {
+                            Ok(mut capture) => capture.process(data, info),
+                            Err(TryLockError::Poisoned(e)) => e.into_inner().process(data, info),
+                            Err(TryLockError::WouldBlock) => {
+                                tracing::warn!("Source capture busy, skipping a callback")
                             }
                         },
-                        Recorder::err_fn,
+                        $errors,
                         None,
                     )
                     .map_err(|e| {
                         tracing::error!("Failed to build input stream: {}", e);
-                        "Failed to build input stream".to_string()
+                        AudioRecorderError::BuildStream {
+                            device: device_name(&$device),
+                            source: e,
+                        }
                     })
                 }
             )+
             other => {
                 tracing::error!("Unsupported sample format: {:?}", other);
-                Err("Unsupported sample format".into())
+                Err(AudioRecorderError::UnsupportedSampleFormat {
+                    device: device_name(&$device),
+                    format: other,
+                })
             }
         }
     }};
@@ -71,7 +120,8 @@ impl Recorder {
     pub fn record_single_device(
         &mut self,
         device: cpal::Device,
-    ) -> Result<Receiver<Vec<TargetFormat>>, AudioRecorderError> {
+        monitor: Option<DeviceMonitor>,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
         tracing::info!("Record single device started");
 
         tracing::debug!(
@@ -79,73 +129,228 @@ impl Recorder {
             device.name().unwrap_or(String::from("Unknown"))
         );
 
-        let config = match device.default_input_config() {
+        let config = match self.input_config(&device) {
             Ok(config) => config,
-            Err(error) => {
-                tracing::error!("Failed to get default input config: {}", error);
-                return Err(AudioRecorderError::DeviceError(
-                    "Failed to get default input config",
-                ));
+            Err(e) => {
+                self.stop();
+                return Err(e);
             }
         };
 
         tracing::debug!("Setting up the recorder");
-        self.target_sample_rate = Some(config.sample_rate().0);
+        self.target_sample_rate = Some(self.sample_rate.unwrap_or(config.sample_rate().0));
         self.channels = Some(config.channels());
         self.sample_size = Some(config.sample_format().sample_size() as u32);
+        self.apply_standard_output();
+        if let Some(delivered_rate) = self.target_sample_rate {
+            self.report_resampling(
+                AudioSource::Input,
+                RateConversion::between(config.sample_rate().0, delivered_rate),
+            );
+        }
+        self.report_resampling(AudioSource::Output, None);
         tracing::debug!("Config: {:?}", self);
 
+        match self.capture_source(AudioSource::Input, "arec-input", device, config, monitor) {
+            Ok(receiver) => Ok(receiver),
+            Err(e) => {
+                self.stop();
+                Err(e)
+            }
+        }
+    }
+
+    /// Captures a single source on a worker thread named `thread_name`, delivering the
+    /// device stream as is on its own data channel.
+    ///
+    /// With a `monitor`, the stream is rebuilt on the new default device of the source
+    /// whenever it changes.
+    ///
+    /// Returns once the stream is playing, or with the error that prevented it.
+    pub fn capture_source(
+        &self,
+        source: AudioSource,
+        thread_name: &str,
+        device: cpal::Device,
+        config: cpal::SupportedStreamConfig,
+        mut monitor: Option<DeviceMonitor>,
+    ) -> Result<AudioReceiver, AudioRecorderError> {
+        let input_tap = self.source_tap(source, &config)?;
+
         // Run the input stream on a separate thread.
         tracing::debug!("Clone recording signal mutex");
         let recording_signal = self.recording_signal.clone();
 
         // A signal to pass on the stream
         tracing::debug!("Create channel for passing data");
-        let (sync_tx, sync_rx) = crossbeam_channel::unbounded::<Vec<TargetFormat>>();
+        let (sync_tx, sync_rx) = self.shared_channel(config.sample_rate().0, config.channels())?;
+        let capture = Arc::new(Mutex::new(SourceCapture {
+            tap: input_tap,
+            tx: sync_tx,
+        }));
+
+        let recorder_config = self.config.clone();
+        let poll_interval = recorder_config.poll_interval();
+        let stop_wakeup = self.stop_wakeup.clone();
+        let events = self.events.clone();
+        let robust = recorder_config.latency_mode() == LatencyMode::Robust;
+        // raised by the error callback, so that robust recordings rebuild the stream
+        let stream_failed = Arc::new(AtomicBool::new(false));
+        let stream_errors = {
+            let mut errors = self.stream_errors(source);
+            let stream_failed = stream_failed.clone();
+            let stop_wakeup = stop_wakeup.clone();
+            move |e| {
+                stream_failed.store(true, Ordering::SeqCst);
+                stop_wakeup.notify();
+                errors(e)
+            }
+        };
+
+        let (startup, started) = worker::startup();
+        let mut startup = Some(startup);
+        let workers = self.workers();
+        let worker_name = thread_name.to_string();
 
         tracing::debug!("Begin recording...");
-        thread::spawn(move || {
-            let stream = match build_input_stream_for!(
-                device,
-                config,
-                config.sample_format(),
-                sync_tx,
-                I8  => i8,
-                I16 => i16,
-                I32 => i32,
-                I64 => i64,
-                U8  => u8,
-                U16 => u16,
-                U32 => u32,
-                U64 => u64,
-                F32 => f32,
-                F64 => f64
-            ) {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!("Failed to build input stream: {}", e);
-                    recording_signal.store(false, std::sync::atomic::Ordering::SeqCst);
-                    return Err("Failed to build input stream".to_string());
+        self.workers().spawn(thread_name, move || {
+            let (mut device, mut config) = (device, config);
+            let mut retries = 0;
+
+            loop {
+                stream_failed.store(false, Ordering::SeqCst);
+
+                let stream = match build_input_stream_for!(
+                    device,
+                    recorder_config.stream_config(&config),
+                    config.sample_format(),
+                    capture.clone(),
+                    stream_errors.clone(),
+                    I8  => i8,
+                    I16 => i16,
+                    I32 => i32,
+                    I64 => i64,
+                    U8  => u8,
+                    U16 => u16,
+                    U32 => u32,
+                    U64 => u64,
+                    F32 => f32,
+                    F64 => f64
+                ) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        tracing::error!("Failed to build input stream: {}", e);
+                        if robust && retries < ROBUST_STREAM_RETRIES {
+                            retries += 1;
+                            tracing::warn!("Retrying the {:?} stream ({})", source, retries);
+                            thread::sleep(ROBUST_RETRY_BACKOFF);
+                            continue;
+                        }
+
+                        workers.fail(&worker_name, &mut startup, e);
+                        return;
+                    }
+                };
+
+                tracing::info!("Stream started");
+                if let Err(e) = stream.play() {
+                    tracing::error!("Failed to play stream: {}", e);
+                    if robust && retries < ROBUST_STREAM_RETRIES {
+                        retries += 1;
+                        tracing::warn!("Retrying the {:?} stream ({})", source, retries);
+                        drop(stream);
+                        thread::sleep(ROBUST_RETRY_BACKOFF);
+                        continue;
+                    }
+
+                    workers.fail(
+                        &worker_name,
+                        &mut startup,
+                        AudioRecorderError::PlayStream {
+                            device: device_name(&device),
+                            source: e,
+                        },
+                    );
+                    return;
+                };
+
+                // only the first stream is waited for, rebuilt ones report by events
+                if let Some(startup) = startup.take() {
+                    startup.ready();
                 }
-            };
 
-            tracing::info!("Stream started");
-            if let Err(e) = stream.play() {
-                tracing::error!("Failed to play stream: {}", e);
-                recording_signal.store(false, std::sync::atomic::Ordering::SeqCst);
-                return Err("Failed to play stream".to_string());
-            };
+                retries = 0;
 
-            while recording_signal.load(std::sync::atomic::Ordering::SeqCst) {
-                thread::sleep(Duration::from_millis(CLOCK_DELAY as _));
-            }
+                let failed = || robust && stream_failed.load(Ordering::SeqCst);
+                let mut changed = None;
+                // woken early when stopped or the stream fails, the device monitor is
+                // polled at the interval
+                while changed.is_none() && recording_signal.load(Ordering::SeqCst) && !failed() {
+                    stop_wakeup.wait(poll_interval);
+                    changed = monitor.as_mut().and_then(DeviceMonitor::poll);
+                }
+
+                tracing::debug!("Dropping stream");
+                drop(stream);
 
-            tracing::debug!("Dropping stream");
-            drop(stream);
+                if failed() && recording_signal.load(Ordering::SeqCst) {
+                    tracing::warn!("The {:?} stream failed, rebuilding it", source);
+                    thread::sleep(ROBUST_RETRY_BACKOFF);
+
+                    // the failure may have come with a new default device
+                    let reopened = monitor.as_ref().map(|monitor| {
+                        monitor.open().and_then(|(device, config)| {
+                            capture
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .reconfigure(&config)?;
+                            Ok((device, config))
+                        })
+                    });
+
+                    match reopened {
+                        Some(Ok(reopened)) => (device, config) = reopened,
+                        Some(Err(e)) => {
+                            tracing::warn!("Failed to reopen the default device: {}", e)
+                        }
+                        None => {}
+                    }
+                    continue;
+                }
+
+                let (name, monitor) = match (changed, &monitor) {
+                    (Some(name), Some(monitor)) => (name, monitor),
+                    _ => break,
+                };
+
+                tracing::info!("Rebuilding the {:?} stream on {:?}", source, name);
+                events.emit(RecorderEvent::DeviceChanged {
+                    source,
+                    device: name,
+                });
+
+                let reopened = monitor.open().and_then(|(device, config)| {
+                    capture
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .reconfigure(&config)?;
+                    Ok((device, config))
+                });
+
+                match reopened {
+                    Ok(reopened) => (device, config) = reopened,
+                    Err(e) => {
+                        tracing::error!("Failed to switch to the new device: {}", e);
+                        workers.fail(&worker_name, &mut startup, e);
+                        return;
+                    }
+                }
+            }
 
             tracing::info!("Recording stopped");
-            Ok(())
-        });
+        })?;
+
+        started.wait()?;
 
         Ok(sync_rx)
     }
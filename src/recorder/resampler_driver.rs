@@ -0,0 +1,403 @@
+use ringbuf::traits::{Consumer, Observer, Producer};
+use rubato::{
+    FastFixedIn, FftFixedIn, PolynomialDegree, Resampler, SincFixedIn, SincInterpolationParameters,
+    SincInterpolationType, WindowFunction, calculate_cutoff,
+};
+
+use super::{
+    config::ResamplerQuality,
+    constants::{RESAMPLER_SINC_LEN, RESAMPLER_SINC_OVERSAMPLING, TargetFormat},
+    errors::AudioRecorderError,
+};
+
+/// The rubato resampler of a `ResamplerQuality`.
+enum QualityResampler {
+    Linear(FastFixedIn<TargetFormat>),
+    /// Boxed, being much larger than the interpolators.
+    Fft(Box<FftFixedIn<TargetFormat>>),
+    Sinc(SincFixedIn<TargetFormat>),
+}
+
+/// Calls the same method on whichever resampler `$resampler` holds.
+macro_rules! dispatch {
+    ($resampler:expr, $inner:ident => $call:expr) => {
+        match $resampler {
+            QualityResampler::Linear($inner) => $call,
+            QualityResampler::Fft($inner) => $call,
+            QualityResampler::Sinc($inner) => $call,
+        }
+    };
+}
+
+impl QualityResampler {
+    fn new(
+        origin_rate: usize,
+        target_rate: usize,
+        channels: usize,
+        quality: ResamplerQuality,
+        chunk_size: usize,
+    ) -> Result<Self, AudioRecorderError> {
+        let ratio = target_rate as f64 / origin_rate as f64;
+        let resampler = match quality {
+            ResamplerQuality::Fast => FastFixedIn::<TargetFormat>::new(
+                ratio,
+                1.0,
+                PolynomialDegree::Linear,
+                chunk_size,
+                channels,
+            )
+            .map(QualityResampler::Linear),
+            ResamplerQuality::Balanced => {
+                FftFixedIn::<TargetFormat>::new(origin_rate, target_rate, chunk_size, 2, channels)
+                    .map(|resampler| QualityResampler::Fft(Box::new(resampler)))
+            }
+            ResamplerQuality::High => {
+                let window = WindowFunction::BlackmanHarris2;
+                let parameters = SincInterpolationParameters {
+                    sinc_len: RESAMPLER_SINC_LEN,
+                    f_cutoff: calculate_cutoff(RESAMPLER_SINC_LEN, window),
+                    oversampling_factor: RESAMPLER_SINC_OVERSAMPLING,
+                    interpolation: SincInterpolationType::Cubic,
+                    window,
+                };
+                SincFixedIn::<TargetFormat>::new(ratio, 1.0, parameters, chunk_size, channels)
+                    .map(QualityResampler::Sinc)
+            }
+        };
+
+        resampler.map_err(|e| {
+            tracing::error!("Failed to create {:?} resampler: {}", quality, e);
+            AudioRecorderError::Resampler(e)
+        })
+    }
+
+    fn input_frames_next(&self) -> usize {
+        dispatch!(self, resampler => resampler.input_frames_next())
+    }
+
+    fn output_delay(&self) -> usize {
+        dispatch!(self, resampler => resampler.output_delay())
+    }
+
+    fn output_buffer_allocate(&self) -> Vec<Vec<TargetFormat>> {
+        dispatch!(self, resampler => resampler.output_buffer_allocate(true))
+    }
+
+    fn process_into_buffer(
+        &mut self,
+        input: &[Vec<TargetFormat>],
+        output: &mut [Vec<TargetFormat>],
+    ) -> rubato::ResampleResult<(usize, usize)> {
+        dispatch!(self, resampler => resampler.process_into_buffer(input, output, None))
+    }
+
+    fn process_partial_into_buffer(
+        &mut self,
+        input: Option<&[Vec<TargetFormat>]>,
+        output: &mut [Vec<TargetFormat>],
+    ) -> rubato::ResampleResult<(usize, usize)> {
+        dispatch!(self, resampler => resampler.process_partial_into_buffer(input, output, None))
+    }
+}
+
+/// Drives a resampler between two ring buffers holding interleaved frames.
+///
+/// The resampler can only process chunks of exactly `input_frames_next()` frames, so
+/// samples are accumulated in the source ring buffer until a full chunk is available,
+/// processed, and the produced frames are pushed into the destination ring buffer.
+/// The resampler is a linear interpolator, an FFT resampler or a windowed sinc
+/// interpolator, depending on the `ResamplerQuality`.
+pub struct ResamplerDriver {
+    resampler: QualityResampler,
+    origin_rate: usize,
+    target_rate: usize,
+    channels: usize,
+    ratio: f64,
+    interleaved: Vec<TargetFormat>,
+    input_buffer: Vec<Vec<TargetFormat>>,
+    output_buffer: Vec<Vec<TargetFormat>>,
+}
+
+impl ResamplerDriver {
+    pub fn new(
+        origin_rate: usize,
+        target_rate: usize,
+        channels: usize,
+    ) -> Result<Self, AudioRecorderError> {
+        let quality = ResamplerQuality::default();
+        Self::with_quality(
+            origin_rate,
+            target_rate,
+            channels,
+            quality,
+            quality.chunk_size(),
+        )
+    }
+
+    /// Creates a driver whose resampler of `quality` processes `chunk_size` frames at
+    /// once.
+    pub fn with_quality(
+        origin_rate: usize,
+        target_rate: usize,
+        channels: usize,
+        quality: ResamplerQuality,
+        chunk_size: usize,
+    ) -> Result<Self, AudioRecorderError> {
+        let channels = channels.max(1);
+        let resampler = QualityResampler::new(
+            origin_rate,
+            target_rate,
+            channels,
+            quality,
+            chunk_size.max(1),
+        )?;
+        let output_buffer = resampler.output_buffer_allocate();
+
+        Ok(ResamplerDriver {
+            resampler,
+            origin_rate,
+            target_rate,
+            channels,
+            ratio: target_rate as f64 / origin_rate as f64,
+            interleaved: Vec::new(),
+            input_buffer: vec![Vec::new(); channels],
+            output_buffer,
+        })
+    }
+
+    /// Replaces the resampler with one of `quality` processing `chunk_size` frames at
+    /// once, e.g. to lower its cost while recording.
+    ///
+    /// The frames delayed inside the previous resampler are lost.
+    pub fn set_quality(
+        &mut self,
+        quality: ResamplerQuality,
+        chunk_size: usize,
+    ) -> Result<(), AudioRecorderError> {
+        self.resampler = QualityResampler::new(
+            self.origin_rate,
+            self.target_rate,
+            self.channels,
+            quality,
+            chunk_size.max(1),
+        )?;
+        self.output_buffer = self.resampler.output_buffer_allocate();
+        Ok(())
+    }
+
+    /// Number of frames the resampler needs before it can process the next chunk.
+    pub fn input_frames_next(&self) -> usize {
+        self.resampler.input_frames_next()
+    }
+
+    /// Processes every complete chunk available in `consumer` and pushes the resampled
+    /// frames into `producer`.
+    ///
+    /// Incomplete chunks are left in `consumer` for the next call.
+    ///
+    /// # Returns
+    ///
+    /// The number of frames pushed into `producer`.
+    pub fn drain<C, P>(&mut self, consumer: &mut C, producer: &mut P) -> usize
+    where
+        C: Consumer<Item = TargetFormat>,
+        P: Producer<Item = TargetFormat>,
+    {
+        let mut pushed = 0;
+
+        loop {
+            let next_input_frames = self.resampler.input_frames_next();
+            if consumer.occupied_len() < next_input_frames * self.channels {
+                break;
+            }
+
+            self.interleaved
+                .resize(next_input_frames * self.channels, 0.0);
+            consumer.pop_slice(&mut self.interleaved);
+
+            for (channel, buffer) in self.input_buffer.iter_mut().enumerate() {
+                buffer.clear();
+                buffer.extend(self.interleaved.iter().skip(channel).step_by(self.channels));
+            }
+
+            match self
+                .resampler
+                .process_into_buffer(&self.input_buffer, &mut self.output_buffer)
+            {
+                Ok((_, output_frames)) => pushed += self.push_output(output_frames, producer),
+                Err(e) => {
+                    tracing::error!("Failed to resample: {}", e);
+                }
+            };
+        }
+
+        pushed
+    }
+
+    /// Processes everything left at the end of a stream: the complete chunks, the
+    /// incomplete chunk in `consumer` (padded with silence) and the frames still delayed
+    /// inside the resampler, so the whole stream reaches `producer`.
+    ///
+    /// # Returns
+    ///
+    /// The number of frames pushed into `producer`.
+    pub fn flush<C, P>(&mut self, consumer: &mut C, producer: &mut P) -> usize
+    where
+        C: Consumer<Item = TargetFormat>,
+        P: Producer<Item = TargetFormat>,
+    {
+        let mut pushed = self.drain(consumer, producer);
+
+        let remaining = consumer.occupied_len() / self.channels;
+        self.interleaved.resize(remaining * self.channels, 0.0);
+        consumer.pop_slice(&mut self.interleaved);
+
+        for (channel, buffer) in self.input_buffer.iter_mut().enumerate() {
+            buffer.clear();
+            buffer.extend(self.interleaved.iter().skip(channel).step_by(self.channels));
+        }
+
+        // the padding and the silence pushing out the delayed frames are trimmed
+        let mut wanted =
+            (remaining as f64 * self.ratio).round() as usize + self.resampler.output_delay();
+        let mut first = true;
+
+        while wanted > 0 {
+            let input = match first {
+                true => Some(self.input_buffer.as_slice()),
+                false => None,
+            };
+            first = false;
+
+            let output_frames = match self
+                .resampler
+                .process_partial_into_buffer(input, &mut self.output_buffer)
+            {
+                Ok((_, output_frames)) => output_frames.min(wanted),
+                Err(e) => {
+                    tracing::error!("Failed to flush resampler: {}", e);
+                    break;
+                }
+            };
+
+            if output_frames == 0 {
+                break;
+            }
+
+            wanted -= output_frames;
+            pushed += self.push_output(output_frames, producer);
+        }
+
+        pushed
+    }
+
+    /// Interleaves the first `output_frames` frames of the output buffer into `producer`.
+    fn push_output<P>(&mut self, output_frames: usize, producer: &mut P) -> usize
+    where
+        P: Producer<Item = TargetFormat>,
+    {
+        self.interleaved.clear();
+        for frame in 0..output_frames {
+            for channel in &self.output_buffer {
+                self.interleaved.push(channel[frame]);
+            }
+        }
+
+        let written = producer.push_slice(&self.interleaved) / self.channels;
+        if written < output_frames {
+            tracing::warn!(
+                "resampler output fell behind: dropped {} frames",
+                output_frames - written
+            );
+        }
+
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ringbuf::{
+        HeapRb,
+        traits::{Observer, Producer, Split},
+    };
+
+    use super::*;
+
+    #[test]
+    fn waits_for_a_complete_chunk() {
+        let mut driver = ResamplerDriver::new(48000, 44100, 1).expect("Failed to create driver");
+        let (mut input_producer, mut input_consumer) = HeapRb::<TargetFormat>::new(8192).split();
+        let (mut output_producer, output_consumer) = HeapRb::<TargetFormat>::new(8192).split();
+
+        let next_input_frames = driver.input_frames_next();
+        input_producer.push_slice(&vec![0.5; next_input_frames - 1]);
+
+        assert_eq!(driver.drain(&mut input_consumer, &mut output_producer), 0);
+        assert_eq!(input_consumer.occupied_len(), next_input_frames - 1);
+        assert_eq!(output_consumer.occupied_len(), 0);
+    }
+
+    #[test]
+    fn processes_every_complete_chunk() {
+        let mut driver = ResamplerDriver::new(48000, 44100, 1).expect("Failed to create driver");
+        let (mut input_producer, mut input_consumer) = HeapRb::<TargetFormat>::new(8192).split();
+        let (mut output_producer, output_consumer) = HeapRb::<TargetFormat>::new(8192).split();
+
+        let next_input_frames = driver.input_frames_next();
+        input_producer.push_slice(&vec![0.5; next_input_frames * 3 + 10]);
+
+        let pushed = driver.drain(&mut input_consumer, &mut output_producer);
+
+        assert_eq!(input_consumer.occupied_len(), 10);
+        assert_eq!(output_consumer.occupied_len(), pushed);
+        assert!(pushed > 0);
+        assert!(pushed < next_input_frames * 3);
+    }
+
+    #[test]
+    fn upsampling_produces_more_frames() {
+        let mut driver = ResamplerDriver::new(16000, 48000, 1).expect("Failed to create driver");
+        let (mut input_producer, mut input_consumer) = HeapRb::<TargetFormat>::new(8192).split();
+        let (mut output_producer, _output_consumer) = HeapRb::<TargetFormat>::new(16384).split();
+
+        let next_input_frames = driver.input_frames_next();
+        input_producer.push_slice(&vec![0.5; next_input_frames * 2]);
+
+        let pushed = driver.drain(&mut input_consumer, &mut output_producer);
+
+        assert!(pushed > next_input_frames * 2);
+    }
+
+    #[test]
+    fn keeps_interleaved_channels_together() {
+        let mut driver = ResamplerDriver::new(48000, 44100, 2).expect("Failed to create driver");
+        let (mut input_producer, mut input_consumer) = HeapRb::<TargetFormat>::new(16384).split();
+        let (mut output_producer, output_consumer) = HeapRb::<TargetFormat>::new(16384).split();
+
+        let next_input_frames = driver.input_frames_next();
+        input_producer.push_slice(&vec![0.5; next_input_frames * 2 * 2 + 1]);
+
+        let pushed = driver.drain(&mut input_consumer, &mut output_producer);
+
+        assert_eq!(input_consumer.occupied_len(), 1);
+        assert_eq!(output_consumer.occupied_len(), pushed * 2);
+    }
+
+    #[test]
+    fn flush_delivers_the_whole_stream() {
+        let mut driver = ResamplerDriver::new(48000, 24000, 1).expect("Failed to create driver");
+        let (mut input_producer, mut input_consumer) = HeapRb::<TargetFormat>::new(16384).split();
+        let (mut output_producer, output_consumer) = HeapRb::<TargetFormat>::new(16384).split();
+
+        let next_input_frames = driver.input_frames_next();
+        let delay = driver.resampler.output_delay();
+        input_producer.push_slice(&vec![0.5; next_input_frames * 2 + 100]);
+
+        let pushed = driver.flush(&mut input_consumer, &mut output_producer);
+
+        assert_eq!(input_consumer.occupied_len(), 0);
+        assert_eq!(output_consumer.occupied_len(), pushed);
+        assert_eq!(pushed, next_input_frames + 50 + delay);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+#[cfg(target_os = "linux")]
+use super::linux_utils;
+use super::{Recorder, devices::DeviceType, errors::AudioRecorderError};
+
+/// Name of the recovery file, in the runtime directory of the user.
+const ROUTING_RECOVERY_FILE: &str = "audio-recorder-rs-routing";
+
+/// The default devices of the system before the recorder changed them, kept in the
+/// recovery file until they are restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SavedRouting {
+    /// Process that changed the routing.
+    pid: u32,
+    /// Start time of that process, telling it apart from a later one reusing its pid.
+    start: Option<u64>,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl SavedRouting {
+    /// The recovery file, in `$XDG_RUNTIME_DIR`: only the user can create files there,
+    /// so no other user can plant a routing for the recorder to restore.
+    fn path() -> Option<PathBuf> {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join(ROUTING_RECOVERY_FILE))
+    }
+
+    /// The routing of the system right now, saved by this process.
+    fn current() -> SavedRouting {
+        SavedRouting {
+            pid: std::process::id(),
+            start: process_start_time(std::process::id()),
+            input: default_device(DeviceType::Input),
+            output: default_device(DeviceType::Output),
+        }
+    }
+
+    /// Reads the recovery file, `None` when there is none (or it cannot be read).
+    ///
+    /// Anything but a regular file of the user owning the runtime directory, e.g. a
+    /// symlink, is ignored.
+    fn load() -> Option<SavedRouting> {
+        let path = SavedRouting::path()?;
+        let text = match read_recovery_file(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!("Failed to read the routing recovery file {:?}: {}", path, e);
+                return None;
+            }
+        };
+
+        let mut routing = SavedRouting {
+            pid: 0,
+            start: None,
+            input: None,
+            output: None,
+        };
+        for line in text.lines() {
+            match line.split_once('=') {
+                Some(("pid", pid)) => routing.pid = pid.parse().unwrap_or_default(),
+                Some(("start", start)) => routing.start = start.parse().ok(),
+                Some(("input", name)) => routing.input = Some(name.to_string()),
+                Some(("output", name)) => routing.output = Some(name.to_string()),
+                _ => tracing::warn!("Ignoring line {:?} of the routing recovery file", line),
+            }
+        }
+        Some(routing)
+    }
+
+    /// Creates the recovery file, failing with `ErrorKind::AlreadyExists` if there is
+    /// one already (the routing saved first is the one to restore).
+    fn save(&self) -> Result<(), AudioRecorderError> {
+        let path = match SavedRouting::path() {
+            Some(path) => path,
+            None => {
+                tracing::error!("XDG_RUNTIME_DIR is not set, cannot save the routing");
+                return Err(AudioRecorderError::DeviceError(
+                    "No runtime directory to save the routing to",
+                ));
+            }
+        };
+
+        let mut text = format!("pid={}\n", self.pid);
+        if let Some(start) = self.start {
+            text.push_str(&format!("start={start}\n"));
+        }
+        if let Some(input) = &self.input {
+            text.push_str(&format!("input={input}\n"));
+        }
+        if let Some(output) = &self.output {
+            text.push_str(&format!("output={output}\n"));
+        }
+
+        tracing::debug!("Saving the routing {:?}", self);
+        let mut options = fs::OpenOptions::new();
+        // create_new fails on an existing path, symlinks included, instead of following it
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(AudioRecorderError::IoError)
+    }
+
+    /// Makes the saved devices the defaults again and removes the recovery file.
+    fn restore(&self) -> Result<(), AudioRecorderError> {
+        tracing::info!("Restoring the routing {:?}", self);
+        if let Some(input) = &self.input {
+            set_default_device(DeviceType::Input, input)?;
+        }
+        if let Some(output) = &self.output {
+            set_default_device(DeviceType::Output, output)?;
+        }
+
+        match SavedRouting::path().map(fs::remove_file) {
+            Some(Err(e)) if e.kind() != ErrorKind::NotFound => Err(AudioRecorderError::IoError(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reads the recovery file at `path`, refusing to follow a symlink or to trust a file
+/// planted by another user.
+fn read_recovery_file(path: &Path) -> io::Result<String> {
+    let link = fs::symlink_metadata(path)?;
+    if !link.file_type().is_file() {
+        return Err(io::Error::new(ErrorKind::InvalidData, "not a regular file"));
+    }
+
+    let mut file = fs::File::open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // the path may have been swapped between the two calls, the opened file must be
+        // the one that was checked
+        let opened = file.metadata()?;
+        let owner = path.parent().map(fs::metadata).transpose()?;
+        if opened.dev() != link.dev() || opened.ino() != link.ino() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "file changed"));
+        }
+        if owner.is_some_and(|dir| dir.uid() != opened.uid()) {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "not owned by the user",
+            ));
+        }
+    }
+
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Name of the default device of `device_type`, as known to the sound server.
+fn default_device(device_type: DeviceType) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        match device_type {
+            DeviceType::Input => linux_utils::default_node("source"),
+            DeviceType::Output => linux_utils::default_node("sink"),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device_type;
+        None
+    }
+}
+
+fn set_default_device(device_type: DeviceType, name: &str) -> Result<(), AudioRecorderError> {
+    #[cfg(target_os = "linux")]
+    {
+        let kind = match device_type {
+            DeviceType::Input => "source",
+            DeviceType::Output => "sink",
+        };
+        match linux_utils::set_default_node(kind, name) {
+            true => Ok(()),
+            false => {
+                tracing::error!("Failed to make {} the default {}", name, kind);
+                Err(AudioRecorderError::DeviceError(
+                    "Failed to change the default device",
+                ))
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        tracing::error!(
+            "Cannot make {} the default {:?} device on this platform",
+            name,
+            device_type
+        );
+        Err(AudioRecorderError::DeviceError(
+            "Changing the default device is not supported on this platform",
+        ))
+    }
+}
+
+/// Start time of the process `pid`, in clock ticks since boot, if it is running.
+fn process_start_time(pid: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        // the command name in parentheses may contain spaces, the start time is the
+        // 22nd field and the 20th after it
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(19)?.parse().ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Whether the process that saved `routing` is still running, in which case the routing
+/// it changed is still in use.
+///
+/// The start time is compared along with the pid, so a process that reused the pid of
+/// a crashed one does not keep its routing from being restored.
+fn is_running(routing: &SavedRouting) -> bool {
+    match process_start_time(routing.pid) {
+        Some(start) => routing.start.is_none_or(|saved| saved == start),
+        None => false,
+    }
+}
+
+impl Recorder {
+    /// Makes the device named `name` the default `device_type` device of the system,
+    /// e.g. to route the audio of other apps through a virtual device while recording.
+    ///
+    /// The defaults before the first change are saved to a recovery file in
+    /// `$XDG_RUNTIME_DIR`, so that `restore_routing` puts them back, and
+    /// `Recorder::restore_orphaned_routing` does if the process ended without restoring
+    /// them (e.g. it crashed).
+    ///
+    /// Only supported on Linux, where `name` is the name of the PulseAudio or PipeWire
+    /// source or sink.
+    #[tracing::instrument]
+    pub fn set_default_device(
+        &mut self,
+        device_type: DeviceType,
+        name: &str,
+    ) -> Result<(), AudioRecorderError> {
+        tracing::info!("Making {} the default {:?} device", name, device_type);
+
+        if cfg!(target_os = "linux") {
+            match SavedRouting::current().save() {
+                Err(AudioRecorderError::IoError(e)) if e.kind() == ErrorKind::AlreadyExists => {
+                    tracing::debug!("Keeping the routing saved before the first change");
+                }
+                result => result?,
+            }
+        }
+        set_default_device(device_type, name)
+    }
+
+    /// Restores the default devices saved by `set_default_device`, returning whether
+    /// there was a routing to restore.
+    #[tracing::instrument]
+    pub fn restore_routing(&mut self) -> Result<bool, AudioRecorderError> {
+        match SavedRouting::load() {
+            Some(routing) => routing.restore().map(|_| true),
+            None => {
+                tracing::debug!("No routing to restore");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Restores the routing left behind by a process that ended without restoring it,
+    /// returning whether there was one.
+    ///
+    /// This changes the default devices of the system, so it is never done implicitly:
+    /// call it once at startup, before recording, if the application uses
+    /// `set_default_device`. A routing saved by a process that is still running is
+    /// left alone.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use audio_recorder_rs::Recorder;
+    ///
+    /// // the last run may have crashed while it had rerouted the system audio
+    /// Recorder::restore_orphaned_routing().expect("Failed to restore the routing");
+    /// let recorder = Recorder::new();
+    /// ```
+    pub fn restore_orphaned_routing() -> Result<bool, AudioRecorderError> {
+        let routing = match SavedRouting::load() {
+            Some(routing) => routing,
+            None => return Ok(false),
+        };
+        if is_running(&routing) {
+            tracing::debug!("The routing is still used by process {}", routing.pid);
+            return Ok(false);
+        }
+
+        tracing::warn!(
+            "Process {} ended without restoring the routing, restoring it",
+            routing.pid
+        );
+        routing.restore().map(|_| true).map_err(|e| {
+            tracing::error!("Failed to restore the routing: {}", e);
+            e
+        })
+    }
+}
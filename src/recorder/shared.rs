@@ -0,0 +1,22 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+
+/// An `f32` shared between the recorder and the capture callbacks.
+#[derive(Debug, Clone)]
+pub struct SharedF32(Arc<AtomicU32>);
+
+impl SharedF32 {
+    pub fn new(value: f32) -> Self {
+        SharedF32(Arc::new(AtomicU32::new(value.to_bits())))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
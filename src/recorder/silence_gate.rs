@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use super::{Recorder, dsp::gate::Gate};
+
+/// Settings of the silence-gated recording mode, see `Recorder::set_silence_gate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceGate {
+    /// RMS level, in dBFS, from which the audio is delivered.
+    pub threshold_db: f32,
+    /// How long the audio keeps being delivered after it fell below the threshold,
+    /// bridging the pauses of a conversation.
+    pub hold: Duration,
+    /// Audio kept from before the level reached the threshold, delivered first so the
+    /// onset of the sound is not cut.
+    pub pre_roll: Duration,
+}
+
+impl Default for SilenceGate {
+    fn default() -> Self {
+        SilenceGate {
+            threshold_db: -45.0,
+            hold: Duration::from_secs(2),
+            pre_roll: Duration::from_millis(500),
+        }
+    }
+}
+
+impl SilenceGate {
+    /// Creates the gate of a delivered stream with the given layout.
+    pub fn gate(&self, sample_rate: u32, channels: u16) -> Gate {
+        Gate::new(
+            sample_rate,
+            channels,
+            self.threshold_db,
+            self.hold,
+            self.pre_roll,
+        )
+    }
+}
+
+impl Recorder {
+    /// Only delivers the audio while its level is above the threshold of `gate` (or
+    /// `None` to deliver everything), so long idle meetings do not produce gigabytes of
+    /// silence.
+    ///
+    /// The silent stretches are skipped, the frame positions of the chunks only count
+    /// the delivered audio; their `timestamp` still tells when they were recorded.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_silence_gate(&mut self, gate: Option<SilenceGate>) {
+        self.silence_gate = gate;
+    }
+}
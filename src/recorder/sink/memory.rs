@@ -0,0 +1,74 @@
+use std::thread::{self, JoinHandle};
+
+use crate::recorder::{
+    constants::TargetFormat, errors::AudioRecorderError, receiver::AudioReceiver,
+};
+
+/// A sink that accumulates a whole recording in memory.
+///
+/// Meant for short captures (e.g. voice notes) where writing temp files or draining the
+/// receiver manually is overkill. Samples past `max_samples` are discarded, so a forgotten
+/// recording can't exhaust memory.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{MemorySink, Recorder, RecorderConfig};
+///
+/// let mut recorder = Recorder::new();
+/// let config = RecorderConfig::builder().input_only(true).build();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// let sink =
+///     MemorySink::attach(receiver, 48_000 * 60).expect("Failed to attach memory sink");
+///
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// recorder.stop();
+///
+/// let samples = sink.finish().expect("Failed to collect recording");
+/// ```
+pub struct MemorySink {
+    handle: JoinHandle<Vec<TargetFormat>>,
+}
+
+impl MemorySink {
+    /// Spawns a thread that collects every chunk from `receiver`, keeping at most
+    /// `max_samples` samples.
+    pub fn attach(receiver: AudioReceiver, max_samples: usize) -> Result<Self, AudioRecorderError> {
+        let handle = thread::Builder::new()
+            .name("arec-memory-sink".to_string())
+            .spawn(move || {
+                let mut samples: Vec<TargetFormat> = Vec::new();
+                let mut truncated = false;
+
+                while let Ok(chunk) = receiver.recv() {
+                    let data = chunk.samples;
+                    let remaining = max_samples - samples.len();
+                    if data.len() > remaining && !truncated {
+                        tracing::warn!("Memory sink is full, discarding further samples");
+                        truncated = true;
+                    }
+
+                    samples.extend_from_slice(&data[..data.len().min(remaining)]);
+                }
+
+                samples
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn memory sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(MemorySink { handle })
+    }
+
+    /// Waits for the recording to end and returns the accumulated samples.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    pub fn finish(self) -> Result<Vec<TargetFormat>, AudioRecorderError> {
+        self.handle.join().map_err(|_| {
+            tracing::error!("Memory sink thread panicked");
+            AudioRecorderError::ThreadPanicked("Memory sink")
+        })
+    }
+}
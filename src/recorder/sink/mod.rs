@@ -0,0 +1,24 @@
+/// Module for accumulating a recording in memory.
+mod memory;
+
+/// Module for piping a recording to other processes.
+mod pipe;
+
+/// Module for the error policies of the sinks.
+mod policy;
+
+/// Module for replacing regions of a recorded file.
+mod punch;
+
+/// Module for the pull-based reader of a recording.
+mod pull;
+
+/// Module for writing a recording to WAV files.
+mod wav;
+
+pub use memory::MemorySink;
+pub use pipe::{PipeFrame, PipeSink, read_pipe_frame};
+pub use policy::{SinkGuard, SinkOutcome, SinkPolicy};
+pub use pull::PullReader;
+pub use punch::PunchFill;
+pub use wav::{SyncPolicy, WavRotation, WavSink, WriterStats};
@@ -0,0 +1,320 @@
+use std::{
+    io::{self, BufWriter, Read, Write},
+    thread::{self, JoinHandle},
+};
+
+use crate::recorder::{
+    Recorder,
+    constants::TargetFormat,
+    errors::AudioRecorderError,
+    receiver::AudioReceiver,
+    sink::{SinkGuard, SinkPolicy},
+};
+
+/// Size of the header preceding the samples of every frame.
+const FRAME_HEADER_SIZE: usize = 10;
+
+/// A frame of the pipe protocol, as read back by `read_pipe_frame`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PipeFrame {
+    /// Sample rate of the samples.
+    pub sample_rate: u32,
+    /// Number of interleaved channels of the samples.
+    pub channels: u16,
+    /// Interleaved samples.
+    pub samples: Vec<TargetFormat>,
+}
+
+/// A sink that writes the recording to a pipe (stdout by default) with a simple
+/// length-prefixed framing, so it can be composed with ffmpeg or other processes in
+/// shell pipelines.
+///
+/// Every chunk is written as one frame:
+///
+/// | bytes | content                                         |
+/// |-------|-------------------------------------------------|
+/// | 4     | payload length in bytes, `u32` little-endian    |
+/// | 4     | sample rate, `u32` little-endian                |
+/// | 2     | channels, `u16` little-endian                   |
+/// | n     | interleaved `f32` little-endian samples         |
+///
+/// A frame with an empty payload marks the end of the recording.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{PipeSink, Recorder, RecorderConfig};
+///
+/// let mut recorder = Recorder::new();
+/// let config = RecorderConfig::builder().input_only(true).build();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// let sink = PipeSink::stdout(receiver).expect("Failed to attach pipe sink");
+///
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// recorder.stop();
+///
+/// sink.finish().expect("Failed to write recording");
+/// ```
+pub struct PipeSink {
+    handle: JoinHandle<Result<(), AudioRecorderError>>,
+}
+
+/// Writes one frame of the pipe protocol.
+fn write_frame(
+    writer: &mut impl Write,
+    sample_rate: u32,
+    channels: u16,
+    samples: &[TargetFormat],
+) -> io::Result<()> {
+    let payload_len = (samples.len() * size_of::<TargetFormat>()) as u32;
+
+    writer.write_all(&payload_len.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Reads the next frame written by a `PipeSink`, returning `None` at the end marker.
+pub fn read_pipe_frame(reader: &mut impl Read) -> Result<Option<PipeFrame>, AudioRecorderError> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut header).map_err(|e| {
+        tracing::error!("Failed to read pipe frame header: {}", e);
+        AudioRecorderError::IoError(e)
+    })?;
+
+    let payload_len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let sample_rate = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let channels = u16::from_le_bytes([header[8], header[9]]);
+
+    if payload_len == 0 {
+        return Ok(None);
+    }
+
+    if !payload_len.is_multiple_of(size_of::<TargetFormat>()) {
+        tracing::error!(
+            "Pipe frame payload of {} bytes is not whole samples",
+            payload_len
+        );
+        return Err(AudioRecorderError::MalformedFrame(
+            "payload is not whole samples",
+        ));
+    }
+
+    // grown as the payload arrives, a corrupt length fails instead of allocating it
+    let mut payload = Vec::new();
+    reader
+        .take(payload_len as u64)
+        .read_to_end(&mut payload)
+        .map_err(|e| {
+            tracing::error!("Failed to read pipe frame payload: {}", e);
+            AudioRecorderError::IoError(e)
+        })?;
+    if payload.len() < payload_len {
+        tracing::error!(
+            "Pipe frame payload ended after {} of {} bytes",
+            payload.len(),
+            payload_len
+        );
+        return Err(AudioRecorderError::IoError(
+            io::ErrorKind::UnexpectedEof.into(),
+        ));
+    }
+
+    let samples = payload
+        .chunks_exact(size_of::<TargetFormat>())
+        .map(|b| TargetFormat::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    Ok(Some(PipeFrame {
+        sample_rate,
+        channels,
+        samples,
+    }))
+}
+
+impl PipeSink {
+    /// Spawns a thread that writes every chunk from `receiver` to stdout.
+    pub fn stdout(receiver: AudioReceiver) -> Result<Self, AudioRecorderError> {
+        PipeSink::attach(receiver, io::stdout())
+    }
+
+    /// Spawns a thread that writes every chunk from `receiver` to `writer`.
+    pub fn attach<W: Write + Send + 'static>(
+        receiver: AudioReceiver,
+        writer: W,
+    ) -> Result<Self, AudioRecorderError> {
+        PipeSink::spawn(receiver, writer, None)
+    }
+
+    /// Like `attach`, handling write failures with `policy`.
+    ///
+    /// A frame cannot be written again once part of it reached the pipe, so
+    /// `SinkPolicy::Retry` drops the sink on the first failure.
+    pub fn attach_with_policy<W: Write + Send + 'static>(
+        receiver: AudioReceiver,
+        writer: W,
+        recorder: &Recorder,
+        policy: SinkPolicy,
+    ) -> Result<Self, AudioRecorderError> {
+        let guard = recorder.sink_guard("pipe", policy);
+        PipeSink::spawn(receiver, writer, Some(guard))
+    }
+
+    fn spawn<W: Write + Send + 'static>(
+        receiver: AudioReceiver,
+        writer: W,
+        guard: Option<SinkGuard>,
+    ) -> Result<Self, AudioRecorderError> {
+        let check = move |result: Result<(), AudioRecorderError>| match &guard {
+            Some(guard) => guard.check(result),
+            None => result,
+        };
+
+        let handle = thread::Builder::new()
+            .name("arec-pipe-sink".to_string())
+            .spawn(move || {
+                let mut writer = BufWriter::new(writer);
+                let mut layout = (0, 0);
+
+                while let Ok(chunk) = receiver.recv() {
+                    layout = (chunk.sample_rate, chunk.channels);
+
+                    check(
+                        write_frame(
+                            &mut writer,
+                            chunk.sample_rate,
+                            chunk.channels,
+                            &chunk.samples,
+                        )
+                        // flushing every frame keeps the latency of the pipe low
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| {
+                            tracing::error!("Failed to write pipe frame: {}", e);
+                            AudioRecorderError::IoError(e)
+                        }),
+                    )?;
+                }
+
+                let (sample_rate, channels) = layout;
+                check(
+                    write_frame(&mut writer, sample_rate, channels, &[])
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| {
+                            tracing::error!("Failed to write pipe end marker: {}", e);
+                            AudioRecorderError::IoError(e)
+                        }),
+                )
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn pipe sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(PipeSink { handle })
+    }
+
+    /// Waits for the recording to end and for the end marker to be written.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    pub fn finish(self) -> Result<(), AudioRecorderError> {
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::error!("Pipe sink thread panicked");
+                Err(AudioRecorderError::ThreadPanicked("Pipe sink"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A reader that hands out at most a few bytes per call, like a pipe under load.
+    struct Trickle {
+        data: Cursor<Vec<u8>>,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(3);
+            self.data.read(&mut buf[..len])
+        }
+    }
+
+    fn encode(frames: &[(u32, u16, &[TargetFormat])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (sample_rate, channels, samples) in frames {
+            write_frame(&mut bytes, *sample_rate, *channels, samples)
+                .expect("Failed to write frame");
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trips_frames() {
+        let bytes = encode(&[(48000, 2, &[0.25, -0.5, 1.0, 0.0]), (48000, 2, &[])]);
+        let mut reader = Cursor::new(bytes);
+
+        let frame = read_pipe_frame(&mut reader)
+            .expect("Failed to read frame")
+            .expect("Missing frame");
+        assert_eq!(frame.sample_rate, 48000);
+        assert_eq!(frame.channels, 2);
+        assert_eq!(frame.samples, vec![0.25, -0.5, 1.0, 0.0]);
+
+        assert!(
+            read_pipe_frame(&mut reader)
+                .expect("Failed to read end marker")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn empty_frame_is_the_end_marker() {
+        let bytes = encode(&[(44100, 1, &[])]);
+        assert_eq!(bytes.len(), FRAME_HEADER_SIZE);
+
+        let frame = read_pipe_frame(&mut Cursor::new(bytes)).expect("Failed to read frame");
+        assert!(frame.is_none());
+    }
+
+    #[test]
+    fn reassembles_frames_from_partial_reads() {
+        let samples: Vec<TargetFormat> = (0..32).map(|i| i as TargetFormat / 32.0).collect();
+        let bytes = encode(&[(16000, 1, &samples), (16000, 1, &[])]);
+        let mut reader = Trickle {
+            data: Cursor::new(bytes),
+        };
+
+        let frame = read_pipe_frame(&mut reader)
+            .expect("Failed to read frame")
+            .expect("Missing frame");
+        assert_eq!(frame.samples, samples);
+        assert!(
+            read_pipe_frame(&mut reader)
+                .expect("Failed to read end marker")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn truncated_payload_is_an_error() {
+        let mut bytes = encode(&[(48000, 1, &[0.5, 0.5, 0.5])]);
+        bytes.truncate(bytes.len() - 2);
+
+        let result = read_pipe_frame(&mut Cursor::new(bytes));
+        assert!(
+            matches!(result, Err(AudioRecorderError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::recorder::{
+    Recorder,
+    errors::AudioRecorderError,
+    events::{EventBus, RecorderEvent},
+};
+
+/// What a sink does when writing to its destination fails (disk full, broken pipe, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum SinkPolicy {
+    /// Stop the whole recording.
+    FailSession,
+    /// Stop this sink and let the recording go on.
+    #[default]
+    DropSink,
+    /// Retry the failed write up to `attempts` times, `backoff` apart, while the
+    /// recording keeps buffering in the data channel, then drop the sink.
+    Retry { attempts: u32, backoff: Duration },
+}
+
+/// What happened after a sink failed, reported with `RecorderEvent::SinkError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SinkOutcome {
+    /// The recording was stopped.
+    SessionStopped,
+    /// The sink was stopped, the recording goes on.
+    SinkDropped,
+    /// The write is retried, this is the `attempt`th retry.
+    Retrying { attempt: u32 },
+}
+
+/// Applies a `SinkPolicy` to the writes of a sink, reporting every outcome.
+pub struct SinkGuard {
+    sink: String,
+    policy: SinkPolicy,
+    events: EventBus,
+    recording_signal: Arc<AtomicBool>,
+}
+
+impl SinkGuard {
+    /// Runs a write, retrying it if the policy allows, and stops the sink (or the
+    /// session) with its error otherwise.
+    pub fn run<T>(
+        &self,
+        mut write: impl FnMut() -> Result<T, AudioRecorderError>,
+    ) -> Result<T, AudioRecorderError> {
+        let mut attempt = 0;
+
+        loop {
+            let e = match write() {
+                Ok(value) => {
+                    if attempt > 0 {
+                        tracing::info!("Sink {} recovered", self.sink);
+                        self.events.emit(RecorderEvent::SinkRecovered {
+                            sink: self.sink.clone(),
+                        });
+                    }
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+
+            match self.policy {
+                SinkPolicy::Retry { attempts, backoff } if attempt < attempts => {
+                    attempt += 1;
+                    tracing::warn!("Sink {} failed, retrying ({}): {}", self.sink, attempt, e);
+                    self.report(&e, SinkOutcome::Retrying { attempt });
+                    thread::sleep(backoff);
+                }
+                _ => return Err(self.fail(e)),
+            }
+        }
+    }
+
+    /// Checks the result of a write that cannot be retried, stopping the sink (or the
+    /// session) on error.
+    pub fn check<T>(&self, result: Result<T, AudioRecorderError>) -> Result<T, AudioRecorderError> {
+        result.map_err(|e| self.fail(e))
+    }
+
+    fn fail(&self, e: AudioRecorderError) -> AudioRecorderError {
+        match self.policy {
+            SinkPolicy::FailSession => {
+                tracing::error!("Sink {} failed, stopping the recording: {}", self.sink, e);
+                self.recording_signal.store(false, Ordering::SeqCst);
+                self.report(&e, SinkOutcome::SessionStopped);
+            }
+            _ => {
+                tracing::error!("Sink {} failed, dropping it: {}", self.sink, e);
+                self.report(&e, SinkOutcome::SinkDropped);
+            }
+        }
+
+        e
+    }
+
+    fn report(&self, e: &AudioRecorderError, outcome: SinkOutcome) {
+        self.events.emit(RecorderEvent::SinkError {
+            sink: self.sink.clone(),
+            message: e.to_string(),
+            outcome,
+        });
+    }
+}
+
+impl Recorder {
+    /// Creates the guard applying `policy` to the sink named `sink`.
+    pub fn sink_guard(&self, sink: &str, policy: SinkPolicy) -> SinkGuard {
+        SinkGuard {
+            sink: sink.to_string(),
+            policy,
+            events: self.events.clone(),
+            recording_signal: self.recording_signal.clone(),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use ringbuf::{
+    HeapCons, HeapRb,
+    traits::{Consumer, Observer, Producer, Split},
+};
+
+use crate::recorder::{
+    constants::TargetFormat, errors::AudioRecorderError, receiver::AudioReceiver,
+};
+
+/// A pull-based reader of the recording, for real-time consumers.
+///
+/// A bridge thread moves every chunk from the receiver into a lock-free ring buffer, so
+/// an audio thread (e.g. a host DAW plugin) can `read` exactly the samples it needs
+/// without blocking or waiting on channel wakeups. Chunks that don't fit in the ring
+/// buffer because the reader fell behind are dropped whole, keeping frames aligned.
+///
+/// Samples are interleaved; read in multiples of the channel count reported by
+/// `Recorder::get_config` to stay frame aligned.
+pub struct PullReader {
+    consumer: HeapCons<TargetFormat>,
+    ended: Arc<AtomicBool>,
+}
+
+impl PullReader {
+    /// Spawns the bridge thread feeding a ring buffer of `capacity` samples from `receiver`.
+    pub fn attach(receiver: AudioReceiver, capacity: usize) -> Result<Self, AudioRecorderError> {
+        let (mut producer, consumer) = HeapRb::<TargetFormat>::new(capacity.max(1)).split();
+        let ended = Arc::new(AtomicBool::new(false));
+
+        let bridge_ended = ended.clone();
+        thread::Builder::new()
+            .name("arec-pull-reader".to_string())
+            .spawn(move || {
+                while let Ok(chunk) = receiver.recv() {
+                    let data = chunk.samples;
+                    if producer.vacant_len() < data.len() {
+                        tracing::warn!("Pull reader fell behind: dropping {} samples", data.len());
+                        continue;
+                    }
+
+                    producer.push_slice(&data);
+                }
+
+                bridge_ended.store(true, Ordering::SeqCst);
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn pull reader thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(PullReader { consumer, ended })
+    }
+
+    /// Copies up to `buffer.len()` samples into `buffer` without blocking.
+    ///
+    /// # Returns
+    ///
+    /// The number of samples written to `buffer`.
+    pub fn read(&mut self, buffer: &mut [TargetFormat]) -> usize {
+        self.consumer.pop_slice(buffer)
+    }
+
+    /// Number of samples ready to be read.
+    pub fn available(&self) -> usize {
+        self.consumer.occupied_len()
+    }
+
+    /// Whether the recording has ended and every sample has been read.
+    pub fn is_ended(&self) -> bool {
+        self.ended.load(Ordering::SeqCst) && self.consumer.is_empty()
+    }
+}
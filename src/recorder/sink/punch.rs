@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use hound::{WavReader, WavWriter};
+
+use crate::recorder::{constants::TargetFormat, errors::AudioRecorderError};
+
+/// What replaces a region of a recording, see `WavSink::replace`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PunchFill {
+    /// The region is silenced.
+    Silence,
+    /// The region is replaced by a re-recorded take, interleaved samples in the layout
+    /// of the recording. A shorter take is followed by silence and a longer one is cut
+    /// at the end of the region, so the rest of the recording keeps its position.
+    Take(Vec<TargetFormat>),
+}
+
+impl PunchFill {
+    /// The sample at `index`, counted from the start of the region.
+    fn sample(&self, index: usize) -> TargetFormat {
+        match self {
+            PunchFill::Silence => 0.0,
+            PunchFill::Take(samples) => samples.get(index).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Number of frames of the WAV file at `path`.
+fn segment_frames(path: &Path) -> Result<u64, AudioRecorderError> {
+    let reader = WavReader::open(path).map_err(AudioRecorderError::WavError)?;
+    Ok(reader.duration() as u64)
+}
+
+/// Rewrites the segment at `path`, whose first frame is `start`, with the `punches`
+/// overlapping it.
+fn rewrite_segment(
+    path: &Path,
+    start: u64,
+    punches: &[&(Range<u64>, PunchFill)],
+) -> Result<(), AudioRecorderError> {
+    let mut reader = WavReader::open(path).map_err(AudioRecorderError::WavError)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as u64;
+    let mut samples = reader
+        .samples::<TargetFormat>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AudioRecorderError::WavError)?;
+    drop(reader);
+
+    let end = start + samples.len() as u64 / channels;
+    for (frames, fill) in punches {
+        let first = frames.start.max(start);
+        let last = frames.end.min(end);
+        tracing::debug!("Replacing frames {}..{} of {:?}", first, last, path);
+
+        for frame in first..last {
+            for channel in 0..channels {
+                let index = ((frame - start) * channels + channel) as usize;
+                samples[index] =
+                    fill.sample(((frame - frames.start) * channels + channel) as usize);
+            }
+        }
+    }
+
+    // the edit is written aside, so a failure leaves the recorded file intact
+    let edited = path.with_extension("wav.punch");
+    let mut writer = WavWriter::create(&edited, spec).map_err(AudioRecorderError::WavError)?;
+    for sample in samples {
+        writer
+            .write_sample(sample)
+            .map_err(AudioRecorderError::WavError)?;
+    }
+    writer.finalize().map_err(AudioRecorderError::WavError)?;
+
+    fs::rename(&edited, path).map_err(|e| {
+        tracing::error!("Failed to replace {:?} with its edit: {}", path, e);
+        AudioRecorderError::IoError(e)
+    })
+}
+
+/// Applies `punches`, regions of frames of the stream, to the `segments` of a recording
+/// whose last frame ends at `end_frame` (or that starts at frame zero when unknown).
+pub fn apply_punches(
+    segments: &[PathBuf],
+    end_frame: Option<u64>,
+    punches: &[(Range<u64>, PunchFill)],
+) -> Result<(), AudioRecorderError> {
+    let lengths = segments
+        .iter()
+        .map(|path| segment_frames(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the segments still on disk are the end of the stream, e.g. in circular mode
+    let total: u64 = lengths.iter().sum();
+    let mut start = end_frame.map_or(0, |end| end.saturating_sub(total));
+
+    for (path, length) in segments.iter().zip(lengths) {
+        let end = start + length;
+        let overlapping: Vec<&(Range<u64>, PunchFill)> = punches
+            .iter()
+            .filter(|(frames, _)| frames.start < end && start < frames.end)
+            .collect();
+
+        if !overlapping.is_empty() {
+            rewrite_segment(path, start, &overlapping)?;
+        }
+        start = end;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hound::{SampleFormat, WavSpec};
+
+    use super::*;
+
+    /// A stereo segment in the temp directory whose frame `i` holds `first + i` on both
+    /// channels.
+    fn segment(name: &str, first: u64, frames: u64) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("arec-punch-{}-{}.wav", std::process::id(), name));
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 1000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+
+        let mut writer = WavWriter::create(&path, spec).expect("Failed to create segment");
+        for frame in first..first + frames {
+            for _ in 0..2 {
+                writer
+                    .write_sample(frame as TargetFormat)
+                    .expect("Failed to write segment");
+            }
+        }
+        writer.finalize().expect("Failed to finalize segment");
+
+        path
+    }
+
+    /// The first channel of every frame of the segment at `path`, removing it.
+    fn frames(path: &Path) -> Vec<TargetFormat> {
+        let mut reader = WavReader::open(path).expect("Failed to open segment");
+        let samples = reader
+            .samples::<TargetFormat>()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Failed to read segment");
+        drop(reader);
+        let _ = fs::remove_file(path);
+
+        samples.chunks(2).map(|frame| frame[0]).collect()
+    }
+
+    #[test]
+    fn replaces_a_region_mid_file() {
+        let path = segment("mid", 0, 8);
+        let take = vec![10.0, 10.0, 11.0, 11.0];
+
+        apply_punches(&[path.clone()], None, &[(3..6, PunchFill::Take(take))])
+            .expect("Failed to apply punch");
+
+        // the take is shorter than the region and followed by silence
+        assert_eq!(
+            frames(&path),
+            vec![0.0, 1.0, 2.0, 10.0, 11.0, 0.0, 6.0, 7.0]
+        );
+    }
+
+    #[test]
+    fn region_past_the_end_keeps_the_length() {
+        let path = segment("eof", 0, 8);
+
+        apply_punches(&[path.clone()], None, &[(6..20, PunchFill::Silence)])
+            .expect("Failed to apply punch");
+
+        assert_eq!(frames(&path), vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn later_punches_win_where_they_overlap() {
+        let path = segment("overlap", 0, 8);
+        let take = vec![20.0; 8];
+
+        apply_punches(
+            &[path.clone()],
+            None,
+            &[(1..5, PunchFill::Silence), (3..7, PunchFill::Take(take))],
+        )
+        .expect("Failed to apply punches");
+
+        assert_eq!(
+            frames(&path),
+            vec![0.0, 0.0, 0.0, 20.0, 20.0, 20.0, 20.0, 7.0]
+        );
+    }
+
+    #[test]
+    fn region_spanning_two_segments_of_a_circular_recording() {
+        // the first 100 frames were already discarded
+        let first = segment("circular-0", 100, 4);
+        let second = segment("circular-1", 104, 4);
+
+        apply_punches(
+            &[first.clone(), second.clone()],
+            Some(108),
+            &[(102..106, PunchFill::Silence)],
+        )
+        .expect("Failed to apply punch");
+
+        assert_eq!(frames(&first), vec![100.0, 101.0, 0.0, 0.0]);
+        assert_eq!(frames(&second), vec![0.0, 0.0, 106.0, 107.0]);
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::BufWriter,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::recorder::{
+    Recorder,
+    annotations::AnnotationLog,
+    capture_clock::CaptureClock,
+    constants::TargetFormat,
+    errors::AudioRecorderError,
+    receiver::AudioReceiver,
+    sink::{
+        SinkGuard, SinkPolicy,
+        punch::{PunchFill, apply_punches},
+    },
+};
+
+/// Size of the header hound writes for a float WAV file.
+const WAV_HEADER_SIZE: u64 = 44;
+
+/// When a `WavSink` starts a new segment file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum WavRotation {
+    /// Write the whole recording to a single file.
+    #[default]
+    Never,
+    /// Start a new segment once the current one holds this much audio.
+    Duration(Duration),
+    /// Start a new segment before the current one grows past this many bytes.
+    Size(u64),
+}
+
+/// How often a `WavSink` makes the audio written so far durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum SyncPolicy {
+    /// Only when a file is finalized, leaving the buffering to the writer and the OS.
+    #[default]
+    OnFinalize,
+    /// Flush the buffered samples and update the WAV header this often, so the file
+    /// is playable up to there if the process dies.
+    Flush(Duration),
+    /// Like `Flush`, then also `fsync` the file, so the audio survives a power loss.
+    Fsync(Duration),
+}
+
+impl SyncPolicy {
+    fn interval(self) -> Option<Duration> {
+        match self {
+            SyncPolicy::OnFinalize => None,
+            SyncPolicy::Flush(interval) | SyncPolicy::Fsync(interval) => Some(interval),
+        }
+    }
+}
+
+/// Progress of the writer thread of a `WavSink`, see `WavSink::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriterStats {
+    /// Chunks delivered by the recorder and not written yet.
+    pub queued_chunks: usize,
+    /// Frames written so far, across segments.
+    pub written_frames: u64,
+    /// Number of flushes (and `fsync`s) done by the `SyncPolicy`.
+    pub syncs: u64,
+}
+
+/// Counters shared with the writer thread.
+#[derive(Debug, Default)]
+struct WriterCounters {
+    written_frames: AtomicU64,
+    syncs: AtomicU64,
+}
+
+/// Flushes `writer` to `path`, and `fsync`s it if `fsync` is set.
+fn sync_segment(
+    writer: &mut WavWriter<BufWriter<File>>,
+    path: &Path,
+    fsync: bool,
+) -> Result<(), AudioRecorderError> {
+    writer.flush().map_err(AudioRecorderError::WavError)?;
+
+    if fsync {
+        // hound keeps its file to itself, a second handle syncs the same file
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.sync_data())
+            .map_err(|e| {
+                tracing::error!("Failed to fsync {:?}: {}", path, e);
+                AudioRecorderError::IoError(e)
+            })?;
+    }
+
+    Ok(())
+}
+
+/// A sink that writes the recording to WAV files.
+///
+/// Chunks are written as 32-bit float WAV with the recorder's sample rate and channels,
+/// on a writer thread of their own: the recorder queues them in the data channel, so a
+/// slow disk (e.g. a network drive) delays the files but never the capture. See
+/// `set_sync_policy` for how often the files are flushed.
+///
+/// Without rotation the recording is written to `path`; with rotation, segments are
+/// numbered after it (`path_000.wav`, `path_001.wav`, ...) and always split on frame
+/// boundaries, so they can be stitched back gaplessly with `stitch_wav_segments`.
+///
+/// Regions of the recording can be marked while recording, with `punch_in` and
+/// `punch_out`, and replaced with silence or a re-recorded take once it ended, see
+/// `replace`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use audio_recorder_rs::{Recorder, RecorderConfig, WavRotation, WavSink};
+///
+/// let mut recorder = Recorder::new();
+/// let config = RecorderConfig::builder().input_only(true).build();
+/// let receiver = recorder.start(config).expect("Failed to start recording");
+/// let sink = WavSink::attach(
+///     receiver,
+///     &recorder,
+///     "recording.wav",
+///     WavRotation::Duration(Duration::from_secs(60)),
+/// )
+/// .expect("Failed to attach WAV sink");
+///
+/// std::thread::sleep(std::time::Duration::from_secs(5));
+/// recorder.stop();
+///
+/// let segments = sink.finish().expect("Failed to write recording");
+/// ```
+pub struct WavSink {
+    handle: JoinHandle<Result<Vec<PathBuf>, AudioRecorderError>>,
+    /// A handle on the data channel, only used to measure its backlog.
+    queue: AudioReceiver,
+    sync_policy: Arc<Mutex<SyncPolicy>>,
+    counters: Arc<WriterCounters>,
+    /// Annotations of the recording, written to `manifest` by `finish`.
+    annotations: AnnotationLog,
+    manifest: PathBuf,
+    /// Clock of the recording, telling the frame being recorded.
+    clock: CaptureClock,
+    /// Start of the region marked by `punch_in`.
+    punch_start: Mutex<Option<u64>>,
+    /// Regions replaced by `finish`.
+    punches: Mutex<Vec<(Range<u64>, PunchFill)>>,
+}
+
+/// Path of the segment `index` of a rotated recording written to `path`.
+fn segment_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{stem}_{index:03}.wav"))
+}
+
+/// The segments of an earlier recording written to `path`, with their indices, in order.
+fn existing_segments(path: &Path, rotation: WavRotation) -> Vec<(usize, PathBuf)> {
+    if rotation == WavRotation::Never {
+        return match path.exists() {
+            true => vec![(0, path.to_path_buf())],
+            false => Vec::new(),
+        };
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let prefix = format!("{stem}_");
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Failed to list {:?}: {}", dir, e);
+            return Vec::new();
+        }
+    };
+
+    let mut segments: Vec<(usize, PathBuf)> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_string_lossy().into_owned();
+            let index = name
+                .strip_prefix(&prefix)?
+                .strip_suffix(".wav")?
+                .parse()
+                .ok()?;
+            Some((index, segment_path(path, index)))
+        })
+        .collect();
+    segments.sort_by_key(|(index, _)| *index);
+
+    segments
+}
+
+impl WavSink {
+    /// Spawns a thread that writes every chunk from `receiver` to WAV files at `path`.
+    ///
+    /// The recorder must have been started, as its config describes the stream.
+    pub fn attach(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        rotation: WavRotation,
+    ) -> Result<Self, AudioRecorderError> {
+        WavSink::attach_with_policy(receiver, recorder, path, rotation, SinkPolicy::default())
+    }
+
+    /// Like `attach`, handling write failures with `policy`.
+    pub fn attach_with_policy(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        rotation: WavRotation,
+        policy: SinkPolicy,
+    ) -> Result<Self, AudioRecorderError> {
+        let guard = recorder.sink_guard("wav", policy);
+        WavSink::spawn(
+            receiver,
+            recorder,
+            path.into(),
+            rotation,
+            None,
+            false,
+            guard,
+        )
+    }
+
+    /// Like `attach`, but continues the files of an earlier recording to `path` instead
+    /// of overwriting them: the last file is appended to (or the next segment started
+    /// when it is full) and `finish` returns every file of the recording, old and new.
+    ///
+    /// Together with `Recorder::set_resume_from`, a "continue recording" after an
+    /// intentional stop produces one coherent recording. The stream must have the sample
+    /// rate and channels of the earlier files.
+    pub fn resume(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        rotation: WavRotation,
+    ) -> Result<Self, AudioRecorderError> {
+        let guard = recorder.sink_guard("wav", SinkPolicy::default());
+        WavSink::spawn(receiver, recorder, path.into(), rotation, None, true, guard)
+    }
+
+    /// Spawns a thread that continuously records into a bounded ring of segments of
+    /// `segment` each, deleting the oldest ones so only the last `keep` of audio stays
+    /// on disk (dash-cam mode).
+    ///
+    /// Meant for always-on capture appliances with limited storage; `finish` returns the
+    /// segments still on disk, oldest first.
+    pub fn circular(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: impl Into<PathBuf>,
+        segment: Duration,
+        keep: Duration,
+    ) -> Result<Self, AudioRecorderError> {
+        let max_segments = match segment.is_zero() {
+            true => 1,
+            false => (keep.as_secs_f64() / segment.as_secs_f64()).ceil().max(1.0) as usize,
+        };
+        tracing::debug!("Keeping the last {} WAV segments", max_segments);
+
+        WavSink::spawn(
+            receiver,
+            recorder,
+            path.into(),
+            WavRotation::Duration(segment),
+            Some(max_segments),
+            false,
+            recorder.sink_guard("wav", SinkPolicy::default()),
+        )
+    }
+
+    fn spawn(
+        receiver: AudioReceiver,
+        recorder: &Recorder,
+        path: PathBuf,
+        rotation: WavRotation,
+        max_segments: Option<usize>,
+        resume: bool,
+        guard: SinkGuard,
+    ) -> Result<Self, AudioRecorderError> {
+        let config = recorder
+            .get_config()
+            .inspect_err(|e| tracing::error!("Failed to get recorder config: {}", e))?;
+
+        let spec = WavSpec {
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            bits_per_sample: (size_of::<TargetFormat>() * 8) as u16,
+            sample_format: SampleFormat::Float,
+        };
+
+        let frame_size = (size_of::<TargetFormat>() * config.channels as usize) as u64;
+        let max_frames = match rotation {
+            WavRotation::Never => u64::MAX,
+            WavRotation::Duration(d) => {
+                ((d.as_secs_f64() * config.sample_rate as f64) as u64).max(1)
+            }
+            WavRotation::Size(bytes) => (bytes.saturating_sub(WAV_HEADER_SIZE) / frame_size).max(1),
+        };
+
+        let existing = match resume {
+            true => existing_segments(&path, rotation),
+            false => Vec::new(),
+        };
+        tracing::debug!("Resuming {} WAV segments", existing.len());
+
+        let manifest = path.with_extension("manifest.json");
+        let segment = move |index: usize| match rotation {
+            WavRotation::Never => path.clone(),
+            _ => segment_path(&path, index),
+        };
+
+        let channels = config.channels as usize;
+        let queue = receiver.clone();
+        let sync_policy = Arc::new(Mutex::new(SyncPolicy::default()));
+        let counters = Arc::new(WriterCounters::default());
+        let (thread_policy, thread_counters) = (sync_policy.clone(), counters.clone());
+
+        let handle = thread::Builder::new()
+            .name("arec-wav-sink".to_string())
+            .spawn(move || {
+                let (mut segments, mut writer, mut frames, mut next_index) = match existing.last() {
+                    Some((last_index, last)) => {
+                        let writer: WavWriter<BufWriter<File>> = guard.run(|| {
+                            WavWriter::append(last).map_err(AudioRecorderError::WavError)
+                        })?;
+
+                        if writer.spec() != spec {
+                            tracing::error!("Cannot resume {:?} with another format", last);
+                            return Err(AudioRecorderError::FormatMismatch(last.clone()));
+                        }
+
+                        let frames = writer.len() as u64 / channels.max(1) as u64;
+                        let segments: VecDeque<PathBuf> =
+                            existing.iter().map(|(_, path)| path.clone()).collect();
+                        (segments, writer, frames, last_index + 1)
+                    }
+                    None => {
+                        let first = segment(0);
+                        let writer: WavWriter<BufWriter<File>> = guard.run(|| {
+                            WavWriter::create(&first, spec).map_err(AudioRecorderError::WavError)
+                        })?;
+                        (VecDeque::from([first]), writer, 0, 1)
+                    }
+                };
+
+                let mut last_sync = Instant::now();
+
+                while let Ok(chunk) = receiver.recv() {
+                    let written = chunk.frames();
+
+                    for frame in chunk.samples.chunks(channels) {
+                        if frames >= max_frames {
+                            guard.check(writer.finalize().map_err(AudioRecorderError::WavError))?;
+
+                            let next = segment(next_index);
+                            tracing::debug!("Rotating WAV sink to {:?}", next);
+                            writer = guard.run(|| {
+                                WavWriter::create(&next, spec).map_err(AudioRecorderError::WavError)
+                            })?;
+                            segments.push_back(next);
+                            next_index += 1;
+                            frames = 0;
+
+                            while max_segments.is_some_and(|max| segments.len() > max) {
+                                let Some(oldest) = segments.pop_front() else {
+                                    break;
+                                };
+
+                                tracing::debug!("Deleting oldest WAV segment {:?}", oldest);
+                                if let Err(e) = std::fs::remove_file(&oldest) {
+                                    tracing::warn!("Failed to delete {:?}: {}", oldest, e);
+                                }
+                            }
+                        }
+
+                        for sample in frame {
+                            guard.run(|| {
+                                writer
+                                    .write_sample(*sample)
+                                    .map_err(AudioRecorderError::WavError)
+                            })?;
+                        }
+                        frames += 1;
+                    }
+                    thread_counters
+                        .written_frames
+                        .fetch_add(written, Ordering::Relaxed);
+
+                    let policy = *thread_policy.lock().unwrap_or_else(PoisonError::into_inner);
+                    if policy
+                        .interval()
+                        .is_some_and(|interval| last_sync.elapsed() >= interval)
+                    {
+                        if let Some(current) = segments.back() {
+                            let fsync = matches!(policy, SyncPolicy::Fsync(_));
+                            guard.run(|| sync_segment(&mut writer, current, fsync))?;
+                            thread_counters.syncs.fetch_add(1, Ordering::Relaxed);
+                        }
+                        last_sync = Instant::now();
+                    }
+                }
+
+                guard.check(writer.finalize().map_err(AudioRecorderError::WavError))?;
+                Ok(segments.into())
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn WAV sink thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        Ok(WavSink {
+            handle,
+            queue,
+            sync_policy,
+            counters,
+            annotations: recorder.annotations(),
+            manifest,
+            clock: recorder.capture_clock(),
+            punch_start: Mutex::new(None),
+            punches: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Sets how often the writer thread flushes (or `fsync`s) the current file, from
+    /// the next chunk it writes on.
+    pub fn set_sync_policy(&self, policy: SyncPolicy) {
+        *self
+            .sync_policy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = policy;
+    }
+
+    /// Progress of the writer thread, e.g. to warn when the write queue keeps growing
+    /// because the disk cannot keep up.
+    pub fn stats(&self) -> WriterStats {
+        WriterStats {
+            queued_chunks: self.queue.len(),
+            written_frames: self.counters.written_frames.load(Ordering::Relaxed),
+            syncs: self.counters.syncs.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Marks the start of a region to correct at the frame being recorded now, returning
+    /// it, or `None` before the first chunk was delivered.
+    pub fn punch_in(&self) -> Option<u64> {
+        let frame = self.clock.frame_of(Instant::now())?;
+        tracing::debug!("Punching in at frame {}", frame);
+
+        *self
+            .punch_start
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(frame);
+        Some(frame)
+    }
+
+    /// Ends the region started by `punch_in` at the frame being recorded now, returning
+    /// it to `replace` it, or `None` when no region was started.
+    pub fn punch_out(&self) -> Option<Range<u64>> {
+        let start = self
+            .punch_start
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()?;
+        let end = self.clock.frame_of(Instant::now())?.max(start);
+        tracing::debug!("Punching out at frame {}", end);
+
+        Some(start..end)
+    }
+
+    /// Replaces `frames`, as counted by `AudioChunk::frame` (e.g. a region returned by
+    /// `punch_out`), with `fill` in the files, once the recording ended in `finish`.
+    ///
+    /// The files are rewritten after the recording, so their length and the position of
+    /// the rest of the audio never change. Chunks dropped by the backpressure policy
+    /// are not in the files and shift the positions.
+    pub fn replace(&self, frames: Range<u64>, fill: PunchFill) {
+        tracing::debug!("Replacing frames {:?} when the recording ends", frames);
+        self.punches
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((frames, fill));
+    }
+
+    /// Waits for the recording to end and returns the paths of the written files, in order.
+    ///
+    /// The recording ends once the recorder has been stopped, so call `Recorder::stop`
+    /// before this, otherwise it blocks until it is.
+    ///
+    /// The regions given to `replace` are then replaced in the files. When the
+    /// recording was annotated (see `Recorder::annotations`), its manifest is also
+    /// written next to the files, e.g. `meeting.manifest.json` for `meeting.wav`.
+    pub fn finish(self) -> Result<Vec<PathBuf>, AudioRecorderError> {
+        let segments = self.handle.join().map_err(|_| {
+            tracing::error!("WAV sink thread panicked");
+            AudioRecorderError::ThreadPanicked("WAV sink")
+        })??;
+        let summary = self.queue.summary();
+
+        let punches = self
+            .punches
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !punches.is_empty() {
+            tracing::info!("Replacing {} regions of the recording", punches.len());
+            apply_punches(
+                &segments,
+                summary.as_ref().map(|summary| summary.frames),
+                &punches,
+            )?;
+        }
+
+        if !self.annotations.is_empty() {
+            self.annotations
+                .write_manifest(&self.manifest, summary.as_ref(), &segments)?;
+        }
+
+        Ok(segments)
+    }
+}
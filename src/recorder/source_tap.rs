@@ -0,0 +1,391 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use cpal::{InputCallbackInfo, Sample, StreamInstant};
+
+use super::{
+    Recorder,
+    auto_gain::AutoGainControl,
+    buffer_occupancy::OccupancyCounters,
+    callback_stats::CallbackCounters,
+    config::LatencyMode,
+    constants::{AudioSource, CustomSample, GAIN_RAMP_MS, ROBUST_MAX_BACKFILL, TargetFormat},
+    dsp::{
+        agc::AutoGain, high_pass::HighPass, noise_floor::NoiseFloorEstimator,
+        spectral_subtraction::SpectralSubtractor,
+    },
+    errors::AudioRecorderError,
+    events::{EventBus, RecorderEvent},
+    high_pass::HighPassFilter,
+    level_histogram::LevelCounters,
+    noise_profile::NoiseProfile,
+    raw_capture::RawTee,
+    shared::SharedF32,
+};
+
+/// Runs the body of a capture callback of `source`, containing a panic to the callback.
+///
+/// A malformed buffer then only loses its own audio: the panic does not tear down the
+/// stream, nor unwind into the backend thread calling the callback, which aborts the
+/// process on some hosts.
+pub fn guard_callback(source: AudioSource, callback: impl FnOnce()) {
+    if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(callback)) {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        tracing::error!("{:?} capture callback panicked: {}", source, message);
+    }
+}
+
+#[cfg(feature = "noise-suppression")]
+use super::dsp::rnnoise::NoiseSuppressor;
+
+/// Per-source processing done in the capture callback of a device.
+///
+/// Tees the untouched data if the raw capture debug mode is enabled, converts it to
+/// `TargetFormat`, applies the source gain (ramping changes over a few milliseconds),
+/// removes the DC offset and rumble, removes the noise profile (and suppresses the noise
+/// with RNNoise), updates the noise floor estimate and applies the automatic gain
+/// control.
+///
+/// The capture timestamps of consecutive callbacks are compared with the audio they
+/// carry, reporting a `RecorderEvent::XRun` when the device skipped audio, which is
+/// backfilled with silence in `LatencyMode::Robust`.
+pub struct SourceTap {
+    source: AudioSource,
+    tee: Option<RawTee>,
+    gain: SharedF32,
+    /// Gain applied to the last frame, ramping towards `gain`.
+    current_gain: f32,
+    channels: usize,
+    sample_rate: u32,
+    noise_floor: NoiseFloorEstimator,
+    noise_floor_db: SharedF32,
+    high_pass: Option<HighPassFilter>,
+    filter: Option<HighPass>,
+    noise_profile: Option<NoiseProfile>,
+    denoiser: Option<SpectralSubtractor>,
+    /// Whether the noise is suppressed with RNNoise, by `suppressor`.
+    #[cfg(feature = "noise-suppression")]
+    noise_suppression: bool,
+    #[cfg(feature = "noise-suppression")]
+    suppressor: Option<NoiseSuppressor>,
+    auto_gain: Option<AutoGainControl>,
+    agc: Option<AutoGain>,
+    events: EventBus,
+    /// Capture time and length of the previous callback's audio.
+    last_capture: Option<(StreamInstant, Duration)>,
+    /// Whether skipped audio is replaced by silence.
+    backfill: bool,
+    callbacks: CallbackCounters,
+    buffer: OccupancyCounters,
+    levels: LevelCounters,
+}
+
+impl SourceTap {
+    pub fn process<S: CustomSample>(
+        &mut self,
+        data: &[S],
+        info: Option<&InputCallbackInfo>,
+    ) -> Vec<TargetFormat> {
+        // without capture timestamps (WASAPI loopback), gaps cannot be detected
+        let gap = info.and_then(|info| self.check_xrun(data.len(), info));
+
+        if let Some(tee) = &self.tee {
+            tee.write(data);
+        }
+
+        let backfill_frames = match gap {
+            Some(gap) if self.backfill => {
+                (gap.min(ROBUST_MAX_BACKFILL).as_secs_f64() * self.sample_rate as f64) as usize
+            }
+            _ => 0,
+        };
+
+        let target = self.gain.get();
+        let ramp_frames = (self.sample_rate * GAIN_RAMP_MS / 1000).max(1);
+        let step = (target - self.current_gain) / ramp_frames as f32;
+
+        let mut parsed: Vec<TargetFormat> =
+            vec![TargetFormat::EQUILIBRIUM; backfill_frames * self.channels];
+        parsed.reserve(data.len());
+        for frame in data.chunks(self.channels) {
+            if self.current_gain != target {
+                self.current_gain += step;
+                if (step > 0.0) == (self.current_gain > target) {
+                    self.current_gain = target;
+                }
+            }
+
+            let gain = self.current_gain;
+            parsed.extend(frame.iter().map(|s| s.to_sample::<TargetFormat>() * gain));
+        }
+
+        if let Some(filter) = &mut self.filter {
+            filter.process(&mut parsed);
+        }
+
+        if let Some(denoiser) = &mut self.denoiser {
+            parsed = denoiser.process(&parsed);
+        }
+
+        #[cfg(feature = "noise-suppression")]
+        if let Some(suppressor) = &mut self.suppressor {
+            parsed = suppressor.process(parsed);
+        }
+
+        let frames = parsed.len() / self.channels;
+        let floor_db = self.noise_floor.update(&parsed, frames, self.sample_rate);
+        self.noise_floor_db.set(floor_db);
+
+        // after the noise floor, which tracks the level of the room, not of the AGC
+        if let Some(agc) = &mut self.agc {
+            agc.process(&mut parsed);
+        }
+
+        self.levels.record(&parsed, parsed.len() / self.channels);
+
+        parsed
+    }
+
+    pub fn source(&self) -> AudioSource {
+        self.source
+    }
+
+    /// Records the duration of a callback that started at `started` and carried
+    /// `samples` samples, reporting it when too slow for real time.
+    pub fn finish_callback(&self, started: Instant, samples: usize) {
+        let frames = samples / self.channels;
+        let budget = Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64);
+
+        if let Some(elapsed) = self.callbacks.record(started, budget) {
+            tracing::warn!(
+                "{:?} callback took {:?}, over its {:?} budget",
+                self.source,
+                elapsed,
+                budget
+            );
+            self.events.emit(RecorderEvent::SlowCallback {
+                source: self.source,
+                elapsed,
+                budget,
+            });
+        }
+    }
+
+    /// Reports `dropped` samples of this source lost because a ring buffer was full.
+    pub fn overrun(&self, dropped: usize) {
+        if dropped == 0 {
+            return;
+        }
+
+        tracing::warn!(
+            "{:?} stream fell behind: dropped {} samples",
+            self.source,
+            dropped
+        );
+        self.events.emit(RecorderEvent::BufferOverrun {
+            source: self.source,
+            dropped,
+        });
+    }
+
+    /// Records the occupancy of the ring buffer this source was just pushed into,
+    /// reporting it once it fills up or drains again.
+    pub fn occupancy(&self, occupied: usize, capacity: usize) {
+        match self.buffer.record(occupied, capacity) {
+            Some(true) => {
+                tracing::warn!(
+                    "{:?} ring buffer filling up: {} of {} samples",
+                    self.source,
+                    occupied,
+                    capacity
+                );
+                self.events.emit(RecorderEvent::Backpressure {
+                    source: self.source,
+                    occupied,
+                    capacity,
+                });
+            }
+            Some(false) => {
+                tracing::debug!("{:?} ring buffer drained", self.source);
+                self.events.emit(RecorderEvent::BackpressureRelieved {
+                    source: self.source,
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// Reports a gap between the audio of the previous callback and this one, returning
+    /// its length.
+    fn check_xrun(&mut self, samples: usize, info: &InputCallbackInfo) -> Option<Duration> {
+        let capture = info.timestamp().capture;
+        let frames = samples / self.channels;
+        let length = Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64);
+
+        if let Some((last, last_length)) = self.last_capture.replace((capture, length)) {
+            let elapsed = capture.duration_since(&last)?;
+
+            // timestamps jitter, only a gap longer than the previous callback counts
+            let gap = elapsed.saturating_sub(last_length);
+            if !last_length.is_zero() && gap > last_length {
+                tracing::warn!("{:?} stream skipped {:?} of audio", self.source, gap);
+                self.events.emit(RecorderEvent::XRun {
+                    source: self.source,
+                    gap,
+                });
+                return Some(gap);
+            }
+        }
+
+        None
+    }
+
+    /// Adapts the tap to a stream rebuilt on another device.
+    ///
+    /// The raw tee is closed, as its file name describes the layout of the old device.
+    pub fn reconfigure(&mut self, config: &cpal::SupportedStreamConfig) {
+        if self.tee.take().is_some() {
+            tracing::warn!("Device changed, closing the raw capture of the previous device");
+        }
+
+        self.channels = config.channels().max(1) as usize;
+        self.sample_rate = config.sample_rate().0;
+        self.last_capture = None;
+        self.filter = self
+            .high_pass
+            .map(|filter| filter.filter(self.sample_rate, config.channels()));
+        self.denoiser = self
+            .noise_profile
+            .as_ref()
+            .map(|profile| profile.subtractor(self.sample_rate, config.channels()));
+        #[cfg(feature = "noise-suppression")]
+        if self.noise_suppression {
+            self.suppressor = match NoiseSuppressor::new(self.sample_rate, config.channels()) {
+                Ok(suppressor) => Some(suppressor),
+                Err(e) => {
+                    tracing::error!("Failed to rebuild the noise suppressor: {}", e);
+                    None
+                }
+            };
+        }
+        self.agc = self
+            .auto_gain
+            .map(|agc| agc.processor(self.sample_rate, config.channels()));
+    }
+}
+
+impl Recorder {
+    /// Creates the tap processing the callback data of `source`.
+    pub fn source_tap(
+        &self,
+        source: AudioSource,
+        config: &cpal::SupportedStreamConfig,
+    ) -> Result<SourceTap, AudioRecorderError> {
+        let (name, gain, noise_floor_db, callbacks, buffer, levels) = match source {
+            AudioSource::Input => (
+                "input",
+                self.input_gain.clone(),
+                self.input_noise_floor_db.clone(),
+                self.input_callbacks.clone(),
+                self.input_buffer.clone(),
+                self.input_levels.clone(),
+            ),
+            AudioSource::Output => (
+                "output",
+                self.output_gain.clone(),
+                self.output_noise_floor_db.clone(),
+                self.output_callbacks.clone(),
+                self.output_buffer.clone(),
+                self.output_levels.clone(),
+            ),
+        };
+
+        noise_floor_db.set(f32::NAN);
+        callbacks.reset();
+        buffer.reset();
+
+        let high_pass = match source {
+            AudioSource::Input => self.high_pass,
+            AudioSource::Output => None,
+        };
+        let filter =
+            high_pass.map(|filter| filter.filter(config.sample_rate().0, config.channels()));
+
+        // the noise profile is captured on the input device
+        let noise_profile = match source {
+            AudioSource::Input => self.noise_profile.clone(),
+            AudioSource::Output => None,
+        };
+        let denoiser = noise_profile
+            .as_ref()
+            .map(|profile| profile.subtractor(config.sample_rate().0, config.channels()));
+
+        #[cfg(feature = "noise-suppression")]
+        let noise_suppression = source == AudioSource::Input && self.config.noise_suppression();
+        #[cfg(feature = "noise-suppression")]
+        let suppressor = match noise_suppression {
+            true => Some(NoiseSuppressor::new(
+                config.sample_rate().0,
+                config.channels(),
+            )?),
+            false => None,
+        };
+
+        let auto_gain = match source {
+            AudioSource::Input => self.auto_gain,
+            AudioSource::Output => None,
+        };
+        let agc = auto_gain.map(|agc| agc.processor(config.sample_rate().0, config.channels()));
+
+        Ok(SourceTap {
+            source,
+            tee: self.raw_tee(name, config)?,
+            current_gain: gain.get(),
+            gain,
+            channels: config.channels().max(1) as usize,
+            sample_rate: config.sample_rate().0,
+            noise_floor: NoiseFloorEstimator::new(),
+            noise_floor_db,
+            high_pass,
+            filter,
+            noise_profile,
+            denoiser,
+            #[cfg(feature = "noise-suppression")]
+            noise_suppression,
+            #[cfg(feature = "noise-suppression")]
+            suppressor,
+            auto_gain,
+            agc,
+            events: self.events.clone(),
+            last_capture: None,
+            backfill: self.config.latency_mode() == LatencyMode::Robust,
+            callbacks,
+            buffer,
+            levels,
+        })
+    }
+
+    /// Returns the current ambient noise floor estimate of `source`, in dBFS.
+    ///
+    /// The estimate adapts continuously while recording (quickly towards quieter levels,
+    /// slowly towards louder ones) and is a sensible basis for silence and voice activity
+    /// thresholds. Returns `None` until `source` has delivered audio.
+    pub fn get_noise_floor_db(&self, source: AudioSource) -> Option<f32> {
+        let floor_db = match source {
+            AudioSource::Input => self.input_noise_floor_db.get(),
+            AudioSource::Output => self.output_noise_floor_db.get(),
+        };
+
+        if floor_db.is_nan() {
+            return None;
+        }
+
+        Some(floor_db)
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+
+use super::{
+    Recorder,
+    chunk::AudioChunk,
+    config::RecorderConfig,
+    delivery::StreamSummary,
+    errors::AudioRecorderError,
+    receiver::{AudioReceiver, AudioRecvError},
+};
+
+/// The recorded audio as a `futures::Stream`, returned by `Recorder::start_async`.
+///
+/// The capture thread wakes the task polling the stream whenever it delivers a chunk, so
+/// no bridge thread is needed to use the recorder from an async runtime such as tokio.
+/// The stream yields `None` once the recording has stopped and every chunk was received.
+///
+/// # Examples
+///
+/// ```no_run
+/// # async fn record() {
+/// use futures::StreamExt;
+///
+/// use audio_recorder_rs::{Recorder, RecorderConfig};
+///
+/// let mut recorder = Recorder::new();
+/// let config = RecorderConfig::builder().input_only(true).build();
+/// let mut stream = recorder.start_async(config).expect("Failed to start recording");
+///
+/// while let Some(chunk) = stream.next().await {
+///     println!("Received {} frames", chunk.frames());
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AudioStream {
+    receiver: AudioReceiver,
+}
+
+impl AudioStream {
+    /// Summary of the recording, available once the stream has ended.
+    pub fn summary(&self) -> Option<StreamSummary> {
+        self.receiver.summary()
+    }
+}
+
+impl Stream for AudioStream {
+    type Item = AudioChunk;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // register before checking, so a chunk sent in between still wakes the task
+        self.receiver.waker().register(cx.waker());
+
+        match self.receiver.try_recv() {
+            Ok(chunk) => Poll::Ready(Some(chunk)),
+            Err(AudioRecvError::Ended) => Poll::Ready(None),
+            Err(_) => Poll::Pending,
+        }
+    }
+}
+
+impl AudioReceiver {
+    /// Turns the receiver into an async `Stream` of chunks.
+    pub fn into_stream(self) -> AudioStream {
+        AudioStream { receiver: self }
+    }
+}
+
+impl Recorder {
+    /// Starts recording like `start`, returning the recorded audio as an async `Stream`.
+    pub fn start_async(
+        &mut self,
+        config: RecorderConfig,
+    ) -> Result<AudioStream, AudioRecorderError> {
+        self.start(config).map(AudioReceiver::into_stream)
+    }
+}
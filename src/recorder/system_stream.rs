@@ -0,0 +1,145 @@
+use cpal::{FromSample, InputCallbackInfo, SizedSample, traits::DeviceTrait};
+
+use super::errors::{AudioRecorderError, device_name};
+#[cfg(target_os = "windows")]
+use super::wasapi_loopback::{self, LoopbackStream};
+#[cfg(target_os = "linux")]
+use super::{linux_utils, pulse_monitor::MonitorStream};
+
+/// The stream capturing the system audio.
+///
+/// Capturing an output device with `build_input_stream` only works on some backends.
+/// On Windows, the default render endpoint is captured with WASAPI loopback instead,
+/// falling back to the device stream when its mix format does not match the stream.
+/// On Linux, the monitor source of the default sink is recorded by name instead of
+/// through the sound server device.
+pub enum SystemStream {
+    Device(cpal::Stream),
+    #[cfg(target_os = "windows")]
+    Loopback(LoopbackStream),
+    #[cfg(target_os = "linux")]
+    Monitor(MonitorStream),
+}
+
+impl SystemStream {
+    pub fn play(&self) -> Result<(), cpal::PlayStreamError> {
+        use cpal::traits::StreamTrait;
+
+        match self {
+            SystemStream::Device(stream) => stream.play(),
+            #[cfg(target_os = "windows")]
+            SystemStream::Loopback(stream) => {
+                stream.play();
+                Ok(())
+            }
+            #[cfg(target_os = "linux")]
+            SystemStream::Monitor(stream) => {
+                stream.play();
+                Ok(())
+            }
+        }
+    }
+
+    pub fn pause(&self) -> Result<(), cpal::PauseStreamError> {
+        use cpal::traits::StreamTrait;
+
+        match self {
+            SystemStream::Device(stream) => stream.pause(),
+            #[cfg(target_os = "windows")]
+            SystemStream::Loopback(stream) => {
+                stream.pause();
+                Ok(())
+            }
+            #[cfg(target_os = "linux")]
+            SystemStream::Monitor(stream) => {
+                stream.pause();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether `device` is the default output device of the WASAPI host.
+#[cfg(target_os = "windows")]
+fn is_default_render(device: &cpal::Device) -> bool {
+    use cpal::traits::HostTrait;
+
+    let default = cpal::host_from_id(cpal::HostId::Wasapi)
+        .ok()
+        .and_then(|host| host.default_output_device())
+        .and_then(|device| device.name().ok());
+
+    device.default_output_config().is_ok() && default.is_some() && default == device.name().ok()
+}
+
+/// Builds the stream capturing the system audio of `device`, calling `callback` with its
+/// data and the callback info when the backend provides it.
+pub fn build_system_stream<U>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut callback: impl FnMut(&[U], Option<&InputCallbackInfo>) + Send + 'static,
+    errors: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<SystemStream, AudioRecorderError>
+where
+    U: SizedSample + FromSample<f32>,
+{
+    #[cfg(target_os = "windows")]
+    if is_default_render(device)
+        && wasapi_loopback::available(config.sample_rate.0, config.channels)
+    {
+        tracing::debug!("Capturing the system audio with WASAPI loopback");
+        let mut converted: Vec<U> = Vec::new();
+
+        let stream = LoopbackStream::build(
+            config.sample_rate.0,
+            config.channels,
+            move |data: &[f32]| {
+                converted.clear();
+                converted.extend(data.iter().map(|s| U::from_sample(*s)));
+                callback(&converted, None);
+            },
+            errors,
+        )?;
+
+        return Ok(SystemStream::Loopback(stream));
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(monitor) = linux_utils::monitor_source(device) {
+        tracing::debug!(
+            "Capturing the system audio from the monitor source {}",
+            monitor
+        );
+        let mut converted: Vec<U> = Vec::new();
+
+        let stream = MonitorStream::build(
+            &monitor,
+            config.sample_rate.0,
+            config.channels,
+            move |data: &[f32]| {
+                converted.clear();
+                converted.extend(data.iter().map(|s| U::from_sample(*s)));
+                callback(&converted, None);
+            },
+            errors,
+        )?;
+
+        return Ok(SystemStream::Monitor(stream));
+    }
+
+    device
+        .build_input_stream(
+            config,
+            move |data: &[U], info: &InputCallbackInfo| callback(data, Some(info)),
+            errors,
+            None,
+        )
+        .map(SystemStream::Device)
+        .map_err(|e| {
+            tracing::error!("Failed to build output stream: {}", e);
+            AudioRecorderError::BuildStream {
+                device: device_name(device),
+                source: e,
+            }
+        })
+}
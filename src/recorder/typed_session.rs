@@ -0,0 +1,305 @@
+use std::{marker::PhantomData, time::Duration};
+
+use super::{
+    Recorder,
+    chunk::AudioChunk,
+    config::{LatencyMode, RecorderConfig, RecorderConfigBuilder, ResamplerQuality},
+    constants::TargetFormat,
+    delivery::StreamSummary,
+    errors::AudioRecorderError,
+    mix::{ChannelMode, MixMode},
+    receiver::{AudioReceiver, AudioRecvError},
+};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The channel layout of the chunks of a `TypedReceiver`, known at compile time.
+///
+/// Only implemented by `MonoLayout` and `DualMonoLayout`.
+pub trait ChunkLayout: sealed::Sealed + std::fmt::Debug {
+    /// Channels of every delivered chunk.
+    const CHANNELS: u16;
+}
+
+/// The microphone alone, downmixed to a single channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MonoLayout;
+
+/// The microphone on the left channel and the system audio on the right one, each
+/// downmixed to mono (`MixMode::DualMono`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DualMonoLayout;
+
+impl sealed::Sealed for MonoLayout {}
+impl sealed::Sealed for DualMonoLayout {}
+
+impl ChunkLayout for MonoLayout {
+    const CHANNELS: u16 = 1;
+}
+
+impl ChunkLayout for DualMonoLayout {
+    const CHANNELS: u16 = 2;
+}
+
+/// Builds a recording whose delivered layout is part of its type, started with
+/// `Recorder::start_typed`.
+///
+/// It starts with the microphone alone, and `with_system_audio` adds the system audio
+/// on the platforms capturing it (macOS, Windows and Linux), so a recording of both
+/// sources does not compile where it could not work. The settings changing the layout
+/// (`input_only`, the channel and mix modes and the output channels) are fixed by the
+/// layout.
+///
+/// # Examples
+///
+/// ```no_run
+/// use audio_recorder_rs::{CaptureBuilder, Recorder};
+///
+/// let session = CaptureBuilder::microphone()
+///     .sample_rate(16_000)
+///     .with_system_audio()
+///     .build();
+///
+/// let mut recorder = Recorder::new();
+/// let receiver = recorder.start_typed(session).expect("Failed to start recording");
+/// for chunk in receiver.iter() {
+///     let (microphone, system_audio) = chunk.split();
+///     println!("{} / {} samples", microphone.len(), system_audio.len());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureBuilder<L> {
+    config: RecorderConfigBuilder,
+    layout: PhantomData<L>,
+}
+
+impl CaptureBuilder<MonoLayout> {
+    /// Records the microphone alone, in mono.
+    pub fn microphone() -> Self {
+        CaptureBuilder {
+            config: RecorderConfig::builder(),
+            layout: PhantomData,
+        }
+    }
+
+    /// Records the default input device of this host, see
+    /// `RecorderConfigBuilder::host`.
+    pub fn host(self, host: cpal::HostId) -> Self {
+        self.map(|config| config.host(host))
+    }
+
+    /// Mixes in the system audio, the microphone on the left channel and the system
+    /// audio on the right one.
+    #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+    pub fn with_system_audio(self) -> CaptureBuilder<DualMonoLayout> {
+        CaptureBuilder {
+            config: self.config,
+            layout: PhantomData,
+        }
+    }
+}
+
+impl CaptureBuilder<DualMonoLayout> {
+    /// Records the system audio from the device with this name, see
+    /// `RecorderConfigBuilder::output_device`.
+    pub fn output_device(self, name: impl Into<String>) -> Self {
+        self.map(|config| config.output_device(name))
+    }
+}
+
+impl<L: ChunkLayout> CaptureBuilder<L> {
+    fn map(self, f: impl FnOnce(RecorderConfigBuilder) -> RecorderConfigBuilder) -> Self {
+        CaptureBuilder {
+            config: f(self.config),
+            layout: PhantomData,
+        }
+    }
+
+    /// Sample rate of the delivered stream, see `Recorder::set_sample_rate`.
+    pub fn sample_rate(self, sample_rate: u32) -> Self {
+        self.map(|config| config.sample_rate(sample_rate))
+    }
+
+    /// Records the input device with this name, as listed by `AudioDevice::list_all`.
+    pub fn input_device(self, name: impl Into<String>) -> Self {
+        self.map(|config| config.input_device(name))
+    }
+
+    /// Buffer size requested from the devices, in frames.
+    pub fn buffer_size(self, frames: u32) -> Self {
+        self.map(|config| config.buffer_size(frames))
+    }
+
+    /// Delivers chunks holding exactly `chunk_duration` of audio, see
+    /// `RecorderConfigBuilder::chunk_duration`.
+    pub fn chunk_duration(self, chunk_duration: Duration) -> Self {
+        self.map(|config| config.chunk_duration(chunk_duration))
+    }
+
+    /// See `RecorderConfigBuilder::poll_interval`.
+    pub fn poll_interval(self, poll_interval: Duration) -> Self {
+        self.map(|config| config.poll_interval(poll_interval))
+    }
+
+    pub fn resampler_quality(self, resampler_quality: ResamplerQuality) -> Self {
+        self.map(|config| config.resampler_quality(resampler_quality))
+    }
+
+    /// See `RecorderConfigBuilder::latency_mode`.
+    pub fn latency_mode(self, latency_mode: LatencyMode) -> Self {
+        self.map(|config| config.latency_mode(latency_mode))
+    }
+
+    pub fn build(self) -> TypedSession<L> {
+        let config = match L::CHANNELS {
+            1 => self.config.input_only(true),
+            _ => self
+                .config
+                .input_only(false)
+                .channel_mode(ChannelMode::Stereo)
+                .mix_mode(MixMode::DualMono),
+        };
+
+        TypedSession {
+            config: config.output_channels(L::CHANNELS).build(),
+            layout: PhantomData,
+        }
+    }
+}
+
+/// A recording built by `CaptureBuilder`, delivering chunks in the layout `L`.
+#[derive(Debug, Clone)]
+pub struct TypedSession<L> {
+    config: RecorderConfig,
+    layout: PhantomData<L>,
+}
+
+impl<L> TypedSession<L> {
+    /// The config the recording is started with.
+    pub fn config(&self) -> &RecorderConfig {
+        &self.config
+    }
+}
+
+/// A chunk delivered in the layout `L`; `chunk.channels` is always `L::CHANNELS`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedChunk<L> {
+    chunk: AudioChunk,
+    layout: PhantomData<L>,
+}
+
+impl<L> TypedChunk<L> {
+    pub fn chunk(&self) -> &AudioChunk {
+        &self.chunk
+    }
+
+    pub fn into_inner(self) -> AudioChunk {
+        self.chunk
+    }
+
+    /// Number of frames in the chunk.
+    pub fn frames(&self) -> u64 {
+        self.chunk.frames()
+    }
+}
+
+impl TypedChunk<MonoLayout> {
+    /// The samples of the microphone.
+    pub fn samples(&self) -> &[TargetFormat] {
+        &self.chunk.samples
+    }
+}
+
+impl TypedChunk<DualMonoLayout> {
+    /// The samples of the microphone.
+    pub fn microphone(&self) -> impl Iterator<Item = TargetFormat> + '_ {
+        self.chunk.samples.iter().step_by(2).copied()
+    }
+
+    /// The samples of the system audio.
+    pub fn system_audio(&self) -> impl Iterator<Item = TargetFormat> + '_ {
+        self.chunk.samples.iter().skip(1).step_by(2).copied()
+    }
+
+    /// Splits the chunk into the samples of the microphone and of the system audio.
+    pub fn split(&self) -> (Vec<TargetFormat>, Vec<TargetFormat>) {
+        (self.microphone().collect(), self.system_audio().collect())
+    }
+}
+
+/// The stream of a recording started with `Recorder::start_typed`, see `AudioReceiver`.
+#[derive(Debug, Clone)]
+pub struct TypedReceiver<L> {
+    receiver: AudioReceiver,
+    layout: PhantomData<L>,
+}
+
+impl<L: ChunkLayout> TypedReceiver<L> {
+    /// Blocks until a chunk is available or the recording has ended.
+    pub fn recv(&self) -> Result<TypedChunk<L>, AudioRecvError> {
+        self.receiver.recv().map(TypedReceiver::typed)
+    }
+
+    /// Blocks until a chunk is available, the recording has ended, or `timeout` elapses.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<TypedChunk<L>, AudioRecvError> {
+        self.receiver
+            .recv_timeout(timeout)
+            .map(TypedReceiver::typed)
+    }
+
+    /// Returns a chunk if one is available, without blocking.
+    pub fn try_recv(&self) -> Result<TypedChunk<L>, AudioRecvError> {
+        self.receiver.try_recv().map(TypedReceiver::typed)
+    }
+
+    /// Iterates over every chunk, blocking between them, until the recording has ended.
+    pub fn iter(&self) -> impl Iterator<Item = TypedChunk<L>> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Summary of the recording, available once the end of stream has been received.
+    pub fn summary(&self) -> Option<StreamSummary> {
+        self.receiver.summary()
+    }
+
+    /// The untyped receiver, e.g. to attach a sink.
+    pub fn into_inner(self) -> AudioReceiver {
+        self.receiver
+    }
+
+    fn typed(chunk: AudioChunk) -> TypedChunk<L> {
+        if chunk.channels != L::CHANNELS {
+            // the config fixes the layout, so this is a bug of the recorder
+            tracing::error!(
+                "Chunk of {} channels delivered instead of {}",
+                chunk.channels,
+                L::CHANNELS
+            );
+        }
+
+        TypedChunk {
+            chunk,
+            layout: PhantomData,
+        }
+    }
+}
+
+impl Recorder {
+    /// Starts the recording built with `CaptureBuilder`, delivering its chunks in the
+    /// layout of its type.
+    #[tracing::instrument]
+    pub fn start_typed<L: ChunkLayout>(
+        &mut self,
+        session: TypedSession<L>,
+    ) -> Result<TypedReceiver<L>, AudioRecorderError> {
+        tracing::info!("Starting a recording of {} channels", L::CHANNELS);
+
+        let receiver = self.start(session.config)?;
+        Ok(TypedReceiver {
+            receiver,
+            layout: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use std::{fmt::Display, path::PathBuf};
+
+use cpal::{SampleFormat, traits::DeviceTrait};
+
+use super::{
+    Recorder,
+    config::RecorderConfig,
+    get_default_device::{get_default_input_device, get_default_output_device},
+    resampler_driver::ResamplerDriver,
+};
+
+/// A problem found by `Recorder::validate` that would prevent a recording from starting.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// No default input device was found.
+    NoInputDevice,
+    /// No default output device was found.
+    NoOutputDevice,
+    /// The device exists but its stream config could not be read, usually because of
+    /// missing permissions or because it is in exclusive use.
+    DeviceUnavailable { device: String, reason: String },
+    /// The device captures in a sample format the recorder cannot convert.
+    UnsupportedSampleFormat {
+        device: String,
+        format: SampleFormat,
+    },
+    /// The streams cannot be resampled between the given rates.
+    UnsupportedSampleRate { from: u32, to: u32 },
+    /// The raw capture directory cannot be written to.
+    RawCaptureDirUnwritable { dir: PathBuf, reason: String },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::NoInputDevice => write!(f, "No default input device found"),
+            ValidationIssue::NoOutputDevice => write!(f, "No default output device found"),
+            ValidationIssue::DeviceUnavailable { device, reason } => {
+                write!(f, "Device {device:?} is unavailable: {reason}")
+            }
+            ValidationIssue::UnsupportedSampleFormat { device, format } => {
+                write!(
+                    f,
+                    "Device {device:?} uses unsupported sample format {format:?}"
+                )
+            }
+            ValidationIssue::UnsupportedSampleRate { from, to } => {
+                write!(f, "Cannot resample from {from} Hz to {to} Hz")
+            }
+            ValidationIssue::RawCaptureDirUnwritable { dir, reason } => {
+                write!(f, "Raw capture directory {dir:?} is not writable: {reason}")
+            }
+        }
+    }
+}
+
+/// Whether the capture callbacks can convert samples of `format`.
+fn is_supported_format(format: SampleFormat) -> bool {
+    matches!(
+        format,
+        SampleFormat::I8
+            | SampleFormat::I16
+            | SampleFormat::I32
+            | SampleFormat::I64
+            | SampleFormat::U8
+            | SampleFormat::U16
+            | SampleFormat::U32
+            | SampleFormat::U64
+            | SampleFormat::F32
+            | SampleFormat::F64
+    )
+}
+
+impl Recorder {
+    /// Checks, without opening any stream, whether `start(config)` would succeed with
+    /// the current settings.
+    ///
+    /// Every problem found is returned at once, so settings screens can report them all
+    /// instead of failing on the first one. An empty list means the recording can start.
+    #[tracing::instrument]
+    pub fn validate(&self, config: &RecorderConfig) -> Vec<ValidationIssue> {
+        tracing::info!("Validating the recorder settings");
+        let mut issues = Vec::new();
+
+        let mut rates = Vec::new();
+
+        match get_default_input_device() {
+            Ok(device) => {
+                if let Some(rate) = self.validate_device(&device, true, &mut issues) {
+                    rates.push(rate);
+                }
+            }
+            Err(_) => issues.push(ValidationIssue::NoInputDevice),
+        }
+
+        if !config.input_only() {
+            match get_default_output_device() {
+                Ok(device) => {
+                    if let Some(rate) = self.validate_device(&device, false, &mut issues) {
+                        rates.push(rate);
+                    }
+                }
+                Err(_) => issues.push(ValidationIssue::NoOutputDevice),
+            }
+        }
+
+        // every source is resampled to the chosen rate, or the slowest device rate when
+        // recording from both
+        let target = match config.sample_rate().or(self.sample_rate) {
+            Some(rate) => Some(rate),
+            None if !config.input_only() => rates.iter().copied().min(),
+            None => None,
+        };
+        if let Some(target) = target {
+            for rate in rates.iter().copied().filter(|rate| *rate != target) {
+                if ResamplerDriver::new(rate as usize, target as usize, 2).is_err() {
+                    issues.push(ValidationIssue::UnsupportedSampleRate {
+                        from: rate,
+                        to: target,
+                    });
+                }
+            }
+        }
+
+        if let Some(dir) = &self.raw_capture_dir {
+            let probe = dir.join(".arec_probe");
+            let writable = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&probe, []))
+                .and_then(|_| std::fs::remove_file(&probe));
+
+            if let Err(e) = writable {
+                issues.push(ValidationIssue::RawCaptureDirUnwritable {
+                    dir: dir.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        for issue in &issues {
+            tracing::warn!("Validation issue: {}", issue);
+        }
+
+        issues
+    }
+
+    /// Validates the config of a device, returning its sample rate when it is usable.
+    fn validate_device(
+        &self,
+        device: &cpal::Device,
+        input: bool,
+        issues: &mut Vec<ValidationIssue>,
+    ) -> Option<u32> {
+        let name = device.name().unwrap_or(String::from("Unknown"));
+
+        let config = match input {
+            true => device.default_input_config(),
+            false => device.default_output_config(),
+        };
+
+        let config = match config {
+            Ok(c) => c,
+            Err(e) => {
+                issues.push(ValidationIssue::DeviceUnavailable {
+                    device: name,
+                    reason: e.to_string(),
+                });
+                return None;
+            }
+        };
+
+        if !is_supported_format(config.sample_format()) {
+            issues.push(ValidationIssue::UnsupportedSampleFormat {
+                device: name,
+                format: config.sample_format(),
+            });
+            return None;
+        }
+
+        Some(config.sample_rate().0)
+    }
+}
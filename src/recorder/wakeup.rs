@@ -0,0 +1,34 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Wakes a worker thread as soon as there is something to do (data pushed by a capture
+/// callback, or the recording stopped), instead of having it poll on a timer.
+///
+/// A notification sent while the thread is busy is kept for its next `wait`, so none is
+/// lost, and notifications piling up wake it once. Each waiting thread has its own.
+#[derive(Debug, Clone, Default)]
+pub struct Wakeup(Arc<(Mutex<bool>, Condvar)>);
+
+impl Wakeup {
+    pub fn notify(&self) {
+        let (pending, condvar) = &*self.0;
+        *pending.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        condvar.notify_one();
+    }
+
+    /// Blocks until notified, or at most `timeout`, returning whether it was notified.
+    ///
+    /// The timeout bounds how late a thread notices a change nobody notified it of, e.g.
+    /// the recording stopped by the watchdog.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let (pending, condvar) = &*self.0;
+        let guard = pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut guard, _) = condvar
+            .wait_timeout_while(guard, timeout, |pending| !*pending)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        std::mem::replace(&mut *guard, false)
+    }
+}
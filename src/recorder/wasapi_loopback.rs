@@ -0,0 +1,288 @@
+use std::{
+    ptr, slice,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Media::Audio::{
+        AUDCLNT_BUFFERFLAGS_DATA_DISCONTINUITY, AUDCLNT_BUFFERFLAGS_SILENT,
+        AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_LOOPBACK, IAudioCaptureClient, IAudioClient,
+        IMMDeviceEnumerator, MMDeviceEnumerator, eConsole, eRender,
+    },
+    System::Com::{CLSCTX_ALL, CoCreateInstance, CoTaskMemFree},
+};
+
+use super::{com::ComGuard, errors::AudioRecorderError};
+
+/// Shared-mode buffer requested from the endpoint.
+const LOOPBACK_BUFFER_MS: i64 = 200;
+/// How often the capture thread drains the endpoint.
+const LOOPBACK_POLL: Duration = Duration::from_millis(10);
+/// Without packets for this long, nothing is playing and silence is delivered.
+const LOOPBACK_IDLE: Duration = Duration::from_millis(50);
+
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// A shared-mode client of the default render endpoint, initialized in loopback mode.
+struct LoopbackClient {
+    client: IAudioClient,
+    capture: IAudioCaptureClient,
+    sample_rate: u32,
+    channels: usize,
+}
+
+fn wasapi_error(context: &'static str) -> impl FnOnce(windows::core::Error) -> AudioRecorderError {
+    move |e| {
+        tracing::error!("{}: {}", context, e);
+        AudioRecorderError::DeviceError(context)
+    }
+}
+
+impl LoopbackClient {
+    /// Opens the default render endpoint, if its mix format is 32-bit float at the given
+    /// sample rate and channels.
+    ///
+    /// COM must be initialized on the calling thread, and the client stays on it.
+    fn open(sample_rate: u32, channels: u16) -> Result<Self, AudioRecorderError> {
+        // SAFETY: COM is initialized on this thread, the mix format is read before it is
+        // freed, and freed once.
+        unsafe {
+            let enumerator: IMMDeviceEnumerator =
+                CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                    .map_err(wasapi_error("Failed to create the device enumerator"))?;
+            let device = enumerator
+                .GetDefaultAudioEndpoint(eRender, eConsole)
+                .map_err(wasapi_error("Failed to get the default render endpoint"))?;
+            let client: IAudioClient = device
+                .Activate(CLSCTX_ALL, None)
+                .map_err(wasapi_error("Failed to activate the audio client"))?;
+
+            let format = client
+                .GetMixFormat()
+                .map_err(wasapi_error("Failed to get the mix format"))?;
+            let (tag, bits) = ((*format).wFormatTag, (*format).wBitsPerSample);
+            let layout = ((*format).nSamplesPerSec, (*format).nChannels);
+
+            let supported = matches!(tag, WAVE_FORMAT_IEEE_FLOAT | WAVE_FORMAT_EXTENSIBLE)
+                && bits == 32
+                && layout == (sample_rate, channels);
+
+            let initialized = match supported {
+                true => client.Initialize(
+                    AUDCLNT_SHAREMODE_SHARED,
+                    AUDCLNT_STREAMFLAGS_LOOPBACK,
+                    LOOPBACK_BUFFER_MS * 10_000,
+                    0,
+                    format,
+                    None,
+                ),
+                false => Ok(()),
+            };
+            CoTaskMemFree(Some(format as *const _));
+
+            if !supported {
+                tracing::debug!(
+                    "Loopback mix format {} Hz {}ch ({} bits) differs from the stream",
+                    layout.0,
+                    layout.1,
+                    bits
+                );
+                return Err(AudioRecorderError::DeviceError(
+                    "Unsupported loopback mix format",
+                ));
+            }
+            initialized.map_err(wasapi_error("Failed to initialize loopback capture"))?;
+
+            let capture: IAudioCaptureClient = client
+                .GetService()
+                .map_err(wasapi_error("Failed to get the capture client"))?;
+
+            Ok(LoopbackClient {
+                client,
+                capture,
+                sample_rate,
+                channels: channels as usize,
+            })
+        }
+    }
+
+    /// Delivers every packet queued on the endpoint, returning how many frames it read.
+    fn read(&self, callback: &mut impl FnMut(&[f32])) -> windows::core::Result<usize> {
+        let mut total = 0;
+
+        // SAFETY: the buffer returned by `GetBuffer` holds `frames` frames of the 32-bit
+        // float mix format until `ReleaseBuffer`.
+        unsafe {
+            while self.capture.GetNextPacketSize()? > 0 {
+                let mut data = ptr::null_mut();
+                let mut frames = 0;
+                let mut flags = 0;
+                self.capture
+                    .GetBuffer(&mut data, &mut frames, &mut flags, None, None)?;
+
+                let samples = frames as usize * self.channels;
+                if flags & AUDCLNT_BUFFERFLAGS_DATA_DISCONTINUITY.0 as u32 != 0 {
+                    tracing::warn!("Loopback capture skipped audio");
+                }
+
+                if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 || data.is_null() {
+                    callback(&vec![0.0; samples]);
+                } else {
+                    callback(slice::from_raw_parts(data as *const f32, samples));
+                }
+
+                self.capture.ReleaseBuffer(frames)?;
+                total += frames as usize;
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+/// Whether the default render endpoint can be captured in loopback mode as a stream
+/// with the given layout.
+pub fn available(sample_rate: u32, channels: u16) -> bool {
+    let _com = ComGuard::init();
+    LoopbackClient::open(sample_rate, channels).is_ok()
+}
+
+/// WASAPI loopback capture of the default render endpoint, on its own thread.
+///
+/// Loopback endpoints deliver no packets at all while nothing plays, so the stream
+/// delivers silence for the idle time instead, keeping the system audio in step with
+/// the microphone.
+pub struct LoopbackStream {
+    playing: Arc<AtomicBool>,
+    alive: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LoopbackStream {
+    /// Opens the capture, delivering interleaved samples to `callback` once playing.
+    pub fn build(
+        sample_rate: u32,
+        channels: u16,
+        mut callback: impl FnMut(&[f32]) + Send + 'static,
+        mut errors: impl FnMut(cpal::StreamError) + Send + 'static,
+    ) -> Result<Self, AudioRecorderError> {
+        let playing = Arc::new(AtomicBool::new(false));
+        let alive = Arc::new(AtomicBool::new(true));
+        let (opened_tx, opened_rx) = crossbeam_channel::bounded(1);
+
+        let thread_playing = playing.clone();
+        let thread_alive = alive.clone();
+
+        let handle = thread::Builder::new()
+            .name("arec-loopback".to_string())
+            .spawn(move || {
+                let _com = ComGuard::init();
+
+                // the COM objects stay on the thread that created them
+                let client = match LoopbackClient::open(sample_rate, channels) {
+                    Ok(client) => {
+                        let _ = opened_tx.send(Ok(()));
+                        client
+                    }
+                    Err(e) => {
+                        let _ = opened_tx.send(Err(e));
+                        return;
+                    }
+                };
+
+                let mut started = false;
+                let mut last_data = Instant::now();
+
+                while thread_alive.load(Ordering::SeqCst) {
+                    let play = thread_playing.load(Ordering::SeqCst);
+
+                    // SAFETY: the client was initialized on this thread.
+                    let toggled = match (play, started) {
+                        (true, false) => unsafe { client.client.Start() },
+                        (false, true) => unsafe { client.client.Stop() },
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = toggled {
+                        tracing::error!("Failed to toggle loopback capture: {}", e);
+                    } else if play != started {
+                        started = play;
+                        last_data = Instant::now();
+                    }
+
+                    if started {
+                        match client.read(&mut callback) {
+                            Ok(0) if last_data.elapsed() >= LOOPBACK_IDLE => {
+                                let frames = (last_data.elapsed().as_secs_f64()
+                                    * client.sample_rate as f64)
+                                    as usize;
+                                callback(&vec![0.0; frames * client.channels]);
+                                last_data = Instant::now();
+                            }
+                            Ok(0) => {}
+                            Ok(_) => last_data = Instant::now(),
+                            Err(e) => {
+                                tracing::error!("Loopback capture failed: {}", e);
+                                errors(cpal::StreamError::BackendSpecific {
+                                    err: cpal::BackendSpecificError {
+                                        description: e.to_string(),
+                                    },
+                                });
+                                break;
+                            }
+                        }
+                    }
+
+                    thread::sleep(LOOPBACK_POLL);
+                }
+
+                if started {
+                    // SAFETY: the client was initialized on this thread.
+                    if let Err(e) = unsafe { client.client.Stop() } {
+                        tracing::error!("Failed to stop loopback capture: {}", e);
+                    }
+                }
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to spawn loopback thread: {}", e);
+                AudioRecorderError::IoError(e)
+            })?;
+
+        let stream = LoopbackStream {
+            playing,
+            alive,
+            handle: Some(handle),
+        };
+
+        match opened_rx.recv() {
+            Ok(Ok(())) => Ok(stream),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(AudioRecorderError::ThreadPanicked("Loopback")),
+        }
+    }
+
+    pub fn play(&self) {
+        self.playing.store(true, Ordering::SeqCst);
+    }
+
+    pub fn pause(&self) {
+        self.playing.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for LoopbackStream {
+    fn drop(&mut self) {
+        self.alive.store(false, Ordering::SeqCst);
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                tracing::error!("Loopback thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    Recorder, delivery::ChunkSender, errors::AudioRecorderError, events::RecorderEvent,
+    receiver::AudioReceiver,
+};
+
+/// Time of the last chunk delivered on a data channel, shared with its watchdog.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    epoch: Instant,
+    last: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    fn new() -> Self {
+        Heartbeat {
+            epoch: Instant::now(),
+            last: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Records a delivery.
+    pub fn beat(&self) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        self.last.store(now, Ordering::Relaxed);
+    }
+
+    /// Whether the data channel was dropped, ending the stream it watched.
+    fn is_orphaned(&self) -> bool {
+        Arc::strong_count(&self.last) == 1
+    }
+
+    /// Time since the last delivery, or since the channel was created.
+    fn elapsed(&self) -> Duration {
+        let last = Duration::from_millis(self.last.load(Ordering::Relaxed));
+        self.epoch.elapsed().saturating_sub(last)
+    }
+}
+
+impl Recorder {
+    /// Watches the delivered stream (or disables the watchdog with `None`), emitting a
+    /// `RecorderEvent::Stalled` when no chunk has been delivered for `timeout` while the
+    /// recording is still in progress.
+    ///
+    /// A capture thread that died with its device, leaving `get_is_recording` true
+    /// forever, is caught this way. With `stop_on_stall`, the stalled recording is also
+    /// stopped, so it can be started again with `restart`.
+    ///
+    /// Takes effect on the next call to `start`.
+    pub fn set_watchdog(&mut self, timeout: Option<Duration>, stop_on_stall: bool) {
+        self.watchdog = timeout;
+        self.watchdog_stop = stop_on_stall;
+    }
+
+    /// Stops the current recording and starts a new one with the config of the last
+    /// call to `start`, typically after a `RecorderEvent::Stalled`.
+    pub fn restart(&mut self) -> Result<AudioReceiver, AudioRecorderError> {
+        tracing::info!("Restarting the recorder");
+        self.stop();
+
+        let config = self.config.clone();
+        self.start(config)
+    }
+
+    /// Spawns the watchdog of a data channel if enabled.
+    pub fn watch(&self, sender: &mut ChunkSender) -> Result<(), AudioRecorderError> {
+        let timeout = match self.watchdog {
+            Some(timeout) => timeout,
+            None => return Ok(()),
+        };
+
+        let heartbeat = Heartbeat::new();
+        sender.heartbeat = Some(heartbeat.clone());
+
+        let events = self.events.clone();
+        let recording_signal = self.recording_signal.clone();
+        let stop_on_stall = self.watchdog_stop;
+        let poll_interval = (timeout / 4)
+            .min(self.config.poll_interval())
+            .max(Duration::from_millis(10));
+
+        self.workers().spawn("arec-watchdog", move || {
+            let mut stalled = false;
+
+            while recording_signal.load(Ordering::SeqCst) {
+                thread::sleep(poll_interval);
+
+                // the stream ended, a restarted recording has its own watchdog
+                if heartbeat.is_orphaned() {
+                    break;
+                }
+
+                let elapsed = heartbeat.elapsed();
+                if elapsed < timeout {
+                    stalled = false;
+                    continue;
+                }
+
+                if stalled {
+                    continue;
+                }
+                stalled = true;
+
+                tracing::warn!("No audio delivered for {:?}", elapsed);
+                events.emit(RecorderEvent::Stalled { elapsed });
+
+                if stop_on_stall {
+                    tracing::warn!("Stopping the stalled recording");
+                    recording_signal.store(false, Ordering::SeqCst);
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+
+use super::{
+    Recorder,
+    com::ComGuard,
+    constants::STARTUP_TIMEOUT,
+    errors::AudioRecorderError,
+    events::{EventBus, RecorderEvent},
+    wakeup::Wakeup,
+};
+
+/// The worker side of the startup handshake, reporting whether the streams started.
+pub struct Startup {
+    tx: Sender<Result<(), AudioRecorderError>>,
+}
+
+impl Startup {
+    /// Reports that the streams of the worker are playing.
+    pub fn ready(self) {
+        self.report(Ok(()));
+    }
+
+    /// Reports why the streams of the worker could not be started.
+    pub fn fail(self, e: AudioRecorderError) {
+        self.report(Err(e));
+    }
+
+    fn report(self, result: Result<(), AudioRecorderError>) {
+        if self.tx.send(result).is_err() {
+            tracing::debug!("Nobody is waiting for the worker to start");
+        }
+    }
+}
+
+/// The caller side of the startup handshake.
+///
+/// Capture threads build and play their streams after `start` spawned them, so without
+/// the handshake a device failing at that point would leave the caller with a receiver
+/// that never yields data.
+pub struct StartupWait {
+    rx: Receiver<Result<(), AudioRecorderError>>,
+}
+
+impl StartupWait {
+    /// Waits for the worker to report, up to `STARTUP_TIMEOUT`.
+    pub fn wait(self) -> Result<(), AudioRecorderError> {
+        match self.rx.recv_timeout(STARTUP_TIMEOUT) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                tracing::error!("Streams did not start within {:?}", STARTUP_TIMEOUT);
+                Err(AudioRecorderError::DeviceError(
+                    "Streams did not start in time",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                tracing::error!("Worker exited before starting its streams");
+                Err(AudioRecorderError::DeviceError(
+                    "Worker exited before starting its streams",
+                ))
+            }
+        }
+    }
+}
+
+/// Creates the startup handshake of a worker.
+pub fn startup() -> (Startup, StartupWait) {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    (Startup { tx }, StartupWait { rx })
+}
+
+/// Spawns the named worker threads of a recording.
+///
+/// A panicking worker no longer dies silently: the panic is caught, reported as a
+/// `RecorderEvent::StreamError` and the recording is stopped, which lets the other
+/// workers wind down and the data channel deliver its end marker.
+///
+/// Every worker initializes COM for its lifetime, see `ComGuard`. The join handles are
+/// kept, see `Recorder::stop_blocking`.
+#[derive(Clone)]
+pub struct Workers {
+    events: EventBus,
+    recording_signal: Arc<AtomicBool>,
+    stop_wakeup: Wakeup,
+    handles: WorkerHandles,
+}
+
+/// The join handles of the workers not joined yet, with their names. Every worker
+/// returns whether it exited without panicking.
+pub type WorkerHandles = Arc<Mutex<Vec<(String, JoinHandle<bool>)>>>;
+
+/// Joins the workers that already exited, e.g. those of past recordings, so a recorder
+/// started again and again without `Recorder::stop_blocking` does not keep their
+/// handles forever.
+pub fn prune(handles: &WorkerHandles) {
+    let finished = {
+        let mut handles = handles.lock().unwrap_or_else(PoisonError::into_inner);
+        let (finished, running): (Vec<_>, Vec<_>) = std::mem::take(&mut *handles)
+            .into_iter()
+            .partition(|(_, handle)| handle.is_finished());
+        *handles = running;
+        finished
+    };
+
+    for (name, handle) in finished {
+        match handle.join() {
+            Ok(true) => tracing::debug!("Worker {} exited", name),
+            _ => tracing::warn!("Worker {} exited after a panic", name),
+        }
+    }
+}
+
+/// Outcome of `Recorder::stop_blocking`, listing the worker threads by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ShutdownReport {
+    /// Workers that exited normally.
+    pub exited: Vec<String>,
+    /// Workers that exited after a panic.
+    pub panicked: Vec<String>,
+    /// Workers still running when the timeout elapsed.
+    pub running: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Whether every worker exited normally, releasing its devices and streams.
+    pub fn is_clean(&self) -> bool {
+        self.panicked.is_empty() && self.running.is_empty()
+    }
+}
+
+impl Workers {
+    pub fn spawn<F>(&self, name: &str, body: F) -> Result<(), AudioRecorderError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let events = self.events.clone();
+        let recording_signal = self.recording_signal.clone();
+        let stop_wakeup = self.stop_wakeup.clone();
+        let thread_name = name.to_string();
+
+        let spawned = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                // every worker may touch devices, which needs COM on Windows
+                let _com = ComGuard::init();
+
+                let payload = match panic::catch_unwind(AssertUnwindSafe(body)) {
+                    Ok(()) => return true,
+                    Err(payload) => payload,
+                };
+
+                let message = match payload.downcast_ref::<&str>() {
+                    Some(s) => s.to_string(),
+                    None => match payload.downcast_ref::<String>() {
+                        Some(s) => s.clone(),
+                        None => "unknown panic".to_string(),
+                    },
+                };
+
+                tracing::error!("Thread {} panicked: {}", thread_name, message);
+                recording_signal.store(false, Ordering::SeqCst);
+                stop_wakeup.notify();
+                events.emit(RecorderEvent::StreamError {
+                    thread: thread_name,
+                    message,
+                });
+
+                false
+            });
+
+        match spawned {
+            Ok(handle) => {
+                self.handles
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push((name.to_string(), handle));
+                Ok(())
+            }
+            Err(e) => {
+                tracing::error!("Failed to spawn thread {}: {}", name, e);
+                Err(AudioRecorderError::IoError(e))
+            }
+        }
+    }
+
+    /// Stops the recording after the worker `thread` failed, reporting `e` to the caller
+    /// still waiting on `startup`, or as a `RecorderEvent::StreamError` once it started.
+    pub fn fail(&self, thread: &str, startup: &mut Option<Startup>, e: AudioRecorderError) {
+        self.recording_signal.store(false, Ordering::SeqCst);
+        self.stop_wakeup.notify();
+
+        match startup.take() {
+            Some(startup) => startup.fail(e),
+            None => self.events.emit(RecorderEvent::StreamError {
+                thread: thread.to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+impl Recorder {
+    pub fn workers(&self) -> Workers {
+        Workers {
+            events: self.events.clone(),
+            recording_signal: self.recording_signal.clone(),
+            stop_wakeup: self.stop_wakeup.clone(),
+            handles: self.worker_handles.clone(),
+        }
+    }
+
+    /// Stops the recording and waits up to `timeout` for every worker thread to exit,
+    /// so the devices and streams are known to be released before starting again.
+    ///
+    /// Workers still running after `timeout` are reported and can be waited for by
+    /// calling this again.
+    #[tracing::instrument]
+    pub fn stop_blocking(&mut self, timeout: Duration) -> ShutdownReport {
+        self.stop();
+
+        let deadline = Instant::now() + timeout;
+        let mut report = ShutdownReport::default();
+
+        // a worker panicking while holding the handles leaves them consistent
+        let mut pending = std::mem::take(
+            &mut *self
+                .worker_handles
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+
+        loop {
+            let (finished, running): (Vec<_>, Vec<_>) = pending
+                .into_iter()
+                .partition(|(_, handle)| handle.is_finished());
+            pending = running;
+
+            for (name, handle) in finished {
+                match handle.join() {
+                    Ok(true) => report.exited.push(name),
+                    _ => report.panicked.push(name),
+                }
+            }
+
+            if pending.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        for (name, handle) in pending {
+            tracing::warn!("Worker {} still running after {:?}", name, timeout);
+            report.running.push(name.clone());
+
+            self.worker_handles
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((name, handle));
+        }
+
+        tracing::info!("Shutdown finished: {:?}", report);
+        report
+    }
+}
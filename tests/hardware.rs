@@ -0,0 +1,103 @@
+//! Captures a known signal through the system audio path of each platform:
+//! ScreenCaptureKit on macOS, WASAPI loopback on Windows and the monitor source of the
+//! default sink on Linux. Needs a machine with a working output device, run with
+//! `cargo test --features hw-tests --test hardware`.
+#![cfg(feature = "hw-tests")]
+
+use std::{
+    f32::consts::PI,
+    thread,
+    time::{Duration, Instant},
+};
+
+use audio_recorder_rs::{AudioDevice, DeviceType, Recorder};
+use cpal::traits::{DeviceTrait, HostTrait};
+use tracing_test::traced_test;
+
+/// Frequency of `AudioDevice::play_test_tone`.
+const TONE_HZ: f32 = 440.0;
+/// A frequency absent from the test tone, to compare its power against.
+const REFERENCE_HZ: f32 = 1000.0;
+
+/// Power of `frequency` in mono `samples`, with the Goertzel algorithm.
+fn goertzel(samples: &[f32], sample_rate: u32, frequency: f32) -> f32 {
+    let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate as f32).cos();
+    let (mut previous, mut before) = (0.0, 0.0);
+
+    for sample in samples {
+        let current = sample + coefficient * previous - before;
+        before = previous;
+        previous = current;
+    }
+
+    previous * previous + before * before - coefficient * previous * before
+}
+
+/// Plays the test tone on the default output device while recording the system audio,
+/// and checks the tone was captured.
+fn assert_captures_tone() {
+    let output = cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.name().ok())
+        .expect("No default output device");
+    let output =
+        AudioDevice::find(&output, &[DeviceType::Output]).expect("Output device not listed");
+
+    let mut recorder = Recorder::new();
+    let receivers = recorder
+        .start_separate()
+        .expect("Failed to start recording");
+
+    let tone = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        output.play_test_tone(Duration::from_millis(1500))
+    });
+
+    let mut samples = Vec::new();
+    let mut sample_rate = 0;
+    let instant = Instant::now();
+    while instant.elapsed() < Duration::from_secs(2) {
+        let chunk = match receivers.system.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+
+        sample_rate = chunk.sample_rate;
+        samples.extend(Recorder::channels_to_mono(chunk.samples, chunk.channels));
+    }
+    recorder.stop();
+    tone.join()
+        .expect("Tone thread panicked")
+        .expect("Failed to play the test tone");
+
+    assert!(sample_rate > 0, "No system audio captured");
+
+    let tone = goertzel(&samples, sample_rate, TONE_HZ);
+    let reference = goertzel(&samples, sample_rate, REFERENCE_HZ);
+    tracing::info!("Tone power {}, reference power {}", tone, reference);
+    assert!(
+        tone > reference * 10.0,
+        "Test tone not found in the system audio ({tone} vs {reference})"
+    );
+}
+
+#[test]
+#[traced_test]
+#[cfg(target_os = "macos")]
+fn screencapturekit_captures_test_tone() {
+    assert_captures_tone();
+}
+
+#[test]
+#[traced_test]
+#[cfg(target_os = "windows")]
+fn wasapi_loopback_captures_test_tone() {
+    assert_captures_tone();
+}
+
+#[test]
+#[traced_test]
+#[cfg(target_os = "linux")]
+fn pulse_monitor_captures_test_tone() {
+    assert_captures_tone();
+}
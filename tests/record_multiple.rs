@@ -1,4 +1,4 @@
-use audio_recorder_rs::Recorder;
+use audio_recorder_rs::{Recorder, RecorderConfig};
 use tracing_test::traced_test;
 
 #[test]
@@ -10,7 +10,7 @@ fn record_multiple_devices() {
     let mut recorder = Recorder::new();
 
     tracing::info!("Starting recorder");
-    let receiver = match recorder.start(false) {
+    let receiver = match recorder.start(RecorderConfig::default()) {
         Ok(receiver) => receiver,
         Err(e) => {
             panic!("Failed to start recorder: {e}");
@@ -48,7 +48,7 @@ fn record_multiple_devices() {
             break;
         }
 
-        for sample in d {
+        for sample in d.samples {
             writer.write_sample(sample).ok();
         }
     }
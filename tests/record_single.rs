@@ -1,4 +1,4 @@
-use audio_recorder_rs::Recorder;
+use audio_recorder_rs::{LatencyMode, Recorder, RecorderConfig};
 use tracing_test::traced_test;
 
 #[test]
@@ -10,7 +10,7 @@ fn record_input_only() {
     let mut recorder = Recorder::new();
 
     tracing::info!("Starting recorder");
-    let receiver = match recorder.start(true) {
+    let receiver = match recorder.start(RecorderConfig::builder().input_only(true).build()) {
         Ok(receiver) => receiver,
         Err(e) => {
             panic!("Failed to start recorder: {e}");
@@ -48,7 +48,7 @@ fn record_input_only() {
             break;
         }
 
-        for sample in d {
+        for sample in d.samples {
             writer.write_sample(sample).ok();
         }
     }
@@ -66,3 +66,87 @@ fn record_input_only() {
         tracing::error!("Failed to delete output.wav: {}", e);
     }
 }
+
+#[test]
+#[traced_test]
+fn record_low_latency() {
+    tracing::info!("Spawning low latency test");
+
+    let mut recorder = Recorder::new();
+    let config = RecorderConfig::builder()
+        .input_only(true)
+        .latency_mode(LatencyMode::Low)
+        .build();
+
+    let receiver = match recorder.start(config) {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            panic!("Failed to start recorder: {e}");
+        }
+    };
+
+    // the first frame of a chunk waited for the rest of the chunk to be captured, then
+    // for the chunk to be received, so both add up to its capture to delivery latency
+    let bound = std::time::Duration::from_millis(20);
+    let mut chunks = 0;
+    let mut worst = std::time::Duration::ZERO;
+    let instant = std::time::Instant::now();
+
+    while let Ok(chunk) = receiver.recv() {
+        let received = std::time::Instant::now();
+        if instant.elapsed().as_secs() > 1 {
+            break;
+        }
+
+        let latency = chunk.duration() + received.saturating_duration_since(chunk.timestamp);
+        worst = worst.max(latency);
+        chunks += 1;
+    }
+    recorder.stop();
+
+    assert!(chunks > 0, "No audio was delivered");
+
+    tracing::info!("Worst capture to delivery latency: {:?}", worst);
+    assert!(
+        worst < bound,
+        "Capture to delivery latency of {worst:?} exceeds the low latency target"
+    );
+}
+
+#[test]
+#[traced_test]
+fn record_output_channels() {
+    tracing::info!("Spawning output channels test");
+
+    let mut recorder = Recorder::new();
+    let config = RecorderConfig::builder()
+        .input_only(true)
+        .output_channels(4)
+        .build();
+
+    let receiver = match recorder.start(config) {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            panic!("Failed to start recorder: {e}");
+        }
+    };
+
+    let config = recorder.get_config().expect("Failed to get config");
+    assert_eq!(config.channels, 4, "Reported channels were not remixed");
+
+    let mut chunks = 0;
+    let instant = std::time::Instant::now();
+
+    while let Ok(chunk) = receiver.recv() {
+        if instant.elapsed().as_secs() > 1 {
+            break;
+        }
+
+        chunks += 1;
+        assert_eq!(chunk.channels, 4);
+        assert_eq!(chunk.samples.len() % 4, 0, "Chunk holds partial frames");
+    }
+    recorder.stop();
+
+    assert!(chunks > 0, "No audio was delivered");
+}